@@ -0,0 +1,326 @@
+//! # Allocator-Backed Tables
+//!
+//! A hash consing table whose map and values are allocated in an
+//! [`allocator_api2`](https://docs.rs/allocator-api2) allocator, such as an arena, a pool or an
+//! allocator that tracks how much memory the interner holds.
+//!
+//! `Arc` and `Rc` cannot be placed in a custom allocator on stable Rust, so this table does not
+//! wrap `HcTable`. Instead, every value lives in a node allocated in the table's allocator and
+//! reference counted by the table itself: the node is removed from the map and freed as soon as
+//! its last handle is dropped, and the map and allocator are freed once the table and every
+//! handle are gone. Handles are neither `Send` nor `Sync`.
+//!
+//! ## Example
+//! ```
+//! use allocator_api2::alloc::Global;
+//! use hash_cons::allocator::{AllocHc, AllocHcTable};
+//!
+//! #[derive(Hash, PartialEq, Eq)]
+//! enum BoolExpr {
+//!     Const(bool),
+//!     Not(AllocHc<BoolExpr>),
+//! }
+//!
+//! let table = AllocHcTable::new_in(Global);
+//!
+//! let t = table.hashcons(BoolExpr::Const(true));
+//! let not_t = table.hashcons(BoolExpr::Not(t.clone()));
+//!
+//! assert!(table.hashcons(BoolExpr::Not(t)) == not_t);
+//! assert_eq!(table.len(), 2);
+//!
+//! drop(not_t);
+//! assert_eq!(table.len(), 0);
+//! ```
+
+use crate::hasher::HashState;
+use ::allocator_api2::alloc::{handle_alloc_error, Allocator, Global, Layout};
+use ::hashbrown::HashTable;
+use std::cell::{Cell, RefCell};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::ptr::{self, NonNull};
+
+/// # `Node<T, A>`
+/// A value interned in an [`AllocHcTable`], allocated in the allocator of its table.
+///
+/// ## Fields
+/// * `value`: The interned value.
+/// * `hash`: The hash of `value`, kept so that the map can grow without rehashing values.
+/// * `handles`: The number of live handles to this node.
+/// * `shared`: The state of the table the node belongs to.
+struct Node<T, A: Allocator> {
+    value: T,
+    hash: u64,
+    handles: Cell<usize>,
+    shared: NonNull<Shared<T, A>>,
+}
+
+/// # `Shared<T, A>`
+/// The state of an [`AllocHcTable`], shared by the table and its nodes.
+///
+/// ## Fields
+/// * `map`: The interned nodes, keyed by the hash of their value.
+/// * `hasher`: The hasher state of the table.
+/// * `refs`: One reference for the table plus one for each node, the state is freed at zero.
+/// * `alloc`: The allocator of the map, the nodes and this state.
+struct Shared<T, A: Allocator> {
+    map: RefCell<HashTable<NonNull<Node<T, A>>, A>>,
+    hasher: HashState,
+    refs: Cell<usize>,
+    alloc: A,
+}
+
+/// Drops a reference to `shared`, freeing it in its own allocator if it was the last one.
+///
+/// # Safety
+/// `shared` must be live and the caller must own one of its references.
+unsafe fn release<T, A: Allocator>(shared: NonNull<Shared<T, A>>) {
+    let refs = shared.as_ref().refs.get() - 1;
+    shared.as_ref().refs.set(refs);
+    if refs > 0 {
+        return;
+    }
+
+    // Every node holds a reference, so the map is empty here.
+    let Shared { map, alloc, .. } = ptr::read(shared.as_ptr());
+    drop(map);
+    alloc.deallocate(shared.cast(), Layout::new::<Shared<T, A>>());
+}
+
+/// # `AllocHc<T, A>`
+/// A handle to a value interned in an [`AllocHcTable`].
+///
+/// The value is freed when its last handle is dropped. Handles are compared and hashed by
+/// address, which is equivalent to comparing the values for handles from the same table.
+pub struct AllocHc<T, A: Allocator = Global> {
+    node: NonNull<Node<T, A>>,
+    _marker: PhantomData<Node<T, A>>,
+}
+
+impl<T, A: Allocator> AllocHc<T, A> {
+    /// Retrieves a reference to the interned value.
+    pub fn get(&self) -> &T {
+        &self.node().value
+    }
+
+    fn node(&self) -> &Node<T, A> {
+        // SAFETY: a node is live as long as one of its handles is.
+        unsafe { self.node.as_ref() }
+    }
+}
+
+impl<T, A: Allocator> Clone for AllocHc<T, A> {
+    fn clone(&self) -> Self {
+        let handles = &self.node().handles;
+        handles.set(handles.get().checked_add(1).expect("too many handles"));
+        AllocHc {
+            node: self.node,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, A: Allocator> Drop for AllocHc<T, A> {
+    fn drop(&mut self) {
+        let node = self.node();
+        let handles = node.handles.get() - 1;
+        node.handles.set(handles);
+        if handles > 0 {
+            return;
+        }
+
+        let shared = node.shared;
+        // SAFETY: the node holds a reference to the state of its table.
+        let state = unsafe { shared.as_ref() };
+        if let Ok(entry) = state
+            .map
+            .borrow_mut()
+            .find_entry(node.hash, |&n| n == self.node)
+        {
+            entry.remove();
+        }
+
+        // SAFETY: the node was the last handle's and is no longer reachable from the map.
+        let Node { value, .. } = unsafe { ptr::read(self.node.as_ptr()) };
+        unsafe {
+            state
+                .alloc
+                .deallocate(self.node.cast(), Layout::new::<Node<T, A>>())
+        };
+
+        // The value may own handles to the same table, so it is dropped once the map is
+        // released and before the node gives up its reference to the state.
+        drop(value);
+        unsafe { release(shared) };
+    }
+}
+
+impl<T, A: Allocator> PartialEq for AllocHc<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<T, A: Allocator> Eq for AllocHc<T, A> {}
+
+impl<T, A: Allocator> Hash for AllocHc<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.node.hash(state);
+    }
+}
+
+impl<T, A: Allocator> std::ops::Deref for AllocHc<T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T, A: Allocator> AsRef<T> for AllocHc<T, A> {
+    fn as_ref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T: std::fmt::Debug, A: Allocator> std::fmt::Debug for AllocHc<T, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+impl<T: std::fmt::Display, A: Allocator> std::fmt::Display for AllocHc<T, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+/// # `AllocHcTable<T, A>`
+/// A single-threaded hash consing table allocating its map and values in `A`.
+///
+/// Handles may outlive the table: the map and the allocator are kept until the last of them
+/// is dropped.
+pub struct AllocHcTable<T, A: Allocator = Global> {
+    shared: NonNull<Shared<T, A>>,
+    _marker: PhantomData<Shared<T, A>>,
+}
+
+impl<T> AllocHcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty table allocating in the global allocator.
+    pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+impl<T> Default for AllocHcTable<T>
+where
+    T: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, A> AllocHcTable<T, A>
+where
+    T: Hash + Eq,
+    A: Allocator + Clone,
+{
+    /// Creates an empty table allocating in `alloc`.
+    ///
+    /// The map, the values and the state of the table all go through clones of `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        let layout = Layout::new::<Shared<T, A>>();
+        let shared = alloc
+            .allocate(layout)
+            .unwrap_or_else(|_| handle_alloc_error(layout))
+            .cast::<Shared<T, A>>();
+        // SAFETY: `shared` was just allocated with the layout of `Shared<T, A>`.
+        unsafe {
+            shared.as_ptr().write(Shared {
+                map: RefCell::new(HashTable::new_in(alloc.clone())),
+                hasher: HashState::new(),
+                refs: Cell::new(1),
+                alloc,
+            })
+        };
+        AllocHcTable {
+            shared,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the handle of `value`, allocating it if it was not interned yet.
+    pub fn hashcons(&self, value: T) -> AllocHc<T, A> {
+        let state = self.state();
+        let hash = state.hasher.hash_one(&value);
+        let mut map = state.map.borrow_mut();
+        // SAFETY (closures below): the map only holds live nodes.
+        if let Some(&node) = map.find(hash, |n| unsafe { n.as_ref() }.value == value) {
+            // `value` is dropped after the map is released, as it may own handles.
+            drop(map);
+            let handles = unsafe { &node.as_ref().handles };
+            handles.set(handles.get().checked_add(1).expect("too many handles"));
+            return AllocHc {
+                node,
+                _marker: PhantomData,
+            };
+        }
+
+        map.reserve(1, |n| unsafe { n.as_ref() }.hash);
+        let layout = Layout::new::<Node<T, A>>();
+        let node = state
+            .alloc
+            .allocate(layout)
+            .unwrap_or_else(|_| handle_alloc_error(layout))
+            .cast::<Node<T, A>>();
+        // SAFETY: `node` was just allocated with the layout of `Node<T, A>`.
+        unsafe {
+            node.as_ptr().write(Node {
+                value,
+                hash,
+                handles: Cell::new(1),
+                shared: self.shared,
+            })
+        };
+        state.refs.set(state.refs.get() + 1);
+        map.insert_unique(hash, node, |n| unsafe { n.as_ref() }.hash);
+        AllocHc {
+            node,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, A: Allocator> AllocHcTable<T, A> {
+    /// Returns the number of values interned in this table.
+    pub fn len(&self) -> usize {
+        self.state().map.borrow().len()
+    }
+
+    /// Returns `true` if nothing is interned in this table.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the allocator of this table.
+    pub fn allocator(&self) -> &A {
+        &self.state().alloc
+    }
+
+    fn state(&self) -> &Shared<T, A> {
+        // SAFETY: the table holds a reference to its state.
+        unsafe { self.shared.as_ref() }
+    }
+}
+
+impl<T, A: Allocator> Drop for AllocHcTable<T, A> {
+    fn drop(&mut self) {
+        // SAFETY: the table owns one reference to its state.
+        unsafe { release(self.shared) };
+    }
+}
@@ -0,0 +1,151 @@
+//! # Arena-Backed Tables
+//!
+//! A hash consing table whose values live in a [`bumpalo`](https://docs.rs/bumpalo) arena for
+//! the lifetime of that arena. Handles are plain `Copy` references, so cloning and dropping
+//! them costs nothing, and no value is ever freed individually: the memory is reclaimed when
+//! the arena is dropped or reset.
+//!
+//! This suits batch workloads, such as a compiler pass that throws the whole table away at the
+//! end, where the reference counting of `HcTable` is pure overhead.
+//!
+//! `bumpalo` does not run destructors, so values that own heap memory, such as `String`s, leak
+//! that memory when the arena goes away.
+//!
+//! ## Example
+//! ```
+//! use bumpalo::Bump;
+//! use hash_cons::arena::{ArenaHc, ArenaHcTable};
+//!
+//! #[derive(Hash, PartialEq, Eq)]
+//! enum BoolExpr<'a> {
+//!     Const(bool),
+//!     Not(ArenaHc<'a, BoolExpr<'a>>),
+//! }
+//!
+//! let arena = Bump::new();
+//! let table = ArenaHcTable::new(&arena);
+//!
+//! let t = table.hashcons(BoolExpr::Const(true));
+//! let not_t = table.hashcons(BoolExpr::Not(t));
+//!
+//! assert!(table.hashcons(BoolExpr::Not(t)) == not_t);
+//! assert_eq!(table.len(), 2);
+//! ```
+
+use ::bumpalo::Bump;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// # `ArenaHc<'a, T>`
+/// A handle to a value interned in an [`ArenaHcTable`], valid for the lifetime of its arena.
+///
+/// Handles are compared and hashed by address, which is equivalent to comparing the values
+/// for handles from the same table.
+pub struct ArenaHc<'a, T> {
+    elem: &'a T,
+}
+
+impl<'a, T> ArenaHc<'a, T> {
+    /// Retrieves a reference to the interned value, valid for the lifetime of the arena.
+    pub fn get(self) -> &'a T {
+        self.elem
+    }
+}
+
+impl<T> Clone for ArenaHc<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaHc<'_, T> {}
+
+impl<T> PartialEq for ArenaHc<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.elem, other.elem)
+    }
+}
+
+impl<T> Eq for ArenaHc<'_, T> {}
+
+impl<T> Hash for ArenaHc<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.elem, state);
+    }
+}
+
+impl<T> std::ops::Deref for ArenaHc<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.elem
+    }
+}
+
+impl<T> AsRef<T> for ArenaHc<'_, T> {
+    fn as_ref(&self) -> &T {
+        self.elem
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ArenaHc<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.elem.fmt(f)
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for ArenaHc<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.elem.fmt(f)
+    }
+}
+
+/// # `ArenaHcTable<'a, T>`
+/// A single-threaded hash consing table allocating its values in a borrowed arena.
+///
+/// ## Fields
+/// * `arena`: The arena the values are allocated in.
+/// * `table`: The interned values.
+pub struct ArenaHcTable<'a, T>
+where
+    T: Hash + Eq,
+{
+    arena: &'a Bump,
+    table: RefCell<HashSet<&'a T>>,
+}
+
+impl<'a, T> ArenaHcTable<'a, T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty table allocating in `arena`.
+    pub fn new(arena: &'a Bump) -> Self {
+        ArenaHcTable {
+            arena,
+            table: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Returns the handle of `value`, allocating it in the arena if it was not interned yet.
+    pub fn hashcons(&self, value: T) -> ArenaHc<'a, T> {
+        let mut table = self.table.borrow_mut();
+        if let Some(&elem) = table.get(&value) {
+            return ArenaHc { elem };
+        }
+
+        let elem: &'a T = self.arena.alloc(value);
+        table.insert(elem);
+        ArenaHc { elem }
+    }
+
+    /// Returns the number of values interned in this table.
+    pub fn len(&self) -> usize {
+        self.table.borrow().len()
+    }
+
+    /// Returns `true` if nothing has been interned in this table.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
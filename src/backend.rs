@@ -0,0 +1,191 @@
+//! # Table Backends
+//!
+//! The [`ConsTable`] trait covers what the tables of this crate have in common, so that code
+//! generic over it switches backends with one type parameter. Besides the thread-safe and the
+//! single-threaded `HcTable`, it is implemented by [`NullTable`], which stores every value on
+//! its own without looking for equal ones. Running a workload on both tells what hash consing
+//! gains it end to end.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::backend::{ConsTable, NullTable};
+//! use hash_cons::HcTable;
+//!
+//! fn load<B: ConsTable<String>>(table: &B) -> Vec<B::Handle> {
+//!     ["x", "y", "x"].iter().map(|name| table.hashcons(name.to_string())).collect()
+//! }
+//!
+//! let interned = HcTable::new();
+//! let names = load(&interned);
+//! assert_eq!(interned.len(), 2);
+//!
+//! let baseline = NullTable::new();
+//! let names = load(&baseline);
+//! assert_eq!(baseline.len(), 3);
+//! assert_eq!(names[0], names[2]);
+//! ```
+
+use crate::sync::{Arc, AtomicUsize, Ordering};
+use crate::{single_threaded, thread_safe};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// # `ConsTable`
+/// A table that stores values and hands out shared handles to them.
+pub trait ConsTable<T> {
+    /// The handle to a stored value.
+    type Handle: Clone + Deref<Target = T>;
+
+    /// Stores `value`, or finds an equal stored value, and returns a handle to it.
+    fn hashcons(&self, value: T) -> Self::Handle;
+
+    /// Returns the number of stored values.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if no values are stored.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, M> ConsTable<T> for thread_safe::HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    type Handle = thread_safe::Hc<T, M>;
+
+    fn hashcons(&self, value: T) -> Self::Handle {
+        thread_safe::HcTable::hashcons(self, value)
+    }
+
+    fn len(&self) -> usize {
+        thread_safe::HcTable::len(self)
+    }
+}
+
+impl<T, M> ConsTable<T> for single_threaded::HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    type Handle = single_threaded::Hc<T, M>;
+
+    fn hashcons(&self, value: T) -> Self::Handle {
+        single_threaded::HcTable::hashcons(self, value)
+    }
+
+    fn len(&self) -> usize {
+        single_threaded::HcTable::len(self)
+    }
+}
+
+/// # `NullTable<T>`
+/// A table that does not deduplicate: every value is stored in an allocation of its own.
+///
+/// Its handles compare and hash by value like those of `HcTable`, so that swapping it in only
+/// changes the memory used and the time spent hashing.
+///
+/// ## Fields
+/// * `stored`: The number of values stored so far.
+pub struct NullTable<T> {
+    stored: AtomicUsize,
+    _values: PhantomData<fn(T)>,
+}
+
+impl<T> NullTable<T> {
+    /// Creates an empty `NullTable`.
+    pub fn new() -> Self {
+        NullTable {
+            stored: AtomicUsize::new(0),
+            _values: PhantomData,
+        }
+    }
+
+    /// Stores `value` in a new allocation and returns a handle to it.
+    pub fn hashcons(&self, value: T) -> NullHc<T> {
+        self.stored.fetch_add(1, Ordering::Relaxed);
+        NullHc {
+            value: Arc::new(value),
+        }
+    }
+
+    /// Returns the number of values stored so far. Values are never removed, as the table does
+    /// not keep them.
+    pub fn len(&self) -> usize {
+        self.stored.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if no value was stored yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for NullTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for NullTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NullTable")
+            .field("stored", &self.len())
+            .finish()
+    }
+}
+
+impl<T> ConsTable<T> for NullTable<T> {
+    type Handle = NullHc<T>;
+
+    fn hashcons(&self, value: T) -> Self::Handle {
+        NullTable::hashcons(self, value)
+    }
+
+    fn len(&self) -> usize {
+        NullTable::len(self)
+    }
+}
+
+/// # `NullHc<T>`
+/// A handle to a value stored by a `NullTable`.
+pub struct NullHc<T> {
+    value: Arc<T>,
+}
+
+impl<T> Clone for NullHc<T> {
+    fn clone(&self) -> Self {
+        NullHc {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T> Deref for NullHc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for NullHc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        *self.value == *other.value
+    }
+}
+
+impl<T: Eq> Eq for NullHc<T> {}
+
+impl<T: Hash> Hash for NullHc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for NullHc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.value, f)
+    }
+}
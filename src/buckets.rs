@@ -0,0 +1,489 @@
+//! # Hash Buckets
+//!
+//! The storage shared by the table implementations. Entries are grouped by a precomputed hash
+//! and compared with a caller supplied predicate, so that the table decides which values are
+//! equivalent instead of the `Eq` implementation of the key. Hashes are 128 bits wide so that
+//! tables in trusted-hash mode can identify values by their hash alone. As they are hashes
+//! already, the map uses them as its own hashes unchanged, which also makes the layout of the
+//! entries depend on nothing but the hasher of the table.
+//!
+//! The tables do not run the `Hash` and `Eq` implementations of their values while their
+//! storage is locked, since those may use the table. They collect the entries of a hash with
+//! `bucket`, compare them after releasing the lock, and only then lock the storage again to
+//! change it. Other user code only runs in the predicates of `find_mut` and `remove`, before
+//! anything is changed, so a panicking predicate leaves the storage consistent. The predicates
+//! of `retain` run while entries are moved, and must not panic.
+
+use crate::hasher::IdentityHasher;
+#[cfg(feature = "history")]
+use crate::history::{History, HistoryKind};
+use crate::stats::{HashDiagnostics, TableStats};
+use crate::strategy::{IdAllocator, SequentialIds};
+use crate::sync::Arc;
+use std::collections::{HashMap, TryReserveError};
+use std::hash::BuildHasherDefault;
+use std::mem::size_of;
+use std::sync::{Mutex, PoisonError};
+
+/// The map of the storage, from hashes to the entries sharing them.
+type Map<K, W> = HashMap<u128, Vec<Entry<K, W>>, BuildHasherDefault<IdentityHasher>>;
+
+/// # `Entry<K, W>`
+/// A stored value, the weak pointer to its handle, and when it was stored.
+///
+/// ## Fields
+/// * `key`: The shared pointer to the stored value.
+/// * `weak`: The weak pointer to the handle allocation of the value.
+/// * `id`: The id of the value, assigned by the allocator of the storage.
+/// * `seq`: The sequence number of the value, unique within the storage and increasing in
+///   storing order whatever the allocator.
+/// * `generation`: The generation of the table when the value was stored.
+/// * `last_access`: The second, counted from the creation of the table, in which the value was
+///   last stored or looked up.
+pub(crate) struct Entry<K, W> {
+    pub(crate) key: K,
+    pub(crate) weak: W,
+    pub(crate) id: u64,
+    pub(crate) seq: u64,
+    pub(crate) generation: u64,
+    #[cfg(feature = "access-time")]
+    pub(crate) last_access: u32,
+}
+
+impl<K, W> Entry<K, W> {
+    /// Replaces the value of this entry by a newly stored one.
+    ///
+    /// ## Returns
+    /// The key and weak pointer of the replaced value.
+    pub(crate) fn replace(
+        &mut self,
+        key: K,
+        weak: W,
+        id: u64,
+        seq: u64,
+        generation: u64,
+    ) -> (K, W) {
+        self.id = id;
+        self.seq = seq;
+        self.generation = generation;
+        (
+            std::mem::replace(&mut self.key, key),
+            std::mem::replace(&mut self.weak, weak),
+        )
+    }
+}
+
+/// # `Buckets<K, W>`
+/// A multimap from hashes to entries.
+///
+/// ## Type Parameters
+/// * `K` - The shared pointer to a stored value.
+/// * `W` - The weak pointer to the handle allocation of that value.
+///
+/// ## Fields
+/// * `map`: The entries, grouped by hash.
+/// * `len`: The total number of entries.
+/// * `stats`: The counters of the table owning the storage.
+/// * `sweep`: The hashes the current round of the incremental sweep has still to visit.
+/// * `drift`: The hashes the current round of the hash drift check has still to visit.
+/// * `generation`: The generation new entries are stamped with.
+/// * `ids`: The allocator of the ids of new entries, shared with the storage of forks.
+/// * `next_seq`: The sequence number of the next stored value.
+/// * `created`: When the storage was created, the origin of the access times of its entries.
+/// * `history`: The most recent entries stored and removed.
+pub(crate) struct Buckets<K, W> {
+    map: Map<K, W>,
+    len: usize,
+    pub(crate) stats: TableStats,
+    sweep: Vec<u128>,
+    drift: Vec<u128>,
+    generation: u64,
+    ids: Arc<Mutex<Box<dyn IdAllocator>>>,
+    next_seq: u64,
+    #[cfg(feature = "access-time")]
+    created: std::time::Instant,
+    #[cfg(feature = "history")]
+    pub(crate) history: History,
+}
+
+impl<K, W> Buckets<K, W> {
+    /// Creates empty storage.
+    pub(crate) fn new() -> Self {
+        Buckets {
+            map: HashMap::default(),
+            len: 0,
+            stats: TableStats::default(),
+            sweep: Vec::new(),
+            drift: Vec::new(),
+            generation: 0,
+            ids: Arc::new(Mutex::new(Box::new(SequentialIds::default()))),
+            next_seq: 0,
+            #[cfg(feature = "access-time")]
+            created: std::time::Instant::now(),
+            #[cfg(feature = "history")]
+            history: History::new(),
+        }
+    }
+
+    /// Creates empty storage whose ids are assigned by `ids`.
+    pub(crate) fn with_ids(ids: Box<dyn IdAllocator>) -> Self {
+        Buckets {
+            ids: Arc::new(Mutex::new(ids)),
+            ..Self::new()
+        }
+    }
+
+    /// Creates empty storage whose ids are assigned by the allocator of this one, so that the
+    /// two never assign the same id unless the allocator does so for equal hashes.
+    pub(crate) fn sharing_ids(&self) -> Self {
+        Buckets {
+            ids: self.ids.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the number of entries.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the generation new entries are stamped with.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Returns the sequence number the next stored value receives.
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Allocates the id and the sequence number of a value about to be stored under `hash`.
+    ///
+    /// ## Returns
+    /// The id from the allocator of the storage, and the sequence number.
+    pub(crate) fn take_id(&mut self, hash: u128) -> (u64, u64) {
+        // A panic of the allocator leaves it as usable as any other state it may be in
+        let id = self
+            .ids
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .allocate(hash);
+        self.next_seq += 1;
+        (id, self.next_seq - 1)
+    }
+
+    /// Counts the replacement of the dead value `evicted` of an entry by a new value with the
+    /// id `id`.
+    #[cfg_attr(not(feature = "history"), allow(unused_variables))]
+    pub(crate) fn record_replacement(&mut self, evicted: u64, id: u64) {
+        self.stats.created += 1;
+        self.stats.removed += 1;
+        #[cfg(feature = "history")]
+        {
+            self.history.record(HistoryKind::Evicted, evicted);
+            self.history.record(HistoryKind::Interned, id);
+        }
+    }
+
+    /// Starts a new generation, returning it.
+    pub(crate) fn advance_generation(&mut self) -> u64 {
+        self.generation += 1;
+        self.generation
+    }
+
+    #[cfg(feature = "access-time")]
+    /// Returns the current second, counted from the creation of the storage.
+    pub(crate) fn now(&self) -> u32 {
+        u32::try_from(self.created.elapsed().as_secs()).unwrap_or(u32::MAX)
+    }
+
+    /// Returns the counters of the owning table, together with the current number of entries.
+    pub(crate) fn stats(&self) -> TableStats {
+        TableStats {
+            len: self.len,
+            ..self.stats
+        }
+    }
+
+    /// Returns the number of distinct hashes the map can hold without reallocating.
+    pub(crate) fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    /// Returns the fraction of the capacity of the map in use, or `0.0` if it holds nothing.
+    pub(crate) fn load_factor(&self) -> f64 {
+        match self.map.capacity() {
+            0 => 0.0,
+            capacity => self.map.len() as f64 / capacity as f64,
+        }
+    }
+
+    /// Shrinks the map and its buckets as much as possible.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.map.values_mut().for_each(Vec::shrink_to_fit);
+        self.map.shrink_to_fit();
+    }
+
+    /// Returns the distribution of the entries over their hashes.
+    pub(crate) fn diagnostics(&self) -> HashDiagnostics {
+        let mut diagnostics = HashDiagnostics {
+            distinct_hashes: self.map.len(),
+            ..HashDiagnostics::default()
+        };
+        for bucket in self.map.values() {
+            let shared = bucket.len();
+            if diagnostics.occupancy.len() <= shared {
+                diagnostics.occupancy.resize(shared + 1, 0);
+            }
+            diagnostics.occupancy[shared] += 1;
+            if shared > 1 {
+                diagnostics.colliding_values += shared;
+            }
+            diagnostics.max_collisions = diagnostics.max_collisions.max(shared);
+        }
+        diagnostics
+    }
+
+    /// Returns all entries, in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Entry<K, W>> {
+        self.map.values().flatten()
+    }
+
+    /// Returns all entries with their hashes, in no particular order.
+    pub(crate) fn iter_hashed(&self) -> impl Iterator<Item = (u128, &Entry<K, W>)> {
+        self.map
+            .iter()
+            .flat_map(|(&hash, bucket)| bucket.iter().map(move |entry| (hash, entry)))
+    }
+
+    /// Returns an estimate of the bytes allocated by the map and its buckets, not counting
+    /// the memory the entries point to.
+    pub(crate) fn allocated_bytes(&self) -> usize {
+        // The map stores one control byte next to each slot.
+        let map = self.map.capacity() * (size_of::<(u128, Vec<Entry<K, W>>)>() + 1);
+        let buckets: usize = self.map.values().map(Vec::capacity).sum();
+        map + buckets * size_of::<Entry<K, W>>()
+    }
+
+    /// Returns the entries with the given hash.
+    pub(crate) fn bucket(&self, hash: u128) -> &[Entry<K, W>] {
+        self.map.get(&hash).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the entry with the given hash and sequence number, if it is still stored.
+    #[cfg(feature = "access-time")]
+    pub(crate) fn find_seq_mut(&mut self, hash: u128, seq: u64) -> Option<&mut Entry<K, W>> {
+        self.map
+            .get_mut(&hash)?
+            .iter_mut()
+            .find(|entry| entry.seq == seq)
+    }
+
+    /// Returns the first entry with the given hash whose key satisfies `matches`.
+    pub(crate) fn find_mut(
+        &mut self,
+        hash: u128,
+        mut matches: impl FnMut(&K) -> bool,
+    ) -> Option<&mut Entry<K, W>> {
+        self.map
+            .get_mut(&hash)?
+            .iter_mut()
+            .find(|entry| matches(&entry.key))
+    }
+
+    /// Adds an entry with an id and sequence number from `take_id` without checking for an
+    /// equivalent one.
+    ///
+    /// ## Returns
+    /// The number of entries sharing `hash`, including the new one.
+    pub(crate) fn insert(&mut self, hash: u128, (id, seq): (u64, u64), key: K, weak: W) -> usize {
+        let capacity = self.map.capacity();
+        #[cfg(feature = "access-time")]
+        let now = self.now();
+        let bucket = self.map.entry(hash).or_default();
+        bucket.push(Entry {
+            key,
+            weak,
+            id,
+            seq,
+            generation: self.generation,
+            #[cfg(feature = "access-time")]
+            last_access: now,
+        });
+        let shared = bucket.len();
+        self.record_growth(capacity);
+        self.len += 1;
+        self.stats.created += 1;
+        #[cfg(feature = "history")]
+        self.history.record(HistoryKind::Interned, id);
+        shared
+    }
+
+    /// Counts a lookup that found the value of the entry with the given hash and sequence
+    /// number, and refreshes its access time if the entry is still stored.
+    #[cfg_attr(not(feature = "access-time"), allow(unused_variables))]
+    pub(crate) fn record_reuse(&mut self, hash: u128, seq: u64) {
+        self.stats.reused += 1;
+        #[cfg(feature = "access-time")]
+        {
+            let now = self.now();
+            if let Some(entry) = self.find_seq_mut(hash, seq) {
+                entry.last_access = now;
+            }
+        }
+    }
+
+    /// Counts a reallocation of the map if its capacity changed from `capacity`.
+    fn record_growth(&mut self, capacity: usize) {
+        if self.map.capacity() != capacity {
+            self.stats.map_reallocations += 1;
+        }
+    }
+
+    /// Reserves room for at least `additional` more hashes.
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let capacity = self.map.capacity();
+        self.map.try_reserve(additional)?;
+        self.record_growth(capacity);
+        Ok(())
+    }
+
+    /// Reserves room for one more entry with the given hash, so that a following `insert` with
+    /// that hash does not allocate.
+    pub(crate) fn try_reserve_entry(&mut self, hash: u128) -> Result<(), TryReserveError> {
+        match self.map.get_mut(&hash) {
+            Some(bucket) => bucket.try_reserve(1),
+            None => {
+                let mut bucket = Vec::new();
+                bucket.try_reserve(1)?;
+                self.try_reserve(1)?;
+                self.map.insert(hash, bucket);
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes and returns the first entry with the given hash that satisfies `matches`.
+    pub(crate) fn remove(
+        &mut self,
+        hash: u128,
+        matches: impl FnMut(&Entry<K, W>) -> bool,
+    ) -> Option<Entry<K, W>> {
+        let bucket = self.map.get_mut(&hash)?;
+        let index = bucket.iter().position(matches)?;
+        let entry = bucket.swap_remove(index);
+        if bucket.is_empty() {
+            self.map.remove(&hash);
+        }
+        self.len -= 1;
+        self.stats.removed += 1;
+        #[cfg(feature = "history")]
+        self.history.record(HistoryKind::Evicted, entry.id);
+        Some(entry)
+    }
+
+    /// Removes every entry for which `keep` returns `false`, returning the removed entries so
+    /// that the caller can drop them outside of its lock.
+    #[cfg_attr(feature = "auto-cleanup", allow(dead_code))]
+    pub(crate) fn retain(
+        &mut self,
+        mut keep: impl FnMut(&Entry<K, W>) -> bool,
+    ) -> Vec<Entry<K, W>> {
+        let mut removed = Vec::new();
+        self.map.retain(|_, bucket| {
+            let mut index = 0;
+            while index < bucket.len() {
+                if keep(&bucket[index]) {
+                    index += 1;
+                } else {
+                    removed.push(bucket.swap_remove(index));
+                }
+            }
+            !bucket.is_empty()
+        });
+        self.len -= removed.len();
+        self.stats.removed += removed.len() as u64;
+        #[cfg(feature = "history")]
+        for entry in &removed {
+            self.history.record(HistoryKind::Evicted, entry.id);
+        }
+        removed
+    }
+
+    /// Like `retain`, but only visits the entries of the hashes not yet visited in the current
+    /// round, up to `max_entries` entries or at least one hash. A round starts with the hashes
+    /// stored when it starts, and ends once each of them was visited.
+    ///
+    /// ## Note
+    /// The hashes of a round are snapshotted when it starts, so that inserting entries or
+    /// growing the map while it runs neither skips nor revisits any of them. Hashes first used
+    /// during a round are visited in the next one.
+    #[cfg_attr(feature = "auto-cleanup", allow(dead_code))]
+    pub(crate) fn retain_batch(
+        &mut self,
+        max_entries: usize,
+        mut keep: impl FnMut(&Entry<K, W>) -> bool,
+    ) -> Vec<Entry<K, W>> {
+        let mut removed = Vec::new();
+        for hash in Self::next_batch(&self.map, &mut self.sweep, max_entries) {
+            let Some(bucket) = self.map.get_mut(&hash) else {
+                continue;
+            };
+            let mut index = 0;
+            while index < bucket.len() {
+                if keep(&bucket[index]) {
+                    index += 1;
+                } else {
+                    removed.push(bucket.swap_remove(index));
+                }
+            }
+            if bucket.is_empty() {
+                self.map.remove(&hash);
+            }
+        }
+
+        self.len -= removed.len();
+        self.stats.removed += removed.len() as u64;
+        #[cfg(feature = "history")]
+        for entry in &removed {
+            self.history.record(HistoryKind::Evicted, entry.id);
+        }
+        removed
+    }
+
+    /// Returns the entries of the hashes not yet visited in the current round of the hash drift
+    /// check, with their hashes, up to `max_entries` entries or at least one hash. Rounds are
+    /// snapshotted like the ones of `retain_batch`.
+    pub(crate) fn drift_batch(&mut self, max_entries: usize) -> Vec<(u128, &Entry<K, W>)> {
+        let hashes = Self::next_batch(&self.map, &mut self.drift, max_entries);
+        hashes
+            .into_iter()
+            .flat_map(|hash| self.bucket(hash).iter().map(move |entry| (hash, entry)))
+            .collect()
+    }
+
+    /// Takes the hashes of `round` to visit next, up to `max_entries` entries or at least one
+    /// hash, after starting a new round with the stored hashes if it is over. Hashes whose
+    /// entries were all removed since the round started are skipped.
+    fn next_batch(map: &Map<K, W>, round: &mut Vec<u128>, max_entries: usize) -> Vec<u128> {
+        if round.is_empty() {
+            *round = map.keys().copied().collect();
+        }
+        let mut batch = Vec::new();
+        let mut entries = 0;
+        while let Some(&hash) = round.last() {
+            let Some(bucket) = map.get(&hash) else {
+                round.pop();
+                continue;
+            };
+            if !batch.is_empty() && entries + bucket.len() > max_entries {
+                break;
+            }
+            round.pop();
+            entries += bucket.len();
+            batch.push(hash);
+        }
+        batch
+    }
+}
@@ -0,0 +1,75 @@
+//! # `bytes` Interop
+//!
+//! Hash consing of byte strings held in [`bytes`](https://docs.rs/bytes) buffers, for network
+//! servers deduplicating header values and payload fragments as they arrive.
+//!
+//! A `Bytes` is stored as is, so interning it copies no data, and handing it back with
+//! `Bytes::from(&hc)` only bumps a reference count. Lookups borrow a `&[u8]`, which is copied
+//! into a new buffer only if the table does not hold it yet.
+//!
+//! A stored `Bytes` keeps the whole buffer it was split from alive. Values that outlive their
+//! buffer by far, such as a short header of a large request, are better interned with
+//! `hashcons_slice`, which stores a copy of just the value.
+//!
+//! ## Example
+//! ```
+//! use bytes::Bytes;
+//! use hash_cons::bytes::{get_slice, hashcons_slice};
+//! use hash_cons::HcTable;
+//!
+//! let table = HcTable::new();
+//! let request = Bytes::from_static(b"GET /index.html");
+//! let method = table.hashcons(request.slice(0..3));
+//!
+//! assert_eq!(get_slice(&table, b"GET"), Some(method.clone()));
+//! assert_eq!(hashcons_slice(&table, b"GET"), method);
+//! assert!(get_slice(&table, b"PUT").is_none());
+//! ```
+
+use crate::{Hc, HcTable};
+use ::bytes::{Bytes, BytesMut};
+
+impl From<&Hc<Bytes>> for Bytes {
+    /// Returns the stored buffer, sharing its data with the table.
+    fn from(hc: &Hc<Bytes>) -> Self {
+        hc.get().clone()
+    }
+}
+
+impl From<Hc<Bytes>> for Bytes {
+    /// Returns the stored buffer, sharing its data with the table.
+    fn from(hc: Hc<Bytes>) -> Self {
+        Bytes::from(&hc)
+    }
+}
+
+/// Hash conses the contents of a `BytesMut` into `table`, freezing it without copying.
+///
+/// ## Returns
+/// The handle of the byte string in `table`, shared with every other handle of an equal one.
+pub fn hashcons_bytes_mut(table: &HcTable<Bytes>, bytes: BytesMut) -> Hc<Bytes> {
+    table.hashcons(bytes.freeze())
+}
+
+/// Hash conses a borrowed byte string into `table`, copying it only if `table` does not hold
+/// it yet.
+///
+/// ## Returns
+/// The handle of the byte string in `table`, shared with every other handle of an equal one.
+pub fn hashcons_slice(table: &HcTable<Bytes>, bytes: &[u8]) -> Hc<Bytes> {
+    table.get_or_insert_with(bytes, Bytes::copy_from_slice)
+}
+
+/// Looks up a borrowed byte string in `table`, without copying or storing it.
+///
+/// ## Returns
+/// The handle of the byte string, if `table` holds it.
+///
+/// ## Note
+/// Tables with a custom equivalence, hash function or normalization cannot be searched by a
+/// borrowed key and never find the byte string, see `HcTable::get_or_insert_with()`.
+pub fn get_slice(table: &HcTable<Bytes>, bytes: &[u8]) -> Option<Hc<Bytes>> {
+    table
+        .get_or_try_insert_with(bytes, |_| Err::<Bytes, _>(()))
+        .ok()
+}
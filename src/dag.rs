@@ -0,0 +1,726 @@
+//! # Hash Consed DAGs
+//!
+//! Recursive types whose children are `Hc<T>` handles form directed acyclic graphs in which
+//! equal subterms are shared. The [`HashConsed`] trait exposes that structure so that generic
+//! utilities can walk a DAG visiting every shared node only once.
+
+use crate::{Hc, HcTable};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+
+/// # `HashConsed`
+/// A node type whose direct children are hash consed handles of the same type.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{HashConsed, Hc};
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum BoolExpr {
+///     Const(bool),
+///     And(Hc<BoolExpr>, Hc<BoolExpr>),
+///     Not(Hc<BoolExpr>),
+/// }
+///
+/// impl HashConsed for BoolExpr {
+///     fn children(&self) -> Vec<&Hc<BoolExpr>> {
+///         match self {
+///             BoolExpr::Const(_) => vec![],
+///             BoolExpr::And(lhs, rhs) => vec![lhs, rhs],
+///             BoolExpr::Not(inner) => vec![inner],
+///         }
+///     }
+/// }
+/// ```
+pub trait HashConsed: Hash + Eq + Sized {
+    /// Returns the direct children of this node, in order.
+    fn children(&self) -> Vec<&Hc<Self>>;
+
+    /// Returns `true` if this node and `other` differ in their children at most.
+    ///
+    /// Used by [`diff`] to decide whether two nodes are compared child by child or reported as
+    /// a whole. The default compares enum variants, which suits types whose variants hold
+    /// nothing but their children and leaves.
+    fn same_shape(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl<T> Hc<T>
+where
+    T: HashConsed,
+{
+    /// Compares the values of this handle and `other` structurally, also if they come from
+    /// different tables.
+    ///
+    /// Handles of the same table are equal exactly when they point to the same value, but
+    /// handles of different tables never do, and comparing them with `==` walks every path
+    /// of both DAGs. This method compares each pair of distinct nodes once instead: leaves with
+    /// `T::eq`, and other nodes by their shape, see [`HashConsed::same_shape`], and children.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::{HashConsed, Hc, HcTable};
+    ///
+    /// #[derive(Hash, PartialEq, Eq)]
+    /// enum BoolExpr {
+    ///     Const(bool),
+    ///     Not(Hc<BoolExpr>),
+    /// }
+    ///
+    /// impl HashConsed for BoolExpr {
+    ///     fn children(&self) -> Vec<&Hc<BoolExpr>> {
+    ///         match self {
+    ///             BoolExpr::Const(_) => vec![],
+    ///             BoolExpr::Not(inner) => vec![inner],
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let (first, second) = (HcTable::new(), HcTable::new());
+    /// let a = first.hashcons(BoolExpr::Not(first.hashcons(BoolExpr::Const(true))));
+    /// let b = second.hashcons(BoolExpr::Not(second.hashcons(BoolExpr::Const(true))));
+    ///
+    /// assert!(a.deep_eq_across_tables(&b));
+    /// ```
+    pub fn deep_eq_across_tables(&self, other: &Self) -> bool {
+        // Every pair on the stack must be equal for the roots to be, so the first unequal pair
+        // decides the result, and a pair compared before needs no second look.
+        let mut compared = HashSet::new();
+        let mut stack = vec![(self.clone(), other.clone())];
+
+        while let Some((a, b)) = stack.pop() {
+            if a.addr() == b.addr() || !compared.insert((a.addr(), b.addr())) {
+                continue;
+            }
+
+            let a_children = a.children();
+            let b_children = b.children();
+            if a_children.is_empty() && b_children.is_empty() {
+                if a.get() != b.get() {
+                    return false;
+                }
+                continue;
+            }
+            if a_children.len() != b_children.len() || !a.get().same_shape(b.get()) {
+                return false;
+            }
+            stack.extend(
+                a_children
+                    .into_iter()
+                    .zip(b_children)
+                    .map(|(a, b)| (a.clone(), b.clone())),
+            );
+        }
+        true
+    }
+}
+
+/// Visits every distinct node reachable from `root` exactly once, children before parents.
+///
+/// Nodes are told apart by the address of their shared allocation, so a subterm shared by
+/// several parents is visited a single time. The traversal uses an explicit stack and does not
+/// overflow on deep terms.
+pub(crate) fn postorder<T, F>(root: &Hc<T>, mut visit: F)
+where
+    T: HashConsed,
+    F: FnMut(&Hc<T>),
+{
+    let mut visited = HashSet::new();
+    let mut stack = vec![(root.clone(), false)];
+
+    while let Some((node, expanded)) = stack.pop() {
+        if visited.contains(&node.addr()) {
+            continue;
+        }
+
+        if expanded {
+            visited.insert(node.addr());
+            visit(&node);
+        } else {
+            let children: Vec<Hc<T>> = node
+                .children()
+                .into_iter()
+                .filter(|child| !visited.contains(&child.addr()))
+                .cloned()
+                .collect();
+            stack.push((node, true));
+            // Pushed in reverse so that the leftmost child is visited first.
+            stack.extend(children.into_iter().rev().map(|child| (child, false)));
+        }
+    }
+}
+
+/// Displays the DAG below `root` with explicit sharing.
+///
+/// Every node with children that is shared by several parents is bound once with `let`, and
+/// referred to by name afterwards, so that the output grows with the number of distinct nodes
+/// instead of the number of paths. The `Display` implementation of a node is expected to write
+/// only its own label, such as `And` or `true`: the children are appended in parentheses.
+///
+/// ## Example
+/// ```
+/// use hash_cons::dag::display_shared;
+/// use hash_cons::{HashConsed, Hc, HcTable};
+/// use std::fmt;
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum BoolExpr {
+///     Const(bool),
+///     And(Hc<BoolExpr>, Hc<BoolExpr>),
+///     Not(Hc<BoolExpr>),
+/// }
+///
+/// impl HashConsed for BoolExpr {
+///     fn children(&self) -> Vec<&Hc<BoolExpr>> {
+///         match self {
+///             BoolExpr::Const(_) => vec![],
+///             BoolExpr::And(lhs, rhs) => vec![lhs, rhs],
+///             BoolExpr::Not(inner) => vec![inner],
+///         }
+///     }
+/// }
+///
+/// impl fmt::Display for BoolExpr {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         match self {
+///             BoolExpr::Const(value) => write!(f, "{value}"),
+///             BoolExpr::And(..) => f.write_str("And"),
+///             BoolExpr::Not(_) => f.write_str("Not"),
+///         }
+///     }
+/// }
+///
+/// let table = HcTable::new();
+/// let not = table.hashcons(BoolExpr::Not(table.hashcons(BoolExpr::Const(true))));
+/// let and = table.hashcons(BoolExpr::And(not.clone(), not));
+///
+/// assert_eq!(display_shared(&and).to_string(), "let x0 = Not(true);\nAnd(x0, x0)");
+/// ```
+pub fn display_shared<T>(root: &Hc<T>) -> DisplayShared<'_, T>
+where
+    T: HashConsed + fmt::Display,
+{
+    DisplayShared { root }
+}
+
+/// # `DisplayShared<'a, T>`
+/// Displays a DAG with explicit sharing. Created with [`display_shared`].
+pub struct DisplayShared<'a, T>
+where
+    T: HashConsed,
+{
+    root: &'a Hc<T>,
+}
+
+impl<T> fmt::Display for DisplayShared<'_, T>
+where
+    T: HashConsed + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parents: HashMap<usize, usize> = HashMap::new();
+        let mut nodes = Vec::new();
+        postorder(self.root, |node| {
+            for child in node.children() {
+                *parents.entry(child.addr()).or_default() += 1;
+            }
+            nodes.push(node.clone());
+        });
+
+        // Children come first in postorder, so names are bound before they are used. Leaves
+        // have a constant size and are repeated instead.
+        let mut names = HashMap::new();
+        for node in &nodes {
+            let shared = parents.get(&node.addr()).is_some_and(|&count| count > 1);
+            if shared && !node.children().is_empty() {
+                let name = format!("x{}", names.len());
+                write!(f, "let {name} = ")?;
+                write_node(f, node, &names)?;
+                writeln!(f, ";")?;
+                names.insert(node.addr(), name);
+            }
+        }
+        write_node(f, self.root, &names)
+    }
+}
+
+/// Writes `node`, referring to the children bound in `names` by name and writing the others
+/// inline.
+fn write_node<T>(
+    f: &mut fmt::Formatter<'_>,
+    node: &Hc<T>,
+    names: &HashMap<usize, String>,
+) -> fmt::Result
+where
+    T: HashConsed + fmt::Display,
+{
+    write!(f, "{}", node.get())?;
+    let children = node.children();
+    if children.is_empty() {
+        return Ok(());
+    }
+    f.write_str("(")?;
+    for (index, child) in children.into_iter().enumerate() {
+        if index > 0 {
+            f.write_str(", ")?;
+        }
+        match names.get(&child.addr()) {
+            Some(name) => f.write_str(name)?,
+            None => write_node(f, child, names)?,
+        }
+    }
+    f.write_str(")")
+}
+
+/// # `Difference<T>`
+/// A pair of differing subterms found by [`diff`].
+pub struct Difference<T>
+where
+    T: HashConsed,
+{
+    /// The indices of the children leading from the roots to the subterms.
+    pub path: Vec<usize>,
+    /// The subterm below the first root.
+    pub old: Hc<T>,
+    /// The subterm below the second root.
+    pub new: Hc<T>,
+}
+
+impl<T> Clone for Difference<T>
+where
+    T: HashConsed,
+{
+    fn clone(&self) -> Self {
+        Difference {
+            path: self.path.clone(),
+            old: self.old.clone(),
+            new: self.new.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Difference<T>
+where
+    T: HashConsed + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Difference")
+            .field("path", &self.path)
+            .field("old", &self.old)
+            .field("new", &self.new)
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for Difference<T>
+where
+    T: HashConsed + fmt::Display,
+{
+    /// Formats the difference as `path: old -> new`, printing both subterms with
+    /// [`display_shared`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: {} -> {}",
+            self.path,
+            display_shared(&self.old),
+            display_shared(&self.new)
+        )
+    }
+}
+
+/// Compares the DAGs below `old` and `new`, returning the smallest differing subterms.
+///
+/// Pointer-identical subterms are skipped without looking into them, so the cost depends on the
+/// size of the changes rather than the size of the terms. Two differing nodes are compared child
+/// by child if they have the same shape, see [`HashConsed::same_shape`], and the same number of
+/// children, and reported as a whole otherwise. A pair of subterms reachable along several
+/// paths is reported once, at the first path in left-to-right order.
+///
+/// ## Example
+/// ```
+/// use hash_cons::dag::diff;
+/// use hash_cons::{HashConsed, Hc, HcTable};
+///
+/// #[derive(Hash, PartialEq, Eq, Debug)]
+/// enum BoolExpr {
+///     Const(bool),
+///     And(Hc<BoolExpr>, Hc<BoolExpr>),
+///     Not(Hc<BoolExpr>),
+/// }
+///
+/// impl HashConsed for BoolExpr {
+///     fn children(&self) -> Vec<&Hc<BoolExpr>> {
+///         match self {
+///             BoolExpr::Const(_) => vec![],
+///             BoolExpr::And(lhs, rhs) => vec![lhs, rhs],
+///             BoolExpr::Not(inner) => vec![inner],
+///         }
+///     }
+/// }
+///
+/// let table = HcTable::new();
+/// let yes = table.hashcons(BoolExpr::Const(true));
+/// let no = table.hashcons(BoolExpr::Const(false));
+/// let old = table.hashcons(BoolExpr::And(yes.clone(), table.hashcons(BoolExpr::Not(yes.clone()))));
+/// let new = table.hashcons(BoolExpr::And(yes.clone(), table.hashcons(BoolExpr::Not(no.clone()))));
+///
+/// let differences = diff(&old, &new);
+///
+/// assert_eq!(differences.len(), 1);
+/// assert_eq!(differences[0].path, [1, 0]);
+/// assert!(differences[0].old == yes && differences[0].new == no);
+/// ```
+pub fn diff<T>(old: &Hc<T>, new: &Hc<T>) -> Vec<Difference<T>>
+where
+    T: HashConsed,
+{
+    let mut differences = Vec::new();
+    let mut compared = HashSet::new();
+    let mut stack = vec![(old.clone(), new.clone(), Vec::new())];
+
+    while let Some((old, new, path)) = stack.pop() {
+        if old.addr() == new.addr() || !compared.insert((old.addr(), new.addr())) {
+            continue;
+        }
+
+        let old_children = old.children();
+        let new_children = new.children();
+        if old_children.len() != new_children.len() || !old.get().same_shape(new.get()) {
+            differences.push(Difference { path, old, new });
+            continue;
+        }
+        let differing: Vec<usize> = (0..old_children.len())
+            .filter(|&index| old_children[index].addr() != new_children[index].addr())
+            .collect();
+        // Nodes of the same shape with identical children differ in something else.
+        if differing.is_empty() {
+            differences.push(Difference { path, old, new });
+            continue;
+        }
+
+        // Pushed in reverse so that the leftmost child is compared first.
+        let pairs: Vec<_> = differing
+            .into_iter()
+            .rev()
+            .map(|index| {
+                let mut child_path = path.clone();
+                child_path.push(index);
+                (
+                    old_children[index].clone(),
+                    new_children[index].clone(),
+                    child_path,
+                )
+            })
+            .collect();
+        stack.extend(pairs);
+    }
+    differences
+}
+
+/// # `Transform<'a, T, U>`
+/// Rebuilds DAGs of one table in another table, mapping every node with a function that may
+/// change its type, e.g. when lowering terms from one intermediate representation to the next.
+///
+/// Each source node is mapped once, the first time it is reached, and every later occurrence
+/// reuses its image. Sharing in the source therefore carries over to the target, and the cost
+/// of a transformation is linear in the number of distinct nodes. The images are remembered
+/// across calls, so that several roots of the same source can be transformed one after the
+/// other.
+///
+/// ## Fields
+/// * `table`: The table images are interned in.
+/// * `images`: The mapped nodes and their images, by address of the mapped node.
+///
+/// ## Example
+/// ```
+/// use hash_cons::dag::Transform;
+/// use hash_cons::{HashConsed, Hc, HcTable};
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum Expr {
+///     Lit(u32),
+///     Add(Hc<Expr>, Hc<Expr>),
+/// }
+///
+/// impl HashConsed for Expr {
+///     fn children(&self) -> Vec<&Hc<Expr>> {
+///         match self {
+///             Expr::Lit(_) => vec![],
+///             Expr::Add(lhs, rhs) => vec![lhs, rhs],
+///         }
+///     }
+/// }
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum Op {
+///     Push(u32),
+///     Sum(Vec<Hc<Op>>),
+/// }
+///
+/// let exprs = HcTable::new();
+/// let one = exprs.hashcons(Expr::Lit(1));
+/// let sum = exprs.hashcons(Expr::Add(one.clone(), one));
+///
+/// let ops = HcTable::new();
+/// let mut lower = Transform::new(&ops);
+/// let lowered = lower.apply(&sum, |expr, children| match expr {
+///     Expr::Lit(n) => Op::Push(*n),
+///     Expr::Add(..) => Op::Sum(children.to_vec()),
+/// });
+///
+/// assert!(matches!(&*lowered, Op::Sum(children) if children[0] == children[1]));
+/// assert_eq!(ops.len(), 2);
+/// ```
+pub struct Transform<'a, T, U>
+where
+    T: HashConsed,
+    U: Hash + Eq,
+{
+    table: &'a HcTable<U>,
+    images: HashMap<usize, (Hc<T>, Hc<U>)>,
+}
+
+impl<'a, T, U> Transform<'a, T, U>
+where
+    T: HashConsed,
+    U: Hash + Eq,
+{
+    /// Creates a `Transform` into `table`.
+    pub fn new(table: &'a HcTable<U>) -> Self {
+        Transform {
+            table,
+            images: HashMap::new(),
+        }
+    }
+
+    /// Returns the image of `root`, mapping every node below it that was not mapped before.
+    ///
+    /// ## Parameters
+    /// * `root`: The root of the DAG to rebuild.
+    /// * `map`: Maps a node, given the images of its children in order.
+    ///
+    /// Nodes are mapped children first with an explicit stack, so deep terms do not overflow.
+    pub fn apply(&mut self, root: &Hc<T>, mut map: impl FnMut(&T, &[Hc<U>]) -> U) -> Hc<U> {
+        let mut stack = vec![(root.clone(), false)];
+
+        while let Some((node, expanded)) = stack.pop() {
+            if self.images.contains_key(&node.addr()) {
+                continue;
+            }
+
+            if expanded {
+                let children: Vec<Hc<U>> = node
+                    .children()
+                    .into_iter()
+                    .map(|child| self.images[&child.addr()].1.clone())
+                    .collect();
+                let image = self.table.hashcons(map(node.get(), &children));
+                self.images.insert(node.addr(), (node, image));
+            } else {
+                let children: Vec<Hc<T>> = node
+                    .children()
+                    .into_iter()
+                    .filter(|child| !self.images.contains_key(&child.addr()))
+                    .cloned()
+                    .collect();
+                stack.push((node, true));
+                // Pushed in reverse so that the leftmost child is mapped first.
+                stack.extend(children.into_iter().rev().map(|child| (child, false)));
+            }
+        }
+        self.images[&root.addr()].1.clone()
+    }
+
+    /// Returns the image of `node`, if it was mapped.
+    pub fn get(&self, node: &Hc<T>) -> Option<Hc<U>> {
+        self.images
+            .get(&node.addr())
+            .map(|(_, image)| image.clone())
+    }
+
+    /// Returns the number of mapped nodes.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Returns `true` if no node was mapped yet.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+/// Compares two nodes apart from their children, see `CanonicalOrder::new`.
+type LabelOrder<T> = Box<dyn Fn(&T, &T) -> Ordering>;
+
+/// # `CanonicalOrder<T>`
+/// A total order on DAGs, comparing two nodes by their labels and then their children
+/// lexicographically, e.g. to sort the operands of commutative operators canonically.
+///
+/// Comparing two nodes with `Ord` would compare shared subterms once per path. This order
+/// remembers the result for every pair of distinct nodes it compared, so that each pair is
+/// compared once, also across calls, and sorting many terms sharing subterms stays cheap.
+///
+/// The labels are compared with a function given to `new`, which must compare the nodes as a
+/// whole, apart from their children, and be a total order on them: the order of two nodes is
+/// decided by their labels first, then by their children in order, and a node with fewer
+/// children comes first if all others are equal. Equal nodes, and only those, compare equal.
+///
+/// ## Fields
+/// * `cmp_label`: Compares two nodes apart from their children.
+/// * `memo`: The order of every pair of distinct nodes compared so far, by their addresses.
+/// * `nodes`: The nodes compared so far, kept alive so that their addresses are not reused.
+///
+/// ## Example
+/// ```
+/// use hash_cons::dag::CanonicalOrder;
+/// use hash_cons::{HashConsed, Hc, HcTable};
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum Expr {
+///     Lit(u32),
+///     Add(Hc<Expr>, Hc<Expr>),
+/// }
+///
+/// impl HashConsed for Expr {
+///     fn children(&self) -> Vec<&Hc<Expr>> {
+///         match self {
+///             Expr::Lit(_) => vec![],
+///             Expr::Add(lhs, rhs) => vec![lhs, rhs],
+///         }
+///     }
+/// }
+///
+/// let table = HcTable::new();
+/// let (one, two) = (table.hashcons(Expr::Lit(1)), table.hashcons(Expr::Lit(2)));
+/// let sum = table.hashcons(Expr::Add(two.clone(), one.clone()));
+///
+/// // Literals come before sums, and are ordered by value
+/// let mut order = CanonicalOrder::new(|a: &Expr, b: &Expr| match (a, b) {
+///     (Expr::Lit(a), Expr::Lit(b)) => a.cmp(b),
+///     (Expr::Lit(_), Expr::Add(..)) => std::cmp::Ordering::Less,
+///     (Expr::Add(..), Expr::Lit(_)) => std::cmp::Ordering::Greater,
+///     (Expr::Add(..), Expr::Add(..)) => std::cmp::Ordering::Equal,
+/// });
+/// let mut operands = vec![sum.clone(), two.clone(), one.clone()];
+/// order.sort(&mut operands);
+///
+/// assert!(operands == [one, two, sum]);
+/// ```
+pub struct CanonicalOrder<T>
+where
+    T: HashConsed,
+{
+    cmp_label: LabelOrder<T>,
+    memo: HashMap<(usize, usize), Ordering>,
+    nodes: HashMap<usize, Hc<T>>,
+}
+
+impl<T> CanonicalOrder<T>
+where
+    T: HashConsed,
+{
+    /// Creates a `CanonicalOrder` comparing the labels of nodes with `cmp_label`.
+    pub fn new(cmp_label: impl Fn(&T, &T) -> Ordering + 'static) -> Self {
+        CanonicalOrder {
+            cmp_label: Box::new(cmp_label),
+            memo: HashMap::new(),
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Compares the DAGs below `a` and `b`.
+    ///
+    /// Pairs of nodes are compared children first with an explicit stack, so deep terms do not
+    /// overflow.
+    pub fn cmp(&mut self, a: &Hc<T>, b: &Hc<T>) -> Ordering {
+        if let Some(order) = self.shallow_cmp(a, b) {
+            return order;
+        }
+
+        // Every frame holds a pair of nodes with equal labels and the index of the next pair of
+        // children to compare. `finished` is the order of the pair whose frame was popped last.
+        let mut stack = vec![(a.clone(), b.clone(), 0)];
+        let mut finished = None;
+        while let Some((a, b, next)) = stack.last_mut() {
+            let mut order = match finished.take() {
+                Some(Ordering::Equal) => {
+                    *next += 1;
+                    None
+                }
+                decided => decided,
+            };
+            let mut descend = None;
+            if order.is_none() {
+                let (a_children, b_children) = (a.children(), b.children());
+                while order.is_none() && descend.is_none() {
+                    match (a_children.get(*next), b_children.get(*next)) {
+                        (Some(a_child), Some(b_child)) => {
+                            match self.shallow_cmp(a_child, b_child) {
+                                Some(Ordering::Equal) => *next += 1,
+                                Some(decided) => order = Some(decided),
+                                None => descend = Some(((*a_child).clone(), (*b_child).clone(), 0)),
+                            }
+                        }
+                        (a_child, b_child) => {
+                            order = Some(a_child.is_some().cmp(&b_child.is_some()))
+                        }
+                    }
+                }
+            }
+
+            match (order, descend) {
+                (_, Some(frame)) => stack.push(frame),
+                (Some(order), None) => {
+                    let (a, b, _) = stack.pop().expect("the frame is on the stack");
+                    self.remember(a, b, order);
+                    finished = Some(order);
+                }
+                (None, None) => unreachable!("the frame is neither decided nor descended into"),
+            }
+        }
+        finished.expect("the roots were compared")
+    }
+
+    /// Sorts `nodes` by this order.
+    pub fn sort(&mut self, nodes: &mut [Hc<T>]) {
+        nodes.sort_by(|a, b| self.cmp(a, b));
+    }
+
+    /// Returns the number of pairs of distinct nodes compared so far.
+    pub fn len(&self) -> usize {
+        self.memo.len() / 2
+    }
+
+    /// Returns `true` if no pair of distinct nodes was compared yet.
+    pub fn is_empty(&self) -> bool {
+        self.memo.is_empty()
+    }
+
+    /// Compares `a` and `b` without looking at their children, if that decides their order.
+    fn shallow_cmp(&self, a: &Hc<T>, b: &Hc<T>) -> Option<Ordering> {
+        if a.addr() == b.addr() {
+            return Some(Ordering::Equal);
+        }
+        if let Some(&order) = self.memo.get(&(a.addr(), b.addr())) {
+            return Some(order);
+        }
+        match (self.cmp_label)(a.get(), b.get()) {
+            Ordering::Equal if a.children().is_empty() && b.children().is_empty() => {
+                Some(Ordering::Equal)
+            }
+            Ordering::Equal => None,
+            order => Some(order),
+        }
+    }
+
+    /// Remembers the order of `a` and `b`, both ways.
+    fn remember(&mut self, a: Hc<T>, b: Hc<T>, order: Ordering) {
+        self.memo.insert((a.addr(), b.addr()), order);
+        self.memo.insert((b.addr(), a.addr()), order.reverse());
+        self.nodes.insert(a.addr(), a);
+        self.nodes.insert(b.addr(), b);
+    }
+}
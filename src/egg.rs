@@ -0,0 +1,118 @@
+//! # `egg` Interop
+//!
+//! Conversions between hash consed DAGs and [`egg`](https://docs.rs/egg) recursive expressions
+//! and e-graphs, so that an IR interned with `hash_cons` can be handed to equality saturation
+//! and the extracted result interned again.
+//!
+//! Both directions preserve sharing: every distinct node of the DAG becomes exactly one entry
+//! of the `RecExpr`, and every entry of a `RecExpr` is hash consed once, so shared `Id`s become
+//! shared handles again.
+//!
+//! The node mapping is supplied by the caller as a closure, because the relation between the
+//! interned node type and the `egg::Language` is specific to each application.
+
+use crate::dag::{postorder, HashConsed};
+use crate::{Hc, HcTable};
+use ::egg::{Analysis, EGraph, Id, Language, RecExpr};
+use std::collections::HashMap;
+
+/// Converts the DAG rooted at `root` into a `RecExpr`.
+///
+/// ## Parameters
+/// * `root`: The root of the DAG.
+/// * `node`: Builds the `egg` node for a DAG node, given the `Id`s of its children in the
+///   order returned by [`HashConsed::children`].
+///
+/// ## Returns
+/// A `RecExpr` whose root is the converted `root`, with one entry per distinct node.
+pub fn to_rec_expr<T, L, F>(root: &Hc<T>, mut node: F) -> RecExpr<L>
+where
+    T: HashConsed,
+    L: Language,
+    F: FnMut(&T, &[Id]) -> L,
+{
+    let mut expr = RecExpr::default();
+    let mut ids: HashMap<usize, Id> = HashMap::new();
+
+    postorder(root, |hc| {
+        let children: Vec<Id> = hc
+            .children()
+            .iter()
+            .map(|child| ids[&child.addr()])
+            .collect();
+        let id = expr.add(node(hc.get(), &children));
+        ids.insert(hc.addr(), id);
+    });
+
+    expr
+}
+
+/// Adds the DAG rooted at `root` to an e-graph.
+///
+/// ## Parameters
+/// * `egraph`: The e-graph to add the nodes to.
+/// * `root`: The root of the DAG.
+/// * `node`: Builds the `egg` node for a DAG node, given the e-class `Id`s of its children.
+///
+/// ## Returns
+/// The e-class `Id` of `root`.
+pub fn add_to_egraph<T, L, N, F>(egraph: &mut EGraph<L, N>, root: &Hc<T>, mut node: F) -> Id
+where
+    T: HashConsed,
+    L: Language,
+    N: Analysis<L>,
+    F: FnMut(&T, &[Id]) -> L,
+{
+    let mut ids: HashMap<usize, Id> = HashMap::new();
+    let mut root_id = None;
+
+    postorder(root, |hc| {
+        let children: Vec<Id> = hc
+            .children()
+            .iter()
+            .map(|child| ids[&child.addr()])
+            .collect();
+        let id = egraph.add(node(hc.get(), &children));
+        ids.insert(hc.addr(), id);
+        root_id = Some(id);
+    });
+
+    // The root is always the last node visited.
+    root_id.expect("the root of a DAG is always visited")
+}
+
+/// Hash conses every node of a `RecExpr` into `table` and returns the handle of its root.
+///
+/// This is also the way back from an e-graph: extract a `RecExpr` with `egg::Extractor` and
+/// convert it with this function.
+///
+/// ## Parameters
+/// * `expr`: The expression to convert.
+/// * `table`: The table the nodes are hash consed in.
+/// * `node`: Builds the DAG node for an `egg` node, given the handles of its children in the
+///   order returned by [`Language::children`].
+///
+/// ## Returns
+/// The handle of the root of `expr`.
+///
+/// ## Panics
+/// Panics if `expr` is empty.
+pub fn from_rec_expr<T, L, F>(expr: &RecExpr<L>, table: &HcTable<T>, mut node: F) -> Hc<T>
+where
+    T: HashConsed,
+    L: Language,
+    F: FnMut(&L, &[Hc<T>]) -> T,
+{
+    let mut handles: Vec<Hc<T>> = Vec::with_capacity(expr.len());
+
+    for enode in expr.as_ref() {
+        let children: Vec<Hc<T>> = enode
+            .children()
+            .iter()
+            .map(|&id| handles[usize::from(id)].clone())
+            .collect();
+        handles.push(table.hashcons(node(enode, &children)));
+    }
+
+    handles.pop().expect("cannot convert an empty RecExpr")
+}
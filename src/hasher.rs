@@ -0,0 +1,172 @@
+//! # Hasher Selection
+//!
+//! The hash function tables use to turn the data written by their `ConsHash` into a hash. It is
+//! SipHash, the default of `std`, unless one of the following features is enabled:
+//!
+//! - **ahash**: Uses `ahash`, which is much faster on short keys and still randomly seeded.
+//! - **fxhash**: Uses the `FxHasher` of `rustc-hash`, the fastest choice for small nodes such
+//!   as enum variants holding a few handles. Each table still draws a random salt, which
+//!   varies the hashes between tables but does not make FxHash collision-resistant: the two
+//!   salted halves of a trusted hash can collide together, see
+//!   `HcTableBuilder::trusted_hash()`.
+//!
+//! If both features are enabled, `ahash` is used. Tables are seeded randomly unless built with
+//! `HcTableBuilder::seed()`. Tables built with
+//! `HcTableBuilder::prehashed()` skip hashing and use an [`IdentityHasher`] instead.
+
+use std::hash::{BuildHasher, Hasher};
+
+#[cfg(feature = "ahash")]
+type Build = ahash::RandomState;
+
+#[cfg(all(feature = "fxhash", not(feature = "ahash")))]
+type Build = rustc_hash::FxBuildHasher;
+
+#[cfg(not(any(feature = "ahash", feature = "fxhash")))]
+type Build = SipState;
+
+/// # `SipState`
+/// The state of SipHash, either randomly keyed or with the fixed keys of `DefaultHasher::new()`.
+#[cfg(not(any(feature = "ahash", feature = "fxhash")))]
+#[derive(Clone)]
+pub(crate) enum SipState {
+    Random(std::collections::hash_map::RandomState),
+    Fixed,
+}
+
+#[cfg(not(any(feature = "ahash", feature = "fxhash")))]
+impl BuildHasher for SipState {
+    type Hasher = std::collections::hash_map::DefaultHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        match self {
+            SipState::Random(state) => state.build_hasher(),
+            SipState::Fixed => Default::default(),
+        }
+    }
+}
+
+/// # `HashState`
+/// The hasher state of a table, created once per table.
+///
+/// ## Fields
+/// * `build`: The hasher state of the selected hash function.
+/// * `salt`: A value written before every value, if the hash function cannot be seeded with it.
+#[derive(Clone)]
+pub(crate) struct HashState {
+    build: Build,
+    salt: Option<u64>,
+}
+
+impl HashState {
+    /// Creates a randomly seeded hasher state.
+    pub(crate) fn new() -> Self {
+        #[cfg(feature = "ahash")]
+        return HashState {
+            build: ahash::RandomState::new(),
+            salt: None,
+        };
+
+        #[cfg(all(feature = "fxhash", not(feature = "ahash")))]
+        return HashState {
+            build: rustc_hash::FxBuildHasher,
+            salt: Some(std::collections::hash_map::RandomState::new().hash_one(0u8)),
+        };
+
+        #[cfg(not(any(feature = "ahash", feature = "fxhash")))]
+        return HashState {
+            build: SipState::Random(Default::default()),
+            salt: None,
+        };
+    }
+
+    /// Creates a hasher state that hashes every value the same way each time it is created
+    /// with `seed`, for the same build of the crate on the same platform.
+    pub(crate) fn with_seed(seed: u64) -> Self {
+        #[cfg(feature = "ahash")]
+        return HashState {
+            build: ahash::RandomState::with_seeds(
+                seed,
+                seed ^ 0x243f_6a88_85a3_08d3,
+                seed ^ 0x1319_8a2e_0370_7344,
+                seed ^ 0xa409_3822_299f_31d0,
+            ),
+            salt: None,
+        };
+
+        #[cfg(all(feature = "fxhash", not(feature = "ahash")))]
+        return HashState {
+            build: rustc_hash::FxBuildHasher,
+            salt: Some(seed),
+        };
+
+        #[cfg(not(any(feature = "ahash", feature = "fxhash")))]
+        return HashState {
+            build: SipState::Fixed,
+            salt: Some(seed),
+        };
+    }
+}
+
+impl BuildHasher for HashState {
+    type Hasher = <Build as BuildHasher>::Hasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        let mut hasher = self.build.build_hasher();
+        if let Some(salt) = self.salt {
+            hasher.write_u64(salt);
+        }
+        hasher
+    }
+}
+
+/// # `IdentityHasher`
+/// A hasher that passes an integer written to it through unchanged, for values that already
+/// carry a high-quality hash such as a content fingerprint.
+///
+/// Tables built with `HcTableBuilder::prehashed()` hash values with it instead of rehashing the
+/// data their `ConsHash` writes. Writing a `u64` or `u128` sets the hash, other data is mixed
+/// in with a cheap multiplicative step, so values should write exactly one fingerprint.
+///
+/// ## Example
+/// ```
+/// use hash_cons::IdentityHasher;
+/// use std::hash::Hasher;
+///
+/// let mut hasher = IdentityHasher::default();
+/// hasher.write_u64(0x9e37_79b9_7f4a_7c15);
+///
+/// assert_eq!(hasher.finish(), 0x9e37_79b9_7f4a_7c15);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityHasher {
+    hash: u128,
+}
+
+impl IdentityHasher {
+    /// Returns all 128 bits of the hash, which identify values in trusted-hash mode.
+    pub(crate) fn finish_u128(&self) -> u128 {
+        self.hash
+    }
+}
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.hash as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash =
+                (self.hash ^ byte as u128).wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.hash = value as u128;
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.hash = value;
+    }
+}
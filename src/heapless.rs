@@ -0,0 +1,259 @@
+//! # Fixed-Capacity Tables
+//!
+//! A hash consing table that never allocates: values are moved into a [`HeaplessStorage`] of
+//! `N` slots provided by the caller, e.g. a `static` buffer or a local array, and the index of
+//! the table lives inline. Once all slots are taken, interning a new value returns it inside a
+//! [`TableFull`] error instead of growing.
+//!
+//! Like the handles of arena tables, handles are plain `Copy` references valid for as long as
+//! the storage is borrowed, and no value is freed individually: the values are dropped with the
+//! storage.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::heapless::{HeaplessHc, HeaplessHcTable, HeaplessStorage};
+//!
+//! #[derive(Hash, PartialEq, Eq)]
+//! enum Frame<'a> {
+//!     Byte(u8),
+//!     Seq(HeaplessHc<'a, Frame<'a>>, HeaplessHc<'a, Frame<'a>>),
+//! }
+//!
+//! let mut storage = HeaplessStorage::<Frame, 4>::new();
+//! let table = HeaplessHcTable::new(&mut storage);
+//!
+//! let ack = table.hashcons(Frame::Byte(0x06)).unwrap();
+//! let seq = table.hashcons(Frame::Seq(ack, ack)).unwrap();
+//!
+//! assert!(table.hashcons(Frame::Seq(ack, ack)).unwrap() == seq);
+//! assert_eq!((table.len(), table.capacity()), (2, 4));
+//! ```
+
+use crate::hasher::HashState;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+/// # `HeaplessHc<'a, T>`
+/// A handle to a value interned in a [`HeaplessHcTable`], valid for as long as its storage is
+/// borrowed.
+///
+/// Handles are compared and hashed by address, which is equivalent to comparing the values
+/// for handles from the same table.
+pub struct HeaplessHc<'a, T> {
+    elem: &'a T,
+}
+
+impl<'a, T> HeaplessHc<'a, T> {
+    /// Retrieves a reference to the interned value, valid for as long as the storage is
+    /// borrowed.
+    pub fn get(self) -> &'a T {
+        self.elem
+    }
+}
+
+impl<T> Clone for HeaplessHc<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for HeaplessHc<'_, T> {}
+
+impl<T> PartialEq for HeaplessHc<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.elem, other.elem)
+    }
+}
+
+impl<T> Eq for HeaplessHc<'_, T> {}
+
+impl<T> Hash for HeaplessHc<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.elem, state);
+    }
+}
+
+impl<T> std::ops::Deref for HeaplessHc<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.elem
+    }
+}
+
+impl<T> AsRef<T> for HeaplessHc<'_, T> {
+    fn as_ref(&self) -> &T {
+        self.elem
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for HeaplessHc<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.elem.fmt(f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for HeaplessHc<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.elem.fmt(f)
+    }
+}
+
+/// # `HeaplessStorage<T, N>`
+/// The `N` slots a [`HeaplessHcTable`] moves its values into.
+///
+/// `new` is a `const fn`, so that the storage can be placed in a `static`.
+pub struct HeaplessStorage<T, const N: usize> {
+    slots: [Option<T>; N],
+}
+
+impl<T, const N: usize> HeaplessStorage<T, N> {
+    /// Creates storage with `N` empty slots.
+    pub const fn new() -> Self {
+        HeaplessStorage {
+            slots: [const { None }; N],
+        }
+    }
+}
+
+impl<T, const N: usize> Default for HeaplessStorage<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # `TableFull<T>`
+/// The error returned by `HeaplessHcTable::hashcons()` when every slot is taken, holding the
+/// value that could not be interned.
+pub struct TableFull<T>(pub T);
+
+impl<T> TableFull<T> {
+    /// Returns the value that could not be interned.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for TableFull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TableFull(..)")
+    }
+}
+
+impl<T> fmt::Display for TableFull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the hash consing table is full")
+    }
+}
+
+impl<T> std::error::Error for TableFull<T> {}
+
+/// # `HeaplessHcTable<'a, T, N>`
+/// A single-threaded hash consing table storing at most `N` values, without allocating.
+///
+/// The index is an open-addressing array of `N` slots, so lookups of values that were not
+/// interned get slower as the table fills up.
+///
+/// ## Fields
+/// * `free`: The slots of the storage not taken yet.
+/// * `index`: The hashes of the interned values and the values, by position of the hash.
+/// * `len`: The number of interned values.
+/// * `hasher`: The hasher state used to hash values.
+pub struct HeaplessHcTable<'a, T, const N: usize> {
+    free: RefCell<&'a mut [Option<T>]>,
+    index: RefCell<[Option<(u64, &'a T)>; N]>,
+    len: Cell<usize>,
+    hasher: HashState,
+}
+
+impl<'a, T, const N: usize> HeaplessHcTable<'a, T, N>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty table moving its values into `storage`.
+    pub fn new(storage: &'a mut HeaplessStorage<T, N>) -> Self {
+        HeaplessHcTable {
+            free: RefCell::new(&mut storage.slots),
+            index: RefCell::new([None; N]),
+            len: Cell::new(0),
+            hasher: HashState::new(),
+        }
+    }
+
+    /// Returns the handle of `value`, moving it into the storage if it was not interned yet.
+    ///
+    /// ## Errors
+    /// Returns `value` inside a [`TableFull`] if it was not interned yet and every slot is
+    /// taken.
+    pub fn hashcons(&self, value: T) -> Result<HeaplessHc<'a, T>, TableFull<T>> {
+        let hash = self.hasher.hash_one(&value);
+        let position = match self.find(hash, &value) {
+            Ok(elem) => return Ok(HeaplessHc { elem }),
+            Err(Some(position)) => position,
+            Err(None) => return Err(TableFull(value)),
+        };
+
+        let mut free = self.free.borrow_mut();
+        let (slot, rest) = match std::mem::take(&mut *free).split_first_mut() {
+            Some(split) => split,
+            None => return Err(TableFull(value)),
+        };
+        *free = rest;
+        let elem: &'a T = slot.insert(value);
+        self.index.borrow_mut()[position] = Some((hash, elem));
+        self.len.set(self.len.get() + 1);
+        Ok(HeaplessHc { elem })
+    }
+
+    /// Returns the handle of the value equal to `value`, if it was interned.
+    pub fn get(&self, value: &T) -> Option<HeaplessHc<'a, T>> {
+        let hash = self.hasher.hash_one(value);
+        let elem = self.find(hash, value).ok()?;
+        Some(HeaplessHc { elem })
+    }
+
+    /// Returns `true` if a value equal to `value` was interned.
+    pub fn contains(&self, value: &T) -> bool {
+        self.get(value).is_some()
+    }
+
+    /// Returns the number of values interned in this table.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Returns `true` if nothing has been interned in this table.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values the table can hold, `N`.
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the value equal to `value`, or the free position of the index to store it at,
+    /// or `None` if the index is full.
+    fn find(&self, hash: u64, value: &T) -> Result<&'a T, Option<usize>> {
+        let index = self.index.borrow();
+        let start = (hash % N.max(1) as u64) as usize;
+        for probe in 0..N {
+            match index[(start + probe) % N] {
+                Some((stored, elem)) if stored == hash && elem == value => return Ok(elem),
+                Some(_) => {}
+                None => return Err(Some((start + probe) % N)),
+            }
+        }
+        Err(None)
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for HeaplessHcTable<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeaplessHcTable")
+            .field("len", &self.len.get())
+            .field("capacity", &N)
+            .finish()
+    }
+}
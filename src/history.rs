@@ -0,0 +1,105 @@
+//! # Intern History
+//!
+//! A bounded log of the most recent changes to a table, for finding out after the fact which
+//! thread stored or evicted a value, and when. Enabled by the **history** feature, which makes
+//! every table record its last `DEFAULT_HISTORY_CAPACITY` events. Recording takes the time and
+//! the id of the current thread on every change, so the feature is meant for debugging.
+//!
+//! Events refer to values by the ids `HcTable::entry_id()` reports for them.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::history::HistoryKind;
+//! use hash_cons::HcTable;
+//!
+//! let table = HcTable::new();
+//! let value = table.hashcons("node");
+//! let id = table.entry_id(&value).unwrap();
+//!
+//! let created = table.history().into_iter().find(|event| event.id == id).unwrap();
+//! assert_eq!(created.kind, HistoryKind::Interned);
+//! assert_eq!(created.thread, std::thread::current().id());
+//! ```
+
+use std::collections::VecDeque;
+use std::thread::ThreadId;
+use std::time::SystemTime;
+
+/// The number of events a table keeps unless configured otherwise.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1024;
+
+/// # `HistoryKind`
+/// What happened to a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HistoryKind {
+    /// The value was stored in the table.
+    Interned,
+    /// The value was removed from the table.
+    Evicted,
+}
+
+/// # `HistoryEvent`
+/// A change to a table, as recorded by its history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryEvent {
+    /// What happened to the value.
+    pub kind: HistoryKind,
+    /// The id of the entry of the value.
+    pub id: u64,
+    /// When it happened.
+    pub time: SystemTime,
+    /// The thread that changed the table. With auto cleanup, this is the thread that collected
+    /// a dropped value, not necessarily the one that dropped it.
+    pub thread: ThreadId,
+}
+
+/// # `History`
+/// A ring buffer of the most recent events of a table.
+///
+/// ## Fields
+/// * `events`: The recorded events, oldest first.
+/// * `capacity`: The number of events kept.
+pub(crate) struct History {
+    events: VecDeque<HistoryEvent>,
+    capacity: usize,
+}
+
+impl History {
+    /// Creates an empty history keeping `DEFAULT_HISTORY_CAPACITY` events.
+    pub(crate) fn new() -> Self {
+        History {
+            events: VecDeque::new(),
+            capacity: DEFAULT_HISTORY_CAPACITY,
+        }
+    }
+
+    /// Records that `kind` happened to the value with the given id on this thread, now,
+    /// forgetting the oldest event if the history is full.
+    pub(crate) fn record(&mut self, kind: HistoryKind, id: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(HistoryEvent {
+            kind,
+            id,
+            time: SystemTime::now(),
+            thread: std::thread::current().id(),
+        });
+    }
+
+    /// Returns the recorded events, oldest first.
+    pub(crate) fn events(&self) -> Vec<HistoryEvent> {
+        self.events.iter().copied().collect()
+    }
+
+    /// Keeps the last `capacity` events from now on, forgetting older ones.
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let excess = self.events.len().saturating_sub(capacity);
+        self.events.drain(..excess);
+        self.events.shrink_to(capacity);
+    }
+}
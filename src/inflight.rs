@@ -0,0 +1,175 @@
+//! # In-Flight Constructions
+//!
+//! Tracks the values that are being constructed outside of the table lock by
+//! `get_or_insert_with`, so that callers missing on the same key wait for the first
+//! construction instead of repeating it. Constructions are identified by the hash of their key
+//! alone: callers whose keys merely share a hash wait too, and construct their own value once
+//! they find the table still lacks it.
+
+use crate::strategy::PoisonPolicy;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+#[cfg(feature = "async")]
+use std::task::Waker;
+
+/// # `InFlight`
+/// The constructions in progress in a table.
+///
+/// ## Fields
+/// * `pending`: The constructions in progress, by hash of their key.
+/// * `poison`: How the locks of the constructions handle being poisoned.
+pub(crate) struct InFlight {
+    pending: Mutex<HashMap<u128, Arc<Pending>>>,
+    poison: Poison,
+}
+
+/// The poison policy of the table of an `InFlight`, and the prefix of its messages.
+#[derive(Clone)]
+struct Poison {
+    policy: PoisonPolicy,
+    prefix: Arc<str>,
+}
+
+/// A construction in progress.
+///
+/// ## Fields
+/// * `state`: Whether the construction finished, and the tasks to wake once it does.
+/// * `finished`: Signalled once the construction finished.
+struct Pending {
+    state: Mutex<PendingState>,
+    finished: Condvar,
+}
+
+#[derive(Default)]
+struct PendingState {
+    done: bool,
+    #[cfg(feature = "async")]
+    wakers: Vec<Waker>,
+}
+
+/// # `Lookup<H>`
+/// The outcome of looking up a key before constructing its value.
+pub(crate) enum Lookup<'a, H> {
+    /// The value is stored already.
+    Found(H),
+    /// The caller constructs the value. The construction is tracked while the claim, if any,
+    /// is held.
+    Claimed(Option<Claim<'a>>),
+    /// Another caller is constructing a value with the same hash.
+    Wait(Waiter),
+}
+
+/// # `Claim<'a>`
+/// The right to construct the value of a key. Dropping it, also by unwinding, finishes the
+/// construction and wakes the waiting callers.
+pub(crate) struct Claim<'a> {
+    in_flight: &'a InFlight,
+    hash: u128,
+    pending: Arc<Pending>,
+}
+
+/// # `Waiter`
+/// Waits for a construction of another caller to finish.
+pub(crate) struct Waiter {
+    pending: Arc<Pending>,
+    poison: Poison,
+}
+
+impl InFlight {
+    /// Creates an empty tracker, handling poisoned locks as `policy` says with messages
+    /// starting with `prefix`.
+    pub(crate) fn new(policy: PoisonPolicy, prefix: String) -> Self {
+        InFlight {
+            pending: Mutex::new(HashMap::new()),
+            poison: Poison {
+                policy,
+                prefix: prefix.into(),
+            },
+        }
+    }
+
+    /// Claims the construction of the value with the given hash, or returns a `Waiter` for the
+    /// construction of the caller that claimed it first.
+    pub(crate) fn claim(&self, hash: u128) -> Result<Claim<'_>, Waiter> {
+        let mut pending = self.poison.lock(&self.pending);
+        if let Some(pending) = pending.get(&hash) {
+            return Err(Waiter {
+                pending: pending.clone(),
+                poison: self.poison.clone(),
+            });
+        }
+        let claimed = Arc::new(Pending {
+            state: Mutex::new(PendingState::default()),
+            finished: Condvar::new(),
+        });
+        pending.insert(hash, claimed.clone());
+        Ok(Claim {
+            in_flight: self,
+            hash,
+            pending: claimed,
+        })
+    }
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        let poison = &self.in_flight.poison;
+        poison.lock(&self.in_flight.pending).remove(&self.hash);
+        let mut state = poison.lock(&self.pending.state);
+        state.done = true;
+        #[cfg(feature = "async")]
+        state.wakers.drain(..).for_each(Waker::wake);
+        drop(state);
+        self.pending.finished.notify_all();
+    }
+}
+
+impl Waiter {
+    /// Blocks the current thread until the construction finished.
+    pub(crate) fn wait(self) {
+        let mut state = self.poison.lock(&self.pending.state);
+        while !state.done {
+            state = self
+                .pending
+                .finished
+                .wait(state)
+                .unwrap_or_else(|poisoned| {
+                    self.poison
+                        .policy
+                        .recover(&self.poison.prefix, poisoned, || {
+                            self.pending.state.clear_poison()
+                        })
+                });
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl std::future::Future for Waiter {
+    type Output = ();
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<()> {
+        let mut state = self.poison.lock(&self.pending.state);
+        if state.done {
+            return std::task::Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        std::task::Poll::Pending
+    }
+}
+
+impl Poison {
+    /// Acquires `mutex`, handling a poisoned one as the policy says. Constructions run outside
+    /// of these locks, so a poisoned one still holds consistent data.
+    fn lock<'a, V>(&self, mutex: &'a Mutex<V>) -> MutexGuard<'a, V> {
+        mutex.lock().unwrap_or_else(|poisoned| {
+            self.policy
+                .recover(&self.prefix, poisoned, || mutex.clear_poison())
+        })
+    }
+}
@@ -0,0 +1,195 @@
+//! # Interner Trait
+//!
+//! Compilers in the style of rustc abstract their interner behind a trait whose interned values
+//! may borrow the interner, e.g. `Ty<'tcx>`. The [`Interner`] trait has that shape, with a
+//! generic associated type for the interned values, so that code written against such a trait
+//! runs on the tables of this crate unchanged.
+//!
+//! It is implemented by the thread-safe and the single-threaded `HcTable`, whose interned values
+//! are handles that do not borrow the table, and by [`StrongHcTable`], whose interned values
+//! are [`Interned`] references into it.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::interner::Interner;
+//! use hash_cons::strong::StrongHcTable;
+//! use hash_cons::HcTable;
+//!
+//! fn intern_twice<I: Interner<String>>(interner: &I) -> bool {
+//!     let first = interner.intern("x".to_string());
+//!     let second = interner.intern("x".to_string());
+//!     first == second && interner.id(&first) == interner.id(&second)
+//! }
+//!
+//! assert!(intern_twice(&HcTable::new()));
+//! assert!(intern_twice(&StrongHcTable::new()));
+//! ```
+
+use crate::strong::{StrongHcTable, StrongId};
+use crate::{single_threaded, thread_safe};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+/// # `Interner<T>`
+/// An interner of values of type `T`, whose interned values may borrow it.
+pub trait Interner<T> {
+    /// An interned value, which compares and hashes in constant time.
+    type Interned<'a>: Clone + Eq + Hash + Deref<Target = T>
+    where
+        Self: 'a;
+
+    /// The id of an interned value, unique within the interner.
+    type Id: Copy + Eq + Hash;
+
+    /// Interns `value`, or finds an equal interned value.
+    fn intern(&self, value: T) -> Self::Interned<'_>;
+
+    /// Returns the interned value equal to `value`, without interning it.
+    fn lookup(&self, value: &T) -> Option<Self::Interned<'_>>;
+
+    /// Returns the id of `interned`.
+    ///
+    /// ## Panics
+    /// May panic if `interned` was not interned by this interner.
+    fn id<'a>(&'a self, interned: &Self::Interned<'a>) -> Self::Id;
+}
+
+impl<T, M> Interner<T> for thread_safe::HcTable<T, M>
+where
+    T: Hash + Eq,
+    thread_safe::Hc<T, M>: Eq + Hash,
+{
+    type Interned<'a>
+        = thread_safe::Hc<T, M>
+    where
+        Self: 'a;
+    type Id = u64;
+
+    fn intern(&self, value: T) -> Self::Interned<'_> {
+        self.hashcons(value)
+    }
+
+    fn lookup(&self, value: &T) -> Option<Self::Interned<'_>> {
+        self.get_many(std::slice::from_ref(value)).pop().flatten()
+    }
+
+    /// Returns the id `export_entries` reports for the value.
+    fn id<'a>(&'a self, interned: &Self::Interned<'a>) -> u64 {
+        self.entry_id(interned)
+            .expect("the handle belongs to the interner")
+    }
+}
+
+impl<T, M> Interner<T> for single_threaded::HcTable<T, M>
+where
+    T: Hash + Eq,
+    single_threaded::Hc<T, M>: Eq + Hash,
+{
+    type Interned<'a>
+        = single_threaded::Hc<T, M>
+    where
+        Self: 'a;
+    type Id = u64;
+
+    fn intern(&self, value: T) -> Self::Interned<'_> {
+        self.hashcons(value)
+    }
+
+    fn lookup(&self, value: &T) -> Option<Self::Interned<'_>> {
+        self.get_many(std::slice::from_ref(value)).pop().flatten()
+    }
+
+    /// Returns the id `export_entries` reports for the value.
+    fn id<'a>(&'a self, interned: &Self::Interned<'a>) -> u64 {
+        self.entry_id(interned)
+            .expect("the handle belongs to the interner")
+    }
+}
+
+impl<T> Interner<T> for StrongHcTable<T>
+where
+    T: Hash + Eq,
+{
+    type Interned<'a>
+        = Interned<'a, T>
+    where
+        Self: 'a;
+    type Id = StrongId<T>;
+
+    fn intern(&self, value: T) -> Self::Interned<'_> {
+        let id = self.hashcons(value);
+        Interned {
+            id,
+            value: self.get(id),
+        }
+    }
+
+    fn lookup(&self, value: &T) -> Option<Self::Interned<'_>> {
+        StrongHcTable::lookup(self, value).map(|id| Interned {
+            id,
+            value: self.get(id),
+        })
+    }
+
+    fn id<'a>(&'a self, interned: &Self::Interned<'a>) -> StrongId<T> {
+        interned.id
+    }
+}
+
+/// # `Interned<'a, T>`
+/// A value interned in a [`StrongHcTable`], borrowing the table.
+///
+/// Interned values are `Copy`, and compare and hash by their id.
+pub struct Interned<'a, T> {
+    id: StrongId<T>,
+    value: &'a T,
+}
+
+impl<'a, T> Interned<'a, T> {
+    /// Returns the id of the value.
+    pub fn id(self) -> StrongId<T> {
+        self.id
+    }
+
+    /// Returns the value, borrowed for as long as the table.
+    pub fn get(self) -> &'a T {
+        self.value
+    }
+}
+
+impl<T> Clone for Interned<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Interned<'_, T> {}
+
+impl<T> Deref for Interned<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> PartialEq for Interned<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Interned<'_, T> {}
+
+impl<T> Hash for Interned<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Interned<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.value, f)
+    }
+}
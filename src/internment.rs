@@ -0,0 +1,91 @@
+//! # `internment` Interop
+//!
+//! Conversions between `Hc<T>` handles and the [`internment`](https://docs.rs/internment)
+//! interners, for dependency trees that end up with both crates and need to move values across
+//! the boundary.
+//!
+//! The two crates keep their values in separate allocations, so every conversion clones the value
+//! once. Conversions into an interner that already holds an equal value reuse that value and only
+//! drop the temporary clone.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::internment::hashcons_arc_intern;
+//! use hash_cons::HcTable;
+//! use internment::ArcIntern;
+//!
+//! let table = HcTable::new();
+//! let hc = table.hashcons("shared".to_string());
+//!
+//! let intern: ArcIntern<String> = ArcIntern::from(&hc);
+//! assert_eq!(*intern, "shared");
+//!
+//! let back = hashcons_arc_intern(&table, &intern);
+//! assert_eq!(back, hc);
+//! ```
+
+use crate::{Hc, HcTable};
+use ::internment::{ArcIntern, Intern};
+use std::hash::Hash;
+
+impl<T> From<&Hc<T>> for ArcIntern<T>
+where
+    T: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    /// Interns a copy of the value behind `hc` in the global `ArcIntern` pool.
+    fn from(hc: &Hc<T>) -> Self {
+        ArcIntern::new(hc.get().clone())
+    }
+}
+
+impl<T> From<Hc<T>> for ArcIntern<T>
+where
+    T: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    /// Interns a copy of the value behind `hc` in the global `ArcIntern` pool.
+    fn from(hc: Hc<T>) -> Self {
+        ArcIntern::from(&hc)
+    }
+}
+
+impl<T> From<&Hc<T>> for Intern<T>
+where
+    T: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    /// Interns a copy of the value behind `hc` in the global, never freed `Intern` pool.
+    fn from(hc: &Hc<T>) -> Self {
+        Intern::new(hc.get().clone())
+    }
+}
+
+impl<T> From<Hc<T>> for Intern<T>
+where
+    T: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    /// Interns a copy of the value behind `hc` in the global, never freed `Intern` pool.
+    fn from(hc: Hc<T>) -> Self {
+        Intern::from(&hc)
+    }
+}
+
+/// Hash conses a copy of the value behind an `ArcIntern<T>` into `table`.
+///
+/// ## Returns
+/// The handle of the value in `table`, shared with every other handle of an equal value.
+pub fn hashcons_arc_intern<T>(table: &HcTable<T>, value: &ArcIntern<T>) -> Hc<T>
+where
+    T: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    table.hashcons(T::clone(value))
+}
+
+/// Hash conses a copy of the value behind an `Intern<T>` into `table`.
+///
+/// ## Returns
+/// The handle of the value in `table`, shared with every other handle of an equal value.
+pub fn hashcons_intern<T>(table: &HcTable<T>, value: Intern<T>) -> Hc<T>
+where
+    T: Clone + Eq + Hash + Send + Sync + 'static,
+{
+    table.hashcons(T::clone(&value))
+}
@@ -0,0 +1,121 @@
+//! # JSON Deduplication
+//!
+//! Hash consing of [`serde_json`](https://docs.rs/serde_json) documents. A `Value` is interned
+//! bottom-up into a table of [`JsonNode`]s, so that equal subtrees, within a document and across
+//! documents, are stored once. Log-processing pipelines holding many near-identical records
+//! keep only the parts that differ.
+//!
+//! Interned documents serialize like the `Value` they were built from, and can be turned back
+//! into a `Value` with [`to_value`].
+//!
+//! ## Example
+//! ```
+//! use hash_cons::json::{hashcons_value, to_value, JsonNode};
+//! use hash_cons::HcTable;
+//! use serde_json::json;
+//!
+//! let table: HcTable<JsonNode> = HcTable::new();
+//! let first = hashcons_value(&table, json!({"level": "info", "tags": ["a", "b"]}));
+//! let second = hashcons_value(&table, json!({"level": "warn", "tags": ["a", "b"]}));
+//!
+//! let (JsonNode::Object(first), JsonNode::Object(second)) = (first.get(), second.get()) else {
+//!     unreachable!()
+//! };
+//! assert_eq!(first[1].1, second[1].1);
+//! assert_eq!(serde_json::to_string(&*first[1].1).unwrap(), r#"["a","b"]"#);
+//! ```
+
+use crate::{Hc, HcTable};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::{Map, Number, Value};
+
+/// # `JsonNode`
+/// A node of an interned JSON document, whose children are handles to other nodes.
+///
+/// Object members keep the order of the `Value` they were interned from, which depends on the
+/// `preserve_order` feature of `serde_json`. Objects are therefore only shared if their
+/// members come in the same order.
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub enum JsonNode {
+    /// `null`.
+    Null,
+    /// `true` or `false`.
+    Bool(bool),
+    /// A number.
+    Number(Number),
+    /// A string.
+    String(String),
+    /// An array of nodes.
+    Array(Vec<Hc<JsonNode>>),
+    /// The members of an object, in order.
+    Object(Vec<(String, Hc<JsonNode>)>),
+}
+
+impl Serialize for JsonNode {
+    /// Serializes the node like the `Value` it was interned from.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            JsonNode::Null => serializer.serialize_unit(),
+            JsonNode::Bool(value) => serializer.serialize_bool(*value),
+            JsonNode::Number(value) => value.serialize(serializer),
+            JsonNode::String(value) => serializer.serialize_str(value),
+            JsonNode::Array(elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for element in elements {
+                    seq.serialize_element(element.get())?;
+                }
+                seq.end()
+            }
+            JsonNode::Object(members) => {
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (key, value) in members {
+                    map.serialize_entry(key, value.get())?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Hash conses a JSON document into `table`, children first.
+///
+/// ## Returns
+/// The handle of the root node, sharing every subtree with the equal subtrees interned before.
+pub fn hashcons_value(table: &HcTable<JsonNode>, value: Value) -> Hc<JsonNode> {
+    let node = match value {
+        Value::Null => JsonNode::Null,
+        Value::Bool(value) => JsonNode::Bool(value),
+        Value::Number(value) => JsonNode::Number(value),
+        Value::String(value) => JsonNode::String(value),
+        Value::Array(elements) => JsonNode::Array(
+            elements
+                .into_iter()
+                .map(|element| hashcons_value(table, element))
+                .collect(),
+        ),
+        Value::Object(members) => JsonNode::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key, hashcons_value(table, value)))
+                .collect(),
+        ),
+    };
+    table.hashcons(node)
+}
+
+/// Rebuilds the JSON document rooted at `node`, copying every shared subtree.
+pub fn to_value(node: &Hc<JsonNode>) -> Value {
+    match node.get() {
+        JsonNode::Null => Value::Null,
+        JsonNode::Bool(value) => Value::Bool(*value),
+        JsonNode::Number(value) => Value::Number(value.clone()),
+        JsonNode::String(value) => Value::String(value.clone()),
+        JsonNode::Array(elements) => Value::Array(elements.iter().map(to_value).collect()),
+        JsonNode::Object(members) => Value::Object(
+            members
+                .iter()
+                .map(|(key, value)| (key.clone(), to_value(value)))
+                .collect::<Map<String, Value>>(),
+        ),
+    }
+}
@@ -0,0 +1,60 @@
+//! # `lasso` Interop
+//!
+//! Conversions between hash consed strings and [`lasso`](https://docs.rs/lasso) keys, so that
+//! a string interned with `hash_cons` can be handed to code built around a `lasso` interner and
+//! back.
+//!
+//! The functions are generic over `lasso`'s `Interner` and `Resolver` traits and therefore work
+//! with `Rodeo`, `&ThreadedRodeo`, `RodeoReader` and `RodeoResolver` alike. Interning into
+//! `lasso` borrows the string, while converting a key back copies the resolved string into the
+//! table, where it is dropped again if the table already holds it.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::lasso::{hashcons_key, to_key};
+//! use hash_cons::HcTable;
+//! use lasso::Rodeo;
+//!
+//! let table = HcTable::new();
+//! let mut rodeo = Rodeo::default();
+//!
+//! let hc = table.hashcons("ident".to_string());
+//! let key = to_key(&hc, &mut rodeo);
+//! assert_eq!(rodeo.resolve(&key), "ident");
+//!
+//! assert_eq!(hashcons_key(&table, &rodeo, &key), hc);
+//! ```
+
+use crate::{Hc, HcTable};
+use ::lasso::{Interner, Resolver};
+
+/// Interns the string behind `hc` in a `lasso` interner and returns its key.
+pub fn to_key<S, K, I>(hc: &Hc<S>, interner: &mut I) -> K
+where
+    S: AsRef<str> + std::hash::Hash + Eq,
+    I: Interner<K>,
+{
+    interner.get_or_intern(hc.get().as_ref())
+}
+
+/// Hash conses the string a `lasso` key resolves to into `table`.
+///
+/// ## Panics
+/// Panics if `key` is not known to `resolver`, like `Resolver::resolve`.
+pub fn hashcons_key<K, R>(table: &HcTable<String>, resolver: &R, key: &K) -> Hc<String>
+where
+    R: Resolver<K>,
+{
+    table.hashcons(resolver.resolve(key).to_owned())
+}
+
+/// Hash conses the string a `lasso` key resolves to into `table`, or returns `None` if `key` is
+/// not known to `resolver`.
+pub fn try_hashcons_key<K, R>(table: &HcTable<String>, resolver: &R, key: &K) -> Option<Hc<String>>
+where
+    R: Resolver<K>,
+{
+    resolver
+        .try_resolve(key)
+        .map(|value| table.hashcons(value.to_owned()))
+}
@@ -0,0 +1,56 @@
+//! # Leak Reports
+//!
+//! With the **leak-check** feature, dropping the last `HcTable` of a table while some of its
+//! values still have handles prints a [`LeakReport`] to stderr. Those values, and the storage
+//! of the table, live on until their handles are dropped, which is often a sign of a cycle or
+//! of handles stashed in a global.
+//!
+//! Each value also records where it was created, if backtraces are enabled for the process
+//! through `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`, see `std::backtrace::Backtrace::capture()`.
+//! The report then lists the creation backtrace of every leaked value.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::HcTable;
+//!
+//! let table = HcTable::new();
+//! let leaked = table.hashcons("kept");
+//!
+//! let report = table.leak_report();
+//! assert_eq!(report.leaked, 1);
+//! drop(table); // prints the report
+//! ```
+
+use std::fmt;
+
+/// # `LeakReport`
+/// The values of a table that still have handles, returned by `HcTable::leak_report()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    /// The type of the values of the table.
+    pub type_name: &'static str,
+    /// The name of the table, if it was given one.
+    pub table_name: Option<&'static str>,
+    /// The number of values that still have handles.
+    pub leaked: usize,
+    /// Where the leaked values were created, for those whose backtrace was captured.
+    pub backtraces: Vec<String>,
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.table_name {
+            Some(name) => write!(f, "hash_cons: the table `{name}` of `{}`", self.type_name)?,
+            None => write!(f, "hash_cons: a table of `{}`", self.type_name)?,
+        }
+        write!(
+            f,
+            " was dropped while {} of its values still have handles",
+            self.leaked
+        )?;
+        for (index, backtrace) in self.backtraces.iter().enumerate() {
+            write!(f, "\n\nvalue {index} was created at:\n{backtrace}")?;
+        }
+        Ok(())
+    }
+}
@@ -14,9 +14,67 @@
 //! ## Features
 //!
 //! - **auto_cleanup**: Enabled by default, this feature allows the library to automatically clean up and
-//!  manage memory efficiently by removing unused entries.
+//!   manage memory efficiently by removing unused entries.
 //! - **single-threaded**: Disabled by default, enable this feature if you are working in a single-threaded environment
 //!   or if you want to manage synchronization yourself.
+//!   Both implementations are always available as the [`thread_safe`] and [`single_threaded`]
+//!   modules, this feature selects the one re-exported at the crate root. Their `Migration`
+//!   types move a populated table from one to the other, e.g. once a build phase goes parallel.
+//! - **salsa**: Disabled by default, enables the [`salsa`] module, which hands out dense,
+//!   salsa-style interning keys with durability hints for hash consed values.
+//! - **egg**: Disabled by default, enables the [`egg`] module, which converts hash consed DAGs
+//!   to and from `egg` recursive expressions and e-graphs while preserving sharing.
+//! - **internment** / **lasso**: Disabled by default, enable the [`internment`] and [`lasso`]
+//!   modules, which convert values between `Hc<T>` and the interners of those crates.
+//! - **bytes**: Disabled by default, enables the [`bytes`] module, which interns `bytes::Bytes`
+//!   buffers without copying them and looks them up by borrowed `&[u8]`.
+//! - **arena**: Disabled by default, enables the [`arena`] module, an arena-backed table whose
+//!   `Copy` handles live as long as the arena and are never freed individually.
+//! - **allocator-api2**: Disabled by default, enables the [`allocator`] module, a table whose map
+//!   and values are allocated in a user-supplied `allocator_api2::alloc::Allocator`.
+//! - **heapless**: Disabled by default, enables the [`heapless`] module, a fixed-capacity
+//!   table storing its values in caller-provided slots, which returns `TableFull` instead of
+//!   allocating once they are taken.
+//! - **persistent**: Disabled by default, enables the [`persistent`] module, a thread-safe
+//!   table on an `im` persistent map whose snapshots take constant time and never block
+//!   interning.
+//! - **json**: Disabled by default, enables the [`json`] module, which interns `serde_json`
+//!   documents bottom-up so that equal subtrees are stored once.
+//! - **tiered**: Disabled by default, enables the experimental [`tiered`] module, a table that
+//!   spills unused values to an on-disk cold tier and interns them again on demand.
+//! - **compression**: Disabled by default, enables `tiered` and adds tables whose cold tier is
+//!   kept in memory, compressed with LZ4.
+//! - **wal**: Disabled by default, enables `serde` and the [`wal`] module, a table that appends
+//!   the values it stores to a log and rebuilds itself from the log after a crash.
+//! - **serde**: Disabled by default, enables the [`serde_hc`] module, a field adapter that
+//!   serializes handles as their values and interns them again when deserializing.
+//!   Tables serialize as the list of their values, sharing the values they point to.
+//! - **prometheus**: Disabled by default, enables the [`prometheus`] module, a
+//!   `prometheus-client` collector publishing the counters of thread-safe tables.
+//! - **ahash** / **fxhash**: Disabled by default, replace SipHash as the hash function of every
+//!   table with `ahash` or `FxHasher`. Interning small nodes is dominated by hashing, so this is
+//!   a cheap speedup. `ahash` wins if both are enabled.
+//! - **access-time**: Disabled by default, records the second in which each entry was last
+//!   interned, so that tables without `auto_cleanup` can evict values unused for a while with
+//!   `cleanup_idle_for`.
+//! - **history**: Disabled by default, makes every table log its most recent stores and
+//!   evictions with their time and thread, see the [`history`] module.
+//! - **leak-check**: Disabled by default, reports the values that still have handles when the
+//!   last `HcTable` of a table is dropped, with their creation backtraces if enabled, see the
+//!   [`leak`] module.
+//! - **async**: Disabled by default, adds `get_or_insert_with_async`, which constructs missing
+//!   values asynchronously and lets concurrent callers await the first construction.
+//! - **portable-atomic**: Disabled by default, makes thread-safe tables use the `Arc` and
+//!   atomics of `portable-atomic`, for targets whose `std` lacks atomic compare-and-swap. With
+//!   the `critical-section` feature of `portable-atomic`, they fall back to critical sections.
+//!   Trait-object handles are not available then, as that `Arc` cannot be unsized on stable.
+//! - **unicode**: Disabled by default, adds `Folding::Nfc`, which makes a [`symbols`] table
+//!   deduplicate spellings by their Unicode normalization form C.
+//! - **tokio**: Disabled by default, adds `spawn_maintenance` to thread-safe tables, which
+//!   sweeps dead entries periodically on a Tokio task instead of a dedicated thread.
+//! - **testing**: Disabled by default, enables the [`testing`] module, a deterministic table
+//!   that injects failures and a generator of random DAGs, for tests and benchmarks of code
+//!   using an interner.
 //!
 //! ## Usage
 //!
@@ -119,14 +177,135 @@
 //! ```
 //!
 
-#[cfg(feature = "single-threaded")]
 pub mod single_threaded;
 
 #[cfg(feature = "single-threaded")]
 pub use single_threaded::*;
 
-#[cfg(not(feature = "single-threaded"))]
 pub mod thread_safe;
 
 #[cfg(not(feature = "single-threaded"))]
-pub use thread_safe::*;
\ No newline at end of file
+pub use thread_safe::*;
+
+pub mod backend;
+
+pub mod interner;
+
+pub mod dag;
+
+pub use dag::HashConsed;
+
+pub mod remote;
+
+pub mod normalize;
+
+mod buckets;
+
+mod hasher;
+
+mod inflight;
+
+#[cfg(feature = "history")]
+pub mod history;
+
+#[cfg(feature = "leak-check")]
+pub mod leak;
+
+#[cfg(feature = "auto-cleanup")]
+mod retire;
+
+mod sync;
+
+pub use hasher::IdentityHasher;
+
+pub mod mem;
+
+pub use mem::{MemSize, MemoryUsage};
+
+pub mod pressure;
+
+pub use pressure::{MemoryPressure, TryRelease};
+
+pub mod stats;
+
+pub use stats::{CleanupReport, HashDiagnostics, TableStats};
+
+pub mod paths;
+
+pub mod skipped;
+
+pub use skipped::Skipped;
+
+pub mod slab;
+
+pub mod strong;
+
+pub mod symbols;
+
+pub mod timeout;
+
+pub use timeout::Timeout;
+
+pub mod token;
+
+pub use token::HcToken;
+
+pub mod strategy;
+
+pub use strategy::{
+    Canonical, ConsEq, ConsHash, ConsNormalize, ConsWeight, ContentIds, DefaultEq, DefaultHash,
+    EvictionOrder, IdAllocator, Identity, PoisonPolicy, RandomIds, SequentialIds, Structural,
+};
+
+pub mod tagged;
+
+pub use tagged::Tagged;
+
+pub mod wrapped;
+
+pub use wrapped::{Transparent, Wrapped};
+
+#[cfg(feature = "salsa")]
+pub mod salsa;
+
+#[cfg(feature = "egg")]
+pub mod egg;
+
+#[cfg(feature = "internment")]
+pub mod internment;
+
+#[cfg(feature = "lasso")]
+pub mod lasso;
+
+#[cfg(feature = "arena")]
+pub mod arena;
+
+#[cfg(feature = "allocator-api2")]
+pub mod allocator;
+
+#[cfg(feature = "bytes")]
+pub mod bytes;
+
+#[cfg(feature = "heapless")]
+pub mod heapless;
+
+#[cfg(feature = "persistent")]
+pub mod persistent;
+
+#[cfg(feature = "serde")]
+pub mod serde_hc;
+
+#[cfg(feature = "json")]
+pub mod json;
+
+#[cfg(feature = "tiered")]
+pub mod tiered;
+
+#[cfg(feature = "wal")]
+pub mod wal;
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -0,0 +1,160 @@
+//! # Memory Usage
+//!
+//! Estimates of the memory held by a table, for capacity planning of interner-heavy services.
+//!
+//! The estimates rely on [`MemSize`], which reports the heap memory owned by a value. The
+//! sizes of allocator headers and padding are not included, so the numbers are lower bounds.
+
+use crate::Hc;
+use std::hash::Hash;
+use std::mem::size_of;
+
+/// # `MemSize`
+/// The heap memory owned by a value, not counting the value itself.
+///
+/// ## Example
+/// ```
+/// use hash_cons::MemSize;
+///
+/// struct Symbol {
+///     name: String,
+///     arity: u32,
+/// }
+///
+/// impl MemSize for Symbol {
+///     fn heap_size(&self) -> usize {
+///         self.name.heap_size()
+///     }
+/// }
+///
+/// let symbol = Symbol { name: String::with_capacity(16), arity: 2 };
+/// assert_eq!(symbol.heap_size(), 16);
+/// ```
+pub trait MemSize {
+    /// Returns the number of heap bytes owned by this value. Defaults to zero.
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+macro_rules! impl_mem_size_trivial {
+    ($($ty:ty),*) => {
+        $(impl MemSize for $ty {})*
+    };
+}
+
+impl_mem_size_trivial!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    &str
+);
+
+impl MemSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl MemSize for std::ffi::OsString {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl MemSize for std::path::PathBuf {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: MemSize> MemSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(MemSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: MemSize + ?Sized> MemSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        std::mem::size_of_val::<T>(&**self) + T::heap_size(&**self)
+    }
+}
+
+impl<T: MemSize> MemSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, MemSize::heap_size)
+    }
+}
+
+impl<T: MemSize, const N: usize> MemSize for [T; N] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(MemSize::heap_size).sum()
+    }
+}
+
+impl<T: MemSize> MemSize for [T] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(MemSize::heap_size).sum()
+    }
+}
+
+macro_rules! impl_mem_size_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: MemSize),+> MemSize for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn heap_size(&self) -> usize {
+                let ($($name,)+) = self;
+                0 $(+ $name.heap_size())+
+            }
+        }
+    };
+}
+
+impl_mem_size_tuple!(A);
+impl_mem_size_tuple!(A, B);
+impl_mem_size_tuple!(A, B, C);
+impl_mem_size_tuple!(A, B, C, D);
+
+impl<T> MemSize for Hc<T>
+where
+    T: Hash + Eq,
+{
+    /// A handle owns no memory of its own: the value it points to is accounted for by the table
+    /// it was interned in.
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+/// # `MemoryUsage`
+/// The estimated memory held by a table, in bytes. Returned by `HcTable::memory_usage()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Memory held by values that still have handles.
+    pub live: usize,
+    /// Memory held by entries whose handles were all dropped but which were not cleaned up yet.
+    pub dead: usize,
+    /// Memory held by the table structure itself.
+    pub overhead: usize,
+}
+
+impl MemoryUsage {
+    /// Returns the total number of bytes.
+    pub fn total(&self) -> usize {
+        self.live + self.dead + self.overhead
+    }
+}
@@ -0,0 +1,232 @@
+//! # Canonicalization Combinators
+//!
+//! Reusable normalizations for the intern boundary, see `HcTableBuilder::normalize()`, for
+//! node types that implement [`Operator`]. They rewrite a node whose children are interned
+//! already, so that applying them to every node on its way into the table keeps whole terms
+//! canonical:
+//!
+//! * [`SortOperands`] orders the operands of commutative operators by the ids of their handles.
+//! * [`FlattenAssociative`] inlines the operands of nested applications of an associative
+//!   operator, e.g. `Add[Add[a, b], c]` becomes `Add[a, b, c]`.
+//! * [`StripDoubleNegation`] rewrites `Not(Not(x))` into `x`.
+//!
+//! Combinators are chained with `then`. Flattening before sorting sorts the inlined operands
+//! too.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::normalize::{FlattenAssociative, Operator, SortOperands, StripDoubleNegation};
+//! use hash_cons::{HashConsed, Hc, HcTable};
+//!
+//! #[derive(Hash, PartialEq, Eq, Clone)]
+//! enum Expr {
+//!     Var(char),
+//!     Add(Vec<Hc<Expr>>),
+//!     Neg(Hc<Expr>),
+//! }
+//!
+//! impl HashConsed for Expr {
+//!     fn children(&self) -> Vec<&Hc<Expr>> {
+//!         match self {
+//!             Expr::Var(_) => vec![],
+//!             Expr::Add(operands) => operands.iter().collect(),
+//!             Expr::Neg(operand) => vec![operand],
+//!         }
+//!     }
+//! }
+//!
+//! impl Operator for Expr {
+//!     fn with_children(self, mut children: Vec<Hc<Expr>>) -> Expr {
+//!         match self {
+//!             Expr::Var(_) => self,
+//!             Expr::Add(_) => Expr::Add(children),
+//!             Expr::Neg(_) => Expr::Neg(children.remove(0)),
+//!         }
+//!     }
+//!
+//!     fn is_commutative(&self) -> bool {
+//!         matches!(self, Expr::Add(_))
+//!     }
+//!
+//!     fn is_associative(&self) -> bool {
+//!         matches!(self, Expr::Add(_))
+//!     }
+//!
+//!     fn is_negation(&self) -> bool {
+//!         matches!(self, Expr::Neg(_))
+//!     }
+//! }
+//!
+//! let table = HcTable::builder()
+//!     .normalize(FlattenAssociative.then(SortOperands).then(StripDoubleNegation))
+//!     .build();
+//! let (a, b, c) = (
+//!     table.hashcons(Expr::Var('a')),
+//!     table.hashcons(Expr::Var('b')),
+//!     table.hashcons(Expr::Var('c')),
+//! );
+//!
+//! let ab = table.hashcons(Expr::Add(vec![b.clone(), a.clone()]));
+//! let left = table.hashcons(Expr::Add(vec![ab, c.clone()]));
+//! let bc = table.hashcons(Expr::Add(vec![c, b]));
+//! let right = table.hashcons(Expr::Add(vec![a.clone(), bc]));
+//! assert!(left == right);
+//!
+//! let neg = table.hashcons(Expr::Neg(a.clone()));
+//! assert!(table.hashcons(Expr::Neg(neg)) == a);
+//! ```
+
+use crate::dag::HashConsed;
+use crate::strategy::ConsNormalize;
+use crate::Hc;
+
+/// # `Operator`
+/// A node type that can be rebuilt with other children, and tells which algebraic laws its
+/// operators obey. The laws default to none, so that only the operators that declare them are
+/// rewritten.
+pub trait Operator: HashConsed {
+    /// Returns this node with its children replaced by `children`, which has as many handles
+    /// as `children()` returned, except for associative operators, whose operands can be
+    /// flattened into more.
+    fn with_children(self, children: Vec<Hc<Self>>) -> Self;
+
+    /// Returns `true` if the children of this node can be put in any order.
+    fn is_commutative(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if this node applies an associative operator to its children, so that
+    /// children applying the same operator, see `HashConsed::same_shape()`, can be inlined.
+    fn is_associative(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if this node negates its single child, and negating twice is the
+    /// identity.
+    fn is_negation(&self) -> bool {
+        false
+    }
+}
+
+/// # `Then<A, B>`
+/// Two canonicalizations applied one after the other, created with `then`.
+///
+/// ## Fields
+/// * `first`: The canonicalization applied first.
+/// * `next`: The canonicalization applied to the result of `first`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Then<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<T, A, B> ConsNormalize<T> for Then<A, B>
+where
+    A: ConsNormalize<T>,
+    B: ConsNormalize<T>,
+{
+    fn normalize(&self, value: T) -> T {
+        self.next.normalize(self.first.normalize(value))
+    }
+}
+
+/// Implements `then` for combinators, which cannot be a method of `ConsNormalize` as the
+/// combinators normalize values of any type.
+macro_rules! impl_then {
+    ($($combinator:ty),* $(,)?) => {
+        $(
+            impl $combinator {
+                /// Returns the canonicalization applying this one and then `next`.
+                pub fn then<N>(self, next: N) -> Then<Self, N> {
+                    Then { first: self, next }
+                }
+            }
+        )*
+    };
+}
+
+impl_then!(SortOperands, FlattenAssociative, StripDoubleNegation);
+
+impl<A, B> Then<A, B> {
+    /// Returns the canonicalization applying this one and then `next`.
+    pub fn then<N>(self, next: N) -> Then<Self, N> {
+        Then { first: self, next }
+    }
+}
+
+/// # `SortOperands`
+/// Orders the children of commutative nodes by the order their values were stored in, so that
+/// operands given in any order intern to the same node.
+///
+/// The storing order is deterministic for a deterministic program, unlike ordering by address,
+/// and does not depend on the id allocator of the table.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SortOperands;
+
+impl<T: Operator> ConsNormalize<T> for SortOperands {
+    fn normalize(&self, value: T) -> T {
+        if !value.is_commutative() {
+            return value;
+        }
+        let children = value.children();
+        if children.is_sorted_by_key(|child| child.seq()) {
+            return value;
+        }
+        let mut children: Vec<Hc<T>> = children.into_iter().cloned().collect();
+        children.sort_by_key(Hc::seq);
+        value.with_children(children)
+    }
+}
+
+/// # `FlattenAssociative`
+/// Inlines the children of the children of associative nodes that apply the same operator.
+///
+/// Nodes are flattened on their way into the table, so the children, which were interned
+/// before, are flat already and a single level is inlined.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FlattenAssociative;
+
+impl<T: Operator> ConsNormalize<T> for FlattenAssociative {
+    fn normalize(&self, value: T) -> T {
+        if !value.is_associative() {
+            return value;
+        }
+        let nested = |child: &Hc<T>| child.is_associative() && child.same_shape(&value);
+        if !value.children().into_iter().any(nested) {
+            return value;
+        }
+        let mut children = Vec::new();
+        for child in value.children() {
+            if nested(child) {
+                children.extend(child.children().into_iter().cloned());
+            } else {
+                children.push(child.clone());
+            }
+        }
+        value.with_children(children)
+    }
+}
+
+/// # `StripDoubleNegation`
+/// Rewrites a negation of a negation into the negated value.
+///
+/// The table then hands out the handle of the negated value, whose copy is only used for the
+/// lookup, which is why the values must be `Clone`. Cloning a node copies the handles of its
+/// children, not the values they point at.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StripDoubleNegation;
+
+impl<T: Operator + Clone> ConsNormalize<T> for StripDoubleNegation {
+    fn normalize(&self, value: T) -> T {
+        if !value.is_negation() {
+            return value;
+        }
+        match value.children().first() {
+            Some(child) if child.is_negation() => match child.children().first() {
+                Some(negated) => negated.get().clone(),
+                None => value,
+            },
+            _ => value,
+        }
+    }
+}
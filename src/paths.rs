@@ -0,0 +1,84 @@
+//! # Path Interning
+//!
+//! Hash consing of file system paths and OS strings, for build tools that see the same paths
+//! over and over. Tables store owned `PathBuf`s and `OsString`s, and lookups borrow a `&Path`
+//! or `&OsStr`, which is copied only if the table does not hold it yet.
+//!
+//! Paths are compared like `Path` compares them, component by component, so `a/b` and `a//b`
+//! are the same path. Handles can be passed to any function taking `impl AsRef<Path>`.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::paths::{get_path, hashcons_path};
+//! use hash_cons::HcTable;
+//! use std::path::Path;
+//!
+//! let table = HcTable::new();
+//! let main = hashcons_path(&table, Path::new("src/main.rs"));
+//!
+//! assert_eq!(hashcons_path(&table, Path::new("src//main.rs")), main);
+//! assert_eq!(get_path(&table, Path::new("src/main.rs")), Some(main.clone()));
+//! assert!(get_path(&table, Path::new("src/lib.rs")).is_none());
+//! assert_eq!(main.extension().unwrap(), "rs");
+//! ```
+
+use crate::{Hc, HcTable};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+impl<M> AsRef<Path> for Hc<PathBuf, M> {
+    fn as_ref(&self) -> &Path {
+        self.get()
+    }
+}
+
+impl<M> AsRef<OsStr> for Hc<OsString, M> {
+    fn as_ref(&self) -> &OsStr {
+        self.get()
+    }
+}
+
+/// Hash conses a borrowed path into `table`, copying it only if `table` does not hold it yet.
+///
+/// ## Returns
+/// The handle of the path in `table`, shared with every other handle of an equal one.
+pub fn hashcons_path(table: &HcTable<PathBuf>, path: &Path) -> Hc<PathBuf> {
+    table.get_or_insert_with(path, Path::to_path_buf)
+}
+
+/// Looks up a borrowed path in `table`, without copying or storing it.
+///
+/// ## Returns
+/// The handle of the path, if `table` holds it.
+///
+/// ## Note
+/// Tables with a custom equivalence, hash function or normalization cannot be searched by a
+/// borrowed key and never find the path, see `HcTable::get_or_insert_with()`.
+pub fn get_path(table: &HcTable<PathBuf>, path: &Path) -> Option<Hc<PathBuf>> {
+    table
+        .get_or_try_insert_with(path, |_| Err::<PathBuf, _>(()))
+        .ok()
+}
+
+/// Hash conses a borrowed OS string into `table`, copying it only if `table` does not hold it
+/// yet.
+///
+/// ## Returns
+/// The handle of the OS string in `table`, shared with every other handle of an equal one.
+pub fn hashcons_os_str(table: &HcTable<OsString>, string: &OsStr) -> Hc<OsString> {
+    table.get_or_insert_with(string, OsStr::to_os_string)
+}
+
+/// Looks up a borrowed OS string in `table`, without copying or storing it.
+///
+/// ## Returns
+/// The handle of the OS string, if `table` holds it.
+///
+/// ## Note
+/// Tables with a custom equivalence, hash function or normalization cannot be searched by a
+/// borrowed key and never find the OS string, see `HcTable::get_or_insert_with()`.
+pub fn get_os_str(table: &HcTable<OsString>, string: &OsStr) -> Option<Hc<OsString>> {
+    table
+        .get_or_try_insert_with(string, |_| Err::<OsString, _>(()))
+        .ok()
+}
@@ -0,0 +1,306 @@
+//! # Persistent Storage
+//!
+//! A thread-safe hash consing table built on the persistent hash map of `im`. Taking a
+//! snapshot of it copies a pointer, so that analyses can iterate a stable view of the interned
+//! values while other threads keep interning. Interning copies the path to the changed entry
+//! and publishes the new version with a pointer swap, so snapshots never wait for interning in
+//! progress, and interning never waits for readers of a snapshot.
+//!
+//! Values are never removed: they live until the table and every snapshot are dropped.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::persistent::PersistentHcTable;
+//!
+//! let table = PersistentHcTable::new();
+//! let one = table.hashcons(1u32);
+//! let snapshot = table.snapshot();
+//! let two = table.hashcons(2u32);
+//!
+//! assert!(snapshot.get(&1) == Some(one));
+//! assert!(!snapshot.contains(&2));
+//! assert_eq!((snapshot.len(), table.len()), (1, 2));
+//! ```
+
+use crate::hasher::{HashState, IdentityHasher};
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The persistent map of a table, from hashes to the values sharing them.
+type Map<T> = im::HashMap<u128, Vec<Arc<T>>, BuildHasherDefault<IdentityHasher>>;
+
+/// # `PersistentHc<T>`
+/// A handle to a value interned in a [`PersistentHcTable`].
+///
+/// Two handles from the same table are equal exactly when their values are, which is decided
+/// by comparing their addresses.
+pub struct PersistentHc<T> {
+    elem: Arc<T>,
+}
+
+impl<T> PersistentHc<T> {
+    /// Retrieves a reference to the interned value.
+    pub fn get(&self) -> &T {
+        &self.elem
+    }
+}
+
+impl<T> Clone for PersistentHc<T> {
+    fn clone(&self) -> Self {
+        PersistentHc {
+            elem: self.elem.clone(),
+        }
+    }
+}
+
+impl<T> PartialEq for PersistentHc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.elem, &other.elem)
+    }
+}
+
+impl<T> Eq for PersistentHc<T> {}
+
+impl<T> Hash for PersistentHc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.elem).hash(state)
+    }
+}
+
+impl<T> std::ops::Deref for PersistentHc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.elem
+    }
+}
+
+impl<T> AsRef<T> for PersistentHc<T> {
+    fn as_ref(&self) -> &T {
+        &self.elem
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for PersistentHc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.elem.fmt(f)
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for PersistentHc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.elem.fmt(f)
+    }
+}
+
+/// A version of the map, together with the number of values in it.
+struct Version<T> {
+    map: Map<T>,
+    len: usize,
+}
+
+impl<T> Clone for Version<T> {
+    fn clone(&self) -> Self {
+        Version {
+            map: self.map.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Version<T>
+where
+    T: Hash + Eq,
+{
+    /// Returns the stored value equal to `value`, given its hash.
+    fn find(&self, hash: u128, value: &T) -> Option<PersistentHc<T>> {
+        let elem = self.map.get(&hash)?.iter().find(|elem| ***elem == *value)?;
+        Some(PersistentHc { elem: elem.clone() })
+    }
+}
+
+/// # `Shared<T>`
+/// The state shared by the clones of a table and its snapshots.
+///
+/// ## Fields
+/// * `current`: The latest version of the map. Its lock is only held to copy or replace it.
+/// * `writer`: Serializes interning, so that no new version overwrites a concurrent one.
+/// * `hasher`: The hasher state used to hash values.
+struct Shared<T> {
+    current: RwLock<Version<T>>,
+    writer: Mutex<()>,
+    hasher: HashState,
+}
+
+impl<T> Shared<T> {
+    /// Returns the latest version of the map.
+    fn load(&self) -> Version<T> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                poisoned.into_inner().clone() // continues, versions are replaced as a whole
+            }
+        }
+    }
+
+    /// Publishes `version` as the latest version of the map.
+    fn store(&self, version: Version<T>) {
+        let mut current = match self.current.write() {
+            Ok(current) => current,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                poisoned.into_inner() // continues, versions are replaced as a whole
+            }
+        };
+        let previous = std::mem::replace(&mut *current, version);
+        drop(current);
+        drop(previous); // frees the nodes only the previous version used, outside of the lock
+    }
+}
+
+/// # `PersistentHcTable<T>`
+/// A thread-safe hash consing table whose contents can be snapshotted in constant time.
+///
+/// Clones of the table share its values.
+pub struct PersistentHcTable<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> PersistentHcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        PersistentHcTable {
+            shared: Arc::new(Shared {
+                current: RwLock::new(Version {
+                    map: Map::default(),
+                    len: 0,
+                }),
+                writer: Mutex::new(()),
+                hasher: HashState::new(),
+            }),
+        }
+    }
+
+    /// Returns the handle of `value`, storing it if it was not interned yet.
+    pub fn hashcons(&self, value: T) -> PersistentHc<T> {
+        let hash = self.shared.hasher.hash_one(&value) as u128;
+        if let Some(hc) = self.shared.load().find(hash, &value) {
+            return hc;
+        }
+
+        let _writer = match self.shared.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                poisoned.into_inner() // continues, the mutex guards no data
+            }
+        };
+        // Another thread may have stored the value since it was looked up.
+        let mut version = self.shared.load();
+        if let Some(hc) = version.find(hash, &value) {
+            return hc;
+        }
+        let elem = Arc::new(value);
+        let mut bucket = version.map.get(&hash).cloned().unwrap_or_default();
+        bucket.push(elem.clone());
+        version.map.insert(hash, bucket);
+        version.len += 1;
+        self.shared.store(version);
+        PersistentHc { elem }
+    }
+
+    /// Returns a view of the values interned so far, which is unaffected by later interning.
+    pub fn snapshot(&self) -> PersistentSnapshot<T> {
+        PersistentSnapshot {
+            version: self.shared.load(),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Returns the number of values interned in this table.
+    pub fn len(&self) -> usize {
+        self.shared.load().len
+    }
+
+    /// Returns `true` if nothing has been interned in this table.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for PersistentHcTable<T>
+where
+    T: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for PersistentHcTable<T> {
+    fn clone(&self) -> Self {
+        PersistentHcTable {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// # `PersistentSnapshot<T>`
+/// The values of a [`PersistentHcTable`] at the time `snapshot` was called.
+///
+/// ## Fields
+/// * `version`: The version of the map the snapshot was taken of.
+/// * `shared`: The state of the table, whose hasher locates values.
+pub struct PersistentSnapshot<T> {
+    version: Version<T>,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> PersistentSnapshot<T>
+where
+    T: Hash + Eq,
+{
+    /// Returns the handle of the value equal to `value`, if it was interned when the snapshot
+    /// was taken.
+    pub fn get(&self, value: &T) -> Option<PersistentHc<T>> {
+        let hash = self.shared.hasher.hash_one(value) as u128;
+        self.version.find(hash, value)
+    }
+
+    /// Returns `true` if `value` was interned when the snapshot was taken.
+    pub fn contains(&self, value: &T) -> bool {
+        self.get(value).is_some()
+    }
+
+    /// Returns the handles of the values in the snapshot, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = PersistentHc<T>> + '_ {
+        self.version
+            .map
+            .values()
+            .flatten()
+            .map(|elem| PersistentHc { elem: elem.clone() })
+    }
+
+    /// Returns the number of values in the snapshot.
+    pub fn len(&self) -> usize {
+        self.version.len
+    }
+
+    /// Returns `true` if the snapshot holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.version.len == 0
+    }
+}
+
+impl<T> Clone for PersistentSnapshot<T> {
+    fn clone(&self) -> Self {
+        PersistentSnapshot {
+            version: self.version.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
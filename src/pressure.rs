@@ -0,0 +1,149 @@
+//! # Memory Pressure
+//!
+//! Lets tables give memory back when the process nears its limit, instead of only when their
+//! owners happen to sweep them. Whatever watches the limit, e.g. a cgroup `memory.events`
+//! poller or an allocator hook, asks a [`MemoryPressure`] registry to release some bytes, and
+//! the registry asks its tables and other caches in turn through [`TryRelease`].
+//!
+//! ## Example
+//! ```
+//! use hash_cons::thread_safe::HcTable;
+//! use hash_cons::MemoryPressure;
+//!
+//! let symbols = HcTable::new();
+//! let unused: Vec<_> = (0..100).map(|i| symbols.hashcons(i.to_string())).collect();
+//! drop(unused);
+//!
+//! let pressure = MemoryPressure::new();
+//! pressure.register(&symbols);
+//!
+//! // Called by the watcher of the memory limit.
+//! assert!(pressure.release(1 << 20) > 0);
+//! assert_eq!(symbols.len(), 0);
+//! ```
+
+use crate::mem::MemSize;
+use crate::thread_safe::HcTable;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// # `TryRelease`
+/// Something that can give memory back on request, such as a table or a cache.
+///
+/// Tables of both implementations release their dead entries, heaviest first, and then the
+/// spare capacity of their maps, see `HcTable::memory_usage()`.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{HcTable, TryRelease};
+///
+/// let table = HcTable::new();
+/// let kept = table.hashcons(vec![0u8; 64]);
+///
+/// // Values that still have handles are never released.
+/// table.try_release(usize::MAX);
+/// assert_eq!(table.len(), 1);
+/// ```
+pub trait TryRelease {
+    /// Releases memory, aiming for at least `bytes`.
+    ///
+    /// ## Returns
+    /// The estimated number of bytes released, which may fall short of or exceed `bytes`.
+    fn try_release(&self, bytes: usize) -> usize;
+}
+
+/// Releases memory of a registered holder, or returns `None` once the holder was dropped.
+type Releaser = Box<dyn Fn(usize) -> Option<usize> + Send + Sync>;
+
+/// # `MemoryPressure`
+/// A registry of holders to release memory from when the process runs short.
+///
+/// Clones share the registered holders, so that a clone can be handed to the watcher of the
+/// memory limit while holders are still being registered. The registry does not keep tables
+/// alive: a table is forgotten once it and all of its handles are dropped.
+///
+/// ## Fields
+/// * `holders`: The functions releasing memory of the registered holders, in registration
+///   order.
+#[derive(Clone, Default)]
+pub struct MemoryPressure {
+    holders: Arc<Mutex<Vec<Releaser>>>,
+}
+
+impl MemoryPressure {
+    /// Creates a registry without holders.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Releases memory of `table` when the registry is asked to.
+    pub fn register<T, M>(&self, table: &HcTable<T, M>)
+    where
+        T: Hash + Eq + MemSize + Send + Sync + 'static,
+    {
+        let source = table.release_source();
+        self.lock().push(Box::new(source));
+    }
+
+    /// Calls `release` with the number of bytes still to release when the registry is asked
+    /// to, e.g. to clear a cache of derived data. `release` returns the bytes it released.
+    pub fn register_with<F>(&self, release: F)
+    where
+        F: Fn(usize) -> usize + Send + Sync + 'static,
+    {
+        self.lock()
+            .push(Box::new(move |bytes| Some(release(bytes))));
+    }
+
+    /// Asks the registered holders, in registration order, to release memory until `bytes`
+    /// were released or every holder was asked.
+    ///
+    /// Holders are asked while the registry is locked, so they must not use it themselves.
+    ///
+    /// ## Returns
+    /// The estimated number of bytes released.
+    pub fn release(&self, bytes: usize) -> usize {
+        let mut released = 0;
+        self.lock().retain(|release| {
+            if released >= bytes {
+                return true;
+            }
+            match release(bytes - released) {
+                Some(freed) => {
+                    released += freed;
+                    true
+                }
+                None => false,
+            }
+        });
+        released
+    }
+
+    /// Acquires the registered holders, continuing with the poisoned lock, as registering
+    /// cannot leave them inconsistent.
+    fn lock(&self) -> MutexGuard<'_, Vec<Releaser>> {
+        match self.holders.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                self.holders.clear_poison();
+                poisoned.into_inner()
+            }
+        }
+    }
+}
+
+impl TryRelease for MemoryPressure {
+    fn try_release(&self, bytes: usize) -> usize {
+        self.release(bytes)
+    }
+}
+
+impl fmt::Debug for MemoryPressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryPressure")
+            .field("holders", &self.lock().len())
+            .finish()
+    }
+}
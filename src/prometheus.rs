@@ -0,0 +1,198 @@
+//! # Prometheus Metrics
+//!
+//! Publishes the counters of thread-safe tables, see `HcTable::stats()`, through a
+//! `prometheus-client` [`Collector`], so that services scraped by Prometheus need no exporter
+//! glue of their own. Every metric is labelled with the name the table was registered under:
+//!
+//! * `hashcons_table_entries`: The entries stored, including dead ones not cleaned up yet.
+//! * `hashcons_table_created_total`: The values stored in new allocations.
+//! * `hashcons_table_reused_total`: The lookups that returned an existing handle.
+//! * `hashcons_table_removed_total`: The entries removed by cleanup.
+//! * `hashcons_table_map_reallocations_total`: The times the map of the table had to grow.
+//! * `hashcons_table_reuse_ratio`: The fraction of lookups that returned an existing handle.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::prometheus::TableMetrics;
+//! use hash_cons::thread_safe::HcTable;
+//! use prometheus_client::registry::Registry;
+//!
+//! let symbols = HcTable::new();
+//! let name = symbols.hashcons("main");
+//! let again = symbols.hashcons("main");
+//!
+//! let metrics = TableMetrics::new();
+//! metrics.register("symbols", &symbols);
+//! let mut registry = Registry::default();
+//! registry.register_collector(Box::new(metrics.clone()));
+//!
+//! let body = metrics.render();
+//! assert!(body.contains("hashcons_table_entries{table=\"symbols\"} 1"));
+//! assert!(body.contains("hashcons_table_reused_total{table=\"symbols\"} 1"));
+//! ```
+
+use crate::stats::TableStats;
+use crate::thread_safe::HcTable;
+use prometheus_client::collector::Collector;
+use prometheus_client::encoding::{DescriptorEncoder, EncodeMetric};
+use prometheus_client::metrics::counter::ConstCounter;
+use prometheus_client::metrics::gauge::ConstGauge;
+use prometheus_client::registry::Registry;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/// Reads the counters of a table, or returns `None` once the table and its handles are dropped.
+type StatsSource = Box<dyn Fn() -> Option<TableStats> + Send + Sync>;
+
+/// # `TableMetrics`
+/// A [`Collector`] of the counters of named tables.
+///
+/// Clones share the registered tables, so that a clone can be handed to a `Registry` while
+/// tables are still being registered. The collector does not keep tables alive: a table is
+/// dropped from the metrics once it and all of its handles are.
+///
+/// ## Fields
+/// * `tables`: The names of the registered tables, with the functions reading their counters.
+#[derive(Clone, Default)]
+pub struct TableMetrics {
+    tables: Arc<Mutex<Vec<(String, StatsSource)>>>,
+}
+
+impl TableMetrics {
+    /// Creates a collector without tables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes the counters of `table`, labelled with `table="name"`.
+    ///
+    /// Names should be unique, as tables registered under the same name publish the same
+    /// series.
+    pub fn register<T, M>(&self, name: &str, table: &HcTable<T, M>)
+    where
+        T: Hash + Eq + Send + Sync + 'static,
+    {
+        let source = table.stats_source();
+        self.lock().push((name.to_string(), Box::new(source)));
+    }
+
+    /// Returns the names of the tables whose counters are published, in registration order.
+    pub fn tables(&self) -> Vec<String> {
+        self.stats().into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// Renders the metrics of the registered tables in the OpenMetrics text format, e.g. as the
+    /// body of a `/metrics` endpoint of a service without a registry of its own.
+    pub fn render(&self) -> String {
+        let mut registry = Registry::default();
+        registry.register_collector(Box::new(self.clone()));
+        let mut body = String::new();
+        prometheus_client::encoding::text::encode(&mut body, &registry)
+            .expect("writing to a `String` does not fail");
+        body
+    }
+
+    /// Reads the counters of the registered tables, forgetting the tables that were dropped.
+    fn stats(&self) -> Vec<(String, TableStats)> {
+        let mut tables = self.lock();
+        let mut stats = Vec::with_capacity(tables.len());
+        tables.retain(|(name, source)| match source() {
+            Some(table_stats) => {
+                stats.push((name.clone(), table_stats));
+                true
+            }
+            None => false,
+        });
+        stats
+    }
+
+    /// Acquires the registered tables, continuing with the poisoned lock, as registering
+    /// cannot leave them inconsistent.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(String, StatsSource)>> {
+        match self.tables.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                self.tables.clear_poison();
+                poisoned.into_inner()
+            }
+        }
+    }
+}
+
+impl fmt::Debug for TableMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tables = self.lock();
+        f.debug_list()
+            .entries(tables.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+impl Collector for TableMetrics {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> fmt::Result {
+        let tables = self.stats();
+        encode_family(
+            &mut encoder,
+            "hashcons_table_entries",
+            "Entries stored, including dead ones not cleaned up yet.",
+            &tables,
+            |stats| ConstGauge::new(stats.len as i64),
+        )?;
+        encode_family(
+            &mut encoder,
+            "hashcons_table_created",
+            "Values stored in new allocations.",
+            &tables,
+            |stats| ConstCounter::new(stats.created),
+        )?;
+        encode_family(
+            &mut encoder,
+            "hashcons_table_reused",
+            "Lookups that returned an existing handle.",
+            &tables,
+            |stats| ConstCounter::new(stats.reused),
+        )?;
+        encode_family(
+            &mut encoder,
+            "hashcons_table_removed",
+            "Entries removed by cleanup.",
+            &tables,
+            |stats| ConstCounter::new(stats.removed),
+        )?;
+        encode_family(
+            &mut encoder,
+            "hashcons_table_map_reallocations",
+            "Times the map of the table had to grow.",
+            &tables,
+            |stats| ConstCounter::new(stats.map_reallocations),
+        )?;
+        encode_family(
+            &mut encoder,
+            "hashcons_table_reuse_ratio",
+            "Fraction of lookups that returned an existing handle.",
+            &tables,
+            |stats| ConstGauge::new(stats.reuse_ratio()),
+        )
+    }
+}
+
+/// Encodes one metric family, with a metric for each table.
+fn encode_family<V: EncodeMetric>(
+    encoder: &mut DescriptorEncoder,
+    name: &str,
+    help: &str,
+    tables: &[(String, TableStats)],
+    value: impl Fn(&TableStats) -> V,
+) -> fmt::Result {
+    let Some((_, first)) = tables.first() else {
+        return Ok(());
+    };
+    let mut family = encoder.encode_descriptor(name, help, None, value(first).metric_type())?;
+    for (table, stats) in tables {
+        let labels = [("table", table.as_str())];
+        value(stats).encode(family.encode_family(&labels)?)?;
+    }
+    Ok(())
+}
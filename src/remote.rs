@@ -0,0 +1,293 @@
+//! # Remote Handles
+//!
+//! Sending hash consed DAGs between processes. An [`Encoder`] turns the DAG below a handle into
+//! a [`Message`] of [`WireNode`]s, each naming itself and its children by a content fingerprint.
+//! A [`Decoder`] on the other side interns the nodes into its local table and remembers the
+//! handle of every fingerprint it received. The encoder remembers what it sent, so later
+//! messages leave out every node the receiver already has: sending the same huge term again
+//! costs a single fingerprint, and a term sharing most of its subterms with earlier ones costs
+//! only its new nodes.
+//!
+//! Like the `egg` conversions, the mapping between interned nodes and the data on the wire is
+//! supplied by the caller, as the data of a node without its children. Messages are plain data
+//! with public fields, so that any transport and serialization format can carry them.
+//!
+//! Fingerprints hash the data of a node together with the fingerprints of its children, so
+//! equal subterms get equal fingerprints in every process running the same build of the
+//! program. An encoder and a decoder must be used as a pair over one ordered connection: if the
+//! receiving side loses its decoder, [`Encoder::clear`] makes the sender start over.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::remote::{Decoder, Encoder};
+//! use hash_cons::{HashConsed, Hc, HcTable};
+//!
+//! #[derive(Hash, PartialEq, Eq)]
+//! enum Expr {
+//!     Lit(u32),
+//!     Add(Hc<Expr>, Hc<Expr>),
+//! }
+//!
+//! impl HashConsed for Expr {
+//!     fn children(&self) -> Vec<&Hc<Expr>> {
+//!         match self {
+//!             Expr::Lit(_) => vec![],
+//!             Expr::Add(lhs, rhs) => vec![lhs, rhs],
+//!         }
+//!     }
+//! }
+//!
+//! // The data of a node on the wire, without its children.
+//! let data = |expr: &Expr| match expr {
+//!     Expr::Lit(n) => Some(*n),
+//!     Expr::Add(..) => None,
+//! };
+//! let node = |data: &Option<u32>, children: &[Hc<Expr>]| match data {
+//!     Some(n) => Expr::Lit(*n),
+//!     None => Expr::Add(children[0].clone(), children[1].clone()),
+//! };
+//!
+//! let sender = HcTable::new();
+//! let one = sender.hashcons(Expr::Lit(1));
+//! let sum = sender.hashcons(Expr::Add(one.clone(), one));
+//!
+//! let receiver = HcTable::new();
+//! let (mut encoder, mut decoder) = (Encoder::new(), Decoder::new(&receiver));
+//!
+//! let message = encoder.encode(&sum, data);
+//! assert_eq!(message.nodes.len(), 2);
+//! let received = decoder.decode(&message, node).unwrap();
+//! assert!(matches!(&*received, Expr::Add(lhs, rhs) if lhs == rhs));
+//!
+//! // Sending the same term again only sends its fingerprint.
+//! let again = encoder.encode(&sum, data);
+//! assert!(again.nodes.is_empty());
+//! assert!(decoder.decode(&again, node).unwrap() == received);
+//! ```
+
+use crate::dag::HashConsed;
+use crate::hasher::HashState;
+use crate::{Hc, HcTable};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/// The seed of the hasher states fingerprints are computed with.
+const SEED: u64 = 0x6863_5f72_656d_6f74;
+
+/// # `WireNode<L>`
+/// A node of a [`Message`]: its data and the fingerprints of its children, in the order of
+/// [`HashConsed::children`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireNode<L> {
+    /// The fingerprint of the node.
+    pub fingerprint: u128,
+    /// The data of the node, without its children.
+    pub data: L,
+    /// The fingerprints of the children of the node.
+    pub children: Vec<u128>,
+}
+
+/// # `Message<L>`
+/// A DAG sent by an [`Encoder`]: the nodes the receiver does not have yet, children before
+/// parents, and the fingerprint of the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message<L> {
+    /// The new nodes, children before parents.
+    pub nodes: Vec<WireNode<L>>,
+    /// The fingerprint of the root.
+    pub root: u128,
+}
+
+/// # `Encoder<T>`
+/// The sending side of a connection, which remembers the nodes it sent.
+///
+/// It keeps handles to the sent nodes, so that they stay recognizable by their address.
+pub struct Encoder<T> {
+    sent: HashMap<usize, (Hc<T>, u128)>,
+    states: (HashState, HashState),
+}
+
+impl<T> Encoder<T>
+where
+    T: HashConsed,
+{
+    /// Creates an encoder that has sent nothing yet.
+    pub fn new() -> Self {
+        Encoder {
+            sent: HashMap::new(),
+            states: (HashState::with_seed(SEED), HashState::with_seed(!SEED)),
+        }
+    }
+
+    /// Encodes the DAG rooted at `root`, leaving out the nodes sent before.
+    ///
+    /// ## Parameters
+    /// * `root`: The root of the DAG.
+    /// * `data`: Returns the data of a node, without its children.
+    ///
+    /// Nodes are visited with an explicit stack, and subterms sent before are not entered, so
+    /// encoding costs as much as the new nodes.
+    pub fn encode<L, F>(&mut self, root: &Hc<T>, mut data: F) -> Message<L>
+    where
+        L: Hash,
+        F: FnMut(&T) -> L,
+    {
+        let mut nodes = Vec::new();
+        let mut stack = vec![(root.clone(), false)];
+
+        while let Some((node, expanded)) = stack.pop() {
+            if self.sent.contains_key(&node.addr()) {
+                continue;
+            }
+
+            if expanded {
+                let children: Vec<u128> = node
+                    .children()
+                    .into_iter()
+                    .map(|child| self.sent[&child.addr()].1)
+                    .collect();
+                let data = data(node.get());
+                let fingerprint = self.fingerprint(&data, &children);
+                self.sent.insert(node.addr(), (node, fingerprint));
+                nodes.push(WireNode {
+                    fingerprint,
+                    data,
+                    children,
+                });
+            } else {
+                let children: Vec<Hc<T>> = node
+                    .children()
+                    .into_iter()
+                    .filter(|child| !self.sent.contains_key(&child.addr()))
+                    .cloned()
+                    .collect();
+                stack.push((node, true));
+                // Pushed in reverse so that the leftmost child is encoded first.
+                stack.extend(children.into_iter().rev().map(|child| (child, false)));
+            }
+        }
+
+        Message {
+            nodes,
+            root: self.sent[&root.addr()].1,
+        }
+    }
+
+    /// Forgets the sent nodes, so that the next messages are complete. Use it when the receiver
+    /// lost its decoder, e.g. after reconnecting.
+    pub fn clear(&mut self) {
+        self.sent.clear();
+    }
+
+    /// Returns the number of nodes sent.
+    pub fn len(&self) -> usize {
+        self.sent.len()
+    }
+
+    /// Returns `true` if no node was sent yet.
+    pub fn is_empty(&self) -> bool {
+        self.sent.is_empty()
+    }
+
+    /// Returns the fingerprint of a node with the given data and children.
+    fn fingerprint<L: Hash>(&self, data: &L, children: &[u128]) -> u128 {
+        let low = self.states.0.hash_one((data, children));
+        let high = self.states.1.hash_one((data, children));
+        (high as u128) << 64 | low as u128
+    }
+}
+
+impl<T> Default for Encoder<T>
+where
+    T: HashConsed,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # `Decoder<T>`
+/// The receiving side of a connection, which interns the received nodes into a table and
+/// remembers their handles by fingerprint.
+pub struct Decoder<T> {
+    table: HcTable<T>,
+    received: HashMap<u128, Hc<T>>,
+}
+
+impl<T> Decoder<T>
+where
+    T: HashConsed,
+{
+    /// Creates a decoder interning into `table`.
+    pub fn new(table: &HcTable<T>) -> Self {
+        Decoder {
+            table: table.clone(),
+            received: HashMap::new(),
+        }
+    }
+
+    /// Interns the nodes of `message` and returns the handle of its root.
+    ///
+    /// ## Parameters
+    /// * `message`: A message of the encoder this decoder is paired with.
+    /// * `node`: Builds a node from its data, given the handles of its children in order.
+    ///
+    /// ## Errors
+    /// Returns [`UnknownFingerprint`] if the message refers to a node that was neither in it nor
+    /// received before, which happens if messages were lost or reordered. The nodes before it
+    /// are interned and remembered.
+    pub fn decode<L, F>(
+        &mut self,
+        message: &Message<L>,
+        mut node: F,
+    ) -> Result<Hc<T>, UnknownFingerprint>
+    where
+        F: FnMut(&L, &[Hc<T>]) -> T,
+    {
+        for wire in &message.nodes {
+            let children = wire
+                .children
+                .iter()
+                .map(|&fingerprint| self.get(fingerprint))
+                .collect::<Result<Vec<_>, _>>()?;
+            let hc = self.table.hashcons(node(&wire.data, &children));
+            self.received.insert(wire.fingerprint, hc);
+        }
+        self.get(message.root)
+    }
+
+    /// Returns the number of nodes received.
+    pub fn len(&self) -> usize {
+        self.received.len()
+    }
+
+    /// Returns `true` if no node was received yet.
+    pub fn is_empty(&self) -> bool {
+        self.received.is_empty()
+    }
+
+    fn get(&self, fingerprint: u128) -> Result<Hc<T>, UnknownFingerprint> {
+        self.received
+            .get(&fingerprint)
+            .cloned()
+            .ok_or(UnknownFingerprint(fingerprint))
+    }
+}
+
+/// # `UnknownFingerprint`
+/// The error of [`Decoder::decode`] when a message refers to a node the decoder never received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownFingerprint(pub u128);
+
+impl fmt::Display for UnknownFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hash_cons: no node with fingerprint {:032x} was received",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownFingerprint {}
@@ -0,0 +1,91 @@
+//! # Retirement Queue
+//!
+//! A lock-free queue through which dropped handles hand their table entries to the next thread
+//! that takes the write lock anyway, so that dropping a handle never waits for the lock.
+//! Entries are pushed one at a time and only ever taken all at once, which keeps the queue a
+//! plain linked stack free of the ABA problem.
+
+use crate::sync::{AtomicPtr, AtomicUsize, Ordering};
+use std::ptr;
+
+/// # `RetireQueue<V>`
+/// A multi-producer stack that is drained as a whole.
+///
+/// ## Fields
+/// * `head`: The most recently pushed node, or null.
+/// * `len`: The number of values pushed and not yet taken, counted before they are pushed.
+pub(crate) struct RetireQueue<V> {
+    head: AtomicPtr<Node<V>>,
+    len: AtomicUsize,
+}
+
+/// A value in the queue and the node pushed before it.
+struct Node<V> {
+    value: V,
+    next: *mut Node<V>,
+}
+
+// The queue owns its nodes and only moves values between threads.
+unsafe impl<V: Send> Send for RetireQueue<V> {}
+unsafe impl<V: Send> Sync for RetireQueue<V> {}
+
+impl<V> RetireQueue<V> {
+    /// Creates an empty queue.
+    pub(crate) fn new() -> Self {
+        RetireQueue {
+            head: AtomicPtr::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns `true` if nothing was pushed since the queue was last drained.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    /// Pushes `value` without blocking.
+    ///
+    /// ## Returns
+    /// The number of values in the queue, including `value`.
+    pub(crate) fn push(&self, value: V) -> usize {
+        // Counting before publishing keeps `len` from dropping below the number of nodes.
+        let len = self.len.fetch_add(1, Ordering::Relaxed) + 1;
+        let node = Box::into_raw(Box::new(Node {
+            value,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: `node` is not shared until the exchange below succeeds.
+            unsafe { (*node).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        len
+    }
+
+    /// Removes and returns every value in the queue, most recently pushed first.
+    pub(crate) fn take_all(&self) -> Vec<V> {
+        let mut node = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut values = Vec::new();
+        while !node.is_null() {
+            // SAFETY: the swap above made this thread the only owner of the detached nodes.
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            values.push(boxed.value);
+        }
+        self.len.fetch_sub(values.len(), Ordering::Relaxed);
+        values
+    }
+}
+
+impl<V> Drop for RetireQueue<V> {
+    fn drop(&mut self) {
+        self.take_all();
+    }
+}
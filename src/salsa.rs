@@ -0,0 +1,265 @@
+//! # Salsa Adapter
+//!
+//! Exposes hash consed values as salsa-style interning keys, so that `hash_cons` can act as
+//! the interner backing an incremental query engine.
+//!
+//! Every distinct value interned through a [`SalsaInterner`] receives a dense [`InternId`],
+//! starting at zero and increasing by one for each new value. Ids are never reused, and the
+//! interner keeps the value alive for as long as the interner itself lives, which matches the
+//! lifetime salsa expects from interned data. Each id also carries a [`Durability`] hint that
+//! the query engine can use to skip revalidation of rarely changing inputs.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::salsa::{Durability, SalsaInterner};
+//!
+//! let interner: SalsaInterner<String> = SalsaInterner::new();
+//! let id = interner.intern("main".to_string());
+//! let same = interner.intern_with_durability("main".to_string(), Durability::High);
+//!
+//! assert_eq!(id, same);
+//! assert_eq!(id.as_u32(), 0);
+//! assert_eq!(interner.lookup(id).as_str(), "main");
+//! assert_eq!(interner.durability(id), Some(Durability::High));
+//! ```
+
+use crate::{Hc, HcTable};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::RwLock;
+
+/// # `Durability`
+/// A hint describing how often the input behind an interned value is expected to change.
+///
+/// The variants are ordered, `Low < Medium < High`, so that the durability of a query can be
+/// computed as the minimum of the durabilities it depends on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Durability {
+    /// Changes frequently, e.g. the contents of the file being edited.
+    #[default]
+    Low,
+    /// Changes occasionally, e.g. project configuration.
+    Medium,
+    /// Rarely changes, e.g. the standard library.
+    High,
+}
+
+/// # `InternId`
+/// A dense identifier assigned by a [`SalsaInterner`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InternId(u32);
+
+impl InternId {
+    /// The largest id an interner can hand out.
+    pub const MAX: u32 = u32::MAX - 1;
+
+    /// Creates an `InternId` from its raw index.
+    ///
+    /// ## Panics
+    /// Panics if `index` is greater than [`InternId::MAX`].
+    pub fn from_u32(index: u32) -> Self {
+        assert!(index <= Self::MAX, "InternId index out of range");
+        InternId(index)
+    }
+
+    /// Returns the raw index of this id.
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the raw index of this id as a `usize`, suitable for indexing side tables.
+    pub fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// # `InternKey`
+/// Conversion between a user-defined key type and an [`InternId`].
+///
+/// Implement this for newtype keys (`struct FunctionId(InternId)`) so that different kinds of
+/// interned values cannot be mixed up, in the same way salsa's interned queries do.
+pub trait InternKey: Copy {
+    /// Wraps an id handed out by the interner.
+    fn from_intern_id(id: InternId) -> Self;
+
+    /// Returns the id wrapped by this key.
+    fn as_intern_id(&self) -> InternId;
+}
+
+impl InternKey for InternId {
+    fn from_intern_id(id: InternId) -> Self {
+        id
+    }
+
+    fn as_intern_id(&self) -> InternId {
+        *self
+    }
+}
+
+/// # `SalsaInterner<T, K>`
+/// An interner handing out dense keys for hash consed values.
+///
+/// ## Type Parameters
+/// * `T` - The type of the interned values.
+/// * `K` - The key type returned for interned values, [`InternId`] by default.
+///
+/// ## Fields
+/// * `table`: The `HcTable` the values are hash consed in.
+/// * `slots`: The id to value mapping, together with the reverse lookup.
+pub struct SalsaInterner<T, K = InternId>
+where
+    T: Hash + Eq,
+    K: InternKey,
+{
+    table: HcTable<T>,
+    slots: RwLock<Slots<T>>,
+    _key: PhantomData<fn() -> K>,
+}
+
+/// The mutable state of a `SalsaInterner`.
+struct Slots<T>
+where
+    T: Hash + Eq,
+{
+    values: Vec<(Hc<T>, Durability)>,
+    // Keyed by the address of the handle, which is unique per value within a table.
+    ids: HashMap<usize, InternId>,
+}
+
+impl<T, K> SalsaInterner<T, K>
+where
+    T: Hash + Eq,
+    K: InternKey,
+{
+    /// Creates an interner backed by a fresh `HcTable`.
+    pub fn new() -> Self {
+        Self::with_table(HcTable::new())
+    }
+
+    /// Creates an interner backed by an existing `HcTable`.
+    ///
+    /// Values already held in the table keep their identity: interning one of them hands out
+    /// the same handle that other users of the table see.
+    pub fn with_table(table: HcTable<T>) -> Self {
+        SalsaInterner {
+            table,
+            slots: RwLock::new(Slots {
+                values: Vec::new(),
+                ids: HashMap::new(),
+            }),
+            _key: PhantomData,
+        }
+    }
+
+    /// Returns the table backing this interner.
+    pub fn table(&self) -> &HcTable<T> {
+        &self.table
+    }
+
+    /// Interns `value` with [`Durability::Low`] and returns its key.
+    pub fn intern(&self, value: T) -> K {
+        self.intern_with_durability(value, Durability::Low)
+    }
+
+    /// Interns `value` and returns its key.
+    ///
+    /// If the value was interned before, its existing key is returned and its durability is
+    /// raised to `durability` if that is higher than the recorded one.
+    ///
+    /// ## Panics
+    /// Panics if more than [`InternId::MAX`] distinct values are interned.
+    pub fn intern_with_durability(&self, value: T, durability: Durability) -> K {
+        self.intern_hc(self.table.hashcons(value), durability)
+    }
+
+    /// Interns an existing handle from the backing table and returns its key.
+    pub fn intern_hc(&self, hc: Hc<T>, durability: Durability) -> K {
+        let mut slots = match self.slots.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                poisoned.into_inner() // continues, every write leaves the slots consistent
+            }
+        };
+
+        if let Some(&id) = slots.ids.get(&hc.addr()) {
+            let slot = &mut slots.values[id.as_usize()].1;
+            *slot = (*slot).max(durability);
+            return K::from_intern_id(id);
+        }
+
+        let id = InternId::from_u32(
+            u32::try_from(slots.values.len()).expect("too many values interned"),
+        );
+        slots.ids.insert(hc.addr(), id);
+        slots.values.push((hc, durability));
+        K::from_intern_id(id)
+    }
+
+    /// Returns the handle interned under `key`.
+    ///
+    /// ## Panics
+    /// Panics if `key` was not handed out by this interner.
+    pub fn lookup(&self, key: K) -> Hc<T> {
+        self.try_lookup(key)
+            .expect("key was not handed out by this interner")
+    }
+
+    /// Returns the handle interned under `key`, or `None` if the key is unknown.
+    pub fn try_lookup(&self, key: K) -> Option<Hc<T>> {
+        let slots = self.read_slots();
+        slots
+            .values
+            .get(key.as_intern_id().as_usize())
+            .map(|(hc, _)| hc.clone())
+    }
+
+    /// Returns the key of a value without interning it.
+    pub fn key_of(&self, hc: &Hc<T>) -> Option<K> {
+        self.read_slots()
+            .ids
+            .get(&hc.addr())
+            .copied()
+            .map(K::from_intern_id)
+    }
+
+    /// Returns the durability recorded for `key`, or `None` if the key is unknown.
+    pub fn durability(&self, key: K) -> Option<Durability> {
+        let slots = self.read_slots();
+        slots
+            .values
+            .get(key.as_intern_id().as_usize())
+            .map(|&(_, durability)| durability)
+    }
+
+    /// Returns the number of values interned through this interner.
+    pub fn len(&self) -> usize {
+        self.read_slots().values.len()
+    }
+
+    /// Returns `true` if nothing has been interned through this interner.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn read_slots(&self) -> std::sync::RwLockReadGuard<'_, Slots<T>> {
+        match self.slots.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                poisoned.into_inner() // continues, because we only read
+            }
+        }
+    }
+}
+
+impl<T, K> Default for SalsaInterner<T, K>
+where
+    T: Hash + Eq,
+    K: InternKey,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,183 @@
+//! # Serde Field Adapter
+//!
+//! Serializes `Hc<T>` fields as their values, and deserializes them by interning the values
+//! into the table installed with [`with_table`], for use with `#[serde(with = ...)]`:
+//!
+//! ```
+//! use hash_cons::{serde_hc, Hc, HcTable};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Symbol {
+//!     #[serde(with = "hash_cons::serde_hc")]
+//!     name: Hc<String>,
+//!     arity: u32,
+//! }
+//!
+//! let table = HcTable::new();
+//! let name = table.hashcons("cons".to_string());
+//! let json = serde_json::to_string(&Symbol { name: name.clone(), arity: 2 }).unwrap();
+//! assert_eq!(json, r#"{"name":"cons","arity":2}"#);
+//!
+//! let symbol: Symbol = serde_hc::with_table(&table, || serde_json::from_str(&json)).unwrap();
+//! assert_eq!(symbol.name, name);
+//! assert_eq!(table.len(), 1);
+//! ```
+//!
+//! The table is looked up by the type of its values, so one table per value type can be
+//! installed at a time, and calls to `with_table` nest. Sharing is restored by interning:
+//! every occurrence of a value is serialized in full, and equal values deserialize to the same
+//! handle.
+//!
+//! Whole tables serialize as the list of their values with handles, in storing order, so that
+//! every value comes after the values its handles point to. Within that list, handles of the
+//! table serialized with this adapter are written as the position of their value instead of in
+//! full, which keeps shared values shared:
+//!
+//! ```
+//! use hash_cons::{Hc, HcTable};
+//! use serde::Serialize;
+//!
+//! #[derive(Hash, PartialEq, Eq, Serialize)]
+//! enum Term {
+//!     Var(String),
+//!     Pair(
+//!         #[serde(with = "hash_cons::serde_hc")] Hc<Term>,
+//!         #[serde(with = "hash_cons::serde_hc")] Hc<Term>,
+//!     ),
+//! }
+//!
+//! let table = HcTable::new();
+//! let x = table.hashcons(Term::Var("x".to_string()));
+//! let pair = table.hashcons(Term::Pair(x.clone(), x.clone()));
+//!
+//! let json = serde_json::to_string(&table).unwrap();
+//! assert_eq!(json, r#"[{"Var":"x"},{"Pair":[0,0]}]"#);
+//! ```
+
+use crate::{Hc, HcTable};
+use serde::de::Error;
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+thread_local! {
+    /// The tables installed by `with_table` on this thread, innermost last.
+    static TABLES: RefCell<Vec<Box<dyn Any>>> = const { RefCell::new(Vec::new()) };
+}
+
+thread_local! {
+    /// The positions of the values written so far by the tables being serialized on this
+    /// thread, by address of the values, innermost last.
+    static POSITIONS: RefCell<Vec<HashMap<usize, usize>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Removes the innermost installed table when dropped, also by unwinding.
+struct Installed;
+
+impl Drop for Installed {
+    fn drop(&mut self) {
+        TABLES.with(|tables| tables.borrow_mut().pop());
+    }
+}
+
+/// Runs `f` with `table` installed as the table that handles of type `Hc<T>` are deserialized
+/// into on this thread.
+///
+/// ## Returns
+/// The result of `f`.
+pub fn with_table<T, R>(table: &HcTable<T>, f: impl FnOnce() -> R) -> R
+where
+    T: Hash + Eq + 'static,
+{
+    TABLES.with(|tables| tables.borrow_mut().push(Box::new(table.clone())));
+    let _installed = Installed;
+    f()
+}
+
+/// Removes the positions of the innermost table being serialized when dropped, also by
+/// unwinding.
+struct Serializing;
+
+impl Drop for Serializing {
+    fn drop(&mut self) {
+        POSITIONS.with(|positions| positions.borrow_mut().pop());
+    }
+}
+
+/// Serializes the value of `hc`, or its position if the table being serialized on this thread
+/// wrote the value already.
+pub fn serialize<T, S>(hc: &Hc<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Hash + Eq + Serialize,
+    S: Serializer,
+{
+    let address = hc.as_ptr() as usize;
+    let position = POSITIONS.with(|positions| {
+        positions
+            .borrow()
+            .last()
+            .and_then(|written| written.get(&address).copied())
+    });
+    match position {
+        Some(position) => serializer.serialize_u64(position as u64),
+        None => hc.get().serialize(serializer),
+    }
+}
+
+impl<T> Serialize for HcTable<T>
+where
+    T: Hash + Eq + Serialize,
+{
+    /// Serializes the values that still have handles as a sequence, in storing order. Handles
+    /// of this table within the values are written as the position of their value.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries = self.export_entries();
+        POSITIONS.with(|positions| {
+            positions
+                .borrow_mut()
+                .push(HashMap::with_capacity(entries.len()))
+        });
+        let _serializing = Serializing;
+
+        let mut seq = serializer.serialize_seq(Some(entries.len()))?;
+        for (position, (_, hc)) in entries.iter().enumerate() {
+            seq.serialize_element(hc.get())?;
+            POSITIONS.with(|positions| {
+                if let Some(written) = positions.borrow_mut().last_mut() {
+                    written.insert(hc.as_ptr() as usize, position);
+                }
+            });
+        }
+        seq.end()
+    }
+}
+
+/// Deserializes a value and interns it into the innermost table installed for `T`.
+///
+/// ## Errors
+/// Fails if the value fails to deserialize, or if no table for `T` is installed on this thread.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Hc<T>, D::Error>
+where
+    T: Hash + Eq + Deserialize<'de> + 'static,
+    D: Deserializer<'de>,
+{
+    let value = T::deserialize(deserializer)?;
+    let table = TABLES.with(|tables| {
+        tables
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|table| table.downcast_ref::<HcTable<T>>().cloned())
+    });
+    match table {
+        Some(table) => Ok(table.hashcons(value)),
+        None => Err(D::Error::custom(format!(
+            "no table for `{}` is installed, see `serde_hc::with_table`",
+            std::any::type_name::<T>()
+        ))),
+    }
+}
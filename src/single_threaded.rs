@@ -1,18 +1,55 @@
-#[cfg(feature = "single-threaded")]
-use std::cell::RefCell;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
+use crate::buckets::Buckets;
+#[cfg(not(feature = "auto-cleanup"))]
+use crate::buckets::Entry;
+use crate::hasher::{HashState, IdentityHasher};
+#[cfg(feature = "history")]
+use crate::history::HistoryEvent;
+use crate::inflight::{InFlight, Lookup};
+#[cfg(feature = "leak-check")]
+use crate::leak::LeakReport;
+use crate::mem::{MemSize, MemoryUsage};
+use crate::pressure::TryRelease;
+#[cfg(not(feature = "auto-cleanup"))]
+use crate::stats::CleanupReport;
+use crate::stats::{HashDiagnostics, TableStats};
+#[cfg(not(feature = "auto-cleanup"))]
+use crate::strategy::EvictionOrder;
+use crate::strategy::{
+    message_prefix, Canonical, ConsEq, ConsHash, ConsNormalize, ConsWeight, DefaultEq, DefaultHash,
+    IdAllocator, Identity, PoisonPolicy, SequentialIds, Structural,
+};
+use crate::thread_safe;
+use crate::token::{next_table_id, HcToken};
+use std::any::Any;
+use std::borrow::{Borrow, Cow};
+use std::cell::{Cell, RefCell, RefMut};
+#[cfg(not(feature = "auto-cleanup"))]
+use std::collections::HashSet;
+use std::collections::{HashMap, TryReserveError};
+use std::convert::Infallible;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::{size_of, ManuallyDrop};
+use std::ptr::NonNull;
 use std::rc::{Rc, Weak};
+#[cfg(not(feature = "auto-cleanup"))]
+use std::time::{Duration, Instant};
+
+/// The number of retired entries after which the dropping handle collects them, in deferred
+/// reclamation mode.
+#[cfg(feature = "auto-cleanup")]
+const RETIRED_BATCH: usize = 1024;
 
 /// # `Hc<T>`
 /// A single-threaded custom smart pointer type for managing the lifecycle of consed values.
 ///
 /// ## Type Parameters
-/// * `T` - The type of values managed by this smart pointer. Must implement `Hash` and `Eq`.
+/// * `T` - The type of values managed by this smart pointer.
+/// * `M` - Whether handles compare by value, [`Structural`], or by address, [`Identity`].
 ///
 /// ## Fields
 /// * `inner`: `Rc<Inner<T>>` - Reference counted pointer to the inner value.
+/// * `_semantics`: The marker of `M`.
 ///
 /// ## Example
 /// ```
@@ -24,21 +61,25 @@ use std::rc::{Rc, Weak};
 ///
 /// assert_eq!(*hc_pointer.get(), 42);
 /// ```
-pub struct Hc<T>
-where
-    T: Hash + Eq,
-{
+pub struct Hc<T, M = Structural> {
     inner: Rc<Inner<T>>,
+    _semantics: PhantomData<fn() -> M>,
 }
 
-impl<T> Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> Hc<T, M> {
+    /// Wraps the allocation of a stored value into a handle.
+    fn from_inner(inner: Rc<Inner<T>>) -> Self {
+        Hc {
+            inner,
+            _semantics: PhantomData,
+        }
+    }
+
     /// Retrieves a reference to the value stored in this `Hc<T>`.
     ///
     /// ## Returns
-    /// A reference to the stored value.
+    /// A reference to the stored value. The value is shared by every handle to it and cannot
+    /// be mutated, use `make_owned` to intern a modified copy instead.
     ///
     /// ## Example
     /// ```
@@ -52,12 +93,163 @@ where
     pub fn get(&self) -> &T {
         &self.inner.elem
     }
+
+    /// Copies the value out of this `Hc<T>`, so that a modified version can be interned.
+    ///
+    /// Interned values are shared and cannot be mutated through a handle. To change one, copy
+    /// it out, modify the copy and intern it again, which yields a handle to the modified value
+    /// while every existing handle keeps the original.
+    ///
+    /// ## Returns
+    /// A clone of the stored value, owned by the caller.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let original = table.hashcons(vec![1, 2]);
+    ///
+    /// let mut owned = original.make_owned();
+    /// owned.push(3);
+    /// let modified = table.hashcons(owned);
+    ///
+    /// assert_eq!(*original, vec![1, 2]);
+    /// assert_eq!(*modified, vec![1, 2, 3]);
+    /// ```
+    pub fn make_owned(&self) -> T
+    where
+        T: Clone,
+    {
+        T::clone(&self.inner.elem)
+    }
+
+    /// Returns the generation of the table in which the value was stored, see
+    /// `HcTable::advance_generation()`.
+    ///
+    /// Handles of values interned again in later generations keep the generation in which the
+    /// value was first stored, which tells values created in the current phase of a pipeline
+    /// apart from values cached by earlier ones.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let cached = table.hashcons(1);
+    /// table.advance_generation();
+    /// let fresh = table.hashcons(2);
+    ///
+    /// assert_eq!(table.hashcons(1).birth_generation(), 0);
+    /// assert_eq!(fresh.birth_generation(), 1);
+    /// ```
+    pub fn birth_generation(&self) -> u64 {
+        self.inner.generation
+    }
+
+    /// Returns a pointer to the interned value.
+    ///
+    /// The address stays the same for as long as the value is interned, and all handles of a
+    /// table to equal values share it, so it can identify nodes in logs or key external data
+    /// structures. Formatting a handle with `{:p}` prints it.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(5);
+    ///
+    /// assert_eq!(value.as_ptr(), table.hashcons(5).as_ptr());
+    /// assert_eq!(format!("{value:p}"), format!("{:p}", value.as_ptr()));
+    /// ```
+    pub fn as_ptr(&self) -> *const T {
+        Rc::as_ptr(&self.inner.elem)
+    }
+
+    /// Returns the hash the table stored the value under, computed once when it was interned.
+    ///
+    /// Structures keyed by interned values, such as bloom filters or hash-partitioned queues,
+    /// can use it instead of hashing the value again. It is the hash of the table, not the one
+    /// of `T::hash`: tables are seeded randomly unless built with `HcTableBuilder::seed()`, so
+    /// only handles of one table agree on it. In trusted-hash mode it is the lower half of the
+    /// 128-bit hash.
+    pub fn hash_value(&self) -> u64 {
+        self.inner.hash as u64
+    }
+
+    /// Returns a token naming the value of this handle, resolved with `HCTable::resolve`.
+    pub fn token(&self) -> HcToken {
+        HcToken {
+            table: self.inner._table.id,
+            seq: self.inner.seq,
+            hash: self.inner.hash,
+        }
+    }
+
+    /// Returns the sequence number of the entry of the value, which is unique within the table
+    /// and increases in storing order.
+    pub(crate) fn seq(&self) -> u64 {
+        self.inner.seq
+    }
+
+    /// Returns the address of the shared allocation backing this handle.
+    /// Two handles from the same table compare equal exactly when their addresses match.
+    pub(crate) fn addr(&self) -> usize {
+        Rc::as_ptr(&self.inner) as usize
+    }
+
+    /// Converts this handle into a trait-object handle, so that values of different types can be
+    /// stored behind a common trait.
+    ///
+    /// The value is not copied: the returned `HcDyn<U>` points at the same allocation as this
+    /// handle and keeps the table entry alive for as long as it exists.
+    ///
+    /// ## Parameters
+    /// * `coerce`: Performs the unsizing coercion, usually written as `|value| value`.
+    ///   It must return the pointer it was given. The target type can be named with a turbofish,
+    ///   as in `hc.into_dyn::<dyn Trait>(|value| value)`.
+    ///
+    /// ## Returns
+    /// A `HcDyn<U>` sharing the value of this handle.
+    ///
+    /// ## Panics
+    /// If `coerce` returns a different allocation than the one it was given.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::single_threaded::{HcDyn, HcTable};
+    /// use std::fmt::Debug;
+    ///
+    /// let ints = HcTable::new();
+    /// let strs = HcTable::new();
+    ///
+    /// let nodes: Vec<HcDyn<dyn Debug>> = vec![
+    ///     ints.hashcons(5).into_dyn::<dyn Debug>(|value| value),
+    ///     strs.hashcons("five").into_dyn::<dyn Debug>(|value| value),
+    /// ];
+    ///
+    /// assert_eq!(format!("{:?}", nodes[1]), "\"five\"");
+    /// ```
+    pub fn into_dyn<U>(self, coerce: impl FnOnce(Rc<T>) -> Rc<U>) -> HcDyn<U>
+    where
+        T: 'static,
+        U: ?Sized,
+    {
+        let before = Rc::as_ptr(&self.inner.elem);
+        let value = coerce(self.inner.elem.clone());
+        assert!(
+            std::ptr::addr_eq(before, Rc::as_ptr(&value)),
+            "`coerce` must return the pointer it was given"
+        );
+        HcDyn {
+            value,
+            _handle: self.inner,
+        }
+    }
 }
 
-impl<T: PartialEq> PartialEq for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: PartialEq> PartialEq for Hc<T> {
     /// Provides the functionality to compare two `Hc<T>` instances for equality.
     ///
     /// ## Parameters
@@ -83,12 +275,9 @@ where
     }
 }
 
-impl<T> Eq for Hc<T> where T: Hash + Eq {}
+impl<T: Eq> Eq for Hc<T> {}
 
-impl<T> Hash for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: Hash> Hash for Hc<T> {
     /// Provides the functionality to hash `Hc<T>` instances.
     /// This method is useful for storing `Hc<T>` instances in a `HashMap`.
     /// It is also used internally by the `HCTable` to manage the storage of
@@ -115,10 +304,7 @@ where
     }
 }
 
-impl<T> Clone for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> Clone for Hc<T, M> {
     /// Provides the functionality to clone `Hc<T>` instances.
     ///
     /// ## Returns
@@ -143,16 +329,11 @@ where
     /// to retrieve a reference to the value and clone it.
     ///
     fn clone(&self) -> Self {
-        Hc {
-            inner: self.inner.clone(),
-        }
+        Hc::from_inner(self.inner.clone())
     }
 }
 
-impl<T: std::fmt::Debug> std::fmt::Debug for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: std::fmt::Debug, M> std::fmt::Debug for Hc<T, M> {
     /// Provides the functionality to print `Hc<T>` instances.
     /// This method is useful for debugging.
     ///
@@ -174,10 +355,7 @@ where
     }
 }
 
-impl<T: std::fmt::Display> std::fmt::Display for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: std::fmt::Display, M> std::fmt::Display for Hc<T, M> {
     /// Provides the functionality to print `Hc<T>` instances.
     /// This method is useful for debugging.
     ///
@@ -199,10 +377,14 @@ where
     }
 }
 
-impl<T> std::ops::Deref for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> std::fmt::Pointer for Hc<T, M> {
+    /// Formats the address of the interned value, see `as_ptr`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Pointer::fmt(&self.as_ptr(), f)
+    }
+}
+
+impl<T, M> std::ops::Deref for Hc<T, M> {
     type Target = T;
 
     /// Provides the functionality to dereference `Hc<T>` instances.
@@ -231,10 +413,7 @@ where
     }
 }
 
-impl<T> AsRef<T> for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> AsRef<T> for Hc<T, M> {
     /// Provides the functionality to convert `Hc<T>` instances to references.
     /// This method is useful for accessing the underlying value.
     ///
@@ -260,10 +439,7 @@ where
     }
 }
 
-impl<T: PartialOrd> PartialOrd for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: PartialOrd> PartialOrd for Hc<T> {
     /// Provides the functionality to compare two `Hc<T>` instances.
     /// This method is useful for sorting `Hc<T>` instances.
     ///
@@ -293,10 +469,7 @@ where
     }
 }
 
-impl<T> Ord for Hc<T>
-where
-    T: Ord + Hash + Eq,
-{
+impl<T: Ord> Ord for Hc<T> {
     /// Provides the functionality to compare two `Hc<T>` instances.
     /// This method is useful for sorting `Hc<T>` instances.
     ///
@@ -326,214 +499,3116 @@ where
     }
 }
 
-/// # `HCTable<T>`
-/// A table structure for efficiently managing `Hc<T>` instances.
-/// This struct hides the underlying table and its reference count management.
+impl<T> PartialEq for Hc<T, Identity> {
+    /// Compares two handles of a table built with `HcTableBuilder::identity()` by address.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::builder().identity().build();
+    /// let value1 = table.hashcons(5);
+    /// let value2 = table.hashcons(5);
+    ///
+    /// assert_eq!(value1, value2);
+    /// assert_ne!(value1, HcTable::builder().identity().build().hashcons(5));
+    /// ```
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T> Eq for Hc<T, Identity> {}
+
+impl<T> Hash for Hc<T, Identity> {
+    /// Hashes the address of the value, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
+    }
+}
+
+/// # `HcDyn<U>`
+/// A single-threaded handle to a hash consed value viewed through an unsized type, usually a trait
+/// object. Created with [`Hc::into_dyn`].
 ///
-/// This structure utilizes a HashMap to store `Hc<T>` instances, offering
-/// quick retrieval and management capabilities.
+/// Handles are compared and hashed by the address of the shared value. Since a table stores
+/// each distinct value once, two handles created from the same table are equal exactly when
+/// their values are.
 ///
 /// ## Type Parameters
-/// * `T` - The type of values managed by the `Hc<T>` instances within this table.
+/// * `U` - The type the value is viewed as, e.g. `dyn Trait`.
 ///
 /// ## Fields
-/// * `table`: HashMap - The underlying data structure storing `Hc<T>` instances.
+/// * `value`: `Rc<U>` - The shared value.
+/// * `_handle`: The type-erased `Hc<T>` allocation that keeps the table entry alive.
 ///
-pub struct HcTable<T>
+pub struct HcDyn<U>
 where
-    T: Hash + Eq,
+    U: ?Sized,
 {
-    inner: Rc<InnerTable<T>>,
+    value: Rc<U>,
+
+    _handle: Rc<dyn Any>,
 }
 
-impl<T> HcTable<T>
+impl<U> HcDyn<U>
 where
-    T: Hash + Eq,
+    U: ?Sized,
 {
-    /// Creates a new `HCTable`.
+    /// Retrieves a reference to the value stored in this `HcDyn<U>`.
     ///
     /// ## Returns
-    /// A new instance of `HCTable<T>`.
-    pub fn new() -> Self {
-        HcTable {
-            inner: Rc::new(InnerTable::new()),
-        }
+    /// A reference to the stored value.
+    pub fn get(&self) -> &U {
+        &self.value
     }
 
-    /// Simplifies object retrieval or creation with an intuitive interface.
-    ///
-    /// ## Parameters
-    /// * `value`: The value to be managed.
-    ///
-    /// ## Returns
-    /// A `Hc<T>` instance corresponding to the given value.
-    pub fn hashcons(&self, value: T) -> Hc<T> {
-        Hc {
-            inner: self.intern(value),
-        }
+    /// Returns the address of the shared value, ignoring any pointer metadata.
+    fn addr(&self) -> usize {
+        Rc::as_ptr(&self.value) as *const () as usize
     }
+}
 
-    /// Internal method to manage the storage of values in `HCTable`.
-    /// It ensures that each value is stored only once, providing a shared
-    /// reference to the stored value.
-    ///
-    /// ## Parameters
-    /// * `value`: The value to be stored or retrieved.
-    ///
-    /// ## Returns
-    /// A `Rc<Inner<T>>` pointer to the stored value.
-    ///
-    fn intern(&self, value: T) -> Rc<Inner<T>> {
-        let rc_table = self.inner.clone();
-        let rc_table_dup = Rc::clone(&rc_table);
-
-        let mut mut_table = rc_table_dup.table.borrow_mut();
-
-        let rc_value = Rc::new(value);
-        let rc_val_dup = rc_value.clone();
-
-        match mut_table.entry(rc_val_dup) {
-            Entry::Occupied(mut o) => {
-                let weak_hc = o.get();
-
-                if let Some(rc_hc) = weak_hc.upgrade() {
-                    return rc_hc;
-                }
-
-                let elem = rc_value;
-                let _table = rc_table;
-                let new_elem = Rc::new(Inner { elem, _table });
-                o.insert(Rc::downgrade(&new_elem));
-                new_elem
-            }
-
-            Entry::Vacant(v) => {
-                let _table = rc_table;
-                let elem = rc_value;
-                let new_elem = Rc::new(Inner { elem, _table });
-                v.insert(Rc::downgrade(&new_elem));
-                new_elem
-            }
-        }
+impl<U> PartialEq for HcDyn<U>
+where
+    U: ?Sized,
+{
+    /// Compares two `HcDyn<U>` instances by the address of their values.
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
     }
+}
 
-    #[cfg(not(feature = "auto-cleanup"))]
-    /// Cleans up the `HCTable`, removing any values that are no longer in use.
-    /// This method is useful for managing memory and ensuring that unused
-    /// values are not unnecessarily kept in the table.
-    ///
-    pub fn cleanup(&self) {
-        self.inner.cleanup();
-    }
+impl<U> Eq for HcDyn<U> where U: ?Sized {}
 
-    /// Returns the number of elements currently stored in the `HCTable`.
-    ///
-    /// ## Returns
-    /// The number of elements in the `HCTable`.
-    ///
-    pub fn len(&self) -> usize {
-        self.inner.len()
+impl<U> Hash for HcDyn<U>
+where
+    U: ?Sized,
+{
+    /// Hashes the address of the value, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
     }
 }
 
-impl<T> Clone for HcTable<T>
+impl<U> Clone for HcDyn<U>
 where
-    T: Hash + Eq,
+    U: ?Sized,
 {
-    /// Provides the functionality to clone `HcTable<T>` instances.
-    ///
-    /// ## Returns
-    /// A new `HcTable<T>` instance with the same values as the original.
-    ///
-    /// ## Example
-    /// ```
-    /// use hash_cons::HcTable;
-    ///
-    /// let table = HcTable::new();
-    /// let value = table.hashcons(5);
-    /// let table_clone = table.clone();
-    ///
-    /// assert_eq!(table.len(), table_clone.len());
-    /// ```
+    /// Creates a new `HcDyn<U>` pointing to the same value, without cloning the value.
     fn clone(&self) -> Self {
-        HcTable {
-            inner: self.inner.clone(),
+        HcDyn {
+            value: self.value.clone(),
+            _handle: self._handle.clone(),
         }
     }
 }
 
-/// # Inner<T>
-/// A struct to encapsulate the inner workings of `Hc<T>`.
-/// It holds the actual value and a weak reference to its containing table.
-///
-/// ## Type Parameters
-/// * `T` - The type of the encapsulated value.
-///
-/// ## Fields
-/// * `elem`: The actual stored value.
-/// * `_table`: A weak reference to the `HCTable` that contains this value.
-///
-struct Inner<T>
+impl<U> std::fmt::Debug for HcDyn<U>
 where
-    T: Hash + Eq,
+    U: std::fmt::Debug + ?Sized,
 {
-    /// The actual stored value.
-    /// This is the value that is returned when the `Hc<T>` is dereference.
-    elem: Rc<T>,
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
 
-    /// A reference counted pointer to the `HCTable` that contains this value.
-    /// This is used to remove the value from the table when it is no longer in use.
-    _table: Rc<InnerTable<T>>,
+impl<U> std::fmt::Display for HcDyn<U>
+where
+    U: std::fmt::Display + ?Sized,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
 }
 
-#[cfg(feature = "auto-cleanup")]
-impl<T> Drop for Inner<T>
+impl<U> std::ops::Deref for HcDyn<U>
 where
-    T: Hash + Eq,
+    U: ?Sized,
 {
-    /// Provides the functionality to drop `Inner<T>` instances.
-    /// This method is useful for managing the lifecycle of `Hc<T>` instances.
-    fn drop(&mut self) {
-        let rc_table = self._table.clone();
+    type Target = U;
 
-        let key = self.elem.clone();
-        let mut mut_table = rc_table.table.borrow_mut();
-        mut_table.remove_entry(&key);
+    fn deref(&self) -> &Self::Target {
+        &self.value
     }
 }
 
-/// # `InnerTable<T>`
-/// A helper struct to manage the internal storage of `HCTable`.
-/// It provides mechanisms to manage and access stored `Hc<T>` instances.
+impl<U> AsRef<U> for HcDyn<U>
+where
+    U: ?Sized,
+{
+    fn as_ref(&self) -> &U {
+        &self.value
+    }
+}
+
+/// # `HcEdge<T>`
+/// A handle that carries a few attribute bits, such as the complement flag of the edges of
+/// binary decision diagrams or and-inverter graphs.
+///
+/// The bits are packed into the unused low bits of the pointer, so that an edge is as small as
+/// a handle. Edges to the same value with different bits share the value, which is what lets
+/// `HCTable::hashcons_edge()` store `Not(x)` as the complemented edge to `x`.
 ///
 /// ## Type Parameters
-/// * `T` - The type of values stored in the `HCTable`.
+/// * `T` - The type of the value the edge points at.
+/// * `M` - The semantics of the handles of the table, see `HcTableBuilder::identity()`.
 ///
 /// ## Fields
-/// * `table`: The actual HashMap that stores the `Hc<T>` instances.
-///
-pub struct InnerTable<T>
+/// * `tagged`: The pointer `Rc::into_raw` returned for the handle, with the attribute bits set.
+/// * `_handle`: The marker of the handle the edge owns.
+pub struct HcEdge<T, M = Structural> {
+    tagged: NonNull<Inner<T>>,
+    _handle: PhantomData<Hc<T, M>>,
+}
+
+impl<T, M> HcEdge<T, M> {
+    /// The attribute bits an edge can carry, which the alignment of the allocation leaves free.
+    pub const ATTRIBUTE_MASK: u8 = 0b11;
+
+    /// Creates an edge to the value of `hc` with the given attribute bits.
+    ///
+    /// ## Panics
+    /// If `attributes` has bits set outside of `ATTRIBUTE_MASK`.
+    pub fn new(hc: Hc<T, M>, attributes: u8) -> Self {
+        const { assert!(std::mem::align_of::<Inner<T>>() > Self::ATTRIBUTE_MASK as usize) };
+        assert!(
+            attributes & !Self::ATTRIBUTE_MASK == 0,
+            "attribute bits {attributes:#b} exceed the mask {:#b}",
+            Self::ATTRIBUTE_MASK
+        );
+        let raw = Rc::into_raw(hc.inner).cast_mut();
+        // SAFETY: `Rc::into_raw` does not return null, and the alignment asserted above keeps the
+        // attribute bits of the pointer clear.
+        let tagged =
+            unsafe { NonNull::new_unchecked(raw.map_addr(|addr| addr | usize::from(attributes))) };
+        HcEdge {
+            tagged,
+            _handle: PhantomData,
+        }
+    }
+
+    /// Returns the pointer to the allocation, without the attribute bits.
+    fn untagged(&self) -> *const Inner<T> {
+        self.tagged
+            .as_ptr()
+            .map_addr(|addr| addr & !usize::from(Self::ATTRIBUTE_MASK))
+    }
+
+    /// Returns the attribute bits of the edge.
+    pub fn attributes(&self) -> u8 {
+        (self.tagged.as_ptr().addr() & usize::from(Self::ATTRIBUTE_MASK)) as u8
+    }
+
+    /// Retrieves a reference to the value the edge points at, whatever its attribute bits.
+    pub fn get(&self) -> &T {
+        // SAFETY: the edge owns a strong count, which keeps the allocation alive.
+        unsafe { &(*self.untagged()).elem }
+    }
+
+    /// Returns a handle to the value the edge points at, dropping the attribute bits.
+    pub fn handle(&self) -> Hc<T, M> {
+        self.clone().into_handle()
+    }
+
+    /// Converts the edge into a handle to the value it points at, dropping the attribute bits.
+    pub fn into_handle(self) -> Hc<T, M> {
+        let edge = ManuallyDrop::new(self);
+        // SAFETY: the pointer was returned by `Rc::into_raw`, and the strong count the edge
+        // owned is handed to the handle.
+        Hc::from_inner(unsafe { Rc::from_raw(edge.untagged()) })
+    }
+
+    /// Returns the edge to the same value with the given attribute bits.
+    ///
+    /// ## Panics
+    /// If `attributes` has bits set outside of `ATTRIBUTE_MASK`.
+    pub fn with_attributes(self, attributes: u8) -> Self {
+        HcEdge::new(self.into_handle(), attributes)
+    }
+
+    /// Returns the edge to the same value with the given attribute bits toggled, e.g. the
+    /// complement of the edge.
+    ///
+    /// ## Panics
+    /// If `attributes` has bits set outside of `ATTRIBUTE_MASK`.
+    pub fn flip(self, attributes: u8) -> Self {
+        let toggled = self.attributes() ^ attributes;
+        self.with_attributes(toggled)
+    }
+}
+
+impl<T, M> From<Hc<T, M>> for HcEdge<T, M> {
+    /// Creates an edge to the value of `hc` without attribute bits.
+    fn from(hc: Hc<T, M>) -> Self {
+        HcEdge::new(hc, 0)
+    }
+}
+
+impl<T, M> Clone for HcEdge<T, M> {
+    /// Creates a new `HcEdge<T>` with the same value and attribute bits.
+    fn clone(&self) -> Self {
+        // SAFETY: the pointer was returned by `Rc::into_raw`, and the edge keeps it alive.
+        unsafe { Rc::increment_strong_count(self.untagged()) };
+        HcEdge {
+            tagged: self.tagged,
+            _handle: PhantomData,
+        }
+    }
+}
+
+impl<T, M> Drop for HcEdge<T, M> {
+    fn drop(&mut self) {
+        // SAFETY: the pointer was returned by `Rc::into_raw`, and the edge owns a strong count.
+        drop(unsafe { Rc::from_raw(self.untagged()) });
+    }
+}
+
+impl<T: PartialEq> PartialEq for HcEdge<T> {
+    /// Compares the attribute bits and the values of two edges.
+    fn eq(&self, other: &Self) -> bool {
+        self.attributes() == other.attributes() && self.get() == other.get()
+    }
+}
+
+impl<T: Eq> Eq for HcEdge<T> {}
+
+impl<T: Hash> Hash for HcEdge<T> {
+    /// Hashes the attribute bits and the value, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.attributes().hash(state);
+        self.get().hash(state);
+    }
+}
+
+impl<T> PartialEq for HcEdge<T, Identity> {
+    /// Compares the addresses and attribute bits of two edges.
+    fn eq(&self, other: &Self) -> bool {
+        self.tagged == other.tagged
+    }
+}
+
+impl<T> Eq for HcEdge<T, Identity> {}
+
+impl<T> Hash for HcEdge<T, Identity> {
+    /// Hashes the address and attribute bits, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tagged.hash(state);
+    }
+}
+
+impl<T: std::fmt::Debug, M> std::fmt::Debug for HcEdge<T, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HcEdge")
+            .field("value", self.get())
+            .field("attributes", &self.attributes())
+            .finish()
+    }
+}
+
+impl<T, M> std::ops::Deref for HcEdge<T, M> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T, M> AsRef<T> for HcEdge<T, M> {
+    fn as_ref(&self) -> &T {
+        self.get()
+    }
+}
+
+/// # `HCTable<T>`
+/// A table structure for efficiently managing `Hc<T>` instances.
+/// This struct hides the underlying table and its reference count management.
+///
+/// This structure utilizes a HashMap to store `Hc<T>` instances, offering
+/// quick retrieval and management capabilities.
+///
+/// ## Type Parameters
+/// * `T` - The type of values managed by the `Hc<T>` instances within this table.
+/// * `M` - The semantics of the handles of this table, see `HcTableBuilder::identity()`.
+///
+/// ## Fields
+/// * `table`: HashMap - The underlying data structure storing `Hc<T>` instances.
+/// * `_semantics`: The marker of `M`.
+///
+pub struct HcTable<T, M = Structural> {
+    inner: Rc<InnerTable<T>>,
+    _semantics: PhantomData<fn() -> M>,
+}
+
+impl<T> HcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates a new `HCTable`.
+    ///
+    /// ## Returns
+    /// A new instance of `HCTable<T>`.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Creates a builder for a `HCTable` with custom strategies.
+    ///
+    /// ## Returns
+    /// A `HcTableBuilder<T>` configured like `HcTable::new()`.
+    pub fn builder() -> HcTableBuilder<T> {
+        HcTableBuilder {
+            eq: Box::new(DefaultEq),
+            hash: Box::new(DefaultHash),
+            normalize: None,
+            weight: None,
+            custom_strategies: false,
+            trusted_hash: false,
+            prehashed: false,
+            seed: None,
+            #[cfg(feature = "auto-cleanup")]
+            deferred_reclamation: false,
+            collision_warning: None,
+            drift_check: None,
+            ids: Box::new(SequentialIds::default()),
+            on_evict: None,
+            name: None,
+            _semantics: PhantomData,
+        }
+    }
+}
+
+impl<T, M> HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Simplifies object retrieval or creation with an intuitive interface.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be managed.
+    ///
+    /// ## Returns
+    /// A `Hc<T>` instance corresponding to the given value.
+    ///
+    /// ## Panics
+    /// Panics of the hash function, equivalence or normalization of the table, including `Hash`
+    /// and `Eq` implementations, are propagated. They happen before the table is changed, so it
+    /// stays usable and holds no entry for `value`.
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        Hc::from_inner(self.intern(value))
+    }
+
+    /// Interns `value` as an edge, after `canonicalize` split it into the value to store and
+    /// the attribute bits of the edge pointing at it.
+    ///
+    /// Values that differ only in what the attribute bits encode then share their storage, e.g.
+    /// `Not(x)` canonicalizes to the complemented edge to `x` and stores nothing new.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be managed.
+    /// * `canonicalize`: Returns the value to store with the attribute bits of its edge, or an
+    ///   existing edge of this table that `value` is equivalent to.
+    ///
+    /// ## Returns
+    /// The edge to the canonical value.
+    ///
+    /// ## Panics
+    /// If `canonicalize` returns attribute bits outside of `HcEdge::ATTRIBUTE_MASK`.
+    pub fn hashcons_edge(
+        &self,
+        value: T,
+        canonicalize: impl FnOnce(T) -> Canonical<T, HcEdge<T, M>>,
+    ) -> HcEdge<T, M> {
+        match canonicalize(value) {
+            Canonical::Node(value, attributes) => HcEdge::new(self.hashcons(value), attributes),
+            Canonical::Edge(edge) => edge,
+        }
+    }
+
+    /// Like `hashcons`, but reserves the room for a new entry in the map of the table with
+    /// `try_reserve`, and reports a failure to grow the map instead of aborting.
+    ///
+    /// This is not fully fallible interning: a new value and its handle are still allocated
+    /// with `Rc::new`, which aborts if the allocation fails.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be managed.
+    ///
+    /// ## Returns
+    /// A `Hc<T>` instance corresponding to the given value, or the allocation error if the map
+    /// could not make room for a new entry. The table is unchanged in that case.
+    pub fn hashcons_try_reserve(&self, value: T) -> Result<Hc<T, M>, TryReserveError> {
+        let inner = self.intern_with(value, |table, hash| table.try_reserve_entry(hash))?;
+        Ok(Hc::from_inner(inner))
+    }
+
+    /// Returns the handle of the stored value `key` borrows as, constructing the value with
+    /// `make` only if it is missing.
+    ///
+    /// ## Parameters
+    /// * `key`: The borrowed form of the value, e.g. a `&str` for `String` values.
+    /// * `make`: Constructs the value from `key`.
+    ///
+    /// ## Note
+    /// Values are found by `key` only in tables without a custom equivalence, hash function or
+    /// normalization, which may treat keys differently. Other tables run `make` on every call
+    /// and intern its result.
+    pub fn get_or_insert_with<Q>(&self, key: &Q, make: impl FnOnce(&Q) -> T) -> Hc<T, M>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.get_or_try_insert_with(key, |key| Ok::<T, Infallible>(make(key))) {
+            Ok(hc) => hc,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like `get_or_insert_with`, but `make` can fail. An error of `make` is returned as is
+    /// and leaves the table unchanged.
+    pub fn get_or_try_insert_with<Q, E>(
+        &self,
+        key: &Q,
+        make: impl FnOnce(&Q) -> Result<T, E>,
+    ) -> Result<Hc<T, M>, E>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.lookup_or_claim(key) {
+            Lookup::Found(hc) => Ok(hc),
+            Lookup::Claimed(claim) => {
+                let hc = self.hashcons(make(key)?);
+                drop(claim);
+                Ok(hc)
+            }
+            // Only `make` itself or a suspended task can be constructing, and this thread can
+            // wait for neither.
+            Lookup::Wait(_) => Ok(self.hashcons(make(key)?)),
+        }
+    }
+
+    /// Hash conses a value that may be borrowed, e.g. a `Cow<str>` produced by a tokenizer.
+    ///
+    /// A borrowed value is copied only if it is missing, like with `get_or_insert_with`, and an
+    /// owned one is interned as is, without copying its buffer.
+    pub fn hashcons_cow<Q>(&self, value: Cow<'_, Q>) -> Hc<T, M>
+    where
+        Q: ToOwned<Owned = T> + Hash + Eq + ?Sized,
+        T: Borrow<Q>,
+    {
+        match value {
+            Cow::Borrowed(key) => self.get_or_insert_with(key, Q::to_owned),
+            Cow::Owned(value) => self.hashcons(value),
+        }
+    }
+
+    /// Hash conses a value already shared as an `Rc`, storing that allocation if the value is
+    /// missing instead of copying the value into a new one.
+    ///
+    /// If an equivalent value is stored, its handle is returned and `value` is dropped. In
+    /// tables with a normalization, `value` must be in its normalized form.
+    pub fn hashcons_rc(&self, value: Rc<T>) -> Hc<T, M> {
+        let hash = self.inner.hash(&value);
+        match self.intern_hashed(value, hash, |_, _| Ok::<(), Infallible>(())) {
+            Ok(inner) => Hc::from_inner(inner),
+            Err(never) => match never {},
+        }
+    }
+
+    #[cfg(feature = "async")]
+    /// Like `get_or_insert_with`, but constructs the value asynchronously. Tasks missing on the
+    /// same key wait for the first construction instead of repeating it, and a cancelled
+    /// construction is taken over by one of them.
+    pub async fn get_or_insert_with_async<Q, F, Fut>(&self, key: &Q, make: F) -> Hc<T, M>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&Q) -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        loop {
+            match self.lookup_or_claim(key) {
+                Lookup::Found(hc) => return hc,
+                Lookup::Claimed(claim) => {
+                    let hc = self.hashcons(make(key).await);
+                    drop(claim);
+                    return hc;
+                }
+                Lookup::Wait(waiter) => waiter.await,
+            }
+        }
+    }
+
+    /// Looks up the stored value `key` borrows as, claiming its construction if it is missing.
+    fn lookup_or_claim<Q>(&self, key: &Q) -> Lookup<'_, Hc<T, M>>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if !self.inner.borrowed_lookup {
+            return Lookup::Claimed(None);
+        }
+        let hash = self.inner.hash_key(key);
+        let trusted = self.inner.trusted_hasher.is_some();
+        let mut checked = Vec::new();
+        loop {
+            let mut_table = self.inner.table.borrow_mut();
+            let candidates = InnerTable::unchecked(&mut_table, hash, &mut checked);
+            if candidates.is_empty() {
+                // Claiming while the table is borrowed keeps a construction from finishing
+                // between the lookup and the claim unnoticed.
+                return match self.inner.in_flight.claim(hash) {
+                    Ok(claim) => Lookup::Claimed(Some(claim)),
+                    Err(waiter) => Lookup::Wait(waiter),
+                };
+            }
+            // `Q::eq` may use this table, so it only runs after the borrow ends.
+            drop(mut_table);
+            let found = candidates
+                .iter()
+                .find_map(|(seq, candidate)| match candidate {
+                    Candidate::Live(inner)
+                        if trusted || Borrow::<Q>::borrow(&*inner.elem) == key =>
+                    {
+                        Some((*seq, inner.clone()))
+                    }
+                    _ => None,
+                });
+            if let Some((seq, inner)) = found {
+                self.inner.table.borrow_mut().record_reuse(hash, seq);
+                return Lookup::Found(Hc::from_inner(self.inner.pinned(inner)));
+            }
+        }
+    }
+
+    /// Looks up several values at once, acquiring the lock of the `HCTable` only once.
+    ///
+    /// Values are not stored if they are missing, and the lookups are not counted in `stats`.
+    /// In tables with a normalization, the values must be given in their normalized form.
+    ///
+    /// ## Returns
+    /// The handle of each value, or `None` if it is not stored, in the order of `values`.
+    pub fn get_many(&self, values: &[T]) -> Vec<Option<Hc<T, M>>> {
+        // Hashing happens before the lock is acquired.
+        let hashes: Vec<u128> = values.iter().map(|value| self.inner.hash(value)).collect();
+        let table = self.inner.table.borrow();
+        let candidates: Vec<_> = hashes
+            .into_iter()
+            .map(|hash| InnerTable::live(&table, hash))
+            .collect();
+        // The equivalence may use this table, so it only runs after the borrow ends.
+        drop(table);
+        values
+            .iter()
+            .zip(candidates)
+            .map(|(value, live)| {
+                live.into_iter()
+                    .find(|inner| self.inner.matches(&inner.elem, value))
+                    .map(Hc::from_inner)
+            })
+            .collect()
+    }
+
+    /// Like `get_many`, but looks the values up by the keys they borrow as, e.g. `&str` keys
+    /// for `String` values.
+    ///
+    /// ## Note
+    /// As with `get_or_insert_with`, values are found by key only in tables without a custom
+    /// equivalence, hash function or normalization. Other tables find none of the keys.
+    pub fn get_many_by_key<Q>(&self, keys: &[&Q]) -> Vec<Option<Hc<T, M>>>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if !self.inner.borrowed_lookup {
+            return keys.iter().map(|_| None).collect();
+        }
+        let hashes: Vec<u128> = keys.iter().map(|key| self.inner.hash_key(*key)).collect();
+        let trusted = self.inner.trusted_hasher.is_some();
+        let table = self.inner.table.borrow();
+        let candidates: Vec<_> = hashes
+            .into_iter()
+            .map(|hash| InnerTable::live(&table, hash))
+            .collect();
+        // `Q::eq` may use this table, so it only runs after the borrow ends.
+        drop(table);
+        keys.iter()
+            .zip(candidates)
+            .map(|(key, live)| {
+                live.into_iter()
+                    .find(|inner| trusted || Borrow::<Q>::borrow(&*inner.elem) == *key)
+                    .map(Hc::from_inner)
+            })
+            .collect()
+    }
+
+    /// Looks up a value by a hash computed upstream, without constructing or hashing it.
+    ///
+    /// Meant for tables built with `prehashed`, which store values under the fingerprint their
+    /// hash function writes: a streaming parser that fingerprints its input can probe the table
+    /// before building a value. In other tables, `hash` is the `hash_value` of the handle of the
+    /// value. The lookup is not counted in `stats`.
+    ///
+    /// ## Parameters
+    /// * `hash`: The hash the value is stored under.
+    /// * `eq`: Returns `true` for the value looked up, consistently with the equivalence of the
+    ///   table.
+    ///
+    /// ## Panics
+    /// Panics in trusted-hash mode, whose hashes have 128 bits.
+    pub fn get_prehashed(&self, hash: u64, eq: impl Fn(&T) -> bool) -> Option<Hc<T, M>> {
+        let hash = self.inner.given_hash(hash);
+        let live = InnerTable::live(&self.inner.table.borrow(), hash);
+        // `eq` may use this table, so it only runs after the borrow ends.
+        live.into_iter()
+            .find(|inner| eq(&inner.elem))
+            .map(Hc::from_inner)
+    }
+
+    /// Interns `value` under a hash computed upstream, without hashing it, see `get_prehashed`.
+    ///
+    /// `hash` must be the hash the table stores `value` under, which debug builds check, or
+    /// `hashcons` stores the value again. In tables with a normalization, `value` must be in
+    /// its normalized form.
+    ///
+    /// ## Panics
+    /// Panics in trusted-hash mode, whose hashes have 128 bits.
+    pub fn hashcons_prehashed(&self, hash: u64, value: T) -> Hc<T, M> {
+        let hash = self.inner.given_hash(hash);
+        debug_assert!(
+            hash == self.inner.hash(&value),
+            "hash_cons: the given hash differs from the hash of the value"
+        );
+        match self.intern_hashed(value, hash, |_, _| Ok::<(), Infallible>(())) {
+            Ok(inner) => Hc::from_inner(inner),
+            Err(never) => match never {},
+        }
+    }
+
+    /// Removes the value of `hc` from the `HCTable` and returns it, if `hc` is its only handle.
+    ///
+    /// This evicts a value known to be dead right away, instead of waiting for it to be dropped
+    /// or for the next sweep, and hands the value back instead of dropping it.
+    ///
+    /// ## Errors
+    /// Returns `hc` unchanged if other handles of the value exist, if the value is still shared
+    /// through the `Rc` it was interned from with `hashcons_rc`, or if it was interned in
+    /// another table.
+    pub fn remove(&self, hc: Hc<T, M>) -> Result<T, Hc<T, M>> {
+        if !Rc::ptr_eq(&hc.inner._table, &self.inner) {
+            return Err(hc);
+        }
+        let hash = hc.inner.hash;
+        // Only the entry and the handle share a value the caller holds no `Rc` of.
+        if Rc::strong_count(&hc.inner.elem) > 2 {
+            return Err(hc);
+        }
+        let mut mut_table = self.inner.table.borrow_mut();
+        let inner = match Rc::try_unwrap(hc.inner) {
+            Ok(inner) => ManuallyDrop::new(inner),
+            Err(inner) => return Err(Hc::from_inner(inner)),
+        };
+        // SAFETY: `inner` is never used or dropped again, so that its fields are moved out once
+        // and dropping it does not retire the entry removed here.
+        let (elem, table) = unsafe { (std::ptr::read(&inner.elem), std::ptr::read(&inner._table)) };
+        let entry = mut_table.remove(hash, |entry| Rc::ptr_eq(&entry.key, &elem));
+        drop(mut_table);
+        drop((entry, table));
+        // Only entries and handles share the value.
+        Ok(Rc::into_inner(elem).expect("the removed handle and entry own the value"))
+    }
+
+    /// Tries to reserve room for at least `additional` more distinct values in the `HCTable`.
+    ///
+    /// ## Returns
+    /// The allocation error if the capacity could not be increased.
+    pub fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.table.borrow_mut().try_reserve(additional)
+    }
+
+    /// Internal method to manage the storage of values in `HCTable`.
+    /// It ensures that each value is stored only once, providing a shared
+    /// reference to the stored value.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be stored or retrieved.
+    ///
+    /// ## Returns
+    /// A `Rc<Inner<T>>` pointer to the stored value.
+    ///
+    /// ## Note
+    /// Values are only dropped after the table is released, because dropping the children of a
+    /// value can remove them from this table and needs to borrow it again.
+    ///
+    fn intern(&self, value: T) -> Rc<Inner<T>> {
+        match self.intern_with(value, |_, _| Ok::<(), Infallible>(())) {
+            Ok(inner) => inner,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Looks up `value` like `intern`, calling `reserve` before a new entry is added.
+    /// If `reserve` fails, the table is left unchanged and its error is returned.
+    fn intern_with<E>(
+        &self,
+        value: T,
+        reserve: impl FnOnce(&mut Storage<T>, u128) -> Result<(), E>,
+    ) -> Result<Rc<Inner<T>>, E> {
+        let value = self.inner.normalize(value);
+        let hash = self.inner.hash(&value);
+        self.intern_hashed(value, hash, reserve)
+    }
+
+    /// Like `intern_with`, for a normalized value whose hash is known.
+    fn intern_hashed<V, E>(
+        &self,
+        value: V,
+        hash: u128,
+        reserve: impl FnOnce(&mut Storage<T>, u128) -> Result<(), E>,
+    ) -> Result<Rc<Inner<T>>, E>
+    where
+        V: Borrow<T> + Into<Rc<T>>,
+    {
+        let mut checked = Vec::new();
+        let mut dead_match = None;
+        let mut mut_table = loop {
+            let mut_table = self.inner.table.borrow_mut();
+            let candidates = InnerTable::unchecked(&mut_table, hash, &mut checked);
+            if candidates.is_empty() {
+                break mut_table;
+            }
+            // The equivalence may use this table, so it only runs after the borrow ends. Values
+            // stored meanwhile are compared in the next round.
+            drop(mut_table);
+            let found = candidates
+                .iter()
+                .find(|(_, candidate)| self.inner.matches(candidate.value(), value.borrow()));
+            match found {
+                Some((seq, Candidate::Live(inner))) => {
+                    self.inner.table.borrow_mut().record_reuse(hash, *seq);
+                    // `candidates` are dropped after the borrow
+                    return Ok(self.inner.pinned(inner.clone()));
+                }
+                Some((_, Candidate::Dead(value))) => dead_match = Some(value.clone()),
+                None => {}
+            }
+        };
+        let generation = mut_table.generation();
+        #[cfg(feature = "access-time")]
+        let now = mut_table.now();
+
+        // The matched entry may have been taken over meanwhile, which replaces its value, so
+        // it is only taken over if it still holds the matched dead value.
+        let dead = dead_match.filter(|dead| {
+            mut_table
+                .bucket(hash)
+                .iter()
+                .any(|entry| Rc::ptr_eq(&entry.key, dead))
+        });
+        if let Some(dead) = dead {
+            let (id, seq) = mut_table.take_id(hash);
+            let entry = mut_table
+                .find_mut(hash, |key| Rc::ptr_eq(key, &dead))
+                .expect("the dead entry is stored");
+            #[cfg(feature = "access-time")]
+            {
+                entry.last_access = now;
+            }
+
+            // The entry is dead but not yet cleaned up, so it is taken over by the new value.
+            let evicted = entry.id;
+            let new_elem = self.new_inner(value.into(), hash, seq, generation);
+            let stale = entry.replace(
+                new_elem.elem.clone(),
+                Rc::downgrade(&new_elem),
+                id,
+                seq,
+                generation,
+            );
+            mut_table.record_replacement(evicted, id);
+            let drift_batch = self.inner.drift_batch(&mut mut_table);
+            drop(mut_table);
+            self.inner.observe_evicted([&stale.0]);
+            drop(stale);
+            self.inner.check_drift(drift_batch);
+            return Ok(self.inner.pinned(new_elem));
+        }
+
+        reserve(&mut mut_table, hash)?;
+        let ids = mut_table.take_id(hash);
+        let new_elem = self.new_inner(value.into(), hash, ids.1, generation);
+        let shared = mut_table.insert(hash, ids, new_elem.elem.clone(), Rc::downgrade(&new_elem));
+        let drift_batch = self.inner.drift_batch(&mut mut_table);
+        drop(mut_table);
+        self.inner.check_collisions(shared);
+        self.inner.check_drift(drift_batch);
+        Ok(self.inner.pinned(new_elem))
+    }
+
+    /// Allocates the shared `Inner<T>` of a value about to be stored in this table.
+    fn new_inner(&self, value: Rc<T>, hash: u128, seq: u64, generation: u64) -> Rc<Inner<T>> {
+        Rc::new(Inner {
+            elem: value,
+            seq,
+            generation,
+            hash,
+            #[cfg(feature = "leak-check")]
+            created_at: std::backtrace::Backtrace::capture(),
+            _table: self.inner.clone(),
+        })
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the `HCTable`, removing any values that are no longer in use.
+    /// This method is useful for managing memory and ensuring that unused
+    /// values are not unnecessarily kept in the table.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    ///
+    pub fn cleanup(&self) -> CleanupReport {
+        self.inner.cleanup()
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values among the next `max_entries` entries of the `HCTable`, continuing
+    /// where the previous call stopped and starting over after the last entry.
+    ///
+    /// Unlike `cleanup`, which sweeps the whole table while holding its lock, this bounds the
+    /// pause of each call, so that sweeping can be spread over the idle moments of a service.
+    /// Values released by the removed entries are left to later calls. Entries added between
+    /// calls may be visited in a later round only.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the call took.
+    pub fn cleanup_batch(&self, max_entries: usize) -> CleanupReport {
+        self.inner.cleanup_batch(max_entries)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the `HCTable` once the calls of this method saw no lookup in it for at least
+    /// `window`.
+    ///
+    /// Idleness is a polling heuristic: the table does not track when its operations run, and
+    /// this method only compares the number of lookups to the one seen by its previous call. A
+    /// changed number starts the idle period at the time of the call that sees it, and a later
+    /// call sweeps once the number stayed unchanged for `window` since then. Call it
+    /// periodically, e.g. from a timer, at intervals well below `window`: the quiet time before
+    /// a sweep is at least `window` and exceeds it by up to one interval. A burst of interning
+    /// that starts just after the number was read is not seen, so the sweep of that call can
+    /// still overlap with its start. Sweeps otherwise fall into the quiet phases between
+    /// bursts instead of competing with them for the lock. Reading values through handles does not touch the table and does not count as
+    /// activity.
+    ///
+    /// ## Returns
+    /// The report of the sweep, or `None` if the table was not idle for long enough.
+    pub fn cleanup_when_idle(&self, window: Duration) -> Option<CleanupReport> {
+        self.inner.cleanup_when_idle(window)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `HCTable` that were stored before `generation`.
+    ///
+    /// Phased pipelines advance the generation after each phase and drop everything an earlier
+    /// phase left behind in one call, while keeping the garbage of the current phase around
+    /// for reuse. Values that are still in use are kept, whatever their generation.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    pub fn cleanup_older_than(&self, generation: u64) -> CleanupReport {
+        self.inner.cleanup_older_than(generation)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Marks the values stored in the `HCTable` so far, so that a later `rollback` can remove
+    /// the values stored after it.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            seq: self.inner.table.borrow().next_seq(),
+        }
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `HCTable` that were stored after `checkpoint` was taken,
+    /// e.g. once a speculative analysis is abandoned. Values that are still in use are kept,
+    /// as are values stored before the checkpoint.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    pub fn rollback(&self, checkpoint: Checkpoint) -> CleanupReport {
+        self.inner.sweep(|entry| entry.seq >= checkpoint.seq)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes unused values of the `HCTable` until the stored values weigh at most `budget`.
+    ///
+    /// Counting entries is a poor bound on memory when values range from a few bytes to
+    /// megabytes. With a weight function such as `MemSize::heap_size`, this keeps as many
+    /// unused values around for reuse as the budget allows. Values still in use are never
+    /// removed, so the budget is not met if they alone exceed it.
+    ///
+    /// ## Parameters
+    /// * `budget`: The total weight to shrink to.
+    /// * `order`: Which unused values to remove first.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the eviction took.
+    pub fn evict_until_weight(&self, budget: usize, order: EvictionOrder) -> CleanupReport {
+        self.inner.evict_until_weight(budget, order)
+    }
+
+    #[cfg(all(feature = "access-time", not(feature = "auto-cleanup")))]
+    /// Removes the unused values of the `HCTable` that were last interned `idle` or longer ago.
+    ///
+    /// Access times are counted in whole seconds and refreshed whenever a value is interned
+    /// again, so that values which keep coming back stay cached while unused ones expire.
+    /// Reading a value through a handle does not refresh its access time.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    pub fn cleanup_idle_for(&self, idle: Duration) -> CleanupReport {
+        self.inner.cleanup_idle_for(idle)
+    }
+
+    #[cfg(feature = "auto-cleanup")]
+    /// Removes the entries whose last handle was dropped since the previous collection, in a
+    /// table built with `deferred_reclamation()`. Does nothing in other tables.
+    ///
+    /// Calling this from a background thread keeps the removals off the threads that intern
+    /// and drop values.
+    ///
+    /// ## Returns
+    /// The number of entries removed.
+    pub fn collect_retired(&self) -> usize {
+        self.inner.collect_retired()
+    }
+
+    /// Returns the number of elements currently stored in the `HCTable`.
+    ///
+    /// ## Returns
+    /// The number of elements in the `HCTable`.
+    ///
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the `HCTable` contains no elements.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of distinct hashes the `HCTable` can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.inner.table.borrow().capacity()
+    }
+
+    /// Returns the fraction of the capacity in use, between `0.0` and `1.0`.
+    ///
+    /// A low load factor after many values were dropped means that `shrink_to_fit` would
+    /// release memory.
+    pub fn load_factor(&self) -> f64 {
+        self.inner.table.borrow().load_factor()
+    }
+
+    /// Returns the number of entries whose handles were all dropped but which are still stored.
+    ///
+    /// These tombstones are only left behind without the `auto-cleanup` feature, until `cleanup`
+    /// is called or an equal value takes the entry over, and in tables with deferred
+    /// reclamation, until `collect_retired` is called.
+    pub fn dead_count(&self) -> usize {
+        self.inner
+            .table
+            .borrow()
+            .iter()
+            .filter(|entry| entry.weak.strong_count() == 0)
+            .count()
+    }
+
+    /// Shrinks the capacity of the `HCTable` as much as possible.
+    pub fn shrink_to_fit(&self) {
+        self.inner.table.borrow_mut().shrink_to_fit();
+    }
+
+    /// Reports how the stored values are distributed over their hashes.
+    ///
+    /// ## Returns
+    /// The number of hashes shared by one, two, or more values, and how many values collide.
+    pub fn hash_diagnostics(&self) -> HashDiagnostics {
+        self.inner.table.borrow().diagnostics()
+    }
+
+    /// Finds the stored values whose hash changed since they were interned, e.g. because they
+    /// were mutated through a `Cell`, `RefCell` or lock.
+    ///
+    /// The `HCTable` can no longer find such values, so interning an equal value creates a
+    /// second handle.
+    ///
+    /// ## Returns
+    /// Handles of the drifted values, in storing order.
+    pub fn hash_drift(&self) -> Vec<Hc<T, M>> {
+        let table = self.inner.table.borrow();
+        let mut entries: Vec<_> = table
+            .iter_hashed()
+            .filter_map(|(hash, entry)| {
+                Some((entry.seq, hash, Hc::from_inner(entry.weak.upgrade()?)))
+            })
+            .collect();
+        drop(table);
+        entries.sort_unstable_by_key(|&(seq, _, _)| seq);
+        // The values are hashed after the borrow ends, since their `Hash` may use the table
+        entries
+            .into_iter()
+            .filter(|(_, hash, hc)| self.inner.hash(&hc.inner.elem) != *hash)
+            .map(|(_, _, hc)| hc)
+            .collect()
+    }
+
+    /// Returns the total weight of the stored values, including unused ones that were not
+    /// removed yet, as measured by the weight function of the `HCTable`.
+    pub fn total_weight(&self) -> usize {
+        self.inner
+            .stored()
+            .iter()
+            .map(|(value, _, _)| self.inner.weight_of(value))
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Returns the generation of the `HCTable`, which newly stored values are stamped with.
+    pub fn generation(&self) -> u64 {
+        self.inner.table.borrow().generation()
+    }
+
+    /// Starts a new generation of the `HCTable`, e.g. at the end of a phase of a pipeline.
+    ///
+    /// Values stored from now on are stamped with the new generation, so that values from
+    /// earlier phases can be told apart and removed together, see `cleanup_older_than`.
+    ///
+    /// ## Returns
+    /// The new generation.
+    pub fn advance_generation(&self) -> u64 {
+        self.inner.table.borrow_mut().advance_generation()
+    }
+
+    /// Formats up to `limit` of the values that still have handles, in no particular order.
+    ///
+    /// The `Debug` output of the `HCTable` itself only summarizes it, so that tables of any
+    /// value type can be debugged.
+    pub fn debug_entries(&self, limit: usize) -> impl std::fmt::Debug + '_
+    where
+        T: std::fmt::Debug,
+    {
+        DebugEntries { table: self, limit }
+    }
+
+    /// Returns an iterator over the values of the `HCTable` that still have handles, in the
+    /// order they were stored.
+    ///
+    /// ## Consistency
+    /// The values are collected while the table is borrowed once, so the iterator visits every
+    /// value that had a handle at that moment exactly once, and none interned later. The borrow
+    /// ends before the first value is returned, so the loop may intern into the table. The
+    /// iterator holds a handle to each remaining value, which keeps them alive until they are
+    /// visited.
+    pub fn iter(&self) -> Iter<T, M> {
+        Iter {
+            handles: self.export_entries().into_iter(),
+        }
+    }
+
+    /// Returns the values of the `HCTable` that still have handles, together with their ids,
+    /// in the order they were stored.
+    ///
+    /// The entries are a snapshot, like the values visited by `iter`.
+    ///
+    /// Ids come from the allocator of the table, see `HcTableBuilder::ids()`, which decides
+    /// whether they are unique. The default [`SequentialIds`] never hands out an id twice, also
+    /// when a value is dropped and interned again, so that external indices and databases can
+    /// key on them. With [`ContentIds`](crate::ContentIds), values whose hashes agree in their
+    /// lower half share an id, and [`RandomIds`](crate::RandomIds) repeat an id with a
+    /// probability of about one in 2^64 per pair of values. Use `entry_id` to translate handles
+    /// held by the values, such as the children of a node.
+    pub fn export_entries(&self) -> Vec<(u64, Hc<T, M>)> {
+        let table = self.inner.table.borrow();
+        let mut entries: Vec<_> = table
+            .iter()
+            .filter_map(|entry| Some((entry.seq, entry.id, Hc::from_inner(entry.weak.upgrade()?))))
+            .collect();
+        drop(table);
+        entries.sort_unstable_by_key(|&(seq, _, _)| seq);
+        entries.into_iter().map(|(_, id, hc)| (id, hc)).collect()
+    }
+
+    /// Returns the id `export_entries` reports for the value of `hc`, or `None` if `hc` is not
+    /// a handle of this table.
+    pub fn entry_id(&self, hc: &Hc<T, M>) -> Option<u64> {
+        let table = self.inner.table.borrow();
+        let id = table
+            .bucket(hc.inner.hash)
+            .iter()
+            .find(|entry| Rc::ptr_eq(&entry.key, &hc.inner.elem))
+            .map(|entry| entry.id);
+        id
+    }
+
+    /// Returns a handle to the value `token` was taken from, or `None` if the value was removed
+    /// or the token comes from another table.
+    pub fn resolve(&self, token: HcToken) -> Option<Hc<T, M>> {
+        if token.table != self.inner.id {
+            return None;
+        }
+        let table = self.inner.table.borrow();
+        let inner = table
+            .bucket(token.hash)
+            .iter()
+            .find(|entry| entry.seq == token.seq)?
+            .weak
+            .upgrade()?;
+        drop(table);
+        Some(Hc::from_inner(inner))
+    }
+
+    #[cfg(feature = "history")]
+    /// Returns the most recent values stored in and removed from the `HCTable`, oldest first.
+    pub fn history(&self) -> Vec<HistoryEvent> {
+        self.inner.table.borrow().history.events()
+    }
+
+    #[cfg(feature = "history")]
+    /// Makes the `HCTable` keep its last `capacity` events, forgetting older ones. A capacity
+    /// of zero stops recording.
+    pub fn set_history_capacity(&self, capacity: usize) {
+        self.inner.table.borrow_mut().history.set_capacity(capacity);
+    }
+
+    #[cfg(feature = "leak-check")]
+    /// Lists the values of the `HCTable` that still have handles, as reported when its last
+    /// `HCTable` is dropped. Values kept alive by snapshots and forks count as well.
+    pub fn leak_report(&self) -> LeakReport {
+        self.inner.leak_report()
+    }
+
+    /// Returns `true` if the `HCTable` and `other` hold equivalent values that still have
+    /// handles, also if they are different tables.
+    ///
+    /// Each value of one table is looked up in the other table, with the strategies of that
+    /// table. Values held by the values, such as the children of a node, are compared by value.
+    pub fn same_live_set(&self, other: &Self) -> bool {
+        self.difference(other).next().is_none() && other.difference(self).next().is_none()
+    }
+
+    /// Compares the values of the `HCTable` and `other` that still have handles, like
+    /// `same_live_set`, and lists those held by one table only.
+    ///
+    /// ## Returns
+    /// The values of the `HCTable` missing from `other` as `removed`, and those of `other`
+    /// missing from the `HCTable` as `added`, each in the order their table stored them.
+    pub fn diff_live_set(&self, other: &Self) -> LiveSetDiff<T, M> {
+        LiveSetDiff {
+            removed: self.difference(other).collect(),
+            added: other.difference(self).collect(),
+        }
+    }
+
+    /// Returns the values of the `HCTable` that still have handles and have no equivalent in
+    /// `other`, in the order they were stored.
+    ///
+    /// The values are listed when `difference` is called and looked up in `other` lazily, with
+    /// the strategies of `other`, like `same_live_set` does.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = Hc<T, M>> + 'a {
+        let entries = if Rc::ptr_eq(&self.inner, &other.inner) {
+            Vec::new()
+        } else {
+            self.export_entries()
+        };
+        entries
+            .into_iter()
+            .map(|(_, hc)| hc)
+            .filter(move |hc| other.lookup(hc.get()).is_none())
+    }
+
+    /// Returns the values that still have handles in either the `HCTable` or `other` but have
+    /// no equivalent in the other table: first those of the `HCTable`, then those of `other`.
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = Hc<T, M>> + 'a {
+        self.difference(other).chain(other.difference(self))
+    }
+
+    /// Returns the namespace `name` of the `HCTable`, creating it on first use.
+    ///
+    /// Namespaces share the storage of the table, so that values interned through several of
+    /// them are stored once, but remember which values each of them interned. This allows
+    /// listing and evicting the values of e.g. one module without a table per module.
+    pub fn namespace(&self, name: &str) -> Namespace<'_, T, M> {
+        Namespace {
+            table: self,
+            name: name.to_string(),
+        }
+    }
+
+    /// Pins every value interned in the `HCTable` while the returned guard lives.
+    ///
+    /// Pinned values stay stored without handles, so that a phase can drop its handles freely
+    /// and still find its values again. Dropping the guard unpins them, which makes the ones
+    /// without handles left removable again. Scopes may overlap, a value then stays pinned until
+    /// every scope it was interned in ends.
+    pub fn pin_scope(&self) -> PinScope<'_, T, M> {
+        let mut pins = self.inner.pins.borrow_mut();
+        let id = pins.next_scope;
+        pins.next_scope += 1;
+        pins.scopes.insert(id, HashMap::new());
+        PinScope { table: self, id }
+    }
+
+    /// Forks the `HCTable` into a child table that shares its values but stores its own.
+    ///
+    /// Interning through the fork finds the values of the `HCTable` and of the fork, but
+    /// stores new values in the fork only. Speculative work is thrown away by dropping the
+    /// fork, or kept with `HcFork::merge()`. The fork hashes and compares values with the
+    /// strategies of the `HCTable`, and draws the ids of its values from the same allocator,
+    /// so that they differ from the ids of the values of the `HCTable`.
+    pub fn fork(&self) -> HcFork<T, M> {
+        HcFork {
+            parent: self.clone(),
+            child: HcTable {
+                inner: Rc::new(self.inner.fork()),
+                _semantics: PhantomData,
+            },
+        }
+    }
+
+    /// Returns a handle to every value of the `HCTable` that still has one, in no particular order.
+    pub(crate) fn handles(&self) -> Vec<Hc<T, M>> {
+        let table = self.inner.table.borrow();
+        table
+            .iter()
+            .filter_map(|entry| entry.weak.upgrade())
+            .map(|inner| Hc::from_inner(inner))
+            .collect()
+    }
+
+    /// Returns the handle of the stored value `value` is equivalent to, without storing it.
+    fn lookup(&self, value: &T) -> Option<Hc<T, M>> {
+        let hash = self.inner.hash(value);
+        let live = InnerTable::live(&self.inner.table.borrow(), hash);
+        // The equivalence may use this table, so it only runs after the borrow ends.
+        live.into_iter()
+            .find(|inner| self.inner.matches(&inner.elem, value))
+            .map(Hc::from_inner)
+    }
+
+    /// Takes a read-only snapshot of the values of the `HCTable` that still have handles.
+    ///
+    /// Lookups in the snapshot do not borrow the table. The snapshot keeps its values alive
+    /// and does not see values interned later.
+    pub fn freeze(&self) -> FrozenHcTable<T, M> {
+        let table = self.inner.table.borrow();
+        let mut map: HashMap<u128, Vec<Hc<T, M>>, _> = HashMap::default();
+        let mut len = 0;
+        for (hash, entry) in table.iter_hashed() {
+            if let Some(inner) = entry.weak.upgrade() {
+                map.entry(hash).or_default().push(Hc::from_inner(inner));
+                len += 1;
+            }
+        }
+        FrozenHcTable {
+            table: self.inner.clone(),
+            map: Rc::new(map),
+            len,
+        }
+    }
+
+    /// Returns the counters of the `HCTable` since it was created.
+    ///
+    /// ## Returns
+    /// How many values were stored in new allocations, how many reused an existing handle, how
+    /// many entries were removed, and how often the map had to grow. The counters display as a
+    /// one-line summary.
+    pub fn stats(&self) -> TableStats {
+        TableStats {
+            name: self.inner.name,
+            ..self.inner.table.borrow().stats()
+        }
+    }
+
+    /// Returns the name of the `HCTable`, if it was given one with `HcTableBuilder::name`.
+    pub fn name(&self) -> Option<&'static str> {
+        self.inner.name
+    }
+
+    /// Estimates the memory held by the `HCTable`.
+    ///
+    /// ## Returns
+    /// The bytes held by values that still have handles, by dead entries that were not cleaned
+    /// up yet, and by the table structure itself. Values are measured with `MemSize`, so memory
+    /// they share with other values is counted for each of them.
+    pub fn memory_usage(&self) -> MemoryUsage
+    where
+        T: MemSize,
+    {
+        let allocated = self.inner.table.borrow().allocated_bytes();
+        let mut usage = MemoryUsage {
+            overhead: size_of::<InnerTable<T>>() + allocated,
+            ..MemoryUsage::default()
+        };
+
+        for (value, alive, _) in self.inner.stored() {
+            let bytes = entry_bytes(&*value);
+            if alive {
+                usage.live += bytes;
+            } else {
+                usage.dead += bytes;
+            }
+        }
+        usage
+    }
+}
+
+impl<T> Default for HcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty `HCTable`, equivalent to `HcTable::new()`.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, M> TryRelease for HcTable<T, M>
+where
+    T: Hash + Eq + MemSize,
+{
+    /// Removes dead entries of the `HCTable`, heaviest first and only as many as needed, then
+    /// gives the spare capacity of its map back if that did not release `bytes`.
+    ///
+    /// Values that still have handles are never removed. Without `auto-cleanup`, these are
+    /// the unused values; with it, the retired entries of tables with deferred reclamation.
+    fn try_release(&self, bytes: usize) -> usize {
+        let before = self.memory_usage().total();
+        #[cfg(feature = "auto-cleanup")]
+        self.collect_retired();
+        #[cfg(not(feature = "auto-cleanup"))]
+        {
+            let usage = self.memory_usage();
+            let budget = (usage.live + usage.dead).saturating_sub(bytes);
+            self.inner
+                .evict_until(budget, EvictionOrder::Heaviest, entry_bytes);
+        }
+        if before.saturating_sub(self.memory_usage().total()) < bytes {
+            self.shrink_to_fit();
+        }
+        before.saturating_sub(self.memory_usage().total())
+    }
+}
+
+impl<T, M> Clone for HcTable<T, M> {
+    /// Provides the functionality to clone `HcTable<T>` instances.
+    ///
+    /// ## Returns
+    /// A new `HcTable<T>` instance with the same values as the original.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(5);
+    /// let table_clone = table.clone();
+    ///
+    /// assert_eq!(table.len(), table_clone.len());
+    /// ```
+    fn clone(&self) -> Self {
+        #[cfg(feature = "leak-check")]
+        self.inner.tables.set(self.inner.tables.get() + 1);
+        HcTable {
+            inner: self.inner.clone(),
+            _semantics: PhantomData,
+        }
+    }
+}
+
+impl<T, M> PartialEq for HcTable<T, M> {
+    /// Returns `true` if both `HcTable`s are the same table, i.e. one is a clone of the other.
+    /// Tables are never compared by their values.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<u32> = HcTable::new();
+    ///
+    /// assert!(table == table.clone());
+    /// assert!(table != HcTable::new());
+    /// ```
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T, M> Eq for HcTable<T, M> {}
+
+impl<T, M> Hash for HcTable<T, M> {
+    /// Hashes the identity of the table, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.inner).hash(state);
+    }
+}
+
+#[cfg(feature = "leak-check")]
+impl<T, M> Drop for HcTable<T, M> {
+    /// Reports the values that still have handles when the last `HCTable` of the table is
+    /// dropped.
+    fn drop(&mut self) {
+        let tables = self.inner.tables.get() - 1;
+        self.inner.tables.set(tables);
+        if tables == 0 {
+            let report = self.inner.leak_report();
+            if report.leaked > 0 {
+                eprintln!("{report}");
+            }
+        }
+    }
+}
+
+impl<T, M> std::fmt::Debug for HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Summarizes the `HCTable`: its size and how it identifies and removes values. Use
+    /// `debug_entries` to format the values themselves.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let table = self.inner.table.borrow();
+        let mut debug = f.debug_struct("HcTable");
+        if let Some(name) = self.inner.name {
+            debug.field("name", &name);
+        }
+        debug
+            .field("len", &table.len())
+            .field("capacity", &table.capacity())
+            .field("trusted_hash", &self.inner.trusted_hasher.is_some())
+            .field("prehashed", &self.inner.prehashed)
+            .field("normalized", &self.inner.strategies.normalize.is_some())
+            .field("auto_cleanup", &cfg!(feature = "auto-cleanup"))
+            .finish_non_exhaustive()
+    }
+}
+
+/// # `DebugEntries<'a, T>`
+/// Formats the first values of an `HCTable<T>` that still have handles. Created with
+/// `HcTable::debug_entries()`.
+struct DebugEntries<'a, T, M>
+where
+    T: Hash + Eq,
+{
+    table: &'a HcTable<T, M>,
+    limit: usize,
+}
+
+impl<T, M> std::fmt::Debug for DebugEntries<'_, T, M>
+where
+    T: Hash + Eq + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let table = self.table.inner.table.borrow();
+        let live = table
+            .iter()
+            .filter(|entry| entry.weak.strong_count() > 0)
+            .map(|entry| &*entry.key);
+        f.debug_list().entries(live.take(self.limit)).finish()
+    }
+}
+
+#[cfg(not(feature = "auto-cleanup"))]
+/// # `Checkpoint`
+/// The values stored in a `HCTable` at some point. Created with `HCTable::checkpoint()`.
+///
+/// ## Fields
+/// * `seq`: The sequence number of the first value stored after the checkpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    seq: u64,
+}
+
+/// # `Namespace<'a, T>`
+/// A named part of a `HCTable<T>`, created with `HCTable::namespace()`.
+///
+/// Values interned through a namespace are stored in the table like any other value, so that
+/// equivalent values of different namespaces share their handle. The table remembers which
+/// values each namespace interned, until they are removed from it. Views of the same name share
+/// that membership.
+///
+/// ## Fields
+/// * `table`: The table the namespace is part of.
+/// * `name`: The name of the namespace.
+pub struct Namespace<'a, T, M = Structural> {
+    table: &'a HcTable<T, M>,
+    name: String,
+}
+
+impl<T, M> Namespace<'_, T, M>
+where
+    T: Hash + Eq,
+{
+    /// Returns the name of the namespace.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Interns `value` in the table of the namespace, like `HCTable::hashcons()`, and records it as
+    /// a value of the namespace.
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        let hc = self.table.hashcons(value);
+        self.table
+            .inner
+            .namespaces()
+            .entry(self.name.clone())
+            .or_default()
+            .insert(hc.inner.seq, hc.inner.hash);
+        hc
+    }
+
+    /// Returns `true` if `hc` was interned through this namespace of its table.
+    pub fn contains(&self, hc: &Hc<T, M>) -> bool {
+        Rc::ptr_eq(&hc.inner._table, &self.table.inner)
+            && self
+                .table
+                .inner
+                .namespaces()
+                .get(&self.name)
+                .is_some_and(|members| members.contains_key(&hc.inner.seq))
+    }
+
+    /// Returns a handle to every value interned through the namespace that still has one, in
+    /// the order they were stored. Forgets the values that were removed from the table.
+    pub fn handles(&self) -> Vec<Hc<T, M>> {
+        let mut handles = Vec::new();
+        self.visit_members(|weak| handles.extend(weak.upgrade().map(Hc::from_inner)));
+        handles.sort_by_key(|hc| hc.inner.seq);
+        handles
+    }
+
+    /// Returns the number of values interned through the namespace that still have handles.
+    /// Forgets the values that were removed from the table.
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.visit_members(|weak| len += usize::from(weak.strong_count() > 0));
+        len
+    }
+
+    /// Returns `true` if no value interned through the namespace still has a handle.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values that were interned through this namespace and no other one,
+    /// including values released by removing others, and forgets the namespace. Values that
+    /// are still in use are kept, as are values of other namespaces.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    pub fn evict(self) -> CleanupReport {
+        let mut namespaces = self.table.inner.namespaces();
+        let members = namespaces.remove(&self.name).unwrap_or_default();
+        let others: HashSet<u64> = namespaces
+            .values()
+            .flat_map(HashMap::keys)
+            .copied()
+            .collect();
+        drop(namespaces);
+        self.table
+            .inner
+            .sweep(|entry| members.contains_key(&entry.seq) && !others.contains(&entry.seq))
+    }
+
+    /// Calls `visit` with the weak reference of every value interned through the namespace
+    /// that is still stored, looking each one up in the bucket of its hash, and forgets the
+    /// values that were removed from the table.
+    fn visit_members(&self, mut visit: impl FnMut(&Weak<Inner<T>>)) {
+        let mut namespaces = self.table.inner.namespaces();
+        let Some(members) = namespaces.get_mut(&self.name) else {
+            return;
+        };
+        let table = self.table.inner.table.borrow();
+        members.retain(|&seq, &mut hash| {
+            let entry = table.bucket(hash).iter().find(|entry| entry.seq == seq);
+            entry.map(|entry| visit(&entry.weak)).is_some()
+        });
+    }
+}
+
+/// # `PinScope<'a, T>`
+/// A guard pinning the values interned in a `HCTable<T>` while it lives, created with
+/// `HCTable::pin_scope()`.
+///
+/// ## Fields
+/// * `table`: The table whose values are pinned.
+/// * `id`: The key of the values pinned by this scope in the table.
+pub struct PinScope<'a, T, M = Structural> {
+    table: &'a HcTable<T, M>,
+    id: u64,
+}
+
+impl<T, M> PinScope<'_, T, M> {
+    /// Returns the number of distinct values pinned by this scope.
+    pub fn len(&self) -> usize {
+        self.table.inner.pins.borrow().scopes[&self.id].len()
+    }
+
+    /// Returns `true` if no value was interned since the scope started.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, M> Drop for PinScope<'_, T, M> {
+    /// Unpins the values of the scope. They are released after the borrow of the pins ended,
+    /// since releasing a value can remove it from the table.
+    fn drop(&mut self) {
+        let pinned = self.table.inner.pins.borrow_mut().scopes.remove(&self.id);
+        drop(pinned);
+    }
+}
+
+/// # `LiveSetDiff<T>`
+/// The values held by only one of two tables, created with `HCTable::diff_live_set()`.
+///
+/// Golden tests compare the table built by the code under test to an expected one, and
+/// report both lists when they differ.
+pub struct LiveSetDiff<T, M = Structural> {
+    /// The values of the first table missing from the second one, in storing order.
+    pub removed: Vec<Hc<T, M>>,
+    /// The values of the second table missing from the first one, in storing order.
+    pub added: Vec<Hc<T, M>>,
+}
+
+impl<T, M> LiveSetDiff<T, M> {
+    /// Returns `true` if both tables hold equivalent values.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+impl<T: std::fmt::Debug, M> std::fmt::Debug for LiveSetDiff<T, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LiveSetDiff")
+            .field("removed", &self.removed)
+            .field("added", &self.added)
+            .finish()
+    }
+}
+
+/// # `HcFork<T>`
+/// A child of a `HCTable<T>` recording its own additions, created with `HCTable::fork()`.
+///
+/// Values stored by the parent before or after the fork are shared with it, as long as the
+/// fork did not store an equivalent value first. Handles of the fork and of its parent can be
+/// mixed freely: with structural semantics they compare by value.
+///
+/// ## Fields
+/// * `parent`: The table the fork was created from.
+/// * `child`: The table holding the values stored by the fork.
+pub struct HcFork<T, M = Structural> {
+    parent: HcTable<T, M>,
+    child: HcTable<T, M>,
+}
+
+impl<T, M> HcFork<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Returns the handle of the value equivalent to `value` in the fork or its parent,
+    /// storing `value` in the fork if neither holds one.
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        let value = self.child.inner.normalize(value);
+        if let Some(hc) = self.child.lookup(&value) {
+            return hc;
+        }
+        if let Some(hc) = self.parent.lookup(&value) {
+            return hc;
+        }
+        self.child.hashcons(value)
+    }
+
+    /// Returns the table the fork was created from.
+    pub fn parent(&self) -> &HcTable<T, M> {
+        &self.parent
+    }
+
+    /// Returns a handle to every value stored by the fork that still has one, in the order
+    /// they were stored.
+    pub fn additions(&self) -> Vec<Hc<T, M>> {
+        self.child
+            .export_entries()
+            .into_iter()
+            .map(|(_, hc)| hc)
+            .collect()
+    }
+
+    /// Returns the id of the value of `hc` in the fork or its parent, or `None` if `hc` is a
+    /// handle of neither.
+    pub fn entry_id(&self, hc: &Hc<T, M>) -> Option<u64> {
+        self.child.entry_id(hc).or_else(|| self.parent.entry_id(hc))
+    }
+
+    /// Returns the number of values stored by the fork, counted like `HCTable::len()`.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Returns `true` if the fork stores no value.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> HcFork<T>
+where
+    T: Hash + Eq + Clone,
+{
+    /// Merges the values stored by the fork back into its parent, in the order they were stored.
+    ///
+    /// Each value is cloned into the parent, where it shares the handle of an equivalent value
+    /// the parent stored meanwhile. Handles held by the values, such as the children of a node,
+    /// are cloned as they are: they keep pointing into the fork and keep those values alive.
+    /// They are not entries of the parent, so its `entry_id` and `export_entries` do not
+    /// report them, and `resolve` does not find them by their tokens.
+    ///
+    /// Merging requires structural semantics, under which a merged node still equals the node
+    /// the parent builds from its own children. Handles of tables built with `identity()`
+    /// compare by address, so a merged node would never match it and the parent would store
+    /// both. To rebuild the merged nodes from children stored in the parent, use a
+    /// `dag::Transform` into the parent instead.
+    ///
+    /// ## Returns
+    /// The handles of the merged values in the parent, in the order they were stored.
+    pub fn merge(self) -> Vec<Hc<T>> {
+        self.additions()
+            .iter()
+            .map(|hc| self.parent.hashcons(hc.get().clone()))
+            .collect()
+    }
+}
+
+impl<T, M> std::fmt::Debug for HcFork<T, M>
+where
+    T: Hash + Eq,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HcFork")
+            .field("parent", &self.parent)
+            .field("additions", &self.len())
+            .finish()
+    }
+}
+
+/// # `Iter<T>`
+/// An iterator over a snapshot of the values of a `HCTable`, created with `HCTable::iter()`.
+///
+/// ## Fields
+/// * `handles`: The ids and handles of the values not visited yet, in storing order.
+pub struct Iter<T, M = Structural> {
+    handles: std::vec::IntoIter<(u64, Hc<T, M>)>,
+}
+
+impl<T, M> Iterator for Iter<T, M> {
+    type Item = Hc<T, M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.handles.next().map(|(_, hc)| hc)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.handles.size_hint()
+    }
+}
+
+impl<T, M> ExactSizeIterator for Iter<T, M> {}
+
+impl<T, M> std::fmt::Debug for Iter<T, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Iter")
+            .field("remaining", &self.handles.len())
+            .finish()
+    }
+}
+
+impl<T, M> IntoIterator for &HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    type Item = Hc<T, M>;
+    type IntoIter = Iter<T, M>;
+
+    /// Iterates over a snapshot of the values of the table, see `HCTable::iter()`.
+    fn into_iter(self) -> Iter<T, M> {
+        self.iter()
+    }
+}
+
+/// The handles of a frozen table, grouped by hash.
+type HandleMap<T, M> = HashMap<u128, Vec<Hc<T, M>>, BuildHasherDefault<IdentityHasher>>;
+
+/// # `FrozenHcTable<T>`
+/// A read-only snapshot of a `HCTable<T>`, created with `HCTable::freeze()`.
+///
+/// The snapshot owns a handle to each of its values and looks them up with the strategies of
+/// the table it was taken from, without locking. Clones share the snapshot.
+///
+/// ## Fields
+/// * `table`: The table the snapshot was taken from, whose strategies hash and compare values.
+/// * `map`: The handles of the values, grouped by hash.
+/// * `len`: The number of values.
+pub struct FrozenHcTable<T, M = Structural> {
+    table: Rc<InnerTable<T>>,
+    map: Rc<HandleMap<T, M>>,
+    len: usize,
+}
+
+impl<T, M> FrozenHcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Returns the handle of the value equivalent to `value`, if the snapshot holds one.
+    ///
+    /// ## Note
+    /// `value` is looked up as given. A normalization configured for the table is not applied,
+    /// as it takes values by ownership.
+    pub fn get(&self, value: &T) -> Option<&Hc<T, M>> {
+        let hash = self.table.hash(value);
+        self.map
+            .get(&hash)?
+            .iter()
+            .find(|hc| self.table.matches(&hc.inner.elem, value))
+    }
+
+    /// Returns `true` if the snapshot holds a value equivalent to `value`.
+    pub fn contains(&self, value: &T) -> bool {
+        self.get(value).is_some()
+    }
+
+    /// Returns the handles of all values in the snapshot, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Hc<T, M>> {
+        self.map.values().flatten()
+    }
+
+    /// Returns the number of values in the snapshot.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the snapshot holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T, M> Clone for FrozenHcTable<T, M> {
+    fn clone(&self) -> Self {
+        FrozenHcTable {
+            table: self.table.clone(),
+            map: self.map.clone(),
+            len: self.len,
+        }
+    }
+}
+
+/// # `HcPairTable<T, R>`
+/// A memo cache of a binary operation on handles, such as the apply operation of binary
+/// decision diagrams or the unifier of two terms.
+///
+/// Results are cached under the ids of the operand handles, so that a lookup hashes two integers
+/// however large the operands. The cache only holds weak references: it keeps neither operands
+/// nor results alive, and a result is forgotten once the operands or the result are dropped.
+/// Forgotten entries are purged as the cache grows, or by `purge`.
+///
+/// ## Type Parameters
+/// * `T` - The type of the operands.
+/// * `R` - The type of the results.
+/// * `M` - The semantics of the handles, see `HcTableBuilder::identity()`.
+///
+/// ## Fields
+/// * `cache`: The cached results, and the number of entries after which the cache is purged.
+pub struct HcPairTable<T, R = T, M = Structural> {
+    cache: RefCell<PairCache<T, R>>,
+    _semantics: PhantomData<fn() -> M>,
+}
+
+/// The cached results of a `HcPairTable`, by the ids of their operands.
+///
+/// ## Fields
+/// * `map`: The operands and result of each entry.
+/// * `purge_at`: The number of entries at which the next insertion purges the cache.
+struct PairCache<T, R> {
+    map: HashMap<(u64, u64), PairEntry<T, R>>,
+    purge_at: usize,
+}
+
+/// The weak references to the operands and the result of an operation.
+struct PairEntry<T, R> {
+    left: Weak<Inner<T>>,
+    right: Weak<Inner<T>>,
+    result: Weak<Inner<R>>,
+}
+
+impl<T, R> PairEntry<T, R> {
+    /// Returns `true` if the entry was stored for these operands. Ids are only unique within a
+    /// table, so the operands of entries sharing them are told apart by their addresses.
+    fn is_for(&self, left: &Rc<Inner<T>>, right: &Rc<Inner<T>>) -> bool {
+        self.left.as_ptr() == Rc::as_ptr(left) && self.right.as_ptr() == Rc::as_ptr(right)
+    }
+
+    /// Returns `true` if the operands and result of the entry are alive.
+    fn is_alive(&self) -> bool {
+        self.left.strong_count() > 0
+            && self.right.strong_count() > 0
+            && self.result.strong_count() > 0
+    }
+}
+
+/// The least number of entries at which a `HcPairTable` is purged.
+const PAIR_PURGE_MIN: usize = 64;
+
+impl<T, R, M> HcPairTable<T, R, M> {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        HcPairTable {
+            cache: RefCell::new(PairCache {
+                map: HashMap::new(),
+                purge_at: PAIR_PURGE_MIN,
+            }),
+            _semantics: PhantomData,
+        }
+    }
+
+    /// Returns the cached result of the operation on `left` and `right`, if it is still alive.
+    pub fn get(&self, left: &Hc<T, M>, right: &Hc<T, M>) -> Option<Hc<R, M>> {
+        let mut cache = self.cache.borrow_mut();
+        let key = (left.seq(), right.seq());
+        let entry = cache.map.get(&key)?;
+        if !entry.is_for(&left.inner, &right.inner) {
+            return None;
+        }
+        match entry.result.upgrade() {
+            Some(result) => Some(Hc::from_inner(result)),
+            None => {
+                cache.map.remove(&key);
+                None
+            }
+        }
+    }
+
+    /// Caches `result` as the result of the operation on `left` and `right`.
+    pub fn insert(&self, left: &Hc<T, M>, right: &Hc<T, M>, result: &Hc<R, M>) {
+        let entry = PairEntry {
+            left: Rc::downgrade(&left.inner),
+            right: Rc::downgrade(&right.inner),
+            result: Rc::downgrade(&result.inner),
+        };
+        let mut cache = self.cache.borrow_mut();
+        cache.map.insert((left.seq(), right.seq()), entry);
+        if cache.map.len() >= cache.purge_at {
+            cache.map.retain(|_, entry| entry.is_alive());
+            cache.purge_at = (cache.map.len() * 2).max(PAIR_PURGE_MIN);
+        }
+    }
+
+    /// Returns the cached result of the operation on `left` and `right`, computing and caching it
+    /// with `compute` if there is none.
+    ///
+    /// `compute` runs without the cache being locked, so that recursive operations can use the
+    /// cache for their subproblems.
+    pub fn get_or_insert_with(
+        &self,
+        left: &Hc<T, M>,
+        right: &Hc<T, M>,
+        compute: impl FnOnce() -> Hc<R, M>,
+    ) -> Hc<R, M> {
+        if let Some(result) = self.get(left, right) {
+            return result;
+        }
+        let result = compute();
+        self.insert(left, right, &result);
+        result
+    }
+
+    /// Removes the entries whose operands or result were dropped.
+    ///
+    /// ## Returns
+    /// The number of removed entries.
+    pub fn purge(&self) -> usize {
+        let mut cache = self.cache.borrow_mut();
+        let before = cache.map.len();
+        cache.map.retain(|_, entry| entry.is_alive());
+        cache.purge_at = (cache.map.len() * 2).max(PAIR_PURGE_MIN);
+        before - cache.map.len()
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) {
+        let mut cache = self.cache.borrow_mut();
+        cache.map.clear();
+        cache.purge_at = PAIR_PURGE_MIN;
+    }
+
+    /// Returns the number of entries, including forgotten ones that were not purged yet.
+    pub fn len(&self) -> usize {
+        self.cache.borrow().map.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, R, M> Default for HcPairTable<T, R, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R, M> std::fmt::Debug for HcPairTable<T, R, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HcPairTable")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// An operation registered with a `ConsEngine`, and the cache of its results.
+type EngineOp<T, M> = (
+    Box<dyn Fn(&ConsEngine<T, M>, &Hc<T, M>, &Hc<T, M>) -> Hc<T, M>>,
+    HcPairTable<T, T, M>,
+);
+
+/// # `Op`
+/// A binary operation registered with a `ConsEngine`, see `ConsEngine::register()`.
+///
+/// ## Fields
+/// * `index`: The position of the operation in the engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Op {
+    index: usize,
+}
+
+/// # `ConsEngine<T>`
+/// A `HCTable<T>` bundled with a memo cache for each of the binary operations on its values, in
+/// the way the unique table and the computed table of a decision diagram package work together.
+///
+/// Operations are registered once and applied by `apply`, which returns the cached result if
+/// there is one and computes it otherwise. Operations receive the engine, so that they can
+/// intern values and apply operations to subproblems. The caches only hold weak references,
+/// so they never keep values alive or return values the table removed, and `cleanup` and
+/// `purge` drop their stale entries together with the values of the table.
+///
+/// ## Fields
+/// * `table`: The table the values are interned in.
+/// * `ops`: The registered operations with their caches, indexed by `Op`.
+pub struct ConsEngine<T, M = Structural> {
+    table: HcTable<T, M>,
+    ops: Vec<EngineOp<T, M>>,
+}
+
+impl<T> ConsEngine<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an engine with a new table and no operations.
+    pub fn new() -> Self {
+        Self::with_table(HcTable::new())
+    }
+}
+
+impl<T, M> ConsEngine<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Creates an engine interning values in `table`, e.g. a table built with custom strategies.
+    pub fn with_table(table: HcTable<T, M>) -> Self {
+        ConsEngine {
+            table,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Returns the table of the engine.
+    pub fn table(&self) -> &HcTable<T, M> {
+        &self.table
+    }
+
+    /// Interns `value` in the table of the engine, see `HCTable::hashcons()`.
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        self.table.hashcons(value)
+    }
+
+    /// Registers a binary operation, with an empty cache.
+    ///
+    /// The operation must be a function of its operands: its results are cached by operands and
+    /// reused for as long as they are alive.
+    ///
+    /// ## Returns
+    /// The `Op` to apply the operation with. Operations are numbered in registration order,
+    /// see `op`.
+    pub fn register(
+        &mut self,
+        op: impl Fn(&Self, &Hc<T, M>, &Hc<T, M>) -> Hc<T, M> + 'static,
+    ) -> Op {
+        self.ops.push((Box::new(op), HcPairTable::new()));
+        Op {
+            index: self.ops.len() - 1,
+        }
+    }
+
+    /// Returns the `Op` of the operation registered `index`-th, e.g. for operations that apply
+    /// themselves recursively.
+    ///
+    /// ## Panics
+    /// If fewer than `index + 1` operations are registered.
+    pub fn op(&self, index: usize) -> Op {
+        assert!(index < self.ops.len(), "no operation {index} is registered");
+        Op { index }
+    }
+
+    /// Applies `op` to `left` and `right`, returning the cached result if there is one.
+    ///
+    /// ## Panics
+    /// If `op` was registered with another engine that has more operations.
+    pub fn apply(&self, op: Op, left: &Hc<T, M>, right: &Hc<T, M>) -> Hc<T, M> {
+        let (compute, cache) = &self.ops[op.index];
+        cache.get_or_insert_with(left, right, || compute(self, left, right))
+    }
+
+    /// Returns the number of cached results of all operations, including forgotten ones that
+    /// were not purged yet.
+    pub fn cached(&self) -> usize {
+        self.ops.iter().map(|(_, cache)| cache.len()).sum()
+    }
+
+    /// Removes the cached results whose operands or result were dropped, see
+    /// `HcPairTable::purge()`.
+    ///
+    /// ## Returns
+    /// The number of removed results.
+    pub fn purge(&self) -> usize {
+        self.ops.iter().map(|(_, cache)| cache.purge()).sum()
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the table, see `HCTable::cleanup()`, and then removes the cached results of the
+    /// removed values.
+    ///
+    /// ## Returns
+    /// How many entries of the table were removed and remain, and how long the cleanup took.
+    pub fn cleanup(&self) -> CleanupReport {
+        let report = self.table.cleanup();
+        self.purge();
+        report
+    }
+}
+
+impl<T> Default for ConsEngine<T>
+where
+    T: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # `Migration<'a, S, T>`
+/// Moves values from thread-safe handles into a `HCTable<T>`, e.g. to hand the result of a
+/// parallel phase to a single-threaded back-end, and maps every migrated handle to the handle of
+/// its copy.
+///
+/// Values are converted by a function that migrates the handles they hold through the same
+/// `Migration`, so that shared subterms stay shared in the target table. Each source handle is
+/// converted once, and kept alive until the `Migration` is dropped so that its address cannot
+/// be reused by another value.
+///
+/// ## Type Parameters
+/// * `S` - The type of values in the thread-safe table.
+/// * `T` - The type of values in the target table.
+///
+/// ## Fields
+/// * `table`: The table values are migrated into.
+/// * `handles`: The migrated handles and their copies, by address of the migrated handle.
+pub struct Migration<'a, S, T>
+where
+    S: Hash + Eq,
+    T: Hash + Eq,
+{
+    table: &'a HcTable<T>,
+    handles: HashMap<usize, (thread_safe::Hc<S>, Hc<T>)>,
+}
+
+impl<'a, S, T> Migration<'a, S, T>
+where
+    S: Hash + Eq,
+    T: Hash + Eq,
+{
+    /// Creates a `Migration` into `table`.
+    pub fn new(table: &'a HcTable<T>) -> Self {
+        Migration {
+            table,
+            handles: HashMap::new(),
+        }
+    }
+
+    /// Returns the copy of the value of `handle`, converting it with `convert` unless it was
+    /// migrated before.
+    ///
+    /// ## Parameters
+    /// * `handle`: The handle to migrate.
+    /// * `convert`: Converts a value, migrating the handles it holds with this `Migration`.
+    ///
+    /// ## Note
+    /// Children are migrated recursively, so very deep values can exhaust the stack.
+    pub fn migrate(
+        &mut self,
+        handle: &thread_safe::Hc<S>,
+        convert: fn(&S, &mut Self) -> T,
+    ) -> Hc<T> {
+        if let Some((_, migrated)) = self.handles.get(&handle.addr()) {
+            return migrated.clone();
+        }
+        let value = convert(handle.get(), self);
+        let migrated = self.table.hashcons(value);
+        self.handles
+            .insert(handle.addr(), (handle.clone(), migrated.clone()));
+        migrated
+    }
+
+    /// Migrates every value of `source` that still has a handle.
+    ///
+    /// ## Returns
+    /// The number of values that were not migrated before.
+    pub fn migrate_table(
+        &mut self,
+        source: &thread_safe::HcTable<S>,
+        convert: fn(&S, &mut Self) -> T,
+    ) -> usize {
+        let migrated = self.handles.len();
+        for handle in source.handles() {
+            self.migrate(&handle, convert);
+        }
+        self.handles.len() - migrated
+    }
+
+    /// Returns the copy of the value of `handle`, if it was migrated.
+    pub fn get(&self, handle: &thread_safe::Hc<S>) -> Option<Hc<T>> {
+        self.handles
+            .get(&handle.addr())
+            .map(|(_, migrated)| migrated.clone())
+    }
+
+    /// Returns the number of migrated handles.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Returns `true` if no handle was migrated yet.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+}
+
+/// # `HcTableBuilder<T>`
+/// Configures the strategies of a new `HCTable<T>`. Created with `HcTable::builder()`.
+///
+/// ## Fields
+/// * `eq`: The equivalence deciding whether two values share a handle.
+/// * `hash`: The hash function grouping candidate values.
+/// * `normalize`: The canonicalization applied before lookup, if any.
+/// * `weight`: The weight function of values, if any.
+/// * `custom_strategies`: Whether the equivalence, hash function or normalization was set.
+/// * `trusted_hash`: Whether values are identified by a 128-bit hash alone.
+/// * `prehashed`: Whether the data written by `hash` is used as the hash without rehashing it.
+/// * `seed`: The seed of the hasher, or `None` for a random one.
+/// * `deferred_reclamation`: Whether dropping the last handle only queues the entry for removal.
+/// * `collision_warning`: The hook called when too many values share a hash, if any.
+/// * `drift_check`: The hook called with the values whose hash changed, if any.
+/// * `ids`: The allocator of the ids of the table.
+/// * `_semantics`: The semantics of the handles of the built table.
+///
+pub struct HcTableBuilder<T, M = Structural> {
+    eq: Box<dyn ConsEq<T>>,
+    hash: Box<dyn ConsHash<T>>,
+    normalize: Option<Box<dyn ConsNormalize<T>>>,
+    weight: Option<Box<dyn ConsWeight<T>>>,
+    custom_strategies: bool,
+    trusted_hash: bool,
+    prehashed: bool,
+    seed: Option<u64>,
+    #[cfg(feature = "auto-cleanup")]
+    deferred_reclamation: bool,
+    collision_warning: Option<CollisionWarning>,
+    drift_check: Option<DriftCheck<T>>,
+    ids: Box<dyn IdAllocator>,
+    on_evict: Option<EvictObserver<T>>,
+    name: Option<&'static str>,
+    _semantics: PhantomData<fn() -> M>,
+}
+
+impl<T, M> HcTableBuilder<T, M>
 where
     T: Hash + Eq,
 {
-    /// The actual HashMap that stores the `Hc<T>` instances.
+    /// Sets the equivalence deciding whether two values share a handle, `T::eq` by default.
+    ///
+    /// ## Parameters
+    /// * `eq`: The equivalence, which must be consistent with the hash function, see `ConsEq`.
+    pub fn equivalence(mut self, eq: impl ConsEq<T> + 'static) -> Self {
+        self.eq = Box::new(eq);
+        self.custom_strategies = true;
+        self
+    }
+
+    /// Sets the hash function grouping candidate values, `T::hash` by default.
+    ///
+    /// ## Parameters
+    /// * `hash`: The hash function, which must hash equivalent values equally, see `ConsHash`.
+    pub fn hashing(mut self, hash: impl ConsHash<T> + 'static) -> Self {
+        self.hash = Box::new(hash);
+        self.custom_strategies = true;
+        self
+    }
+
+    /// Sets a canonicalization applied to every value before it is looked up.
+    ///
+    /// ## Parameters
+    /// * `normalize`: The canonicalization, see `ConsNormalize`.
+    pub fn normalize(mut self, normalize: impl ConsNormalize<T> + 'static) -> Self {
+        self.normalize = Some(Box::new(normalize));
+        self.custom_strategies = true;
+        self
+    }
+
+    /// Sets the weight function of values, used by `evict_until_weight`. Every value weighs
+    /// `1` by default.
+    ///
+    /// ## Parameters
+    /// * `weight`: The weight function, see `ConsWeight`.
+    pub fn weight(mut self, weight: impl ConsWeight<T> + 'static) -> Self {
+        self.weight = Some(Box::new(weight));
+        self
+    }
+
+    /// Identifies values by a 128-bit hash alone, without ever comparing them.
+    ///
+    /// This avoids a structural comparison on every lookup of an existing value, which is
+    /// worthwhile for very large values. Interning and dropping a value hash it twice instead.
+    ///
+    /// ## Note
+    /// Two different values whose hashes collide are merged into one handle, and the second
+    /// one silently becomes the first. How likely that is depends on the hash function, see
+    /// the `hasher` module:
+    /// * SipHash, the default, and `ahash` are keyed, randomly unless the table is built with
+    ///   `seed`. Among `n` values, two collide with a probability of about `n² / 2^129`, also
+    ///   for values chosen by someone who does not know the keys.
+    /// * With the `fxhash` feature, both halves of the hash are FxHash of the value with
+    ///   different salts. FxHash is not collision-resistant, so values crafted to collide, and
+    ///   possibly regularly structured ones, collide in both halves. Do not use this mode with
+    ///   `fxhash` unless merging distinct values is acceptable.
+    /// * Tables built with `prehashed` use the 128-bit fingerprint of the values as it is, so
+    ///   the risk is that of the fingerprint.
+    ///
+    /// A `ConsHash` that ignores parts of the value merges every value that only differs in
+    /// those parts. The configured `ConsEq` is not used in this mode.
+    pub fn trusted_hash(mut self) -> Self {
+        self.trusted_hash = true;
+        self
+    }
+
+    /// Uses the fingerprint written by the hash function as the hash, without rehashing it.
+    ///
+    /// For values that carry a high-quality hash already, e.g. a content fingerprint, this
+    /// skips hashing entirely. The hash function should write exactly one `u64`, or a `u128` in
+    /// trusted-hash mode, see `IdentityHasher`.
+    ///
+    /// ## Note
+    /// The table does not randomize fingerprints, so a poor fingerprint degrades every lookup.
+    /// `hash_diagnostics` shows whether values collide.
+    pub fn prehashed(mut self) -> Self {
+        self.prehashed = true;
+        self
+    }
+
+    /// Seeds the hasher of the table with `seed` instead of a random value.
+    ///
+    /// Tables built with the same seed hash every value the same way and lay out their entries
+    /// identically, so that anything depending on the order of the entries is reproducible
+    /// across runs. This holds for one build of the crate on one platform, since the hash
+    /// functions do not promise stable output across versions.
+    ///
+    /// ## Note
+    /// Randomly seeded tables resist inputs crafted to collide. Only pin the seed if the values
+    /// do not come from an untrusted source.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    #[cfg(feature = "auto-cleanup")]
+    /// Makes dropping the last handle of a value queue its entry for removal instead of
+    /// removing it right away.
+    ///
+    /// Without this, every last-handle drop takes the write lock of the table, which contends
+    /// with interning when many values die at once. With it, a drop only appends to a queue of
+    /// retired entries, and the entries are removed in batches: by `collect_retired`, or by the
+    /// drop that fills the queue. Retired entries still count towards `len` until then.
+    pub fn deferred_reclamation(mut self) -> Self {
+        self.deferred_reclamation = true;
+        self
+    }
+
+    /// Calls `report` with the number of values sharing a hash the first time more than
+    /// `threshold` distinct values share one, which points to a `Hash` implementation that
+    /// ignores most of the value.
+    ///
+    /// The hook is called after the table is released, so it may use the table.
+    pub fn collision_warning(mut self, threshold: usize, report: impl Fn(usize) + 'static) -> Self {
+        self.collision_warning = Some(CollisionWarning {
+            threshold,
+            report: Box::new(report),
+        });
+        self
+    }
+
+    /// Checks the hashes of the next `every` stored values every time `every` new values were
+    /// stored, and calls `report` with each value whose hash changed, see `hash_drift`.
+    ///
+    /// The checks visit the stored values in rounds, so each of them is checked once every
+    /// time the table stored about as many new values as it holds. The values are hashed and
+    /// reported after the table is released.
+    pub fn hash_drift_check(mut self, every: usize, report: impl Fn(&T) + 'static) -> Self {
+        self.drift_check = Some(DriftCheck {
+            every: every.max(1),
+            report: Box::new(report),
+        });
+        self
+    }
+
+    /// Names the table, so that its `Debug` output, `stats` and leak reports tell it apart
+    /// from the other tables of the process. Forks keep the name of their table.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Makes the ids of the table start at `prefix << 48`, so that tables given distinct
+    /// prefixes, e.g. one per process of a cluster, never assign the same id.
+    ///
+    /// The ids reported by `export_entries` can then be collected from several processes into
+    /// one map without renumbering them, and the upper 16 bits of an id tell which table
+    /// assigned it. Ids still increase in storing order.
+    ///
+    /// ## Panics
+    /// The table panics when storing a value after it assigned all 2^48 ids with the prefix,
+    /// instead of assigning ids with the next prefix.
+    pub fn id_prefix(self, prefix: u16) -> Self {
+        let first = (prefix as u64) << 48;
+        self.ids(SequentialIds::within(first..=first | ((1 << 48) - 1)))
+    }
+
+    /// Sets the allocator of the ids of the table, [`SequentialIds`] from `0` by default.
+    ///
+    /// Forks of the table draw their ids from the same allocator.
+    pub fn ids(mut self, ids: impl IdAllocator + 'static) -> Self {
+        self.ids = Box::new(ids);
+        self
+    }
+
+    /// Calls `observe` with each value evicted from the table, just before it is dropped.
+    ///
+    /// Entries removed by sweeps, by dropping their last handle, or by an equivalent value
+    /// taking over their entry are observed, so that their values can be written behind to disk
+    /// or to an external cache. Values taken back with `remove` or dropped with the table are
+    /// not. `observe` runs after the table is released, so it may use the table.
+    pub fn on_evict(mut self, observe: impl Fn(&T) + 'static) -> Self {
+        self.on_evict = Some(EvictObserver {
+            observe: Box::new(observe),
+        });
+        self
+    }
+
+    /// Makes the handles of the table compare and hash by the address of their value instead
+    /// of the value itself, see [`Identity`].
+    pub fn identity(self) -> HcTableBuilder<T, Identity> {
+        HcTableBuilder {
+            eq: self.eq,
+            hash: self.hash,
+            normalize: self.normalize,
+            weight: self.weight,
+            custom_strategies: self.custom_strategies,
+            trusted_hash: self.trusted_hash,
+            prehashed: self.prehashed,
+            seed: self.seed,
+            #[cfg(feature = "auto-cleanup")]
+            deferred_reclamation: self.deferred_reclamation,
+            collision_warning: self.collision_warning,
+            drift_check: self.drift_check,
+            ids: self.ids,
+            on_evict: self.on_evict,
+            name: self.name,
+            _semantics: PhantomData,
+        }
+    }
+
+    /// Creates the configured `HCTable`.
+    pub fn build(self) -> HcTable<T, M> {
+        HcTable {
+            _semantics: PhantomData,
+            inner: Rc::new(InnerTable::new(self)),
+        }
+    }
+}
+
+/// # Inner<T>
+/// A struct to encapsulate the inner workings of `Hc<T>`.
+/// It holds the actual value and a weak reference to its containing table.
+///
+/// ## Type Parameters
+/// * `T` - The type of the encapsulated value.
+///
+/// ## Fields
+/// * `elem`: The actual stored value.
+/// * `seq`: The sequence number of the entry of the value.
+/// * `generation`: The generation of the table when the value was stored.
+/// * `hash`: The hash the value is stored under.
+/// * `created_at`: Where the value was stored, if backtraces are enabled.
+/// * `_table`: A weak reference to the `HCTable` that contains this value.
+///
+struct Inner<T> {
+    /// The actual stored value.
+    /// This is the value that is returned when the `Hc<T>` is dereference.
+    elem: Rc<T>,
+
+    /// The sequence number of the entry of the value.
+    seq: u64,
+
+    /// The generation of the table when the value was stored.
+    generation: u64,
+
+    /// The hash the value is stored under.
+    hash: u128,
+
+    /// Where the value was stored, if backtraces are enabled.
+    #[cfg(feature = "leak-check")]
+    created_at: std::backtrace::Backtrace,
+
+    /// A reference counted pointer to the `HCTable` that contains this value.
+    /// This is used to remove the value from the table when it is no longer in use.
+    _table: Rc<InnerTable<T>>,
+}
+
+#[cfg(feature = "auto-cleanup")]
+impl<T> Drop for Inner<T> {
+    /// Provides the functionality to drop `Inner<T>` instances.
+    /// This method is useful for managing the lifecycle of `Hc<T>` instances.
+    ///
+    /// Only the entry of this allocation is removed: an equivalent value interned after this
+    /// one died may already have taken over the entry. With deferred reclamation, the entry is
+    /// only queued for removal, see `HcTableBuilder::deferred_reclamation()`.
+    fn drop(&mut self) {
+        let hash = self.hash;
+        if let Some(retired) = &self._table.retired {
+            let mut retired = retired.borrow_mut();
+            retired.push((hash, Rc::as_ptr(&self.elem) as usize));
+            let full = retired.len() >= RETIRED_BATCH;
+            drop(retired);
+            if full {
+                self._table.collect_retired();
+            }
+            return;
+        }
+        let mut mut_table = self._table.table.borrow_mut();
+        let removed = mut_table.remove(hash, |entry| Rc::ptr_eq(&entry.key, &self.elem));
+        drop(mut_table);
+        if removed.is_some() {
+            self._table.observe_evicted([&self.elem]);
+        }
+    }
+}
+
+/// The hook of a table called when too many values share a hash.
+///
+/// ## Fields
+/// * `threshold`: The number of values sharing a hash above which the hook is called.
+/// * `report`: The hook, called with the number of values sharing the hash.
+struct CollisionWarning {
+    threshold: usize,
+    report: Box<dyn Fn(usize)>,
+}
+
+/// The check of a table for values whose hash changed, see `HcTableBuilder::hash_drift_check`.
+///
+/// ## Fields
+/// * `every`: The number of new values after which the next values are checked.
+/// * `report`: The hook, called with each value whose hash changed.
+struct DriftCheck<T> {
+    every: usize,
+    report: Box<dyn Fn(&T)>,
+}
+
+/// The observer of the values evicted from a table, see `HcTableBuilder::on_evict`.
+struct EvictObserver<T> {
+    observe: Box<dyn Fn(&T)>,
+}
+
+/// The values pinned by the live pin scopes of a table, by scope and address, see
+/// `HCTable::pin_scope`.
+struct Pins<T> {
+    next_scope: u64,
+    scopes: HashMap<u64, HashMap<usize, Rc<Inner<T>>>>,
+}
+
+impl<T> Default for Pins<T> {
+    fn default() -> Self {
+        Pins {
+            next_scope: 0,
+            scopes: HashMap::new(),
+        }
+    }
+}
+
+/// # `Strategies<T>`
+/// The strategies a table hashes, compares, normalizes and weighs values with.
+struct Strategies<T> {
+    /// The equivalence deciding whether two values share a handle.
+    eq: Box<dyn ConsEq<T>>,
+
+    /// The hash function grouping candidate values.
+    hash: Box<dyn ConsHash<T>>,
+
+    /// The canonicalization applied before lookup, if any.
+    normalize: Option<Box<dyn ConsNormalize<T>>>,
+
+    /// The weight function of values, if any.
+    weight: Option<Box<dyn ConsWeight<T>>>,
+}
+
+/// # Candidate<T>
+/// A stored value sharing the hash of a looked up value, held so that the two can be compared
+/// after the borrow of the table ends.
+enum Candidate<T> {
+    /// A value with handles.
+    Live(Rc<Inner<T>>),
+    /// A value without handles whose entry was not removed yet.
+    Dead(Rc<T>),
+}
+
+impl<T> Candidate<T> {
+    /// Returns the stored value.
+    fn value(&self) -> &T {
+        match self {
+            Candidate::Live(inner) => &inner.elem,
+            Candidate::Dead(value) => value,
+        }
+    }
+}
+
+/// The buckets of a table, holding each value and the handles of its entry.
+type Storage<T> = Buckets<Rc<T>, Weak<Inner<T>>>;
+
+/// # `InnerTable<T>`
+/// A helper struct to manage the internal storage of `HCTable`.
+/// It provides mechanisms to manage and access stored `Hc<T>` instances.
+///
+/// ## Type Parameters
+/// * `T` - The type of values stored in the `HCTable`.
+///
+/// ## Fields
+/// * `id`: The id of the table, unique within the process.
+/// * `table`: The buckets that store the `Hc<T>` instances, grouped by hash.
+/// * `hasher`: The hasher state used to hash values.
+/// * `trusted_hasher`: The hasher state of the upper 64 bits of the hash in trusted-hash mode.
+/// * `strategies`: The strategies of the table, shared with the tables forked from it.
+/// * `borrowed_lookup`: Whether values are stored under the hash of the keys they borrow as.
+/// * `in_flight`: The values being constructed by `get_or_insert_with`.
+/// * `namespaces`: The hashes of the values interned through each namespace, by their
+///   sequence numbers.
+/// * `prehashed`: Whether the data written by `hash` is used as the hash without rehashing it.
+/// * `collision_warning`: The hook called when too many values share a hash, if any.
+/// * `collision_warned`: Whether the collision hook was called already.
+/// * `drift_check`: The hook called with the values whose hash changed, if any.
+/// * `tables`: The number of `HCTable`s of this table.
+/// * `retired`: The hashes and addresses of the entries queued for removal, in deferred
+///   reclamation mode.
+/// * `last_activity`: The number of lookups when `cleanup_when_idle` last saw them change, and
+///   when that was.
+///
+pub struct InnerTable<T> {
+    /// The id of the table, unique within the process.
+    id: usize,
+
+    /// The buckets that store the `Hc<T>` instances, grouped by hash.
     /// This is the underlying data structure used by `HCTable`.
     /// It is hidden from the user.
-    table: RefCell<HashMap<Rc<T>, Weak<Inner<T>>>>,
+    table: RefCell<Storage<T>>,
+
+    /// The hasher state used to hash values.
+    hasher: HashState,
+
+    /// The hasher state of the upper 64 bits of the hash in trusted-hash mode.
+    trusted_hasher: Option<HashState>,
+
+    /// The strategies of the table, shared with the tables forked from it.
+    strategies: Rc<Strategies<T>>,
+
+    /// Whether values are stored under the hash of the keys they borrow as.
+    borrowed_lookup: bool,
+
+    /// The values being constructed by `get_or_insert_with`.
+    in_flight: InFlight,
+
+    /// The sequence numbers of the values interned through each namespace.
+    namespaces: RefCell<HashMap<String, HashMap<u64, u128>>>,
+
+    /// The values pinned by the live pin scopes.
+    pins: RefCell<Pins<T>>,
+
+    /// Whether the data written by `hash` is used as the hash without rehashing it.
+    prehashed: bool,
+
+    /// The hook called when too many values share a hash, if any.
+    collision_warning: Option<Rc<CollisionWarning>>,
+
+    /// Whether the collision hook was called already.
+    collision_warned: Cell<bool>,
+
+    /// The hook called with the values whose hash changed, if any.
+    drift_check: Option<Rc<DriftCheck<T>>>,
+
+    /// The observer of evicted values, if any.
+    on_evict: Option<Rc<EvictObserver<T>>>,
+
+    /// The name of the table, if any.
+    name: Option<&'static str>,
+
+    /// The number of `HCTable`s of this table.
+    #[cfg(feature = "leak-check")]
+    tables: Cell<usize>,
+
+    /// The hashes and addresses of the entries queued for removal, in deferred reclamation mode.
+    #[cfg(feature = "auto-cleanup")]
+    retired: Option<RefCell<Vec<(u128, usize)>>>,
+
+    /// The number of lookups when `cleanup_when_idle` last saw them change, and when that was.
+    #[cfg(not(feature = "auto-cleanup"))]
+    last_activity: Cell<(u64, Instant)>,
 }
 
-impl<T> InnerTable<T>
-where
-    T: Hash + Eq,
-{
+impl<T> InnerTable<T> {
     /// Creates a new `InnerTable<T>`.
     ///
     /// ## Returns
     /// A new instance of `InnerTable<T>`.
     ///
-    fn new() -> Self {
+    fn new<M>(builder: HcTableBuilder<T, M>) -> Self {
+        let HcTableBuilder {
+            eq,
+            hash,
+            normalize,
+            weight,
+            custom_strategies,
+            trusted_hash,
+            prehashed,
+            seed,
+            #[cfg(feature = "auto-cleanup")]
+            deferred_reclamation,
+            collision_warning,
+            drift_check,
+            ids,
+            on_evict,
+            name,
+            _semantics,
+        } = builder;
+        InnerTable {
+            id: next_table_id(),
+            table: RefCell::new(Buckets::with_ids(ids)),
+            hasher: seed.map_or_else(HashState::new, HashState::with_seed),
+            // The upper half of a trusted hash needs a state independent of the lower half
+            trusted_hasher: trusted_hash
+                .then(|| seed.map_or_else(HashState::new, |seed| HashState::with_seed(!seed))),
+            strategies: Rc::new(Strategies {
+                eq,
+                hash,
+                normalize,
+                weight,
+            }),
+            borrowed_lookup: !custom_strategies,
+            prehashed,
+            collision_warning: collision_warning.map(Rc::new),
+            collision_warned: Cell::new(false),
+            drift_check: drift_check.map(Rc::new),
+            on_evict: on_evict.map(Rc::new),
+            name,
+            #[cfg(feature = "leak-check")]
+            tables: Cell::new(1),
+            in_flight: InFlight::new(PoisonPolicy::default(), message_prefix(name)),
+            namespaces: RefCell::new(HashMap::new()),
+            pins: RefCell::new(Pins::default()),
+            #[cfg(feature = "auto-cleanup")]
+            retired: deferred_reclamation.then(Default::default),
+            #[cfg(not(feature = "auto-cleanup"))]
+            last_activity: Cell::new((0, Instant::now())),
+        }
+    }
+
+    /// Creates an empty table configured like this one, sharing its strategies and the
+    /// allocator of its ids.
+    fn fork(&self) -> Self {
         InnerTable {
-            table: RefCell::new(HashMap::new()),
+            id: next_table_id(),
+            table: RefCell::new(self.table.borrow().sharing_ids()),
+            hasher: self.hasher.clone(),
+            trusted_hasher: self.trusted_hasher.clone(),
+            strategies: self.strategies.clone(),
+            borrowed_lookup: self.borrowed_lookup,
+            prehashed: self.prehashed,
+            collision_warning: self.collision_warning.clone(),
+            collision_warned: Cell::new(false),
+            drift_check: self.drift_check.clone(),
+            on_evict: self.on_evict.clone(),
+            name: self.name,
+            #[cfg(feature = "leak-check")]
+            tables: Cell::new(1),
+            in_flight: InFlight::new(PoisonPolicy::default(), message_prefix(self.name)),
+            namespaces: RefCell::new(HashMap::new()),
+            pins: RefCell::new(Pins::default()),
+            #[cfg(feature = "auto-cleanup")]
+            retired: self.retired.is_some().then(Default::default),
+            #[cfg(not(feature = "auto-cleanup"))]
+            last_activity: Cell::new((0, Instant::now())),
+        }
+    }
+
+    #[cfg(feature = "leak-check")]
+    /// Lists the values that still have handles, with their creation backtraces if captured.
+    fn leak_report(&self) -> LeakReport {
+        let table = self.table.borrow();
+        let mut report = LeakReport {
+            type_name: std::any::type_name::<T>(),
+            table_name: self.name,
+            ..LeakReport::default()
+        };
+        for inner in table.iter().filter_map(|entry| entry.weak.upgrade()) {
+            report.leaked += 1;
+            if inner.created_at.status() == std::backtrace::BacktraceStatus::Captured {
+                report.backtraces.push(inner.created_at.to_string());
+            }
+        }
+        report
+    }
+
+    /// Returns the canonical form of `value`.
+    fn normalize(&self, value: T) -> T {
+        match &self.strategies.normalize {
+            Some(normalize) => normalize.normalize(value),
+            None => value,
+        }
+    }
+
+    /// Returns the hash a value is stored under, given the hash computed by the caller.
+    fn given_hash(&self, hash: u64) -> u128 {
+        assert!(
+            self.trusted_hasher.is_none(),
+            "hash_cons: values cannot be found by a 64-bit hash in trusted-hash mode"
+        );
+        hash as u128
+    }
+
+    /// Returns the hash `value` is stored under.
+    fn hash(&self, value: &T) -> u128 {
+        self.hash_data(|state| self.strategies.hash.write_hash(value, state))
+    }
+
+    /// Returns the hash the value `key` borrows as is stored under, if `borrowed_lookup` is set.
+    fn hash_key<Q>(&self, key: &Q) -> u128
+    where
+        Q: Hash + ?Sized,
+    {
+        self.hash_data(|mut state| key.hash(&mut state))
+    }
+
+    /// Returns the hash of the data `write` writes.
+    fn hash_data(&self, write: impl Fn(&mut dyn Hasher)) -> u128 {
+        if self.prehashed {
+            let mut state = IdentityHasher::default();
+            write(&mut state);
+            return match &self.trusted_hasher {
+                Some(_) => state.finish_u128(),
+                None => state.finish() as u128,
+            };
+        }
+        let low = Self::hash_with(&self.hasher, &write);
+        match &self.trusted_hasher {
+            Some(hasher) => (Self::hash_with(hasher, &write) as u128) << 64 | low as u128,
+            None => low as u128,
+        }
+    }
+
+    /// Hashes the data `write` writes, starting from `hasher`.
+    fn hash_with(hasher: &HashState, write: &impl Fn(&mut dyn Hasher)) -> u64 {
+        let mut state = hasher.build_hasher();
+        write(&mut state);
+        state.finish()
+    }
+
+    /// Calls the collision hook of the table if `shared` values share a hash and its threshold
+    /// is exceeded for the first time. Called after the table is released.
+    fn check_collisions(&self, shared: usize) {
+        if let Some(warning) = &self.collision_warning {
+            if shared > warning.threshold && !self.collision_warned.replace(true) {
+                (warning.report)(shared);
+            }
+        }
+    }
+
+    /// Returns the next stored values to check for a changed hash with their stored hashes, if
+    /// a check is due after a value was stored in `table`.
+    fn drift_batch(&self, table: &mut Storage<T>) -> Vec<(u128, Rc<T>)> {
+        match &self.drift_check {
+            Some(check) if table.stats.created.is_multiple_of(check.every as u64) => table
+                .drift_batch(check.every)
+                .into_iter()
+                .map(|(hash, entry)| (hash, entry.key.clone()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Reports the values of `batch` whose hash changed to the drift check of the table. Called
+    /// after the table is released, since their `Hash` may use the table.
+    fn check_drift(&self, batch: Vec<(u128, Rc<T>)>) {
+        if let Some(check) = &self.drift_check {
+            for (hash, value) in batch {
+                if self.hash(&value) != hash {
+                    (check.report)(&value);
+                }
+            }
+        }
+    }
+
+    /// Collects the entries stored under `hash` whose sequence numbers are not in `checked`, and
+    /// adds their sequence numbers to it.
+    fn unchecked(
+        table: &Storage<T>,
+        hash: u128,
+        checked: &mut Vec<u64>,
+    ) -> Vec<(u64, Candidate<T>)> {
+        let candidates: Vec<_> = table
+            .bucket(hash)
+            .iter()
+            .filter(|entry| !checked.contains(&entry.seq))
+            .map(|entry| {
+                let candidate = match entry.weak.upgrade() {
+                    Some(inner) => Candidate::Live(inner),
+                    None => Candidate::Dead(entry.key.clone()),
+                };
+                (entry.seq, candidate)
+            })
+            .collect();
+        checked.extend(candidates.iter().map(|&(seq, _)| seq));
+        candidates
+    }
+
+    /// Returns every stored value, whether it still has handles, and its generation, so that
+    /// the values can be measured after the lock is released.
+    fn stored(&self) -> Vec<(Rc<T>, bool, u64)> {
+        let table = self.table.borrow();
+        table
+            .iter()
+            .map(|entry| {
+                let alive = entry.weak.strong_count() > 0;
+                (entry.key.clone(), alive, entry.generation)
+            })
+            .collect()
+    }
+
+    /// Returns the values with handles stored under `hash`.
+    fn live(table: &Storage<T>, hash: u128) -> Vec<Rc<Inner<T>>> {
+        table
+            .bucket(hash)
+            .iter()
+            .filter_map(|entry| entry.weak.upgrade())
+            .collect()
+    }
+
+    /// Returns the weight of `value`.
+    fn weight_of(&self, value: &T) -> usize {
+        self.strategies
+            .weight
+            .as_ref()
+            .map_or(1, |weight| weight.weight(value))
+    }
+
+    /// Returns `true` if `value` should share the handle of the stored value `key`, given that
+    /// their hashes are equal.
+    fn matches(&self, key: &T, value: &T) -> bool {
+        self.trusted_hasher.is_some() || self.strategies.eq.equivalent(key, value)
+    }
+
+    #[cfg(feature = "auto-cleanup")]
+    /// Removes the entries queued by dropped handles in deferred reclamation mode.
+    ///
+    /// ## Returns
+    /// The number of entries removed.
+    fn collect_retired(&self) -> usize {
+        let Some(retired) = &self.retired else {
+            return 0;
+        };
+        let mut collected = 0;
+        loop {
+            let queued = retired.take();
+            if queued.is_empty() {
+                return collected;
+            }
+
+            // An entry is only removed if it is still dead: a new value may have taken it over,
+            // possibly at the address of the retired one, before the queue was collected.
+            let mut mut_table = self.table.borrow_mut();
+            let removed: Vec<_> = queued
+                .into_iter()
+                .filter_map(|(hash, addr)| {
+                    mut_table.remove(hash, |entry| {
+                        Rc::as_ptr(&entry.key) as usize == addr && entry.weak.strong_count() == 0
+                    })
+                })
+                .collect();
+            drop(mut_table);
+            collected += removed.len();
+
+            // Dropping the removed values may retire their children, which the next round
+            // collects, so the borrow ended first.
+            self.observe_evicted(removed.iter().map(|entry| &entry.key));
+            drop(removed);
+        }
+    }
+
+    /// Pins `inner` in every live pin scope of the table and returns it.
+    fn pinned(&self, inner: Rc<Inner<T>>) -> Rc<Inner<T>> {
+        let addr = Rc::as_ptr(&inner) as usize;
+        for pinned in self.pins.borrow_mut().scopes.values_mut() {
+            pinned.entry(addr).or_insert_with(|| inner.clone());
+        }
+        inner
+    }
+
+    /// Passes the values of evicted entries to the observer of the table, if any. Called after
+    /// the borrow of the stored values ended.
+    fn observe_evicted<'a>(&self, values: impl IntoIterator<Item = &'a Rc<T>>)
+    where
+        T: 'a,
+    {
+        if let Some(observer) = &self.on_evict {
+            values
+                .into_iter()
+                .for_each(|value| (observer.observe)(value));
         }
     }
 
@@ -546,6 +3621,11 @@ where
         self.table.borrow().len()
     }
 
+    /// Borrows the values interned through each namespace.
+    fn namespaces(&self) -> RefMut<'_, HashMap<String, HashMap<u64, u128>>> {
+        self.namespaces.borrow_mut()
+    }
+
     #[cfg(not(feature = "auto-cleanup"))]
     /// Cleans up the `InnerTable`, removing any values that are no longer in use.
     /// This method is useful for managing memory and ensuring that unused
@@ -556,20 +3636,152 @@ where
     /// It removes any values that have a `strong_count()` of 0.
     /// This is the desired behavior for hash consing.
     ///
-    fn cleanup(&self) {
+    fn cleanup(&self) -> CleanupReport {
+        self.sweep(|_| true)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `InnerTable` stored before `generation`.
+    fn cleanup_older_than(&self, generation: u64) -> CleanupReport {
+        self.sweep(|entry| entry.generation < generation)
+    }
+
+    #[cfg(all(feature = "access-time", not(feature = "auto-cleanup")))]
+    /// Removes the unused values of the `InnerTable` last interned `idle` or longer ago.
+    fn cleanup_idle_for(&self, idle: Duration) -> CleanupReport {
+        let now = self.table.borrow().now();
+        self.sweep(|entry| u64::from(now.saturating_sub(entry.last_access)) >= idle.as_secs())
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `InnerTable` for which `evict` returns `true`,
+    /// including values released by removing others.
+    fn sweep(&self, mut evict: impl FnMut(&Entry<Rc<T>, Weak<Inner<T>>>) -> bool) -> CleanupReport {
+        let start = Instant::now();
+        let mut report = CleanupReport::default();
         loop {
-            let mut mut_table = self.table.borrow_mut();
-            let prev_len = mut_table.len();
-            mut_table.retain(|_, weak_hc: &mut Weak<Inner<T>>| weak_hc.strong_count() > 0);
+            let removed = self
+                .table
+                .borrow_mut()
+                .retain(|entry| entry.weak.strong_count() > 0 || !evict(entry));
+            self.observe_evicted(removed.iter().map(|entry| &entry.key));
+
+            report.removed += removed.len();
+            // Break the loop if no more values were removed. Otherwise, dropping the removed
+            // values may have released their children.
+            if removed.is_empty() {
+                break;
+            }
+        }
+        report.remaining = self.len();
+        report.duration = start.elapsed();
+        report
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes unused values of the `InnerTable` in the given order until the stored values
+    /// weigh at most `budget`, or no unused value is left.
+    fn evict_until_weight(&self, budget: usize, order: EvictionOrder) -> CleanupReport {
+        self.evict_until(budget, order, |value| self.weight_of(value))
+    }
 
-            // Break the loop if no more values were removed.
-            if mut_table.len() == prev_len {
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Like `evict_until_weight`, but weighs the stored values with `weigh`.
+    fn evict_until(
+        &self,
+        budget: usize,
+        order: EvictionOrder,
+        weigh: impl Fn(&T) -> usize,
+    ) -> CleanupReport {
+        let start = Instant::now();
+        let mut report = CleanupReport::default();
+        loop {
+            // The values are weighed while the table is not borrowed, and kept alive until the
+            // evicted ones were removed, so that their addresses identify them.
+            let stored = self.stored();
+            let mut total = 0usize;
+            let mut candidates = Vec::new();
+            for (value, alive, generation) in &stored {
+                let weight = weigh(value);
+                total = total.saturating_add(weight);
+                if !alive {
+                    candidates.push((weight, *generation, Rc::as_ptr(value)));
+                }
+            }
+            if total <= budget || candidates.is_empty() {
                 break;
             }
+
+            match order {
+                EvictionOrder::Heaviest => {
+                    candidates.sort_by_key(|&(weight, _, _)| std::cmp::Reverse(weight))
+                }
+                EvictionOrder::Oldest => candidates.sort_by_key(|&(_, generation, _)| generation),
+            }
+            let mut evicted = HashSet::new();
+            for (weight, _, value) in candidates {
+                if total <= budget {
+                    break;
+                }
+                total -= weight;
+                evicted.insert(value);
+            }
+            let removed = self
+                .table
+                .borrow_mut()
+                .retain(|entry| !evicted.contains(&Rc::as_ptr(&entry.key)));
+            drop(stored);
+            self.observe_evicted(removed.iter().map(|entry| &entry.key));
+
+            // Dropping the removed values may have released their children, which are
+            // candidates of the next round.
+            report.removed += removed.len();
+        }
+        report.remaining = self.len();
+        report.duration = start.elapsed();
+        report
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values among the next `max_entries` entries of the `InnerTable`.
+    fn cleanup_batch(&self, max_entries: usize) -> CleanupReport {
+        let start = Instant::now();
+        // The borrow ends before the removed values are observed and dropped.
+        let removed = self
+            .table
+            .borrow_mut()
+            .retain_batch(max_entries, |entry| entry.weak.strong_count() > 0);
+        self.observe_evicted(removed.iter().map(|entry| &entry.key));
+        CleanupReport {
+            removed: removed.len(),
+            remaining: self.len(),
+            duration: start.elapsed(),
         }
     }
-    /*fn cleanup(&self) {
-        let mut mut_table = self.table.borrow_mut();
-        mut_table.retain(|_, weak_hc: &mut Weak<Inner<T>>| weak_hc.strong_count() > 0);
-    }*/
-}
\ No newline at end of file
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the `InnerTable` if the number of lookups did not change since the call that
+    /// last saw it change, at least `window` ago.
+    fn cleanup_when_idle(&self, window: Duration) -> Option<CleanupReport> {
+        let lookups = self.table.borrow().stats.lookups();
+        let now = Instant::now();
+        let (seen, since) = self.last_activity.get();
+        if seen != lookups {
+            self.last_activity.set((lookups, now));
+            return None;
+        }
+        if now.duration_since(since) < window {
+            return None;
+        }
+        let report = self.cleanup();
+        self.last_activity.set((lookups, Instant::now()));
+        Some(report)
+    }
+}
+
+/// Returns the bytes held by the entry of `value`: the allocation of the value and that of its
+/// handle, both of which start with a strong and a weak reference count, and the heap memory of
+/// the value.
+fn entry_bytes<T: MemSize>(value: &T) -> usize {
+    4 * size_of::<usize>() + size_of::<T>() + size_of::<Inner<T>>() + value.heap_size()
+}
@@ -0,0 +1,100 @@
+//! # Skipped Fields
+//!
+//! Compilers intern syntax trees modulo their source spans: two occurrences of `x + 1` should
+//! share one node, although they were parsed at different places. Wrapping such a field in
+//! [`Skipped`] leaves it out of the identity of the value, so the derived `Hash` and `Eq` of
+//! the value ignore it, while the field is still stored.
+//!
+//! The crate has no derive macro of its own, so fields are skipped by their type rather than by
+//! an attribute, and work with the standard derives.
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+
+/// # `Skipped<T>`
+/// A field stored with a value but left out of its identity.
+///
+/// All `Skipped<T>` are equal and hash alike, so a value deriving `Hash` and `Eq` is interned by
+/// its other fields. Interning a value equal to a stored one returns the stored value, which
+/// keeps the skipped fields it was first interned with.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{HcTable, Skipped};
+///
+/// #[derive(Debug, Hash, PartialEq, Eq)]
+/// struct Ident {
+///     name: String,
+///     span: Skipped<(usize, usize)>,
+/// }
+///
+/// let table = HcTable::new();
+/// let first = table.hashcons(Ident { name: "x".to_string(), span: Skipped((0, 1)) });
+/// let second = table.hashcons(Ident { name: "x".to_string(), span: Skipped((7, 8)) });
+///
+/// assert_eq!(first, second);
+/// assert_eq!(*second.span, (0, 1));
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct Skipped<T>(pub T);
+
+impl<T> Skipped<T> {
+    /// Returns the skipped field.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Skipped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Skipped<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Skipped<T> {
+    fn from(value: T) -> Self {
+        Skipped(value)
+    }
+}
+
+impl<T> PartialEq for Skipped<T> {
+    /// Returns `true`, since skipped fields take no part in comparisons.
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for Skipped<T> {}
+
+impl<T> PartialOrd for Skipped<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Skipped<T> {
+    /// Orders all skipped fields as equal, consistently with `eq`.
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl<T> Hash for Skipped<T> {
+    /// Hashes nothing, consistently with `eq`.
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Skipped<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -0,0 +1,204 @@
+//! # Slab Storage
+//!
+//! A hash consing table that stores its values contiguously in a `Vec` and hands out `Copy`
+//! indices instead of reference counted pointers. Traversing a large DAG then walks through one
+//! allocation in interning order, rather than chasing pointers to nodes scattered across the
+//! heap.
+//!
+//! Values are never removed: the table owns them until it is dropped. Since interning may move
+//! the values when the slab grows, it requires `&mut` access to the table, and values are read
+//! through the table by id.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::slab::{SlabHcTable, SlabId};
+//!
+//! #[derive(Hash, PartialEq, Eq)]
+//! enum BoolExpr {
+//!     Const(bool),
+//!     And(SlabId<BoolExpr>, SlabId<BoolExpr>),
+//! }
+//!
+//! let mut table = SlabHcTable::new();
+//! let t = table.hashcons(BoolExpr::Const(true));
+//! let f = table.hashcons(BoolExpr::Const(false));
+//! let and = table.hashcons(BoolExpr::And(t, f));
+//!
+//! assert_eq!(table.hashcons(BoolExpr::And(t, f)), and);
+//! assert!(table[and] == BoolExpr::And(t, f));
+//! assert_eq!(table.len(), 3);
+//! ```
+
+use crate::buckets::Buckets;
+use crate::hasher::HashState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+
+/// # `SlabId<T>`
+/// The index of a value in a [`SlabHcTable`].
+///
+/// Ids are handed out densely in interning order, starting at zero. Two ids from the same
+/// table are equal exactly when their values are.
+pub struct SlabId<T> {
+    index: u32,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> SlabId<T> {
+    /// Returns the position of the value in the slab.
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+impl<T> Clone for SlabId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SlabId<T> {}
+
+impl<T> PartialEq for SlabId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for SlabId<T> {}
+
+impl<T> PartialOrd for SlabId<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for SlabId<T> {
+    /// Orders ids by interning order, so that children come before their parents.
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> Hash for SlabId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for SlabId<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SlabId({})", self.index)
+    }
+}
+
+/// # `SlabHcTable<T>`
+/// A hash consing table storing its values contiguously.
+///
+/// ## Fields
+/// * `values`: The interned values, in interning order.
+/// * `index`: The ids of the values, grouped by hash.
+/// * `hasher`: The hasher state used to hash values.
+pub struct SlabHcTable<T>
+where
+    T: Hash + Eq,
+{
+    values: Vec<T>,
+    index: Buckets<u32, ()>,
+    hasher: HashState,
+}
+
+impl<T> SlabHcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an empty table with room for `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Self {
+        SlabHcTable {
+            values: Vec::with_capacity(capacity),
+            index: Buckets::new(),
+            hasher: HashState::new(),
+        }
+    }
+
+    /// Returns the id of `value`, appending it to the slab if it was not interned yet.
+    ///
+    /// ## Panics
+    /// Panics if more than `u32::MAX` values are interned.
+    pub fn hashcons(&mut self, value: T) -> SlabId<T> {
+        let hash = self.hasher.hash_one(&value) as u128;
+        let values = &self.values;
+        if let Some(entry) = self
+            .index
+            .find_mut(hash, |&index| values[index as usize] == value)
+        {
+            return SlabId {
+                index: entry.key,
+                _value: PhantomData,
+            };
+        }
+
+        let index = u32::try_from(self.values.len()).expect("too many values interned");
+        self.values.push(value);
+        let ids = self.index.take_id(hash);
+        self.index.insert(hash, ids, index, ());
+        SlabId {
+            index,
+            _value: PhantomData,
+        }
+    }
+
+    /// Returns the value interned under `id`.
+    ///
+    /// ## Panics
+    /// Panics if `id` was not handed out by this table.
+    pub fn get(&self, id: SlabId<T>) -> &T {
+        &self.values[id.index()]
+    }
+
+    /// Returns the interned values in interning order, together with their ids.
+    pub fn iter(&self) -> impl Iterator<Item = (SlabId<T>, &T)> {
+        self.values.iter().enumerate().map(|(index, value)| {
+            let id = SlabId {
+                index: index as u32,
+                _value: PhantomData,
+            };
+            (id, value)
+        })
+    }
+
+    /// Returns the number of values interned in this table.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if nothing has been interned in this table.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<T> Default for SlabHcTable<T>
+where
+    T: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::ops::Index<SlabId<T>> for SlabHcTable<T>
+where
+    T: Hash + Eq,
+{
+    type Output = T;
+
+    fn index(&self, id: SlabId<T>) -> &T {
+        self.get(id)
+    }
+}
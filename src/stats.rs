@@ -0,0 +1,109 @@
+//! # Table Statistics
+//!
+//! Counters describing the workload of a table, queryable at runtime. They tell a table whose
+//! values are rarely shared apart from one whose hash function performs poorly.
+
+use std::time::Duration;
+
+/// # `TableStats`
+/// Counters of a table since it was created. Returned by `HcTable::stats()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableStats {
+    /// The name of the table, if it was given one.
+    pub name: Option<&'static str>,
+    /// Values that were stored in a new allocation.
+    pub created: u64,
+    /// Values whose handle was found in the table instead.
+    pub reused: u64,
+    /// Times the map of the table had to grow.
+    pub map_reallocations: u64,
+    /// Entries removed because all handles of their value were dropped.
+    pub removed: u64,
+    /// Entries stored when the counters were read, including dead ones not cleaned up yet.
+    pub len: usize,
+}
+
+impl TableStats {
+    /// Returns the number of values interned, `created + reused`.
+    pub fn lookups(&self) -> u64 {
+        self.created + self.reused
+    }
+
+    /// Returns the fraction of lookups that reused an existing handle, or `0.0` if nothing was
+    /// interned yet.
+    pub fn reuse_ratio(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            lookups => self.reused as f64 / lookups as f64,
+        }
+    }
+}
+
+impl std::fmt::Display for TableStats {
+    /// Formats the counters as a one-line summary for periodic logging.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::TableStats;
+    ///
+    /// let stats = TableStats {
+    ///     created: 3,
+    ///     reused: 1,
+    ///     len: 2,
+    ///     removed: 1,
+    ///     ..TableStats::default()
+    /// };
+    ///
+    /// assert_eq!(
+    ///     stats.to_string(),
+    ///     "2 entries, 4 lookups (25.0% reused), 1 removed, 0 map reallocations"
+    /// );
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = self.name {
+            write!(f, "{name}: ")?;
+        }
+        write!(
+            f,
+            "{} entries, {} lookups ({:.1}% reused), {} removed, {} map reallocations",
+            self.len,
+            self.lookups(),
+            self.reuse_ratio() * 100.0,
+            self.removed,
+            self.map_reallocations
+        )
+    }
+}
+
+/// # `HashDiagnostics`
+/// The distribution of stored values over hashes. Returned by `HcTable::hash_diagnostics()`.
+///
+/// With a good hash function almost every hash is shared by a single value. Many values sharing
+/// a hash point to a `Hash` implementation that ignores most of the value, which turns every
+/// lookup into a linear scan.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HashDiagnostics {
+    /// `occupancy[n]` is the number of hashes shared by exactly `n` stored values.
+    pub occupancy: Vec<usize>,
+    /// The number of distinct hashes.
+    pub distinct_hashes: usize,
+    /// The number of stored values that share their hash with at least one other value.
+    pub colliding_values: usize,
+    /// The largest number of values sharing one hash.
+    pub max_collisions: usize,
+}
+
+/// # `CleanupReport`
+/// The outcome of a sweep over a table. Returned by `HcTable::cleanup()`.
+///
+/// Comparing `removed` to `remaining` over time tells how much garbage accumulates between
+/// sweeps, so that a scheduler can sweep more or less often.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// The number of dead entries removed.
+    pub removed: usize,
+    /// The number of entries left in the table after the sweep.
+    pub remaining: usize,
+    /// The time the sweep took.
+    pub duration: Duration,
+}
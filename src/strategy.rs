@@ -0,0 +1,426 @@
+//! # Table Strategies
+//!
+//! Strategies customize how a table decides that two values are the same, without changing
+//! the `Eq` and `Hash` implementations of the value type. A type can therefore be interned in several
+//! tables with different notions of identity, e.g. one comparing strings exactly and one
+//! ignoring case. A table can also rewrite values into a canonical form before interning
+//! them.
+//!
+//! Strategies are installed when a table is built, see `HcTable::builder()`. A table can also
+//! be told how much each value weighs, to bound the memory of its unused values, whether its
+//! handles compare by value or by identity, and how it assigns the ids of its values. Values
+//! interned as edges are split into the value to store and attribute bits, see
+//! `HcTable::hashcons_edge()`.
+
+use std::hash::{Hash, Hasher};
+
+/// # `ConsEq<T>`
+/// An equivalence relation used by a table in place of `T::eq`.
+///
+/// The relation must be reflexive, symmetric and transitive, and values it considers
+/// equivalent must hash equally under the table's [`ConsHash`], which is `T::hash` unless
+/// configured otherwise. When two equivalent values are interned, the table hands out the
+/// handle of the one interned first.
+///
+/// Closures of type `Fn(&T, &T) -> bool` implement this trait.
+///
+/// ## Example
+/// ```
+/// use hash_cons::ConsEq;
+///
+/// struct ByLength;
+///
+/// impl ConsEq<Vec<u8>> for ByLength {
+///     fn equivalent(&self, a: &Vec<u8>, b: &Vec<u8>) -> bool {
+///         a.len() == b.len()
+///     }
+/// }
+///
+/// assert!(ByLength.equivalent(&vec![1, 2], &vec![3, 4]));
+/// ```
+pub trait ConsEq<T: ?Sized> {
+    /// Returns `true` if `a` and `b` should be interned as the same value.
+    fn equivalent(&self, a: &T, b: &T) -> bool;
+}
+
+impl<T, F> ConsEq<T> for F
+where
+    T: ?Sized,
+    F: Fn(&T, &T) -> bool,
+{
+    fn equivalent(&self, a: &T, b: &T) -> bool {
+        self(a, b)
+    }
+}
+
+/// # `DefaultEq`
+/// The equivalence used by tables unless another one is configured: `T::eq`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultEq;
+
+impl<T> ConsEq<T> for DefaultEq
+where
+    T: Eq + ?Sized,
+{
+    fn equivalent(&self, a: &T, b: &T) -> bool {
+        a == b
+    }
+}
+
+/// # `ConsHash<T>`
+/// A hash function used by a table in place of `T::hash`.
+///
+/// Values that the table's [`ConsEq`] considers equivalent must write the same data to the
+/// hasher. Leaving out fields that do not take part in the equivalence, such as source
+/// locations, keeps lookups cheap.
+///
+/// Closures of type `Fn(&T, &mut dyn Hasher)` implement this trait.
+///
+/// ## Example
+/// ```
+/// use hash_cons::HcTable;
+/// use std::hash::Hasher;
+///
+/// let table = HcTable::builder()
+///     .equivalence(|a: &String, b: &String| a.eq_ignore_ascii_case(b))
+///     .hashing(|value: &String, state: &mut dyn Hasher| {
+///         state.write(value.to_ascii_lowercase().as_bytes())
+///     })
+///     .build();
+///
+/// let upper = table.hashcons("HashCons".to_string());
+/// let lower = table.hashcons("hashcons".to_string());
+///
+/// assert_eq!(*lower, "HashCons");
+/// assert_eq!(table.len(), 1);
+/// ```
+pub trait ConsHash<T: ?Sized> {
+    /// Writes the data identifying `value` to `state`.
+    fn write_hash(&self, value: &T, state: &mut dyn Hasher);
+}
+
+impl<T, F> ConsHash<T> for F
+where
+    T: ?Sized,
+    F: Fn(&T, &mut dyn Hasher),
+{
+    fn write_hash(&self, value: &T, state: &mut dyn Hasher) {
+        self(value, state)
+    }
+}
+
+/// # `DefaultHash`
+/// The hash function used by tables unless another one is configured: `T::hash`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultHash;
+
+impl<T> ConsHash<T> for DefaultHash
+where
+    T: Hash + ?Sized,
+{
+    fn write_hash(&self, value: &T, mut state: &mut dyn Hasher) {
+        value.hash(&mut state)
+    }
+}
+
+/// # `ConsNormalize<T>`
+/// A canonicalization applied to every value before the table looks it up.
+///
+/// Normalizing at the intern boundary guarantees that the table never holds two values that
+/// only differ in shape, e.g. the two operand orders of a commutative operator.
+///
+/// Closures of type `Fn(T) -> T` implement this trait.
+///
+/// ## Example
+/// ```
+/// use hash_cons::HcTable;
+///
+/// let table = HcTable::builder()
+///     .normalize(|(a, b): (u32, u32)| (a.min(b), a.max(b)))
+///     .build();
+///
+/// let swapped = table.hashcons((2, 1));
+///
+/// assert_eq!(*swapped, (1, 2));
+/// assert_eq!(table.hashcons((1, 2)), swapped);
+/// ```
+pub trait ConsNormalize<T> {
+    /// Returns the canonical form of `value`.
+    fn normalize(&self, value: T) -> T;
+}
+
+impl<T, F> ConsNormalize<T> for F
+where
+    F: Fn(T) -> T,
+{
+    fn normalize(&self, value: T) -> T {
+        self(value)
+    }
+}
+
+/// # `ConsWeight<T>`
+/// The weight of a value, used by a table to decide which unused values to evict.
+///
+/// Weights are in arbitrary units, e.g. bytes or nodes. Tables without a weight function count
+/// every value as `1`.
+///
+/// Closures of type `Fn(&T) -> usize` implement this trait.
+///
+/// ## Example
+/// ```
+/// use hash_cons::ConsWeight;
+///
+/// let bytes = |value: &String| value.len();
+///
+/// assert_eq!(bytes.weight(&"hash".to_string()), 4);
+/// ```
+pub trait ConsWeight<T: ?Sized> {
+    /// Returns the weight of `value`.
+    fn weight(&self, value: &T) -> usize;
+}
+
+impl<T, F> ConsWeight<T> for F
+where
+    T: ?Sized,
+    F: Fn(&T) -> usize,
+{
+    fn weight(&self, value: &T) -> usize {
+        self(value)
+    }
+}
+
+/// # `IdAllocator`
+/// Assigns the ids of the values a table stores, which `export_entries`, `entry_id` and tokens
+/// report. Tables use [`SequentialIds`] unless built with `HcTableBuilder::ids()`.
+///
+/// The allocator is called with the hash of each new value while the table is locked, so it
+/// must not use the table. Values stored at the same time must get distinct ids, except that
+/// values with the same hash may share one, as [`ContentIds`] does. Tables track the storing
+/// order apart from the ids, so `export_entries`, `checkpoint` and `rollback` follow it with
+/// any allocator.
+///
+/// Closures of type `FnMut(u128) -> u64` implement this trait, e.g. to lease ids from a
+/// coordinator shared by several processes.
+///
+/// ## Example
+/// ```
+/// use hash_cons::HcTable;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+///
+/// // Two tables drawing ids from one shared counter never hand out the same id.
+/// let counter = Arc::new(AtomicU64::new(100));
+/// let lease = |counter: Arc<AtomicU64>| move |_hash| counter.fetch_add(1, Ordering::Relaxed);
+/// let first = HcTable::builder().ids(lease(counter.clone())).build();
+/// let second = HcTable::builder().ids(lease(counter)).build();
+///
+/// let (a, b) = (first.hashcons('a'), second.hashcons('b'));
+/// assert_eq!((first.entry_id(&a), second.entry_id(&b)), (Some(100), Some(101)));
+/// ```
+pub trait IdAllocator: Send + Sync {
+    /// Returns the id of a value about to be stored under `hash`.
+    fn allocate(&mut self, hash: u128) -> u64;
+}
+
+impl<F> IdAllocator for F
+where
+    F: FnMut(u128) -> u64 + Send + Sync,
+{
+    fn allocate(&mut self, hash: u128) -> u64 {
+        self(hash)
+    }
+}
+
+/// # `SequentialIds`
+/// The allocator used by tables unless another one is configured: consecutive ids from a
+/// starting point, `0` by default.
+///
+/// The allocator panics once its range is used up instead of starting over, since the ids it
+/// would hand out again may still be in use.
+#[derive(Clone, Copy, Debug)]
+pub struct SequentialIds {
+    next: u64,
+    last: u64,
+    used_up: bool,
+}
+
+impl SequentialIds {
+    /// Creates an allocator whose first id is `first`.
+    pub fn starting_at(first: u64) -> Self {
+        Self::within(first..=u64::MAX)
+    }
+
+    /// Creates an allocator handing out the ids of `ids` in order.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::{HcTable, SequentialIds};
+    ///
+    /// let table = HcTable::builder().ids(SequentialIds::within(10..=11)).build();
+    /// let (a, b) = (table.hashcons('a'), table.hashcons('b'));
+    /// assert_eq!((table.entry_id(&a), table.entry_id(&b)), (Some(10), Some(11)));
+    /// ```
+    pub fn within(ids: std::ops::RangeInclusive<u64>) -> Self {
+        SequentialIds {
+            next: *ids.start(),
+            last: *ids.end(),
+            used_up: ids.is_empty(),
+        }
+    }
+}
+
+impl Default for SequentialIds {
+    fn default() -> Self {
+        Self::starting_at(0)
+    }
+}
+
+impl IdAllocator for SequentialIds {
+    fn allocate(&mut self, _hash: u128) -> u64 {
+        assert!(!self.used_up, "all ids up to {:#x} are used", self.last);
+        let id = self.next;
+        self.used_up = id == self.last;
+        self.next = id.wrapping_add(1);
+        id
+    }
+}
+
+/// # `RandomIds`
+/// Randomly drawn ids, which reveal neither how many values a table stored nor in which order.
+///
+/// Ids are the randomly keyed hashes of a counter, so two of them collide with a probability
+/// of about one in 2^64 for each pair of values.
+#[derive(Clone, Debug, Default)]
+pub struct RandomIds {
+    state: std::collections::hash_map::RandomState,
+    count: u64,
+}
+
+impl IdAllocator for RandomIds {
+    fn allocate(&mut self, _hash: u128) -> u64 {
+        self.count += 1;
+        std::hash::BuildHasher::hash_one(&self.state, self.count)
+    }
+}
+
+/// # `ContentIds`
+/// Ids derived from the hash of the value: its lower 64 bits.
+///
+/// A value gets the same id every time it is interned again after being removed, and in every
+/// table with the same seed, see `HcTableBuilder::seed()`. Values whose hashes agree in their
+/// lower half share an id.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContentIds;
+
+impl IdAllocator for ContentIds {
+    fn allocate(&mut self, hash: u128) -> u64 {
+        hash as u64
+    }
+}
+
+/// # `EvictionOrder`
+/// The order in which `HcTable::evict_until_weight()` removes unused values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionOrder {
+    /// Heaviest values first, so that as few values as possible are evicted.
+    #[default]
+    Heaviest,
+    /// Values stored in the oldest generation first, see `HcTable::advance_generation()`.
+    Oldest,
+}
+
+/// # `PoisonPolicy`
+/// How a thread-safe table handles its locks being poisoned, which happens when a panic, e.g. in
+/// an id allocator, interrupts an operation holding one. Set with
+/// `HcTableBuilder::poison_policy()`, which also covers the constructions of
+/// `get_or_insert_with` and the caches of a `ConsEngine`, or with
+/// `HcPairTable::with_poison_policy()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Prints a warning and continues with the lock. The lock stays poisoned, so every later
+    /// operation taking it warns again.
+    #[default]
+    Continue,
+    /// Clears the poison and continues without a warning.
+    Clear,
+    /// Panics, leaving the lock poisoned so that every later operation panics as well.
+    Panic,
+}
+
+impl PoisonPolicy {
+    /// Handles the poisoned lock `poisoned` as this policy says, clearing its poison with
+    /// `clear_poison`. Messages start with `prefix`, which names the table.
+    pub(crate) fn recover<G>(
+        self,
+        prefix: &str,
+        poisoned: std::sync::PoisonError<G>,
+        clear_poison: impl FnOnce(),
+    ) -> G {
+        match self {
+            PoisonPolicy::Continue => {
+                eprintln!("{prefix}Mutex is poisoned. Continuing with the poisoned lock.")
+            }
+            PoisonPolicy::Clear => clear_poison(),
+            PoisonPolicy::Panic => {
+                panic!("{prefix}Mutex is poisoned by a panic while it was held.")
+            }
+        }
+        poisoned.into_inner()
+    }
+}
+
+/// Returns the prefix of the messages printed about the table named `name`, if it has a name.
+pub(crate) fn message_prefix(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("hash_cons: table `{name}`: "),
+        None => "hash_cons: ".to_string(),
+    }
+}
+
+/// # `Structural`
+/// The semantics of handles unless a table is built with `HcTableBuilder::identity()`: handles
+/// compare, hash and order by their values, so that handles of different tables with equal
+/// values are equal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Structural;
+
+/// # `Identity`
+/// The semantics of handles of tables built with `HcTableBuilder::identity()`: handles compare
+/// and hash by the address of their value, which takes constant time however large the value.
+///
+/// Within a table, equal values share their address, so this only differs from [`Structural`]
+/// for handles of different tables. As the marker is part of the handle type, handles of
+/// either kind cannot be mixed up.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{Hc, HcTable, Identity};
+/// use std::collections::HashSet;
+///
+/// let table = HcTable::builder().identity().build();
+/// let first = table.hashcons(vec![0u8; 4096]);
+/// let second = table.hashcons(vec![0u8; 4096]);
+///
+/// let seen: HashSet<Hc<Vec<u8>, Identity>> = [first].into_iter().collect();
+/// assert!(seen.contains(&second));
+///
+/// let other = HcTable::builder().identity().build();
+/// assert!(!seen.contains(&other.hashcons(vec![0u8; 4096])));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Identity;
+
+/// # `Canonical<T, E>`
+/// The canonical form of a value interned with `HcTable::hashcons_edge()`.
+///
+/// ## Type Parameters
+/// * `T` - The type of the interned values.
+/// * `E` - The edges of the table, `HcEdge<T>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Canonical<T, E> {
+    /// The value to store, and the attribute bits of the edge pointing at it.
+    Node(T, u8),
+    /// An existing edge the value is equivalent to, e.g. the complemented edge to `x` for
+    /// `Not(x)`.
+    Edge(E),
+}
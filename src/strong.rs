@@ -0,0 +1,264 @@
+//! # Strong Tables
+//!
+//! A hash consing table that owns its values outright and hands out `Copy` ids, like the
+//! interners of most compilers. Values are never removed, so handles need no reference counts
+//! and the table keeps no weak pointers: the values are freed all at once with the table.
+//!
+//! Unlike a [`SlabHcTable`](crate::slab::SlabHcTable), the table interns through a shared
+//! reference and can be shared between threads, since stored values never move. Values are read
+//! through the table by id.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::strong::{StrongHcTable, StrongId};
+//!
+//! #[derive(Hash, PartialEq, Eq)]
+//! enum BoolExpr {
+//!     Const(bool),
+//!     Not(StrongId<BoolExpr>),
+//! }
+//!
+//! let table = StrongHcTable::new();
+//! let t = table.hashcons(BoolExpr::Const(true));
+//! let not_t = table.hashcons(BoolExpr::Not(t));
+//!
+//! assert_eq!(table.hashcons(BoolExpr::Not(t)), not_t);
+//! assert!(table[not_t] == BoolExpr::Not(t));
+//! assert_eq!(table.len(), 2);
+//! ```
+
+use crate::buckets::Buckets;
+use crate::hasher::HashState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// The number of values of the first chunk of a table, each further chunk holds twice as many
+/// as the previous one.
+const FIRST_CHUNK: usize = 64;
+
+/// # `StrongId<T>`
+/// The id of a value in a [`StrongHcTable`].
+///
+/// Ids are handed out densely in interning order, starting at zero. Two ids from the same
+/// table are equal exactly when their values are.
+pub struct StrongId<T> {
+    index: u32,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> StrongId<T> {
+    /// Returns the position of the value in interning order.
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+impl<T> Clone for StrongId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for StrongId<T> {}
+
+impl<T> PartialEq for StrongId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for StrongId<T> {}
+
+impl<T> PartialOrd for StrongId<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for StrongId<T> {
+    /// Orders ids by interning order, so that children come before their parents.
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> Hash for StrongId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for StrongId<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "StrongId({})", self.index)
+    }
+}
+
+/// # `StrongHcTable<T>`
+/// A hash consing table owning its values until it is dropped.
+///
+/// ## Fields
+/// * `storage`: The interned values and their index.
+/// * `hasher`: The hasher state used to hash values.
+pub struct StrongHcTable<T>
+where
+    T: Hash + Eq,
+{
+    storage: RwLock<Storage<T>>,
+    hasher: HashState,
+}
+
+/// # `Storage<T>`
+/// The values of a [`StrongHcTable`].
+///
+/// ## Fields
+/// * `chunks`: The interned values in interning order. Chunks are never grown beyond the
+///   capacity they were created with, so that the values never move.
+/// * `index`: The ids of the values, grouped by hash.
+struct Storage<T> {
+    chunks: Vec<Vec<T>>,
+    index: Buckets<u32, ()>,
+}
+
+impl<T> Storage<T> {
+    /// Returns the value at `index` in interning order.
+    fn get(&self, index: u32) -> &T {
+        let (chunk, offset) = locate(index as usize);
+        &self.chunks[chunk][offset]
+    }
+
+    /// Returns the index of the value equal to `value` with the given hash, if it is stored.
+    fn find(&self, hash: u128, value: &T) -> Option<u32>
+    where
+        T: Eq,
+    {
+        self.index
+            .bucket(hash)
+            .iter()
+            .map(|entry| entry.key)
+            .find(|&index| self.get(index) == value)
+    }
+}
+
+/// Returns the chunk holding the value at `index` and the position of the value in it.
+fn locate(index: usize) -> (usize, usize) {
+    let slot = index + FIRST_CHUNK;
+    let chunk = (slot.ilog2() - FIRST_CHUNK.ilog2()) as usize;
+    (chunk, slot - (FIRST_CHUNK << chunk))
+}
+
+impl<T> StrongHcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        StrongHcTable {
+            storage: RwLock::new(Storage {
+                chunks: Vec::new(),
+                index: Buckets::new(),
+            }),
+            hasher: HashState::new(),
+        }
+    }
+
+    /// Returns the id of `value`, storing it if it was not interned yet.
+    ///
+    /// ## Panics
+    /// Panics if more than `u32::MAX` values are interned.
+    pub fn hashcons(&self, value: T) -> StrongId<T> {
+        let hash = self.hasher.hash_one(&value) as u128;
+        if let Some(index) = self.read().find(hash, &value) {
+            return Self::id(index);
+        }
+
+        let mut storage = self.write();
+        // Another thread may have stored the value since the read lock was released.
+        if let Some(index) = storage.find(hash, &value) {
+            return Self::id(index);
+        }
+        let len = storage.index.len();
+        let index = u32::try_from(len).expect("too many values interned");
+        let (chunk, _) = locate(len);
+        if chunk == storage.chunks.len() {
+            storage
+                .chunks
+                .push(Vec::with_capacity(FIRST_CHUNK << chunk));
+        }
+        storage.chunks[chunk].push(value);
+        let ids = storage.index.take_id(hash);
+        storage.index.insert(hash, ids, index, ());
+        Self::id(index)
+    }
+
+    /// Returns the id of `value` if it was interned, without storing it.
+    pub fn lookup(&self, value: &T) -> Option<StrongId<T>> {
+        let hash = self.hasher.hash_one(value) as u128;
+        self.read().find(hash, value).map(Self::id)
+    }
+
+    /// Returns the value interned under `id`.
+    ///
+    /// ## Panics
+    /// Panics if `id` was not handed out by this table.
+    pub fn get(&self, id: StrongId<T>) -> &T {
+        let value: *const T = self.read().get(id.index);
+        // SAFETY: Values are only dropped with the table, and never move: chunks are not grown
+        // beyond their capacity, and moving the list of chunks does not move their values.
+        unsafe { &*value }
+    }
+
+    /// Returns the number of values interned in this table.
+    pub fn len(&self) -> usize {
+        self.read().index.len()
+    }
+
+    /// Returns `true` if nothing has been interned in this table.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wraps the index of a stored value into its id.
+    fn id(index: u32) -> StrongId<T> {
+        StrongId {
+            index,
+            _value: PhantomData,
+        }
+    }
+
+    /// Acquires the read lock on the values, continuing with a poisoned lock, since a panic
+    /// while interning leaves the storage consistent.
+    fn read(&self) -> RwLockReadGuard<'_, Storage<T>> {
+        self.storage
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Acquires the write lock on the values, like `read`.
+    fn write(&self) -> RwLockWriteGuard<'_, Storage<T>> {
+        self.storage
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> Default for StrongHcTable<T>
+where
+    T: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::ops::Index<StrongId<T>> for StrongHcTable<T>
+where
+    T: Hash + Eq,
+{
+    type Output = T;
+
+    fn index(&self, id: StrongId<T>) -> &T {
+        self.get(id)
+    }
+}
@@ -0,0 +1,270 @@
+//! # Symbol Tables
+//!
+//! A string interner built on `HcTable<String>` that also keeps its symbols in order, so that
+//! they can be listed by prefix or range, e.g. to complete identifiers in an editor.
+//!
+//! Symbols live as long as the table, like in most string interners: the ordered index holds a
+//! handle to each of them.
+//!
+//! A table can be created with a [`Folding`], which normalizes spellings before they are
+//! deduplicated, for languages whose identifiers are case-insensitive. The first spelling of a
+//! symbol is kept as its canonical spelling and returned for every equivalent one.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::symbols::SymbolTable;
+//!
+//! let symbols = SymbolTable::new();
+//! for name in ["std::fmt", "foo::baz", "foo::bar", "food"] {
+//!     symbols.intern(name);
+//! }
+//!
+//! let completions: Vec<_> = symbols
+//!     .symbols_with_prefix("foo::")
+//!     .iter()
+//!     .map(|symbol| symbol.as_str().to_owned())
+//!     .collect();
+//! assert_eq!(completions, ["foo::bar", "foo::baz"]);
+//! ```
+
+use crate::{Hc, HcTable};
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Mutex, MutexGuard};
+#[cfg(feature = "unicode")]
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// # `Folding`
+/// The normalization a [`SymbolTable`] applies to spellings before deduplicating them.
+///
+/// ## Example
+/// ```
+/// use hash_cons::symbols::{Folding, SymbolTable};
+///
+/// let symbols = SymbolTable::with_folding(Folding::AsciiCase);
+/// let begin = symbols.intern("Begin");
+///
+/// assert_eq!(symbols.intern("BEGIN"), begin);
+/// assert_eq!(symbols.get("begin").unwrap().as_str(), "Begin");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Folding {
+    /// Spellings are compared as they are.
+    #[default]
+    Exact,
+    /// ASCII letters are compared case-insensitively, other characters as they are.
+    AsciiCase,
+    /// Spellings are compared by their Unicode normalization form C, so that precomposed and
+    /// decomposed characters are the same symbol.
+    #[cfg(feature = "unicode")]
+    Nfc,
+}
+
+impl Folding {
+    /// Returns the normalized form of `name`, borrowing it if it is normalized already.
+    pub fn fold<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            Folding::Exact => Cow::Borrowed(name),
+            Folding::AsciiCase if name.bytes().any(|byte| byte.is_ascii_uppercase()) => {
+                Cow::Owned(name.to_ascii_lowercase())
+            }
+            Folding::AsciiCase => Cow::Borrowed(name),
+            #[cfg(feature = "unicode")]
+            Folding::Nfc if is_nfc(name) => Cow::Borrowed(name),
+            #[cfg(feature = "unicode")]
+            Folding::Nfc => Cow::Owned(name.nfc().collect()),
+        }
+    }
+}
+
+/// A symbol in the ordered index, which can be looked up by its folded spelling.
+///
+/// The folded spelling is only stored if it differs from the canonical one.
+struct Key {
+    folded: Option<Box<str>>,
+    symbol: Hc<String>,
+}
+
+impl Key {
+    fn new(folded: Cow<'_, str>, symbol: Hc<String>) -> Self {
+        let folded = match folded {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(folded) => Some(folded.into_boxed_str()),
+        };
+        Key { folded, symbol }
+    }
+}
+
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        self.folded.as_deref().unwrap_or(self.symbol.as_str())
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        Borrow::<str>::borrow(self) == Borrow::<str>::borrow(other)
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Borrow::<str>::borrow(self).cmp(Borrow::<str>::borrow(other))
+    }
+}
+
+/// # `SymbolTable`
+/// A string interner whose symbols can be listed in order.
+///
+/// Symbols are handles of the underlying `HcTable<String>`, see `table()`, so they compare in
+/// constant time and can be mixed with strings interned there directly. That table holds the
+/// canonical spellings only and does not fold the strings interned there.
+///
+/// ## Fields
+/// * `table`: The table holding the symbols.
+/// * `index`: The symbols, ordered by their folded spelling.
+/// * `folding`: The normalization applied to spellings before deduplicating them.
+pub struct SymbolTable {
+    table: HcTable<String>,
+    index: Mutex<BTreeSet<Key>>,
+    folding: Folding,
+}
+
+impl SymbolTable {
+    /// Creates an empty symbol table.
+    pub fn new() -> Self {
+        Self::with_folding(Folding::Exact)
+    }
+
+    /// Creates an empty symbol table deduplicating spellings by their `folding`.
+    pub fn with_folding(folding: Folding) -> Self {
+        SymbolTable {
+            table: HcTable::new(),
+            index: Mutex::new(BTreeSet::new()),
+            folding,
+        }
+    }
+
+    /// Returns the symbol spelled `name`, interning a copy of it if it is new.
+    ///
+    /// Once folded, equivalent spellings return the symbol of the first one interned.
+    pub fn intern(&self, name: &str) -> Hc<String> {
+        let folded = self.folding.fold(name);
+        let mut index = self.lock();
+        if let Some(key) = index.get(&*folded) {
+            return key.symbol.clone();
+        }
+        let symbol = self.table.get_or_insert_with(name, str::to_owned);
+        index.insert(Key::new(folded, symbol.clone()));
+        symbol
+    }
+
+    /// Returns the symbol spelled `name`, or equivalently once folded, if it was interned.
+    pub fn get(&self, name: &str) -> Option<Hc<String>> {
+        let folded = self.folding.fold(name);
+        self.lock().get(&*folded).map(|key| key.symbol.clone())
+    }
+
+    /// Returns all symbols, in lexicographic order of their folded spelling.
+    pub fn symbols(&self) -> Vec<Hc<String>> {
+        self.symbols_in(..)
+    }
+
+    /// Returns the symbols whose folded spelling starts with the folded `prefix`, in
+    /// lexicographic order of their folded spelling.
+    pub fn symbols_with_prefix(&self, prefix: &str) -> Vec<Hc<String>> {
+        let prefix = self.folding.fold(prefix);
+        self.lock()
+            .range::<str, _>((Bound::Included(&*prefix), Bound::Unbounded))
+            .take_while(|key| Borrow::<str>::borrow(*key).starts_with(&*prefix))
+            .map(|key| key.symbol.clone())
+            .collect()
+    }
+
+    /// Returns the symbols whose folded spelling is within the folded `range`, in
+    /// lexicographic order of their folded spelling.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::symbols::SymbolTable;
+    /// use std::ops::Bound;
+    ///
+    /// let symbols = SymbolTable::new();
+    /// for name in ["a", "b", "c", "d"] {
+    ///     symbols.intern(name);
+    /// }
+    ///
+    /// let range = symbols.symbols_in((Bound::Excluded("a"), Bound::Included("c")));
+    /// assert_eq!(range, [symbols.intern("b"), symbols.intern("c")]);
+    /// ```
+    pub fn symbols_in<'a>(&self, range: impl RangeBounds<&'a str>) -> Vec<Hc<String>> {
+        let start = range.start_bound().map(|start| self.folding.fold(start));
+        let end = range.end_bound().map(|end| self.folding.fold(end));
+        let bounds = (
+            start.as_ref().map(|start| &**start),
+            end.as_ref().map(|end| &**end),
+        );
+        self.lock()
+            .range::<str, _>(bounds)
+            .map(|key| key.symbol.clone())
+            .collect()
+    }
+
+    /// Returns the number of symbols.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no symbol was interned.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the normalization applied to spellings before deduplicating them.
+    pub fn folding(&self) -> Folding {
+        self.folding
+    }
+
+    /// Returns the table holding the symbols.
+    pub fn table(&self) -> &HcTable<String> {
+        &self.table
+    }
+
+    /// Acquires the index, continuing with the poisoned lock, as no user code runs while it is
+    /// held.
+    fn lock(&self) -> MutexGuard<'_, BTreeSet<Key>> {
+        match self.index.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                self.index.clear_poison();
+                poisoned.into_inner()
+            }
+        }
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SymbolTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.lock().iter().map(|key| key.symbol.as_str()))
+            .finish()
+    }
+}
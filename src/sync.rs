@@ -0,0 +1,25 @@
+//! # Atomic Primitives
+//!
+//! The reference counted pointers and atomics of the thread-safe table. They are those of
+//! `std`, unless the **portable-atomic** feature is enabled, which replaces them with the ones
+//! of `portable-atomic` and `portable-atomic-util`. These work on targets whose `std` lacks
+//! atomic compare-and-swap, or where it is emulated, e.g. with a critical section after
+//! enabling the `critical-section` feature of `portable-atomic`.
+
+#[cfg(all(feature = "auto-cleanup", not(feature = "portable-atomic")))]
+pub(crate) use std::sync::atomic::AtomicPtr;
+#[cfg(not(feature = "portable-atomic"))]
+pub(crate) use std::sync::atomic::AtomicUsize;
+#[cfg(not(feature = "portable-atomic"))]
+pub(crate) use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(feature = "portable-atomic"))]
+pub(crate) use std::sync::{Arc, Weak};
+
+#[cfg(all(feature = "auto-cleanup", feature = "portable-atomic"))]
+pub(crate) use portable_atomic::AtomicPtr;
+#[cfg(feature = "portable-atomic")]
+pub(crate) use portable_atomic::AtomicUsize;
+#[cfg(feature = "portable-atomic")]
+pub(crate) use portable_atomic::{AtomicBool, Ordering};
+#[cfg(feature = "portable-atomic")]
+pub(crate) use portable_atomic_util::{Arc, Weak};
@@ -0,0 +1,79 @@
+//! # Tagged Values
+//!
+//! Interning a value together with a tag, such as the type of a literal or the scope of a name,
+//! keeps equal values with different tags apart without adding the tag to the value type. The
+//! tag takes part in the identity of the value, and handles still dereference to the value.
+
+use crate::{single_threaded, thread_safe};
+use std::hash::Hash;
+
+/// # `Tagged<K, T>`
+/// A value interned together with a tag. Created by `HcTable::hashcons_tagged()`.
+///
+/// Two tagged values are equal when both their tags and their values are. A `Tagged<K, T>`
+/// dereferences to its value, so handles to it can be used much like handles to `T`.
+///
+/// ## Example
+/// ```
+/// use hash_cons::HcTable;
+///
+/// #[derive(Hash, PartialEq, Eq, Debug)]
+/// enum Ty {
+///     I32,
+///     I64,
+/// }
+///
+/// let table = HcTable::new();
+/// let small = table.hashcons_tagged(Ty::I32, 1u64);
+/// let large = table.hashcons_tagged(Ty::I64, 1u64);
+///
+/// assert_ne!(small, large);
+/// assert_eq!(small.tag, Ty::I32);
+/// assert_eq!(**small, **large);
+/// assert_eq!(small.count_ones(), 1);
+/// ```
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Tagged<K, T> {
+    /// The tag telling the value apart from equal values with other tags.
+    pub tag: K,
+    /// The interned value.
+    pub value: T,
+}
+
+impl<K, T> std::ops::Deref for Tagged<K, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<K, T> AsRef<T> for Tagged<K, T> {
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<K, T, M> thread_safe::HcTable<Tagged<K, T>, M>
+where
+    K: Hash + Eq,
+    T: Hash + Eq,
+{
+    /// Returns the handle of `value` under `tag`. Equal values under different tags are
+    /// interned separately.
+    pub fn hashcons_tagged(&self, tag: K, value: T) -> thread_safe::Hc<Tagged<K, T>, M> {
+        self.hashcons(Tagged { tag, value })
+    }
+}
+
+impl<K, T, M> single_threaded::HcTable<Tagged<K, T>, M>
+where
+    K: Hash + Eq,
+    T: Hash + Eq,
+{
+    /// Returns the handle of `value` under `tag`. Equal values under different tags are
+    /// interned separately.
+    pub fn hashcons_tagged(&self, tag: K, value: T) -> single_threaded::Hc<Tagged<K, T>, M> {
+        self.hashcons(Tagged { tag, value })
+    }
+}
@@ -0,0 +1,502 @@
+//! # Testing Utilities
+//!
+//! Code built around an interner has error paths that a real table rarely takes: a table poisoned
+//! by a panic on another thread, or one that ran out of room. [`MockTable`] is a deterministic
+//! thread-safe table that takes these paths on demand, so that downstream unit tests can cover
+//! them.
+//!
+//! Passes over hash consed terms are best exercised on DAGs that share like real ones.
+//! [`DagBuilder`] generates random DAGs with a given shape into a table, and reports what it
+//! generated, to check the results of a pass against.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::testing::{Failure, Injected, MockTable};
+//!
+//! let table = MockTable::new();
+//! table.fail_next(Failure::Poisoned);
+//!
+//! assert!(matches!(table.try_hashcons(1u32), Err(Injected::Poisoned(1))));
+//! let one = table.try_hashcons(1u32).unwrap();
+//! assert_eq!(table.id(&one), 0);
+//! ```
+
+use crate::backend::ConsTable;
+use crate::dag::HashConsed;
+use crate::thread_safe::{Hc, HcTable};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// # `Failure`
+/// A failure a [`MockTable`] is told to inject into an upcoming interning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The lock of the table is poisoned, as if a panic interrupted another thread holding it.
+    Poisoned,
+    /// The table is full and cannot store the value.
+    Full,
+}
+
+/// # `Injected<T>`
+/// The error returned by `MockTable::try_hashcons()` for an injected failure, holding the value
+/// that was not interned.
+pub enum Injected<T> {
+    /// The injected `Failure::Poisoned`.
+    Poisoned(T),
+    /// The injected `Failure::Full`, or the capacity of the table was reached.
+    Full(T),
+}
+
+impl<T> Injected<T> {
+    /// Returns the failure that was injected.
+    pub fn failure(&self) -> Failure {
+        match self {
+            Injected::Poisoned(_) => Failure::Poisoned,
+            Injected::Full(_) => Failure::Full,
+        }
+    }
+
+    /// Returns the value that was not interned.
+    pub fn into_inner(self) -> T {
+        match self {
+            Injected::Poisoned(value) | Injected::Full(value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Injected<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Injected::Poisoned(_) => f.write_str("Poisoned(..)"),
+            Injected::Full(_) => f.write_str("Full(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for Injected<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Injected::Poisoned(_) => f.write_str("the hash consing table is poisoned (injected)"),
+            Injected::Full(_) => f.write_str("the hash consing table is full (injected)"),
+        }
+    }
+}
+
+impl<T> std::error::Error for Injected<T> {}
+
+/// # `MockTable<T>`
+/// A deterministic thread-safe table for tests, which fails interning on demand.
+///
+/// The table is seeded with a fixed seed and assigns sequential ids starting at `0`, so runs of a
+/// test see the same ids and the same order of entries. Its handles are those of `HcTable`, so
+/// code under test works on them unchanged.
+///
+/// ## Fields
+/// * `table`: The table storing the values.
+/// * `capacity`: The number of values after which storing another one fails, if any.
+/// * `failures`: The failures to inject, in the order of the upcoming internings.
+/// * `calls`: The number of internings so far, including failed ones.
+pub struct MockTable<T>
+where
+    T: Hash + Eq,
+{
+    table: HcTable<T>,
+    capacity: Option<usize>,
+    failures: Mutex<VecDeque<Failure>>,
+    calls: AtomicUsize,
+}
+
+impl<T> MockTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty `MockTable` without a capacity.
+    pub fn new() -> Self {
+        MockTable {
+            table: HcTable::builder().seed(0).build(),
+            capacity: None,
+            failures: Mutex::new(VecDeque::new()),
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates an empty `MockTable` failing with `Injected::Full` once `capacity` values are
+    /// stored. Values that are stored already are still found.
+    pub fn with_capacity(capacity: usize) -> Self {
+        MockTable {
+            capacity: Some(capacity),
+            ..Self::new()
+        }
+    }
+
+    /// Makes an upcoming interning fail with `failure`. Failures queued by several calls are
+    /// injected one per interning, in the order they were queued.
+    pub fn fail_next(&self, failure: Failure) {
+        self.failures().push_back(failure);
+    }
+
+    /// Returns the handle of `value`, or the injected failure.
+    pub fn try_hashcons(&self, value: T) -> Result<Hc<T>, Injected<T>> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        match self.failures().pop_front() {
+            Some(Failure::Poisoned) => return Err(Injected::Poisoned(value)),
+            Some(Failure::Full) => return Err(Injected::Full(value)),
+            None => {}
+        }
+        if let Some(capacity) = self.capacity {
+            let stored = self.table.get_many_by_key(&[&value])[0].is_some();
+            if !stored && self.table.len() >= capacity {
+                return Err(Injected::Full(value));
+            }
+        }
+        Ok(self.table.hashcons(value))
+    }
+
+    /// Returns the handle of `value`.
+    ///
+    /// ## Panics
+    /// Panics with the injected failure, like a table whose `PoisonPolicy` is `Panic`.
+    pub fn hashcons(&self, value: T) -> Hc<T> {
+        self.try_hashcons(value)
+            .unwrap_or_else(|failure| panic!("hash_cons: {failure}"))
+    }
+
+    /// Returns the id the table assigned to `hc`.
+    ///
+    /// ## Panics
+    /// Panics if `hc` was not interned by this table.
+    pub fn id(&self, hc: &Hc<T>) -> u64 {
+        self.table
+            .entry_id(hc)
+            .expect("the handle belongs to the mock table")
+    }
+
+    /// Returns the number of internings so far, including those that failed.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of stored values.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if no values are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the table storing the values, to inspect it.
+    pub fn table(&self) -> &HcTable<T> {
+        &self.table
+    }
+
+    /// Acquires the queued failures, continuing with a poisoned lock, as a test that panicked
+    /// while queueing leaves the queue consistent.
+    fn failures(&self) -> MutexGuard<'_, VecDeque<Failure>> {
+        self.failures
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> Default for MockTable<T>
+where
+    T: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for MockTable<T>
+where
+    T: Hash + Eq,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockTable")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .field("pending_failures", &self.failures().len())
+            .finish()
+    }
+}
+
+impl<T> ConsTable<T> for MockTable<T>
+where
+    T: Hash + Eq,
+{
+    type Handle = Hc<T>;
+
+    fn hashcons(&self, value: T) -> Self::Handle {
+        MockTable::hashcons(self, value)
+    }
+
+    fn len(&self) -> usize {
+        MockTable::len(self)
+    }
+}
+
+/// # `RandomNode`
+/// A node of a DAG generated by a [`DagBuilder`].
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub enum RandomNode {
+    /// A leaf with its label.
+    Leaf(u32),
+    /// An inner node with its label and children.
+    Branch(u32, Vec<crate::Hc<RandomNode>>),
+}
+
+impl HashConsed for RandomNode {
+    fn children(&self) -> Vec<&crate::Hc<RandomNode>> {
+        match self {
+            RandomNode::Leaf(_) => vec![],
+            RandomNode::Branch(_, children) => children.iter().collect(),
+        }
+    }
+}
+
+/// # `DagStats`
+/// What a [`DagBuilder`] generated, counted while generating.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DagStats {
+    /// The number of distinct nodes of the DAG.
+    pub nodes: usize,
+    /// The number of distinct leaves of the DAG.
+    pub leaves: usize,
+    /// The number of edges between distinct nodes, i.e. the sum of their numbers of children.
+    pub edges: usize,
+    /// The number of children that reuse a node generated before instead of a new one.
+    pub shared_children: usize,
+    /// The number of nodes of the DAG unfolded into a tree, saturating at `u128::MAX`.
+    pub tree_size: u128,
+}
+
+/// # `DagBuilder`
+/// Generates random DAGs into a table, e.g. to benchmark passes over shared terms.
+///
+/// A DAG is generated from its root down. Every inner node gets between one and `arity`
+/// children, and every child is, with probability `sharing`, a node of the same depth generated
+/// before, or a new one otherwise. All paths from the root to a leaf have length `depth`. The
+/// generator is seeded, so a builder generates the same DAG on every run.
+///
+/// ## Note
+/// Interning a node hashes its children by value, so generating a DAG takes time in the size of
+/// its tree, see `DagStats::tree_size`, rather than its number of nodes.
+///
+/// ## Example
+/// ```
+/// use hash_cons::testing::DagBuilder;
+/// use hash_cons::HcTable;
+///
+/// let table = HcTable::new();
+/// let (root, stats) = DagBuilder::new().depth(6).arity(3).sharing(0.5).build(&table);
+///
+/// assert_eq!(table.len(), stats.nodes);
+/// assert!(stats.tree_size > stats.nodes as u128);
+/// # drop(root);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DagBuilder {
+    depth: usize,
+    arity: usize,
+    sharing: f64,
+    labels: u32,
+    leaves: u32,
+    seed: u64,
+}
+
+impl DagBuilder {
+    /// Creates a builder of binary DAGs of depth 4, sharing half of the children, with 4 labels
+    /// of inner nodes and 16 of leaves.
+    pub fn new() -> Self {
+        DagBuilder {
+            depth: 4,
+            arity: 2,
+            sharing: 0.5,
+            labels: 4,
+            leaves: 16,
+            seed: 0,
+        }
+    }
+
+    /// Sets the length of the paths from the root to the leaves.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Sets the largest number of children of an inner node.
+    ///
+    /// ## Panics
+    /// Panics if `arity` is `0`.
+    pub fn arity(mut self, arity: usize) -> Self {
+        assert!(arity > 0, "inner nodes have at least one child");
+        self.arity = arity;
+        self
+    }
+
+    /// Sets the probability with which a child reuses a node generated before.
+    ///
+    /// ## Panics
+    /// Panics if `sharing` is not between `0.0` and `1.0`.
+    pub fn sharing(mut self, sharing: f64) -> Self {
+        assert!((0.0..=1.0).contains(&sharing), "sharing is a probability");
+        self.sharing = sharing;
+        self
+    }
+
+    /// Sets the number of distinct labels of inner nodes and of leaves.
+    ///
+    /// ## Panics
+    /// Panics if either is `0`.
+    pub fn labels(mut self, labels: u32, leaves: u32) -> Self {
+        assert!(labels > 0 && leaves > 0, "nodes need at least one label");
+        self.labels = labels;
+        self.leaves = leaves;
+        self
+    }
+
+    /// Sets the seed of the generator.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Generates a DAG into `table`.
+    ///
+    /// ## Returns
+    /// The root of the DAG, and what was generated.
+    pub fn build(&self, table: &crate::HcTable<RandomNode>) -> (crate::Hc<RandomNode>, DagStats) {
+        let mut rng = SplitMix(self.seed);
+        let mut dag = Generated {
+            table,
+            stats: DagStats::default(),
+            by_depth: vec![Vec::new(); self.depth + 1],
+            tree_sizes: HashMap::new(),
+        };
+        if self.depth == 0 {
+            let leaf = dag.intern(RandomNode::Leaf(rng.below(self.leaves)), 0);
+            return dag.finish(leaf);
+        }
+
+        // Every frame holds an inner node being generated: its depth, label, number of children
+        // and the children generated so far.
+        let mut stack = vec![self.frame(self.depth, &mut rng)];
+        loop {
+            let (depth, _, arity, children) = stack.last_mut().expect("the root is generated");
+            if children.len() < *arity {
+                let child_depth = *depth - 1;
+                let shareable = &dag.by_depth[child_depth];
+                if !shareable.is_empty() && rng.chance(self.sharing) {
+                    children.push(shareable[rng.below(shareable.len() as u32) as usize].clone());
+                    dag.stats.shared_children += 1;
+                } else if child_depth == 0 {
+                    children.push(dag.intern(RandomNode::Leaf(rng.below(self.leaves)), 0));
+                } else {
+                    let frame = self.frame(child_depth, &mut rng);
+                    stack.push(frame);
+                }
+                continue;
+            }
+
+            let (depth, label, _, children) = stack.pop().expect("the frame is on the stack");
+            let node = dag.intern(RandomNode::Branch(label, children), depth);
+            match stack.last_mut() {
+                Some((_, _, _, siblings)) => siblings.push(node),
+                None => return dag.finish(node),
+            }
+        }
+    }
+
+    /// Draws the label and number of children of an inner node at `depth`.
+    fn frame(&self, depth: usize, rng: &mut SplitMix) -> Frame {
+        let arity = 1 + rng.below(self.arity as u32) as usize;
+        (
+            depth,
+            rng.below(self.labels),
+            arity,
+            Vec::with_capacity(arity),
+        )
+    }
+}
+
+/// An inner node being generated: its depth, label, number of children and the children
+/// generated so far.
+type Frame = (usize, u32, usize, Vec<crate::Hc<RandomNode>>);
+
+/// # `Generated<'a>`
+/// The nodes a `DagBuilder` generated so far.
+///
+/// ## Fields
+/// * `table`: The table the nodes are interned in.
+/// * `stats`: What was generated so far.
+/// * `by_depth`: The generated nodes by depth, to share.
+/// * `tree_sizes`: The tree sizes of the distinct nodes, by address.
+struct Generated<'a> {
+    table: &'a crate::HcTable<RandomNode>,
+    stats: DagStats,
+    by_depth: Vec<Vec<crate::Hc<RandomNode>>>,
+    tree_sizes: HashMap<usize, u128>,
+}
+
+impl Generated<'_> {
+    /// Interns `node` at `depth`, counting it if it is new.
+    fn intern(&mut self, node: RandomNode, depth: usize) -> crate::Hc<RandomNode> {
+        let hc = self.table.hashcons(node);
+        if !self.tree_sizes.contains_key(&hc.addr()) {
+            let children = hc.children();
+            let tree_size = children.iter().fold(1u128, |size, child| {
+                size.saturating_add(self.tree_sizes[&child.addr()])
+            });
+            self.tree_sizes.insert(hc.addr(), tree_size);
+            self.stats.nodes += 1;
+            self.stats.edges += children.len();
+            self.stats.leaves += usize::from(children.is_empty());
+        }
+        self.by_depth[depth].push(hc.clone());
+        hc
+    }
+
+    /// Returns `root` and the statistics of the DAG below it.
+    fn finish(self, root: crate::Hc<RandomNode>) -> (crate::Hc<RandomNode>, DagStats) {
+        let stats = DagStats {
+            tree_size: self.tree_sizes[&root.addr()],
+            ..self.stats
+        };
+        (root, stats)
+    }
+}
+
+impl Default for DagBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A small seeded generator of random numbers, see <https://prng.di.unimi.it/splitmix64.c>.
+struct SplitMix(u64);
+
+impl SplitMix {
+    /// Returns the next random number.
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number below `bound`.
+    fn below(&mut self, bound: u32) -> u32 {
+        (((self.next() >> 32) * u64::from(bound)) >> 32) as u32
+    }
+
+    /// Returns `true` with probability `probability`.
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next() >> 11) as f64) < probability * (1u64 << 53) as f64
+    }
+}
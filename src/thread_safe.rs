@@ -1,17 +1,60 @@
-#[cfg(not(feature = "single-threaded"))]
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::sync::{Arc, RwLock, Weak};
+use crate::buckets::Buckets;
+#[cfg(not(feature = "auto-cleanup"))]
+use crate::buckets::Entry;
+use crate::hasher::{HashState, IdentityHasher};
+#[cfg(feature = "history")]
+use crate::history::HistoryEvent;
+use crate::inflight::{InFlight, Lookup};
+#[cfg(feature = "leak-check")]
+use crate::leak::LeakReport;
+use crate::mem::{MemSize, MemoryUsage};
+use crate::pressure::TryRelease;
+#[cfg(feature = "auto-cleanup")]
+use crate::retire::RetireQueue;
+use crate::single_threaded;
+#[cfg(not(feature = "auto-cleanup"))]
+use crate::stats::CleanupReport;
+use crate::stats::{HashDiagnostics, TableStats};
+#[cfg(not(feature = "auto-cleanup"))]
+use crate::strategy::EvictionOrder;
+use crate::strategy::{
+    message_prefix, Canonical, ConsEq, ConsHash, ConsNormalize, ConsWeight, DefaultEq, DefaultHash,
+    IdAllocator, Identity, PoisonPolicy, SequentialIds, Structural,
+};
+use crate::sync::{Arc, AtomicBool, AtomicUsize, Ordering, Weak};
+use crate::timeout::Timeout;
+use crate::token::{next_table_id, HcToken};
+#[cfg(not(feature = "portable-atomic"))]
+use std::any::Any;
+use std::borrow::{Borrow, Cow};
+#[cfg(not(feature = "auto-cleanup"))]
+use std::collections::HashSet;
+use std::collections::{HashMap, TryReserveError};
+use std::convert::Infallible;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::{size_of, ManuallyDrop};
+use std::ptr::NonNull;
+use std::sync::{
+    LockResult, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError,
+    TryLockResult,
+};
+use std::time::{Duration, Instant};
+
+/// The number of retired entries after which the dropping handle collects them.
+#[cfg(feature = "auto-cleanup")]
+const RETIRED_BATCH: usize = 1024;
 
 /// # `Hc<T>`
 /// A thread-safe custom smart pointer type for managing the lifecycle of consed values.
 ///
 /// ## Type Parameters
-/// * `T` - The type of values managed by this smart pointer. Must implement `Hash` and `Eq`.
+/// * `T` - The type of values managed by this smart pointer.
+/// * `M` - Whether handles compare by value, [`Structural`], or by address, [`Identity`].
 ///
 /// ## Fields
 /// * `inner`: `Arc<Inner<T>>` - Atomically reference counted pointer to the inner value.
+/// * `_semantics`: The marker of `M`.
 ///
 /// ## Example
 /// ```
@@ -23,23 +66,27 @@ use std::sync::{Arc, RwLock, Weak};
 ///
 /// assert_eq!(*hc_pointer.get(), 42);
 /// ```
-pub struct Hc<T>
-where
-    T: Hash + Eq,
-{
+pub struct Hc<T, M = Structural> {
     // This is the reference to the underlying value.
     inner: Arc<Inner<T>>,
+    _semantics: PhantomData<fn() -> M>,
 }
 
 // Implementing the traits for the custom smart pointer type.
-impl<T> Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> Hc<T, M> {
+    /// Wraps the allocation of a stored value into a handle.
+    fn from_inner(inner: Arc<Inner<T>>) -> Self {
+        Hc {
+            inner,
+            _semantics: PhantomData,
+        }
+    }
+
     /// Retrieves a reference to the value stored in this `Hc<T>`.
     ///
     /// ## Returns
-    /// A reference to the stored value.
+    /// A reference to the stored value. The value is shared by every handle to it and cannot
+    /// be mutated, use `make_owned` to intern a modified copy instead.
     ///
     /// ## Example
     /// ```
@@ -54,12 +101,194 @@ where
     pub fn get(&self) -> &T {
         &self.inner.elem
     }
+
+    /// Copies the value out of this `Hc<T>`, so that a modified version can be interned.
+    ///
+    /// Interned values are shared and cannot be mutated through a handle. To change one, copy
+    /// it out, modify the copy and intern it again, which yields a handle to the modified value
+    /// while every existing handle keeps the original.
+    ///
+    /// ## Returns
+    /// A clone of the stored value, owned by the caller.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let original = table.hashcons(vec![1, 2]);
+    ///
+    /// let mut owned = original.make_owned();
+    /// owned.push(3);
+    /// let modified = table.hashcons(owned);
+    ///
+    /// assert_eq!(*original, vec![1, 2]);
+    /// assert_eq!(*modified, vec![1, 2, 3]);
+    /// ```
+    pub fn make_owned(&self) -> T
+    where
+        T: Clone,
+    {
+        T::clone(&self.inner.elem)
+    }
+
+    /// Returns the generation of the table in which the value was stored, see
+    /// `HcTable::advance_generation()`.
+    ///
+    /// Handles of values interned again in later generations keep the generation in which the
+    /// value was first stored, which tells values created in the current phase of a pipeline
+    /// apart from values cached by earlier ones.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let cached = table.hashcons(1);
+    /// table.advance_generation();
+    /// let fresh = table.hashcons(2);
+    ///
+    /// assert_eq!(table.hashcons(1).birth_generation(), 0);
+    /// assert_eq!(fresh.birth_generation(), 1);
+    /// ```
+    pub fn birth_generation(&self) -> u64 {
+        self.inner.generation
+    }
+
+    /// Returns a pointer to the interned value.
+    ///
+    /// The address stays the same for as long as the value is interned, and all handles of a
+    /// table to equal values share it, so it can identify nodes in logs or key external data
+    /// structures. Formatting a handle with `{:p}` prints it.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(5);
+    ///
+    /// assert_eq!(value.as_ptr(), table.hashcons(5).as_ptr());
+    /// assert_eq!(format!("{value:p}"), format!("{:p}", value.as_ptr()));
+    /// ```
+    pub fn as_ptr(&self) -> *const T {
+        Arc::as_ptr(&self.inner.elem)
+    }
+
+    /// Returns the hash the table stored the value under, computed once when it was interned.
+    ///
+    /// Structures keyed by interned values, such as bloom filters or hash-partitioned queues,
+    /// can use it instead of hashing the value again. It is the hash of the table, not the one
+    /// of `T::hash`: tables are seeded randomly unless built with `HcTableBuilder::seed()`, so
+    /// only handles of one table agree on it. In trusted-hash mode it is the lower half of the
+    /// 128-bit hash.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let term = table.hashcons("f(x)".to_string());
+    /// let partition = term.hash_value() % 8;
+    ///
+    /// assert_eq!(table.hashcons("f(x)".to_string()).hash_value() % 8, partition);
+    /// ```
+    pub fn hash_value(&self) -> u64 {
+        self.inner.hash as u64
+    }
+
+    /// Returns a token naming the value of this handle, which can be sent to other threads
+    /// without touching the reference count and resolved with `HcTable::resolve`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(7);
+    /// let token = value.token();
+    ///
+    /// assert_eq!(table.resolve(token), Some(value));
+    /// assert_eq!(HcTable::<i32>::new().resolve(token), None);
+    /// ```
+    pub fn token(&self) -> HcToken {
+        HcToken {
+            table: self.inner._table.id,
+            seq: self.inner.seq,
+            hash: self.inner.hash,
+        }
+    }
+
+    /// Returns the sequence number of the entry of the value, which is unique within the table
+    /// and increases in storing order.
+    pub(crate) fn seq(&self) -> u64 {
+        self.inner.seq
+    }
+
+    /// Returns the address of the shared allocation backing this handle.
+    /// Two handles from the same table compare equal exactly when their addresses match.
+    pub(crate) fn addr(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+
+    #[cfg(feature = "tiered")]
+    /// Returns the number of handles to the value, including this one.
+    pub(crate) fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+
+    #[cfg(not(feature = "portable-atomic"))]
+    /// Converts this handle into a trait-object handle, so that values of different types can be
+    /// stored behind a common trait.
+    ///
+    /// The value is not copied: the returned `HcDyn<U>` points at the same allocation as this
+    /// handle and keeps the table entry alive for as long as it exists.
+    ///
+    /// ## Parameters
+    /// * `coerce`: Performs the unsizing coercion, usually written as `|value| value`.
+    ///   It must return the pointer it was given. The target type can be named with a turbofish,
+    ///   as in `hc.into_dyn::<dyn Trait>(|value| value)`.
+    ///
+    /// ## Returns
+    /// A `HcDyn<U>` sharing the value of this handle.
+    ///
+    /// ## Panics
+    /// If `coerce` returns a different allocation than the one it was given.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::{HcDyn, HcTable};
+    /// use std::fmt::Debug;
+    ///
+    /// let ints = HcTable::new();
+    /// let strs = HcTable::new();
+    ///
+    /// let nodes: Vec<HcDyn<dyn Debug + Send + Sync>> = vec![
+    ///     ints.hashcons(5).into_dyn::<dyn Debug + Send + Sync>(|value| value),
+    ///     strs.hashcons("five").into_dyn::<dyn Debug + Send + Sync>(|value| value),
+    /// ];
+    ///
+    /// assert_eq!(format!("{:?}", nodes[1]), "\"five\"");
+    /// ```
+    pub fn into_dyn<U>(self, coerce: impl FnOnce(Arc<T>) -> Arc<U>) -> HcDyn<U>
+    where
+        T: Send + Sync + 'static,
+        U: ?Sized,
+    {
+        let before = Arc::as_ptr(&self.inner.elem);
+        let value = coerce(self.inner.elem.clone());
+        assert!(
+            std::ptr::addr_eq(before, Arc::as_ptr(&value)),
+            "`coerce` must return the pointer it was given"
+        );
+        HcDyn {
+            value,
+            _handle: self.inner,
+        }
+    }
 }
 
-impl<T: PartialEq> PartialEq for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: PartialEq> PartialEq for Hc<T> {
     /// Provides the functionality to compare two `Hc<T>` instances for equality.
     ///
     /// ## Parameters
@@ -85,12 +314,9 @@ where
     }
 }
 
-impl<T> Eq for Hc<T> where T: Hash + Eq {}
+impl<T: Eq> Eq for Hc<T> {}
 
-impl<T> Hash for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: Hash> Hash for Hc<T> {
     /// Provides the functionality to hash `Hc<T>` instances.
     /// This method is useful for storing `Hc<T>` instances in a `HashMap`.
     /// It is also used internally by the `HcTable` to manage the storage of
@@ -117,10 +343,7 @@ where
     }
 }
 
-impl<T> Clone for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> Clone for Hc<T, M> {
     /// Provides the functionality to clone `Hc<T>` instances.
     ///
     /// ## Returns
@@ -146,16 +369,11 @@ where
     /// to retrieve a reference to the value and clone it.
     ///
     fn clone(&self) -> Self {
-        Hc {
-            inner: self.inner.clone(),
-        }
+        Hc::from_inner(self.inner.clone())
     }
 }
 
-impl<T: std::fmt::Debug> std::fmt::Debug for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: std::fmt::Debug, M> std::fmt::Debug for Hc<T, M> {
     /// Provides the functionality to print `Hc<T>` instances.
     /// This method is useful for debugging.
     /// ## Parameters
@@ -176,10 +394,7 @@ where
     }
 }
 
-impl<T: std::fmt::Display> std::fmt::Display for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: std::fmt::Display, M> std::fmt::Display for Hc<T, M> {
     /// Provides the functionality to print `Hc<T>` instances.
     /// This method is useful for debugging.
     ///
@@ -201,10 +416,14 @@ where
     }
 }
 
-impl<T> std::ops::Deref for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> std::fmt::Pointer for Hc<T, M> {
+    /// Formats the address of the interned value, see `as_ptr`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Pointer::fmt(&self.as_ptr(), f)
+    }
+}
+
+impl<T, M> std::ops::Deref for Hc<T, M> {
     type Target = T;
 
     /// Provides the functionality to dereference `Hc<T>` instances.
@@ -229,16 +448,12 @@ where
     /// If you need to dereference the underlying value, you can use the `get()` method
     /// to retrieve a reference to the value.
     ///
-
     fn deref(&self) -> &Self::Target {
         &self.inner.elem
     }
 }
 
-impl<T> AsRef<T> for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T, M> AsRef<T> for Hc<T, M> {
     /// Provides the functionality to convert `Hc<T>` instances to references.
     /// This method is useful for accessing the underlying value.
     ///
@@ -268,10 +483,7 @@ where
     }
 }
 
-impl<T: PartialOrd> PartialOrd for Hc<T>
-where
-    T: Hash + Eq,
-{
+impl<T: PartialOrd> PartialOrd for Hc<T> {
     /// Provides the functionality to compare two `Hc<T>` instances.
     /// This method is useful for sorting `Hc<T>` instances.
     /// ## Parameters
@@ -300,10 +512,7 @@ where
     }
 }
 
-impl<T> Ord for Hc<T>
-where
-    T: Ord + Hash + Eq,
-{
+impl<T: Ord> Ord for Hc<T> {
     /// Provides the functionality to compare two `Hc<T>` instances.
     /// This method is useful for sorting `Hc<T>` instances.
     /// ## Parameters
@@ -332,187 +541,3753 @@ where
     }
 }
 
-///  # `HcTable<T>`
-/// A table structure for efficiently managing `Hc<T>` instances.
-/// This struct hides the underlying table and its reference count management.
+impl<T> PartialEq for Hc<T, Identity> {
+    /// Compares two handles of a table built with `HcTableBuilder::identity()` by address.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::builder().identity().build();
+    /// let value1 = table.hashcons(5);
+    /// let value2 = table.hashcons(5);
+    ///
+    /// assert_eq!(value1, value2);
+    /// assert_ne!(value1, HcTable::builder().identity().build().hashcons(5));
+    /// ```
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T> Eq for Hc<T, Identity> {}
+
+impl<T> Hash for Hc<T, Identity> {
+    /// Hashes the address of the value, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
+    }
+}
+
+#[cfg(not(feature = "portable-atomic"))]
+/// # `HcDyn<U>`
+/// A thread-safe handle to a hash consed value viewed through an unsized type, usually a trait
+/// object. Created with [`Hc::into_dyn`].
 ///
-/// This structure utilizes a HashMap to store `Hc<T>` instances, offering
-/// quick retrieval and management capabilities.
+/// Handles are compared and hashed by the address of the shared value. Since a table stores
+/// each distinct value once, two handles created from the same table are equal exactly when
+/// their values are.
+///
+/// Not available with the `portable-atomic` feature, whose `Arc` cannot be coerced to an
+/// unsized type on stable Rust.
 ///
 /// ## Type Parameters
-/// * `T` - The type of values managed by the `Hc<T>` instances within this table.
+/// * `U` - The type the value is viewed as, e.g. `dyn Trait + Send + Sync`.
 ///
 /// ## Fields
-/// * `inner`: HashMap - The underlying data structure storing `Hc<T>` instances.
+/// * `value`: `Arc<U>` - The shared value.
+/// * `_handle`: The type-erased `Hc<T>` allocation that keeps the table entry alive.
 ///
-pub struct HcTable<T>
+pub struct HcDyn<U>
 where
-    T: Hash + Eq,
+    U: ?Sized,
 {
-    inner: Arc<InnerTable<T>>,
+    value: Arc<U>,
+
+    _handle: Arc<dyn Any + Send + Sync>,
 }
 
-// Implementing the traits for the custom smart pointer type.
-impl<T> HcTable<T>
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> HcDyn<U>
 where
-    T: Hash + Eq,
+    U: ?Sized,
 {
-    /// Creates a new `HcTable`.
+    /// Retrieves a reference to the value stored in this `HcDyn<U>`.
     ///
     /// ## Returns
-    /// A new instance of `HcTable<T>`.
-    ///
-    /// ## Example
-    /// ```
-    /// use hash_cons::HcTable;
-    ///
-    /// let table: HcTable<i32> = HcTable::new();
-    /// ```
-    pub fn new() -> Self {
-        HcTable {
-            inner: Arc::new(InnerTable::new()),
-        }
+    /// A reference to the stored value.
+    pub fn get(&self) -> &U {
+        &self.value
     }
 
-    /// Simplifies object retrieval or creation with an intuitive interface.
-    ///
-    /// ## Parameters
-    /// * `value`: The value to be managed.
-    ///
-    /// ## Returns
-    /// A `Hc<T>` instance corresponding to the given value.
-    ///
-    /// ## Example
-    /// ```
-    /// use hash_cons::HcTable;
-    ///
-    /// let table = HcTable::new();
-    /// let value = table.hashcons(5);
-    /// ```
-    ///
-    pub fn hashcons(&self, value: T) -> Hc<T> {
-        Hc {
-            inner: self.intern(value),
-        }
+    /// Returns the address of the shared value, ignoring any pointer metadata.
+    fn addr(&self) -> usize {
+        Arc::as_ptr(&self.value) as *const () as usize
     }
+}
 
-    /// Internal method to manage the storage of values in `HcTable`.
-    /// It ensures that each value is stored only once, providing a shared
-    /// reference to the stored value.
-    ///
-    /// ## Parameters
-    /// * `value`: The value to be stored or retrieved.
-    ///
-    /// ## Returns
-    /// A `Arc<Inner<T>>` pointer to the stored value.
-    ///
-    ///
-    fn intern(&self, value: T) -> Arc<Inner<T>> {
-        let arc_table = self.inner.clone();
-        let arc_table_dup = arc_table.clone();
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> PartialEq for HcDyn<U>
+where
+    U: ?Sized,
+{
+    /// Compares two `HcDyn<U>` instances by the address of their values.
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
 
-        let mut_table_result = arc_table_dup.table.write();
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> Eq for HcDyn<U> where U: ?Sized {}
 
-        let mut mut_table = match mut_table_result {
-            Ok(guard) => guard,
-            Err(poisoned) => {
-                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
-                poisoned.into_inner() // continues, because we will add a new value
-            }
-        };
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> Hash for HcDyn<U>
+where
+    U: ?Sized,
+{
+    /// Hashes the address of the value, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
+    }
+}
 
-        let rc_value = Arc::new(value);
-        let rc_val_dup = rc_value.clone();
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> Clone for HcDyn<U>
+where
+    U: ?Sized,
+{
+    /// Creates a new `HcDyn<U>` pointing to the same value, without cloning the value.
+    fn clone(&self) -> Self {
+        HcDyn {
+            value: self.value.clone(),
+            _handle: self._handle.clone(),
+        }
+    }
+}
 
-        match mut_table.entry(rc_val_dup) {
-            Entry::Occupied(mut o) => {
-                let weak_hc = o.get();
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> std::fmt::Debug for HcDyn<U>
+where
+    U: std::fmt::Debug + ?Sized,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
 
-                if let Some(rc_hc) = weak_hc.upgrade() {
-                    return rc_hc;
-                }
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> std::fmt::Display for HcDyn<U>
+where
+    U: std::fmt::Display + ?Sized,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
 
-                let elem = rc_value;
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> std::ops::Deref for HcDyn<U>
+where
+    U: ?Sized,
+{
+    type Target = U;
 
-                let _table = arc_table;
-                let new_elem = Arc::new(Inner { elem, _table });
-                o.insert(Arc::downgrade(&new_elem));
-                new_elem
-            }
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
 
-            Entry::Vacant(v) => {
-                let _table = arc_table;
-                let elem = rc_value;
-                let new_elem = Arc::new(Inner { elem, _table });
-                v.insert(Arc::downgrade(&new_elem));
-                new_elem
-            }
-        }
+#[cfg(not(feature = "portable-atomic"))]
+impl<U> AsRef<U> for HcDyn<U>
+where
+    U: ?Sized,
+{
+    fn as_ref(&self) -> &U {
+        &self.value
     }
+}
 
-    #[cfg(not(feature = "auto-cleanup"))]
-    /// Cleans up the `HcTable`, removing any values that are no longer in use.
-    /// This method is useful for managing memory and ensuring that unused
-    /// values are not unnecessarily kept in the table.
-    ///
-    /// ## Example
-    /// ```
-    /// use hash_cons::HcTable;
-    ///
-    /// let table = HcTable::new();
+/// # `HcEdge<T>`
+/// A handle that carries a few attribute bits, such as the complement flag of the edges of
+/// binary decision diagrams or and-inverter graphs.
+///
+/// The bits are packed into the unused low bits of the pointer, so that an edge is as small as
+/// a handle. Edges to the same value with different bits share the value, which is what lets
+/// `HcTable::hashcons_edge()` store `Not(x)` as the complemented edge to `x`.
+///
+/// ## Type Parameters
+/// * `T` - The type of the value the edge points at.
+/// * `M` - The semantics of the handles of the table, see `HcTableBuilder::identity()`.
+///
+/// ## Fields
+/// * `tagged`: The pointer `Arc::into_raw` returned for the handle, with the attribute bits set.
+/// * `_handle`: The marker of the handle the edge owns.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{Canonical, HcEdge, HcTable};
+///
+/// const COMPLEMENT: u8 = 1;
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum Gate {
+///     Input(u32),
+///     Not(HcEdge<Gate>),
+///     And(HcEdge<Gate>, HcEdge<Gate>),
+/// }
+///
+/// fn canonical(gate: Gate) -> Canonical<Gate, HcEdge<Gate>> {
+///     match gate {
+///         Gate::Not(edge) => Canonical::Edge(edge.flip(COMPLEMENT)),
+///         gate => Canonical::Node(gate, 0),
+///     }
+/// }
+///
+/// let table = HcTable::new();
+/// let x = table.hashcons_edge(Gate::Input(0), canonical);
+/// let not_x = table.hashcons_edge(Gate::Not(x.clone()), canonical);
+///
+/// assert_eq!(not_x.attributes(), COMPLEMENT);
+/// assert!(not_x.handle() == x.handle());
+/// assert!(table.hashcons_edge(Gate::Not(not_x), canonical) == x);
+/// assert_eq!(table.len(), 1);
+/// ```
+pub struct HcEdge<T, M = Structural> {
+    tagged: NonNull<Inner<T>>,
+    _handle: PhantomData<Hc<T, M>>,
+}
+
+// The edge owns a strong count of its allocation, like the handle it was created from.
+unsafe impl<T, M> Send for HcEdge<T, M> where Hc<T, M>: Send {}
+unsafe impl<T, M> Sync for HcEdge<T, M> where Hc<T, M>: Sync {}
+
+impl<T, M> HcEdge<T, M> {
+    /// The attribute bits an edge can carry, which the alignment of the allocation leaves free.
+    pub const ATTRIBUTE_MASK: u8 = 0b11;
+
+    /// Creates an edge to the value of `hc` with the given attribute bits.
+    ///
+    /// ## Panics
+    /// If `attributes` has bits set outside of `ATTRIBUTE_MASK`.
+    pub fn new(hc: Hc<T, M>, attributes: u8) -> Self {
+        const { assert!(std::mem::align_of::<Inner<T>>() > Self::ATTRIBUTE_MASK as usize) };
+        assert!(
+            attributes & !Self::ATTRIBUTE_MASK == 0,
+            "attribute bits {attributes:#b} exceed the mask {:#b}",
+            Self::ATTRIBUTE_MASK
+        );
+        let raw = Arc::into_raw(hc.inner).cast_mut();
+        // SAFETY: `Arc::into_raw` does not return null, and the alignment asserted above keeps the
+        // attribute bits of the pointer clear.
+        let tagged =
+            unsafe { NonNull::new_unchecked(raw.map_addr(|addr| addr | usize::from(attributes))) };
+        HcEdge {
+            tagged,
+            _handle: PhantomData,
+        }
+    }
+
+    /// Returns the pointer to the allocation, without the attribute bits.
+    fn untagged(&self) -> *const Inner<T> {
+        self.tagged
+            .as_ptr()
+            .map_addr(|addr| addr & !usize::from(Self::ATTRIBUTE_MASK))
+    }
+
+    /// Returns the attribute bits of the edge.
+    pub fn attributes(&self) -> u8 {
+        (self.tagged.as_ptr().addr() & usize::from(Self::ATTRIBUTE_MASK)) as u8
+    }
+
+    /// Retrieves a reference to the value the edge points at, whatever its attribute bits.
+    pub fn get(&self) -> &T {
+        // SAFETY: the edge owns a strong count, which keeps the allocation alive.
+        unsafe { &(*self.untagged()).elem }
+    }
+
+    /// Returns a handle to the value the edge points at, dropping the attribute bits.
+    pub fn handle(&self) -> Hc<T, M> {
+        self.clone().into_handle()
+    }
+
+    /// Converts the edge into a handle to the value it points at, dropping the attribute bits.
+    pub fn into_handle(self) -> Hc<T, M> {
+        let edge = ManuallyDrop::new(self);
+        // SAFETY: the pointer was returned by `Arc::into_raw`, and the strong count the edge
+        // owned is handed to the handle.
+        Hc::from_inner(unsafe { Arc::from_raw(edge.untagged()) })
+    }
+
+    /// Returns the edge to the same value with the given attribute bits.
+    ///
+    /// ## Panics
+    /// If `attributes` has bits set outside of `ATTRIBUTE_MASK`.
+    pub fn with_attributes(self, attributes: u8) -> Self {
+        HcEdge::new(self.into_handle(), attributes)
+    }
+
+    /// Returns the edge to the same value with the given attribute bits toggled, e.g. the
+    /// complement of the edge.
+    ///
+    /// ## Panics
+    /// If `attributes` has bits set outside of `ATTRIBUTE_MASK`.
+    pub fn flip(self, attributes: u8) -> Self {
+        let toggled = self.attributes() ^ attributes;
+        self.with_attributes(toggled)
+    }
+}
+
+impl<T, M> From<Hc<T, M>> for HcEdge<T, M> {
+    /// Creates an edge to the value of `hc` without attribute bits.
+    fn from(hc: Hc<T, M>) -> Self {
+        HcEdge::new(hc, 0)
+    }
+}
+
+impl<T, M> Clone for HcEdge<T, M> {
+    /// Creates a new `HcEdge<T>` with the same value and attribute bits.
+    fn clone(&self) -> Self {
+        // SAFETY: the pointer was returned by `Arc::into_raw`, and the edge keeps it alive.
+        unsafe { Arc::increment_strong_count(self.untagged()) };
+        HcEdge {
+            tagged: self.tagged,
+            _handle: PhantomData,
+        }
+    }
+}
+
+impl<T, M> Drop for HcEdge<T, M> {
+    fn drop(&mut self) {
+        // SAFETY: the pointer was returned by `Arc::into_raw`, and the edge owns a strong count.
+        drop(unsafe { Arc::from_raw(self.untagged()) });
+    }
+}
+
+impl<T: PartialEq> PartialEq for HcEdge<T> {
+    /// Compares the attribute bits and the values of two edges.
+    fn eq(&self, other: &Self) -> bool {
+        self.attributes() == other.attributes() && self.get() == other.get()
+    }
+}
+
+impl<T: Eq> Eq for HcEdge<T> {}
+
+impl<T: Hash> Hash for HcEdge<T> {
+    /// Hashes the attribute bits and the value, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.attributes().hash(state);
+        self.get().hash(state);
+    }
+}
+
+impl<T> PartialEq for HcEdge<T, Identity> {
+    /// Compares the addresses and attribute bits of two edges.
+    fn eq(&self, other: &Self) -> bool {
+        self.tagged == other.tagged
+    }
+}
+
+impl<T> Eq for HcEdge<T, Identity> {}
+
+impl<T> Hash for HcEdge<T, Identity> {
+    /// Hashes the address and attribute bits, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tagged.hash(state);
+    }
+}
+
+impl<T: std::fmt::Debug, M> std::fmt::Debug for HcEdge<T, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HcEdge")
+            .field("value", self.get())
+            .field("attributes", &self.attributes())
+            .finish()
+    }
+}
+
+impl<T, M> std::ops::Deref for HcEdge<T, M> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T, M> AsRef<T> for HcEdge<T, M> {
+    fn as_ref(&self) -> &T {
+        self.get()
+    }
+}
+
+///  # `HcTable<T>`
+/// A table structure for efficiently managing `Hc<T>` instances.
+/// This struct hides the underlying table and its reference count management.
+///
+/// This structure utilizes a HashMap to store `Hc<T>` instances, offering
+/// quick retrieval and management capabilities.
+///
+/// ## Type Parameters
+/// * `T` - The type of values managed by the `Hc<T>` instances within this table.
+/// * `M` - The semantics of the handles of this table, see `HcTableBuilder::identity()`.
+///
+/// ## Fields
+/// * `inner`: HashMap - The underlying data structure storing `Hc<T>` instances.
+/// * `_semantics`: The marker of `M`.
+///
+pub struct HcTable<T, M = Structural> {
+    inner: Arc<InnerTable<T>>,
+    _semantics: PhantomData<fn() -> M>,
+}
+
+// Implementing the traits for the custom smart pointer type.
+impl<T> HcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates a new `HcTable`.
+    ///
+    /// ## Returns
+    /// A new instance of `HcTable<T>`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<i32> = HcTable::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Creates a builder for a `HcTable` with custom strategies.
+    ///
+    /// ## Returns
+    /// A `HcTableBuilder<T>` configured like `HcTable::new()`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    /// use std::hash::{Hash, Hasher};
+    ///
+    /// #[derive(PartialEq, Eq)]
+    /// struct Ident {
+    ///     name: String,
+    ///     line: u32,
+    /// }
+    ///
+    /// impl Hash for Ident {
+    ///     fn hash<H: Hasher>(&self, state: &mut H) {
+    ///         self.name.hash(state);
+    ///     }
+    /// }
+    ///
+    /// let table = HcTable::builder()
+    ///     .equivalence(|a: &Ident, b: &Ident| a.name == b.name)
+    ///     .build();
+    ///
+    /// let first = table.hashcons(Ident { name: "x".to_string(), line: 1 });
+    /// let second = table.hashcons(Ident { name: "x".to_string(), line: 7 });
+    ///
+    /// assert_eq!(second.line, 1);
+    /// ```
+    pub fn builder() -> HcTableBuilder<T> {
+        HcTableBuilder {
+            eq: Box::new(DefaultEq),
+            hash: Box::new(DefaultHash),
+            normalize: None,
+            weight: None,
+            custom_strategies: false,
+            trusted_hash: false,
+            prehashed: false,
+            seed: None,
+            #[cfg(feature = "auto-cleanup")]
+            deferred_reclamation: false,
+            collision_warning: None,
+            drift_check: None,
+            ids: Box::new(SequentialIds::default()),
+            stall: None,
+            on_evict: None,
+            name: None,
+            poison_policy: PoisonPolicy::default(),
+            _semantics: PhantomData,
+        }
+    }
+}
+
+impl<T, M> HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Simplifies object retrieval or creation with an intuitive interface.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be managed.
+    ///
+    /// ## Returns
+    /// A `Hc<T>` instance corresponding to the given value.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
     /// let value = table.hashcons(5);
+    /// ```
+    ///
+    /// ## Panics
+    /// Panics of the hash function, equivalence or normalization of the table, including `Hash`
+    /// and `Eq` implementations, are propagated. They happen before the table is changed, so it
+    /// stays usable and holds no entry for `value`.
+    ///
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        Hc::from_inner(self.intern(value))
+    }
+
+    /// Interns `value` as an edge, after `canonicalize` split it into the value to store and
+    /// the attribute bits of the edge pointing at it.
+    ///
+    /// Values that differ only in what the attribute bits encode then share their storage, e.g.
+    /// `Not(x)` canonicalizes to the complemented edge to `x` and stores nothing new.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be managed.
+    /// * `canonicalize`: Returns the value to store with the attribute bits of its edge, or an
+    ///   existing edge of this table that `value` is equivalent to.
+    ///
+    /// ## Returns
+    /// The edge to the canonical value.
+    ///
+    /// ## Panics
+    /// If `canonicalize` returns attribute bits outside of `HcEdge::ATTRIBUTE_MASK`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::{Canonical, HcTable};
+    ///
+    /// let table = HcTable::new();
+    /// let magnitude = |value: i32| Canonical::Node(value.abs(), u8::from(value < 0));
+    ///
+    /// let minus = table.hashcons_edge(-7, magnitude);
+    /// let plus = table.hashcons_edge(7, magnitude);
+    ///
+    /// assert_eq!((*minus, minus.attributes()), (7, 1));
+    /// assert!(minus.handle() == plus.handle());
+    /// assert_eq!(table.len(), 1);
+    /// ```
+    pub fn hashcons_edge(
+        &self,
+        value: T,
+        canonicalize: impl FnOnce(T) -> Canonical<T, HcEdge<T, M>>,
+    ) -> HcEdge<T, M> {
+        match canonicalize(value) {
+            Canonical::Node(value, attributes) => HcEdge::new(self.hashcons(value), attributes),
+            Canonical::Edge(edge) => edge,
+        }
+    }
+
+    /// Like `hashcons`, but reserves the room for a new entry in the map of the table with
+    /// `try_reserve`, and reports a failure to grow the map instead of aborting.
+    ///
+    /// This is not fully fallible interning: a new value and its handle are still allocated
+    /// with `Arc::new`, which aborts if the allocation fails.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be managed.
+    ///
+    /// ## Returns
+    /// A `Hc<T>` instance corresponding to the given value, or the allocation error if the map
+    /// could not make room for a new entry. The table is unchanged in that case.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons_try_reserve(5).expect("out of memory");
+    ///
+    /// assert_eq!(*value, 5);
+    /// ```
+    pub fn hashcons_try_reserve(&self, value: T) -> Result<Hc<T, M>, TryReserveError> {
+        let inner = self.intern_with(
+            value,
+            || Ok(self.inner.write()),
+            |table, hash| table.try_reserve_entry(hash),
+        )?;
+        Ok(Hc::from_inner(inner))
+    }
+
+    /// Like `hashcons`, but gives up if the lock of the table could not be acquired within
+    /// `timeout`, e.g. because another thread deadlocked while holding it.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be managed.
+    /// * `timeout`: How long to wait for the lock.
+    ///
+    /// ## Returns
+    /// A `Hc<T>` instance corresponding to the given value, or a `Timeout` if the lock stayed
+    /// taken. The table is unchanged in that case.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::thread_safe::HcTable;
+    /// use std::time::Duration;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons_timeout(5, Duration::from_millis(100)).unwrap();
+    ///
+    /// assert_eq!(value, table.hashcons(5));
+    /// ```
+    pub fn hashcons_timeout(&self, value: T, timeout: Duration) -> Result<Hc<T, M>, Timeout> {
+        let inner = self.intern_with(value, || self.inner.write_within(timeout), |_, _| Ok(()))?;
+        Ok(Hc::from_inner(inner))
+    }
+
+    /// Returns the handle of the stored value `key` borrows as, constructing the value with
+    /// `make` only if it is missing.
+    ///
+    /// If several threads miss on the same key at once, only one of them runs `make`, the
+    /// others wait for it and share its handle. Should `make` panic, one of the waiting threads
+    /// constructs the value instead.
+    ///
+    /// ## Parameters
+    /// * `key`: The borrowed form of the value, e.g. a `&str` for `String` values.
+    /// * `make`: Constructs the value from `key`.
+    ///
+    /// ## Note
+    /// Values are found by `key` only in tables without a custom equivalence, hash function or
+    /// normalization, which may treat keys differently. Other tables run `make` on every call
+    /// and intern its result. Calling `get_or_insert_with` with the same key from within
+    /// `make` deadlocks.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<String> = HcTable::new();
+    /// let first = table.get_or_insert_with("hash", str::to_owned);
+    /// let second = table.get_or_insert_with("hash", |_| unreachable!());
+    ///
+    /// assert_eq!(first, second);
+    /// ```
+    pub fn get_or_insert_with<Q>(&self, key: &Q, make: impl FnOnce(&Q) -> T) -> Hc<T, M>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.get_or_try_insert_with(key, |key| Ok::<T, Infallible>(make(key))) {
+            Ok(hc) => hc,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like `get_or_insert_with`, but `make` can fail.
+    ///
+    /// An error of `make` is returned as is and leaves the table unchanged. Threads waiting for
+    /// the failed construction then run their own `make`, so that a failure for one caller does
+    /// not fail the others.
+    ///
+    /// ## Parameters
+    /// * `key`: The borrowed form of the value, e.g. a `&str` for `String` values.
+    /// * `make`: Constructs the value from `key`, or fails.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<u32> = HcTable::new();
+    /// let parse = |key: &u32| -> Result<u32, String> {
+    ///     (*key < 100).then_some(*key).ok_or(format!("{key} is out of range"))
+    /// };
+    ///
+    /// let answer = table.get_or_try_insert_with(&42, parse).unwrap();
+    ///
+    /// assert_eq!(*answer, 42);
+    /// assert!(table.get_or_try_insert_with(&420, parse).is_err());
+    /// assert_eq!(table.len(), 1);
+    /// ```
+    pub fn get_or_try_insert_with<Q, E>(
+        &self,
+        key: &Q,
+        make: impl FnOnce(&Q) -> Result<T, E>,
+    ) -> Result<Hc<T, M>, E>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        loop {
+            match self.lookup_or_claim(key) {
+                Lookup::Found(hc) => return Ok(hc),
+                Lookup::Claimed(claim) => {
+                    let hc = self.hashcons(make(key)?);
+                    drop(claim);
+                    return Ok(hc);
+                }
+                Lookup::Wait(waiter) => waiter.wait(),
+            }
+        }
+    }
+
+    /// Hash conses a value that may be borrowed, e.g. a `Cow<str>` produced by a tokenizer.
+    ///
+    /// A borrowed value is copied only if it is missing, like with `get_or_insert_with`, and an
+    /// owned one is interned as is, without copying its buffer.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    /// use std::borrow::Cow;
+    ///
+    /// let table: HcTable<String> = HcTable::new();
+    /// let owned = table.hashcons_cow(Cow::<str>::Owned("token".to_string()));
+    /// let borrowed = table.hashcons_cow(Cow::Borrowed("token"));
+    ///
+    /// assert_eq!(owned, borrowed);
+    /// ```
+    pub fn hashcons_cow<Q>(&self, value: Cow<'_, Q>) -> Hc<T, M>
+    where
+        Q: ToOwned<Owned = T> + Hash + Eq + ?Sized,
+        T: Borrow<Q>,
+    {
+        match value {
+            Cow::Borrowed(key) => self.get_or_insert_with(key, Q::to_owned),
+            Cow::Owned(value) => self.hashcons(value),
+        }
+    }
+
+    /// Hash conses a value already shared as an `Arc`, storing that allocation if the value is
+    /// missing instead of copying the value into a new one.
+    ///
+    /// If an equivalent value is stored, its handle is returned and `value` is dropped. In
+    /// tables with a normalization, `value` must be in its normalized form.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::thread_safe::HcTable;
+    /// # #[cfg(feature = "portable-atomic")]
+    /// # use portable_atomic_util::Arc;
+    /// # #[cfg(not(feature = "portable-atomic"))]
+    /// use std::sync::Arc;
+    ///
+    /// let table = HcTable::new();
+    /// let config = Arc::new("shared configuration".to_string());
+    ///
+    /// let hc = table.hashcons_arc(config.clone());
+    ///
+    /// assert!(std::ptr::eq(hc.as_ptr(), Arc::as_ptr(&config)));
+    /// assert_eq!(table.hashcons("shared configuration".to_string()), hc);
+    /// ```
+    pub fn hashcons_arc(&self, value: Arc<T>) -> Hc<T, M> {
+        let hash = self.inner.hash(&value);
+        let lock = || Ok::<_, Infallible>(self.inner.write());
+        match self.intern_hashed(value, hash, lock, |_, _| Ok(())) {
+            Ok(inner) => Hc::from_inner(inner),
+            Err(never) => match never {},
+        }
+    }
+
+    #[cfg(feature = "async")]
+    /// Like `get_or_insert_with`, but constructs the value asynchronously and waits for the
+    /// constructions of other callers without blocking the thread.
+    ///
+    /// A cancelled construction is taken over by one of the waiting callers.
+    pub async fn get_or_insert_with_async<Q, F, Fut>(&self, key: &Q, make: F) -> Hc<T, M>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&Q) -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        loop {
+            match self.lookup_or_claim(key) {
+                Lookup::Found(hc) => return hc,
+                Lookup::Claimed(claim) => {
+                    let hc = self.hashcons(make(key).await);
+                    drop(claim);
+                    return hc;
+                }
+                Lookup::Wait(waiter) => waiter.await,
+            }
+        }
+    }
+
+    /// Looks up the stored value `key` borrows as, claiming its construction if it is missing.
+    fn lookup_or_claim<Q>(&self, key: &Q) -> Lookup<'_, Hc<T, M>>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if !self.inner.borrowed_lookup {
+            return Lookup::Claimed(None);
+        }
+        let hash = self.inner.hash_key(key);
+        let trusted = self.inner.trusted_hasher.is_some();
+        let mut checked = Vec::new();
+        loop {
+            let mut_table = self.inner.write();
+            let candidates = InnerTable::unchecked(&mut_table, hash, &mut checked);
+            if candidates.is_empty() {
+                // Claiming while the table is locked keeps a construction from finishing
+                // between the lookup and the claim unnoticed.
+                return match self.inner.in_flight.claim(hash) {
+                    Ok(claim) => Lookup::Claimed(Some(claim)),
+                    Err(waiter) => Lookup::Wait(waiter),
+                };
+            }
+            // `Q::eq` may use this table, so it only runs after the lock is released.
+            drop(mut_table);
+            let found = candidates
+                .iter()
+                .find_map(|(seq, candidate)| match candidate {
+                    Candidate::Live(inner)
+                        if trusted || Borrow::<Q>::borrow(&*inner.elem) == key =>
+                    {
+                        Some((*seq, inner.clone()))
+                    }
+                    _ => None,
+                });
+            if let Some((seq, inner)) = found {
+                self.inner.write().record_reuse(hash, seq);
+                return Lookup::Found(Hc::from_inner(self.inner.pinned(inner)));
+            }
+        }
+    }
+
+    /// Looks up several values at once, acquiring the lock of the `HcTable` only once.
+    ///
+    /// Values are not stored if they are missing, and the lookups are not counted in `stats`.
+    /// In tables with a normalization, the values must be given in their normalized form.
+    ///
+    /// ## Returns
+    /// The handle of each value, or `None` if it is not stored, in the order of `values`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let two = table.hashcons(2);
+    ///
+    /// let found = table.get_many(&[1, 2]);
+    ///
+    /// assert!(found[0].is_none());
+    /// assert_eq!(found[1], Some(two));
+    /// ```
+    pub fn get_many(&self, values: &[T]) -> Vec<Option<Hc<T, M>>> {
+        // Hashing happens before the lock is acquired.
+        let hashes: Vec<u128> = values.iter().map(|value| self.inner.hash(value)).collect();
+        let table = self.inner.read();
+        let candidates: Vec<_> = hashes
+            .into_iter()
+            .map(|hash| InnerTable::live(&table, hash))
+            .collect();
+        // The equivalence may use this table, so it only runs after the lock is released.
+        drop(table);
+        values
+            .iter()
+            .zip(candidates)
+            .map(|(value, live)| {
+                live.into_iter()
+                    .find(|inner| self.inner.matches(&inner.elem, value))
+                    .map(Hc::from_inner)
+            })
+            .collect()
+    }
+
+    /// Like `get_many`, but looks the values up by the keys they borrow as, e.g. `&str` keys
+    /// for `String` values.
+    ///
+    /// ## Note
+    /// As with `get_or_insert_with`, values are found by key only in tables without a custom
+    /// equivalence, hash function or normalization. Other tables find none of the keys.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<String> = HcTable::new();
+    /// let name = table.hashcons("name".to_string());
+    ///
+    /// assert_eq!(table.get_many_by_key(&["name", "other"]), [Some(name), None]);
+    /// ```
+    pub fn get_many_by_key<Q>(&self, keys: &[&Q]) -> Vec<Option<Hc<T, M>>>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if !self.inner.borrowed_lookup {
+            return keys.iter().map(|_| None).collect();
+        }
+        let hashes: Vec<u128> = keys.iter().map(|key| self.inner.hash_key(*key)).collect();
+        let trusted = self.inner.trusted_hasher.is_some();
+        let table = self.inner.read();
+        let candidates: Vec<_> = hashes
+            .into_iter()
+            .map(|hash| InnerTable::live(&table, hash))
+            .collect();
+        // `Q::eq` may use this table, so it only runs after the lock is released.
+        drop(table);
+        keys.iter()
+            .zip(candidates)
+            .map(|(key, live)| {
+                live.into_iter()
+                    .find(|inner| trusted || Borrow::<Q>::borrow(&*inner.elem) == *key)
+                    .map(Hc::from_inner)
+            })
+            .collect()
+    }
+
+    /// Looks up a value by a hash computed upstream, without constructing or hashing it.
+    ///
+    /// Meant for tables built with `prehashed`, which store values under the fingerprint their
+    /// hash function writes: a streaming parser that fingerprints its input can probe the table
+    /// before building a value. In other tables, `hash` is the `hash_value` of the handle of the
+    /// value. The lookup is not counted in `stats`.
+    ///
+    /// ## Parameters
+    /// * `hash`: The hash the value is stored under.
+    /// * `eq`: Returns `true` for the value looked up, consistently with the equivalence of the
+    ///   table.
+    ///
+    /// ## Panics
+    /// Panics in trusted-hash mode, whose hashes have 128 bits.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    /// use std::hash::Hasher;
+    ///
+    /// let table = HcTable::builder()
+    ///     .hashing(|value: &(u64, String), state: &mut dyn Hasher| state.write_u64(value.0))
+    ///     .prehashed()
+    ///     .build();
+    /// let file = table.hashcons_prehashed(0x5eed_f00d, (0x5eed_f00d, "contents".to_string()));
+    ///
+    /// let found = table.get_prehashed(0x5eed_f00d, |value| value.1 == "contents");
+    ///
+    /// assert_eq!(found, Some(file));
+    /// assert!(table.get_prehashed(0xf00d, |_| true).is_none());
+    /// ```
+    pub fn get_prehashed(&self, hash: u64, eq: impl Fn(&T) -> bool) -> Option<Hc<T, M>> {
+        let hash = self.inner.given_hash(hash);
+        let live = InnerTable::live(&self.inner.read(), hash);
+        // `eq` may use this table, so it only runs after the lock is released.
+        live.into_iter()
+            .find(|inner| eq(&inner.elem))
+            .map(Hc::from_inner)
+    }
+
+    /// Interns `value` under a hash computed upstream, without hashing it, see `get_prehashed`.
+    ///
+    /// `hash` must be the hash the table stores `value` under, which debug builds check, or
+    /// `hashcons` stores the value again. In tables with a normalization, `value` must be in
+    /// its normalized form.
+    ///
+    /// ## Panics
+    /// Panics in trusted-hash mode, whose hashes have 128 bits.
+    pub fn hashcons_prehashed(&self, hash: u64, value: T) -> Hc<T, M> {
+        let hash = self.inner.given_hash(hash);
+        debug_assert!(
+            hash == self.inner.hash(&value),
+            "hash_cons: the given hash differs from the hash of the value"
+        );
+        let lock = || Ok::<_, Infallible>(self.inner.write());
+        match self.intern_hashed(value, hash, lock, |_, _| Ok(())) {
+            Ok(inner) => Hc::from_inner(inner),
+            Err(never) => match never {},
+        }
+    }
+
+    /// Removes the value of `hc` from the `HcTable` and returns it, if `hc` is its only handle.
+    ///
+    /// This evicts a value known to be dead right away, instead of waiting for it to be dropped
+    /// or for the next sweep, and hands the value back instead of dropping it.
+    ///
+    /// ## Errors
+    /// Returns `hc` unchanged if other handles of the value exist, if the value is still shared
+    /// through the `Arc` it was interned from with `hashcons_arc`, or if it was interned in
+    /// another table.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(String::from("temporary"));
+    /// let other = value.clone();
+    ///
+    /// let value = table.remove(value).unwrap_err();
+    /// drop(other);
+    ///
+    /// assert_eq!(table.remove(value).unwrap(), "temporary");
+    /// assert!(table.is_empty());
+    /// ```
+    pub fn remove(&self, hc: Hc<T, M>) -> Result<T, Hc<T, M>> {
+        if !Arc::ptr_eq(&hc.inner._table, &self.inner) {
+            return Err(hc);
+        }
+        let hash = hc.inner.hash;
+        // Sweeps only remove entries without handles, so the entry stays while the lock is held.
+        let mut mut_table = self.inner.write();
+        // Only the entry and the handle share a value the caller holds no `Arc` of.
+        if Arc::strong_count(&hc.inner.elem) > 2 {
+            return Err(hc);
+        }
+        let inner = match Arc::try_unwrap(hc.inner) {
+            Ok(inner) => ManuallyDrop::new(inner),
+            Err(inner) => return Err(Hc::from_inner(inner)),
+        };
+        // SAFETY: `inner` is never used or dropped again, so that its fields are moved out once
+        // and dropping it does not retire the entry removed here.
+        let (elem, table) = unsafe { (std::ptr::read(&inner.elem), std::ptr::read(&inner._table)) };
+        let entry = mut_table.remove(hash, |entry| Arc::ptr_eq(&entry.key, &elem));
+        drop(mut_table);
+        drop((entry, table));
+        // Only entries and handles share the value.
+        Ok(Arc::into_inner(elem).expect("the removed handle and entry own the value"))
+    }
+
+    /// Tries to reserve room for at least `additional` more distinct values in the `HcTable`.
+    ///
+    /// ## Returns
+    /// The allocation error if the capacity could not be increased.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<u32> = HcTable::new();
+    /// table.try_reserve(1_000).expect("out of memory");
+    /// ```
+    pub fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.write().try_reserve(additional)
+    }
+
+    /// Internal method to manage the storage of values in `HcTable`.
+    /// It ensures that each value is stored only once, providing a shared
+    /// reference to the stored value.
+    ///
+    /// ## Parameters
+    /// * `value`: The value to be stored or retrieved.
+    ///
+    /// ## Returns
+    /// A `Arc<Inner<T>>` pointer to the stored value.
+    ///
+    /// ## Note
+    /// Values are only dropped after the lock is released, because dropping the children of a
+    /// value can remove them from this table and needs the lock again.
+    ///
+    fn intern(&self, value: T) -> Arc<Inner<T>> {
+        let lock = || Ok::<_, Infallible>(self.inner.write());
+        match self.intern_with(value, lock, |_, _| Ok(())) {
+            Ok(inner) => inner,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Looks up `value` like `intern` in the table locked by `lock`, calling `reserve` before a
+    /// new entry is added. If `lock` or `reserve` fails, the table is left unchanged and its
+    /// error is returned.
+    fn intern_with<'a, E>(
+        &'a self,
+        value: T,
+        lock: impl Fn() -> Result<RwLockWriteGuard<'a, Storage<T>>, E>,
+        reserve: impl FnOnce(&mut Storage<T>, u128) -> Result<(), E>,
+    ) -> Result<Arc<Inner<T>>, E> {
+        let value = self.inner.normalize(value);
+        let hash = self.inner.hash(&value);
+        self.intern_hashed(value, hash, lock, reserve)
+    }
+
+    /// Like `intern_with`, for a normalized value whose hash is known.
+    fn intern_hashed<'a, V, E>(
+        &'a self,
+        value: V,
+        hash: u128,
+        lock: impl Fn() -> Result<RwLockWriteGuard<'a, Storage<T>>, E>,
+        reserve: impl FnOnce(&mut Storage<T>, u128) -> Result<(), E>,
+    ) -> Result<Arc<Inner<T>>, E>
+    where
+        V: Borrow<T> + Into<Arc<T>>,
+    {
+        let mut checked = Vec::new();
+        let mut dead_match = None;
+        let mut mut_table = loop {
+            let mut_table = lock()?;
+            let candidates = InnerTable::unchecked(&mut_table, hash, &mut checked);
+            if candidates.is_empty() {
+                break mut_table;
+            }
+            // The equivalence may use this table, so it only runs after the lock is released. Values
+            // stored meanwhile are compared in the next round.
+            drop(mut_table);
+            let found = candidates
+                .iter()
+                .find(|(_, candidate)| self.inner.matches(candidate.value(), value.borrow()));
+            match found {
+                Some((seq, Candidate::Live(inner))) => {
+                    lock()?.record_reuse(hash, *seq);
+                    // `candidates` are dropped after the lock
+                    return Ok(self.inner.pinned(inner.clone()));
+                }
+                Some((_, Candidate::Dead(value))) => dead_match = Some(value.clone()),
+                None => {}
+            }
+        };
+        let generation = mut_table.generation();
+        #[cfg(feature = "access-time")]
+        let now = mut_table.now();
+
+        // The matched entry may have been taken over meanwhile, which replaces its value, so
+        // it is only taken over if it still holds the matched dead value.
+        let dead = dead_match.filter(|dead| {
+            mut_table
+                .bucket(hash)
+                .iter()
+                .any(|entry| Arc::ptr_eq(&entry.key, dead))
+        });
+        if let Some(dead) = dead {
+            let (id, seq) = mut_table.take_id(hash);
+            let entry = mut_table
+                .find_mut(hash, |key| Arc::ptr_eq(key, &dead))
+                .expect("the dead entry is stored");
+            #[cfg(feature = "access-time")]
+            {
+                entry.last_access = now;
+            }
+
+            // The entry is dead but not yet cleaned up, so it is taken over by the new value.
+            let evicted = entry.id;
+            let new_elem = self.new_inner(value.into(), hash, seq, generation);
+            let stale = entry.replace(
+                new_elem.elem.clone(),
+                Arc::downgrade(&new_elem),
+                id,
+                seq,
+                generation,
+            );
+            mut_table.record_replacement(evicted, id);
+            let drift_batch = self.inner.drift_batch(&mut mut_table);
+            drop(mut_table);
+            self.inner.observe_evicted([&stale.0]);
+            drop(stale);
+            self.inner.check_drift(drift_batch);
+            return Ok(self.inner.pinned(new_elem));
+        }
+
+        reserve(&mut mut_table, hash)?;
+        let ids = mut_table.take_id(hash);
+        let new_elem = self.new_inner(value.into(), hash, ids.1, generation);
+        let shared = mut_table.insert(hash, ids, new_elem.elem.clone(), Arc::downgrade(&new_elem));
+        let drift_batch = self.inner.drift_batch(&mut mut_table);
+        drop(mut_table);
+        self.inner.check_collisions(shared);
+        self.inner.check_drift(drift_batch);
+        Ok(self.inner.pinned(new_elem))
+    }
+
+    /// Allocates the shared `Inner<T>` of a value about to be stored in this table.
+    fn new_inner(&self, value: Arc<T>, hash: u128, seq: u64, generation: u64) -> Arc<Inner<T>> {
+        Arc::new(Inner {
+            elem: value,
+            seq,
+            generation,
+            hash,
+            #[cfg(feature = "leak-check")]
+            created_at: std::backtrace::Backtrace::capture(),
+            _table: self.inner.clone(),
+        })
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the `HcTable`, removing any values that are no longer in use.
+    /// This method is useful for managing memory and ensuring that unused
+    /// values are not unnecessarily kept in the table.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(5);
+    /// let other = table.hashcons(6);
+    ///
+    /// drop(value);
+    /// let report = table.cleanup();
+    ///
+    /// assert_eq!((report.removed, report.remaining), (1, 1));
+    /// ```
+    ///
+    pub fn cleanup(&self) -> CleanupReport {
+        self.inner.cleanup()
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values among the next `max_entries` entries of the `HcTable`, continuing
+    /// where the previous call stopped and starting over after the last entry.
+    ///
+    /// Unlike `cleanup`, which sweeps the whole table while holding its lock, this bounds the
+    /// pause of each call, so that sweeping can be spread over the idle moments of a service.
+    /// Values released by the removed entries are left to later calls. Entries added between
+    /// calls may be visited in a later round only.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the call took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let values: Vec<_> = (0..100).map(|i| table.hashcons(i)).collect();
+    /// drop(values);
+    ///
+    /// let report = table.cleanup_batch(10);
+    /// assert!(report.removed <= 10);
+    /// assert_eq!(report.remaining, 100 - report.removed);
+    /// ```
+    pub fn cleanup_batch(&self, max_entries: usize) -> CleanupReport {
+        self.inner.cleanup_batch(max_entries)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the `HcTable` once the calls of this method saw no lookup in it for at least
+    /// `window`.
+    ///
+    /// Idleness is a polling heuristic: the table does not track when its operations run, and
+    /// this method only compares the number of lookups to the one seen by its previous call. A
+    /// changed number starts the idle period at the time of the call that sees it, and a later
+    /// call sweeps once the number stayed unchanged for `window` since then. Call it
+    /// periodically, e.g. from a timer, at intervals well below `window`: the quiet time before
+    /// a sweep is at least `window` and exceeds it by up to one interval. A burst of interning
+    /// that starts just after the number was read is not seen, so the sweep of that call can
+    /// still overlap with its start. Sweeps otherwise fall into the quiet phases between
+    /// bursts instead of competing with them for the lock. Reading values through handles does not touch the table and does not count as
+    /// activity.
+    ///
+    /// ## Returns
+    /// The report of the sweep, or `None` if the table was not idle for long enough.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    /// use std::time::Duration;
+    ///
+    /// let table = HcTable::new();
+    /// drop(table.hashcons(5));
+    ///
+    /// // The first call notices the interning and starts measuring the idle period.
+    /// assert!(table.cleanup_when_idle(Duration::ZERO).is_none());
+    /// let report = table.cleanup_when_idle(Duration::ZERO).unwrap();
+    /// assert_eq!(report.removed, 1);
+    /// ```
+    pub fn cleanup_when_idle(&self, window: Duration) -> Option<CleanupReport> {
+        self.inner.cleanup_when_idle(window)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `HcTable` that were stored before `generation`.
+    ///
+    /// Phased pipelines advance the generation after each phase and drop everything an earlier
+    /// phase left behind in one call, while keeping the garbage of the current phase around
+    /// for reuse. Values that are still in use are kept, whatever their generation.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let parsed = table.hashcons("parsed");
+    /// let checkpoint = table.advance_generation();
+    /// let lowered = table.hashcons("lowered");
+    ///
+    /// drop((parsed, lowered));
+    /// let report = table.cleanup_older_than(checkpoint);
+    ///
+    /// assert_eq!((report.removed, report.remaining), (1, 1));
+    /// ```
+    pub fn cleanup_older_than(&self, generation: u64) -> CleanupReport {
+        self.inner.cleanup_older_than(generation)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Marks the values stored in the `HcTable` so far, so that a later `rollback` can remove
+    /// the values stored after it.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            seq: self.inner.read().next_seq(),
+        }
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `HcTable` that were stored after `checkpoint` was taken,
+    /// e.g. once a speculative analysis is abandoned. Values that are still in use are kept,
+    /// as are values stored before the checkpoint.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let kept = table.hashcons("kept");
+    /// let checkpoint = table.checkpoint();
+    /// let speculative = table.hashcons("speculative");
+    /// let reused = table.hashcons("reused");
+    ///
+    /// drop((kept, speculative));
+    /// let report = table.rollback(checkpoint);
+    ///
+    /// assert_eq!((report.removed, report.remaining), (1, 2));
+    /// ```
+    pub fn rollback(&self, checkpoint: Checkpoint) -> CleanupReport {
+        self.inner.sweep(|entry| entry.seq >= checkpoint.seq)
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes unused values of the `HcTable` until the stored values weigh at most `budget`.
+    ///
+    /// Counting entries is a poor bound on memory when values range from a few bytes to
+    /// megabytes. With a weight function such as `MemSize::heap_size`, this keeps as many
+    /// unused values around for reuse as the budget allows. Values still in use are never
+    /// removed, so the budget is not met if they alone exceed it.
+    ///
+    /// ## Parameters
+    /// * `budget`: The total weight to shrink to.
+    /// * `order`: Which unused values to remove first.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the eviction took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::{EvictionOrder, HcTable};
+    ///
+    /// let table = HcTable::builder().weight(|value: &Vec<u8>| value.len()).build();
+    /// let small = table.hashcons(vec![0; 16]);
+    /// drop(table.hashcons(vec![1; 1024]));
+    /// drop(table.hashcons(vec![2; 64]));
+    ///
+    /// let report = table.evict_until_weight(100, EvictionOrder::Heaviest);
+    ///
+    /// assert_eq!(report.removed, 1);
+    /// assert_eq!(table.total_weight(), 80);
+    /// ```
+    pub fn evict_until_weight(&self, budget: usize, order: EvictionOrder) -> CleanupReport {
+        self.inner.evict_until_weight(budget, order)
+    }
+
+    #[cfg(all(feature = "access-time", not(feature = "auto-cleanup")))]
+    /// Removes the unused values of the `HcTable` that were last interned `idle` or longer ago.
+    ///
+    /// Access times are counted in whole seconds and refreshed whenever a value is interned
+    /// again, so that values which keep coming back stay cached while unused ones expire.
+    /// Reading a value through a handle does not refresh its access time.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    /// use std::time::Duration;
+    ///
+    /// let table = HcTable::new();
+    /// drop(table.hashcons(5));
+    ///
+    /// assert_eq!(table.cleanup_idle_for(Duration::from_secs(60)).removed, 0);
+    /// assert_eq!(table.cleanup_idle_for(Duration::ZERO).removed, 1);
+    /// ```
+    pub fn cleanup_idle_for(&self, idle: Duration) -> CleanupReport {
+        self.inner.cleanup_idle_for(idle)
+    }
+
+    #[cfg(feature = "auto-cleanup")]
+    /// Removes the entries whose last handle was dropped since the previous collection.
+    ///
+    /// Tables collect these entries before every intern and query, unless they were built with
+    /// `deferred_reclamation()`. Calling this from a background thread then keeps the removals
+    /// off the threads that intern and drop values.
+    ///
+    /// ## Returns
+    /// The number of entries removed.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::builder().deferred_reclamation().build();
+    /// let values: Vec<_> = (0..10).map(|i| table.hashcons(i)).collect();
+    /// drop(values);
+    ///
+    /// assert_eq!(table.collect_retired(), 10);
+    /// ```
+    pub fn collect_retired(&self) -> usize {
+        self.inner.collect_retired()
+    }
+
+    #[cfg(feature = "tokio")]
+    /// Spawns a task on the current Tokio runtime that removes the dead entries of the `HcTable`
+    /// every `interval`, and refreshes the counters returned by `MaintenanceHandle::stats()`.
+    ///
+    /// Dead entries are the retired entries with `auto-cleanup`, see `collect_retired`, which
+    /// keeps tables with deferred reclamation small, and the values without handles otherwise,
+    /// see `cleanup`. The sweeps run on the runtime and take the write lock of the table.
+    ///
+    /// The task does not keep the table alive: it stops once the table and all of its handles
+    /// are dropped, when the returned handle is dropped, or when the runtime shuts down.
+    ///
+    /// ## Panics
+    /// If called outside of a Tokio runtime.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::thread_safe::HcTable;
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let table = HcTable::new();
+    /// let maintenance = table.spawn_maintenance(Duration::from_millis(10));
+    /// drop(table.hashcons(1));
+    ///
+    /// tokio::time::sleep(Duration::from_millis(50)).await;
+    /// assert_eq!(maintenance.stats().removed, 1);
+    /// # });
+    /// ```
+    pub fn spawn_maintenance(&self, interval: Duration) -> MaintenanceHandle
+    where
+        T: Send + Sync + 'static,
+    {
+        let table = Arc::downgrade(&self.inner);
+        let stats = Arc::new(Mutex::new(self.stats()));
+        let published = stats.clone();
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticks.tick().await; // the first tick completes immediately
+            loop {
+                ticks.tick().await;
+                let Some(table) = table.upgrade() else {
+                    break;
+                };
+                table.maintain();
+                *table.lock_mutex(&published) = table.stats();
+            }
+        });
+        MaintenanceHandle {
+            task,
+            stats,
+            poison_policy: self.inner.poison_policy,
+            prefix: self.inner.prefix(),
+        }
+    }
+
+    /// Returns the number of elements currently stored in the `HcTable`.
+    ///
+    /// ## Returns
+    /// The number of elements in the `HcTable`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(5);
+    ///
+    /// assert_eq!(table.len(), 1);
+    /// ```
+    ///
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the `HcTable` contains no elements.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<i32> = HcTable::new();
+    ///
+    /// assert!(table.is_empty());
+    /// ```
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of distinct hashes the `HcTable` can hold without reallocating.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<u32> = HcTable::new();
+    /// table.try_reserve(100).unwrap();
+    ///
+    /// assert!(table.capacity() >= 100);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.inner.read().capacity()
+    }
+
+    /// Returns the fraction of the capacity in use, between `0.0` and `1.0`.
+    ///
+    /// A low load factor after many values were dropped means that `shrink_to_fit` would
+    /// release memory.
+    pub fn load_factor(&self) -> f64 {
+        self.inner.read().load_factor()
+    }
+
+    /// Returns the number of entries whose handles were all dropped but which are still stored.
+    ///
+    /// These tombstones are only left behind without the `auto-cleanup` feature, until `cleanup`
+    /// is called or an equal value takes the entry over, and in tables with deferred
+    /// reclamation, until `collect_retired` is called.
+    pub fn dead_count(&self) -> usize {
+        self.inner
+            .read()
+            .iter()
+            .filter(|entry| entry.weak.strong_count() == 0)
+            .count()
+    }
+
+    /// Shrinks the capacity of the `HcTable` as much as possible.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let values: Vec<_> = (0..100).map(|i| table.hashcons(i)).collect();
+    /// drop(values);
+    /// # #[cfg(not(feature = "auto-cleanup"))]
+    /// # table.cleanup();
+    ///
+    /// table.shrink_to_fit();
+    /// assert!(table.capacity() < 100);
+    /// ```
+    pub fn shrink_to_fit(&self) {
+        self.inner.write().shrink_to_fit();
+    }
+
+    /// Reports how the stored values are distributed over their hashes.
+    ///
+    /// ## Returns
+    /// The number of hashes shared by one, two, or more values, and how many values collide.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let values: Vec<_> = (0..10).map(|i| table.hashcons(i)).collect();
+    ///
+    /// let diagnostics = table.hash_diagnostics();
+    /// assert_eq!(diagnostics.distinct_hashes, 10);
+    /// assert_eq!(diagnostics.colliding_values, 0);
+    /// ```
+    pub fn hash_diagnostics(&self) -> HashDiagnostics {
+        self.inner.read().diagnostics()
+    }
+
+    /// Finds the stored values whose hash changed since they were interned, e.g. because they
+    /// were mutated through a `Cell`, `RefCell` or lock.
+    ///
+    /// The `HcTable` can no longer find such values, so interning an equal value creates a
+    /// second handle.
+    ///
+    /// ## Returns
+    /// Handles of the drifted values, in storing order.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    /// use std::hash::{Hash, Hasher};
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// #[derive(Debug)]
+    /// struct Version(AtomicU32);
+    ///
+    /// impl PartialEq for Version {
+    ///     fn eq(&self, other: &Self) -> bool {
+    ///         self.0.load(Ordering::Relaxed) == other.0.load(Ordering::Relaxed)
+    ///     }
+    /// }
+    /// impl Eq for Version {}
+    /// impl Hash for Version {
+    ///     fn hash<H: Hasher>(&self, state: &mut H) {
+    ///         self.0.load(Ordering::Relaxed).hash(state)
+    ///     }
+    /// }
+    ///
+    /// let table = HcTable::new();
+    /// let version = table.hashcons(Version(AtomicU32::new(1)));
+    /// assert!(table.hash_drift().is_empty());
+    ///
+    /// version.0.store(2, Ordering::Relaxed);
+    /// assert_eq!(table.hash_drift(), [version]);
+    /// ```
+    pub fn hash_drift(&self) -> Vec<Hc<T, M>> {
+        let table = self.inner.read();
+        let mut entries: Vec<_> = table
+            .iter_hashed()
+            .filter_map(|(hash, entry)| {
+                Some((entry.seq, hash, Hc::from_inner(entry.weak.upgrade()?)))
+            })
+            .collect();
+        drop(table);
+        entries.sort_unstable_by_key(|&(seq, _, _)| seq);
+        // The values are hashed after the lock is released, since their `Hash` may use the table
+        entries
+            .into_iter()
+            .filter(|(_, hash, hc)| self.inner.hash(&hc.inner.elem) != *hash)
+            .map(|(_, _, hc)| hc)
+            .collect()
+    }
+
+    /// Returns the total weight of the stored values, including unused ones that were not
+    /// removed yet, as measured by the weight function of the `HcTable`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::builder().weight(|value: &String| value.len()).build();
+    /// let values = [table.hashcons("hash".to_string()), table.hashcons("cons".to_string())];
+    ///
+    /// assert_eq!(table.total_weight(), 8);
+    /// ```
+    pub fn total_weight(&self) -> usize {
+        self.inner
+            .stored()
+            .iter()
+            .map(|(value, _, _)| self.inner.weight_of(value))
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Returns the generation of the `HcTable`, which newly stored values are stamped with.
+    pub fn generation(&self) -> u64 {
+        self.inner.read().generation()
+    }
+
+    /// Starts a new generation of the `HcTable`, e.g. at the end of a phase of a pipeline.
+    ///
+    /// Values stored from now on are stamped with the new generation, so that values from
+    /// earlier phases can be told apart and removed together, see `cleanup_older_than`.
+    ///
+    /// ## Returns
+    /// The new generation.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<u32> = HcTable::new();
+    /// assert_eq!(table.generation(), 0);
+    /// assert_eq!(table.advance_generation(), 1);
+    /// assert_eq!(table.generation(), 1);
+    /// ```
+    pub fn advance_generation(&self) -> u64 {
+        self.inner.write().advance_generation()
+    }
+
+    /// Formats up to `limit` of the values that still have handles, in no particular order.
+    ///
+    /// The `Debug` output of the `HcTable` itself only summarizes it, so that tables of any
+    /// value type can be debugged.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons("interned");
+    ///
+    /// assert_eq!(format!("{:?}", table.debug_entries(10)), r#"["interned"]"#);
+    /// ```
+    pub fn debug_entries(&self, limit: usize) -> impl std::fmt::Debug + '_
+    where
+        T: std::fmt::Debug,
+    {
+        DebugEntries { table: self, limit }
+    }
+
+    /// Returns an iterator over the values of the `HcTable` that still have handles, in the
+    /// order they were stored.
+    ///
+    /// ## Consistency
+    /// The values are collected under one read lock, so the iterator visits every value that
+    /// had a handle at that moment exactly once, and none interned later. The lock is released
+    /// before the first value is returned: the loop may intern into the table, as may other
+    /// threads, without deadlocking or changing what is visited. The iterator holds a handle to
+    /// each remaining value, which keeps them alive until they are visited.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let values: Vec<_> = (0..3).map(|i| table.hashcons(i)).collect();
+    ///
+    /// for value in &table {
+    ///     table.hashcons(*value + 10);
+    /// }
+    ///
+    /// let visited: Vec<_> = table.iter().map(|value| *value).collect();
+    /// assert_eq!(visited, [0, 1, 2]);
+    /// ```
+    pub fn iter(&self) -> Iter<T, M> {
+        Iter {
+            handles: self.export_entries().into_iter(),
+        }
+    }
+
+    /// Returns the values of the `HcTable` that still have handles, together with their ids,
+    /// in the order they were stored.
+    ///
+    /// The entries are a snapshot, like the values visited by `iter`.
+    ///
+    /// Ids come from the allocator of the table, see `HcTableBuilder::ids()`, which decides
+    /// whether they are unique. The default [`SequentialIds`] never hands out an id twice, also
+    /// when a value is dropped and interned again, so that external indices and databases can
+    /// key on them. With [`ContentIds`](crate::ContentIds), values whose hashes agree in their
+    /// lower half share an id, and [`RandomIds`](crate::RandomIds) repeat an id with a
+    /// probability of about one in 2^64 per pair of values. Use `entry_id` to translate handles
+    /// held by the values, such as the children of a node.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let first = table.hashcons("first");
+    /// let second = table.hashcons("second");
+    ///
+    /// let entries = table.export_entries();
+    /// let values: Vec<_> = entries.iter().map(|(_, hc)| **hc).collect();
+    ///
+    /// assert_eq!(values, ["first", "second"]);
+    /// assert_eq!(table.entry_id(&second), Some(entries[1].0));
+    /// ```
+    pub fn export_entries(&self) -> Vec<(u64, Hc<T, M>)> {
+        let table = self.inner.read();
+        let mut entries: Vec<_> = table
+            .iter()
+            .filter_map(|entry| Some((entry.seq, entry.id, Hc::from_inner(entry.weak.upgrade()?))))
+            .collect();
+        drop(table);
+        entries.sort_unstable_by_key(|&(seq, _, _)| seq);
+        entries.into_iter().map(|(_, id, hc)| (id, hc)).collect()
+    }
+
+    /// Returns the id `export_entries` reports for the value of `hc`, or `None` if `hc` is not
+    /// a handle of this table.
+    pub fn entry_id(&self, hc: &Hc<T, M>) -> Option<u64> {
+        let table = self.inner.read();
+        let id = table
+            .bucket(hc.inner.hash)
+            .iter()
+            .find(|entry| Arc::ptr_eq(&entry.key, &hc.inner.elem))
+            .map(|entry| entry.id);
+        id
+    }
+
+    /// Returns a handle to the value `token` was taken from, or `None` if the value was removed
+    /// or the token comes from another table.
+    pub fn resolve(&self, token: HcToken) -> Option<Hc<T, M>> {
+        if token.table != self.inner.id {
+            return None;
+        }
+        let table = self.inner.read();
+        let inner = table
+            .bucket(token.hash)
+            .iter()
+            .find(|entry| entry.seq == token.seq)?
+            .weak
+            .upgrade()?;
+        drop(table);
+        Some(Hc::from_inner(inner))
+    }
+
+    #[cfg(feature = "history")]
+    /// Returns the most recent values stored in and removed from the `HcTable`, oldest first.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::history::HistoryKind;
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let first = table.hashcons(1);
+    /// let id = table.entry_id(&first).unwrap();
+    /// table.remove(first).unwrap();
+    ///
+    /// let kinds: Vec<_> = table.history().iter().map(|event| (event.kind, event.id)).collect();
+    /// assert_eq!(kinds, [(HistoryKind::Interned, id), (HistoryKind::Evicted, id)]);
+    /// ```
+    pub fn history(&self) -> Vec<HistoryEvent> {
+        self.inner.read().history.events()
+    }
+
+    #[cfg(feature = "history")]
+    /// Makes the `HcTable` keep its last `capacity` events, forgetting older ones. A capacity
+    /// of zero stops recording.
+    pub fn set_history_capacity(&self, capacity: usize) {
+        self.inner.write().history.set_capacity(capacity);
+    }
+
+    #[cfg(feature = "leak-check")]
+    /// Lists the values of the `HcTable` that still have handles, as reported when its last
+    /// `HcTable` is dropped. Values kept alive by snapshots and forks count as well.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let kept = table.hashcons(1);
+    /// drop(table.hashcons(2));
+    ///
+    /// assert_eq!(table.leak_report().leaked, 1);
+    /// ```
+    pub fn leak_report(&self) -> LeakReport {
+        self.inner.leak_report()
+    }
+
+    /// Returns `true` if the `HcTable` and `other` hold equivalent values that still have
+    /// handles, also if they are different tables.
+    ///
+    /// Each value of one table is looked up in the other table, with the strategies of that
+    /// table. Values held by the values, such as the children of a node, are compared by value.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let expected = HcTable::new();
+    /// let actual = HcTable::new();
+    /// let _a = (expected.hashcons("a"), actual.hashcons("a"));
+    ///
+    /// assert!(expected.same_live_set(&actual));
+    /// let _b = actual.hashcons("b");
+    /// assert!(!expected.same_live_set(&actual));
+    /// ```
+    pub fn same_live_set(&self, other: &Self) -> bool {
+        self.difference(other).next().is_none() && other.difference(self).next().is_none()
+    }
+
+    /// Compares the values of the `HcTable` and `other` that still have handles, like
+    /// `same_live_set`, and lists those held by one table only.
+    ///
+    /// ## Returns
+    /// The values of the `HcTable` missing from `other` as `removed`, and those of `other`
+    /// missing from the `HcTable` as `added`, each in the order their table stored them.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let expected = HcTable::new();
+    /// let actual = HcTable::new();
+    /// let _kept = (expected.hashcons("kept"), actual.hashcons("kept"));
+    /// let _old = expected.hashcons("old");
+    /// let _new = actual.hashcons("new");
+    ///
+    /// let diff = expected.diff_live_set(&actual);
+    ///
+    /// assert_eq!(diff.removed, [expected.hashcons("old")]);
+    /// assert_eq!(diff.added, [actual.hashcons("new")]);
+    /// ```
+    pub fn diff_live_set(&self, other: &Self) -> LiveSetDiff<T, M> {
+        LiveSetDiff {
+            removed: self.difference(other).collect(),
+            added: other.difference(self).collect(),
+        }
+    }
+
+    /// Returns the values of the `HcTable` that still have handles and have no equivalent in
+    /// `other`, in the order they were stored.
+    ///
+    /// The values are listed when `difference` is called and looked up in `other` lazily, with
+    /// the strategies of `other`, like `same_live_set` does.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let run_a = HcTable::new();
+    /// let run_b = HcTable::new();
+    /// let _a = (run_a.hashcons("x + 0"), run_a.hashcons("x"));
+    /// let _b = (run_b.hashcons("x"), run_b.hashcons("x * 1"));
+    ///
+    /// let new_in_b: Vec<_> = run_b.difference(&run_a).map(|hc| *hc).collect();
+    /// assert_eq!(new_in_b, ["x * 1"]);
+    /// ```
+    pub fn difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = Hc<T, M>> + 'a {
+        let entries = if Arc::ptr_eq(&self.inner, &other.inner) {
+            Vec::new()
+        } else {
+            self.export_entries()
+        };
+        entries
+            .into_iter()
+            .map(|(_, hc)| hc)
+            .filter(move |hc| other.lookup(hc.get()).is_none())
+    }
+
+    /// Returns the values that still have handles in either the `HcTable` or `other` but have
+    /// no equivalent in the other table: first those of the `HcTable`, then those of `other`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let run_a = HcTable::new();
+    /// let run_b = HcTable::new();
+    /// let _a = (run_a.hashcons("x + 0"), run_a.hashcons("x"));
+    /// let _b = (run_b.hashcons("x"), run_b.hashcons("x * 1"));
+    ///
+    /// let changed: Vec<_> = run_a.symmetric_difference(&run_b).map(|hc| *hc).collect();
+    /// assert_eq!(changed, ["x + 0", "x * 1"]);
+    /// ```
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = Hc<T, M>> + 'a {
+        self.difference(other).chain(other.difference(self))
+    }
+
+    /// Returns the namespace `name` of the `HcTable`, creating it on first use.
+    ///
+    /// Namespaces share the storage of the table, so that values interned through several of
+    /// them are stored once, but remember which values each of them interned. This allows
+    /// listing and evicting the values of e.g. one module without a table per module.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let parser = table.namespace("parser");
+    /// let lexer = table.namespace("lexer");
+    ///
+    /// let ident = parser.hashcons("ident");
+    /// let _keyword = lexer.hashcons("fn");
+    ///
+    /// assert!(lexer.hashcons("ident") == ident);
+    /// assert_eq!(table.len(), 2);
+    /// assert_eq!(lexer.len(), 2);
+    /// assert_eq!(parser.handles(), vec![ident]);
+    /// ```
+    pub fn namespace(&self, name: &str) -> Namespace<'_, T, M> {
+        Namespace {
+            table: self,
+            name: name.to_string(),
+        }
+    }
+
+    /// Pins every value interned in the `HcTable` while the returned guard lives, by any thread.
+    ///
+    /// Pinned values stay stored without handles, so that a phase can drop its handles freely
+    /// and still find its values again. Dropping the guard unpins them, which makes the ones
+    /// without handles left removable again. Scopes may overlap, a value then stays pinned until
+    /// every scope it was interned in ends.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let scope = table.pin_scope();
+    /// drop(table.hashcons("phase"));
+    ///
+    /// assert_eq!((table.len(), scope.len()), (1, 1));
+    ///
+    /// drop(scope);
+    /// # #[cfg(not(feature = "auto-cleanup"))]
+    /// # table.cleanup();
+    /// assert!(table.is_empty());
+    /// ```
+    pub fn pin_scope(&self) -> PinScope<'_, T, M> {
+        let mut pins = self.inner.lock_mutex(&self.inner.pins);
+        let id = pins.next_scope;
+        pins.next_scope += 1;
+        pins.scopes.insert(id, HashMap::new());
+        self.inner.pin_scopes.fetch_add(1, Ordering::Relaxed);
+        PinScope { table: self, id }
+    }
+
+    /// Forks the `HcTable` into a child table that shares its values but stores its own.
+    ///
+    /// Interning through the fork finds the values of the `HcTable` and of the fork, but
+    /// stores new values in the fork only. Speculative work is thrown away by dropping the
+    /// fork, or kept with `HcFork::merge()`. The fork hashes and compares values with the
+    /// strategies of the `HcTable`, and draws the ids of its values from the same allocator,
+    /// so that they differ from the ids of the values of the `HcTable`.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let one = table.hashcons(1);
+    ///
+    /// let fork = table.fork();
+    /// assert!(fork.hashcons(1) == one);
+    /// let _two = fork.hashcons(2);
+    /// assert_eq!((table.len(), fork.len()), (1, 1));
+    ///
+    /// let merged = fork.merge();
+    /// assert_eq!(*merged[0], 2);
+    /// assert_eq!(table.len(), 2);
+    /// ```
+    pub fn fork(&self) -> HcFork<T, M> {
+        HcFork {
+            parent: self.clone(),
+            child: HcTable {
+                inner: Arc::new(self.inner.fork()),
+                _semantics: PhantomData,
+            },
+        }
+    }
+
+    /// Returns a handle to every value of the `HcTable` that still has one, in no particular order.
+    pub(crate) fn handles(&self) -> Vec<Hc<T, M>> {
+        let table = self.inner.read();
+        table
+            .iter()
+            .filter_map(|entry| entry.weak.upgrade())
+            .map(|inner| Hc::from_inner(inner))
+            .collect()
+    }
+
+    /// Returns the handle of the stored value `value` is equivalent to, without storing it.
+    fn lookup(&self, value: &T) -> Option<Hc<T, M>> {
+        let hash = self.inner.hash(value);
+        let live = InnerTable::live(&self.inner.read(), hash);
+        // The equivalence may use this table, so it only runs after the lock is released.
+        live.into_iter()
+            .find(|inner| self.inner.matches(&inner.elem, value))
+            .map(Hc::from_inner)
+    }
+
+    /// Takes a read-only snapshot of the values of the `HcTable` that still have handles.
+    ///
+    /// Lookups in the snapshot take no lock, so that phases which only read the interned
+    /// values, such as a compiler back-end, do not contend with each other. The snapshot keeps
+    /// its values alive and does not see values interned later.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons("ident".to_string());
+    /// let frozen = table.freeze();
+    ///
+    /// assert!(frozen.get(&"ident".to_string()) == Some(&value));
+    /// assert!(!frozen.contains(&"other".to_string()));
+    /// assert_eq!(frozen.iter().count(), 1);
+    /// ```
+    pub fn freeze(&self) -> FrozenHcTable<T, M> {
+        let table = self.inner.read();
+        let mut map: HashMap<u128, Vec<Hc<T, M>>, _> = HashMap::default();
+        let mut len = 0;
+        for (hash, entry) in table.iter_hashed() {
+            if let Some(inner) = entry.weak.upgrade() {
+                map.entry(hash).or_default().push(Hc::from_inner(inner));
+                len += 1;
+            }
+        }
+        FrozenHcTable {
+            table: self.inner.clone(),
+            map: Arc::new(map),
+            len,
+        }
+    }
+
+    /// Returns the counters of the `HcTable` since it was created.
+    ///
+    /// ## Returns
+    /// How many values were stored in new allocations, how many reused an existing handle, how
+    /// many entries were removed, and how often the map had to grow. The counters display as a
+    /// one-line summary.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let first = table.hashcons(5);
+    /// let second = table.hashcons(5);
+    ///
+    /// let stats = table.stats();
+    /// assert_eq!((stats.created, stats.reused), (1, 1));
+    /// ```
+    pub fn stats(&self) -> TableStats {
+        self.inner.stats()
+    }
+
+    /// Returns the name of the `HcTable`, if it was given one with `HcTableBuilder::name`.
+    pub fn name(&self) -> Option<&'static str> {
+        self.inner.name
+    }
+
+    #[cfg(feature = "prometheus")]
+    /// Returns a function reading the counters of the `HcTable`, which returns `None` once the
+    /// table and all of its handles are dropped instead of keeping them alive.
+    pub(crate) fn stats_source(&self) -> impl Fn() -> Option<TableStats> + Send + Sync + 'static
+    where
+        T: Send + Sync + 'static,
+    {
+        let inner = Arc::downgrade(&self.inner);
+        move || inner.upgrade().map(|inner| inner.stats())
+    }
+
+    /// Returns a function releasing memory of the `HcTable`, see `TryRelease`, which returns
+    /// `None` once the table and all of its handles are dropped instead of keeping them alive.
+    pub(crate) fn release_source(&self) -> impl Fn(usize) -> Option<usize> + Send + Sync + 'static
+    where
+        T: MemSize + Send + Sync + 'static,
+    {
+        let inner = Arc::downgrade(&self.inner);
+        move |bytes| {
+            inner.upgrade().map(|inner| {
+                #[cfg(feature = "leak-check")]
+                inner.tables.fetch_add(1, Ordering::Relaxed);
+                HcTable::<T> {
+                    inner,
+                    _semantics: PhantomData,
+                }
+                .try_release(bytes)
+            })
+        }
+    }
+
+    /// Estimates the memory held by the `HcTable`.
+    ///
+    /// ## Returns
+    /// The bytes held by values that still have handles, by dead entries that were not cleaned
+    /// up yet, and by the table structure itself. Values are measured with `MemSize`, so memory
+    /// they share with other values is counted for each of them.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(String::from("interned"));
+    ///
+    /// let usage = table.memory_usage();
+    /// assert!(usage.live >= "interned".len());
+    /// assert_eq!(usage.dead, 0);
+    /// ```
+    pub fn memory_usage(&self) -> MemoryUsage
+    where
+        T: MemSize,
+    {
+        let allocated = self.inner.read().allocated_bytes();
+        let mut usage = MemoryUsage {
+            overhead: size_of::<InnerTable<T>>() + allocated,
+            ..MemoryUsage::default()
+        };
+
+        for (value, alive, _) in self.inner.stored() {
+            let bytes = entry_bytes(&*value);
+            if alive {
+                usage.live += bytes;
+            } else {
+                usage.dead += bytes;
+            }
+        }
+        usage
+    }
+}
+
+impl<T> Default for HcTable<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an empty `HcTable`, equivalent to `HcTable::new()`.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, M> TryRelease for HcTable<T, M>
+where
+    T: Hash + Eq + MemSize,
+{
+    /// Removes dead entries of the `HcTable`, heaviest first and only as many as needed, then
+    /// gives the spare capacity of its map back if that did not release `bytes`.
+    ///
+    /// Values that still have handles are never removed. Without `auto-cleanup`, these are
+    /// the unused values; with it, the retired entries of tables with deferred reclamation.
+    fn try_release(&self, bytes: usize) -> usize {
+        let before = self.memory_usage().total();
+        #[cfg(feature = "auto-cleanup")]
+        self.collect_retired();
+        #[cfg(not(feature = "auto-cleanup"))]
+        {
+            let usage = self.memory_usage();
+            let budget = (usage.live + usage.dead).saturating_sub(bytes);
+            self.inner
+                .evict_until(budget, EvictionOrder::Heaviest, entry_bytes);
+        }
+        if before.saturating_sub(self.memory_usage().total()) < bytes {
+            self.shrink_to_fit();
+        }
+        before.saturating_sub(self.memory_usage().total())
+    }
+}
+
+impl<T, M> Clone for HcTable<T, M> {
+    /// Provides the functionality to clone `HcTable<T>` instances.
+    ///
+    /// ## Returns
+    /// A new `HcTable<T>` instance with the same values as the original.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(5);
+    /// let table_clone = table.clone();
+    ///
+    /// assert_eq!(table.len(), table_clone.len());
+    /// ```
+    ///
+    fn clone(&self) -> Self {
+        #[cfg(feature = "leak-check")]
+        self.inner.tables.fetch_add(1, Ordering::Relaxed);
+        HcTable {
+            inner: self.inner.clone(),
+            _semantics: PhantomData,
+        }
+    }
+}
+
+impl<T, M> PartialEq for HcTable<T, M> {
+    /// Returns `true` if both `HcTable`s are the same table, i.e. one is a clone of the other.
+    /// Tables are never compared by their values.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table: HcTable<u32> = HcTable::new();
+    ///
+    /// assert!(table == table.clone());
+    /// assert!(table != HcTable::new());
+    /// ```
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T, M> Eq for HcTable<T, M> {}
+
+impl<T, M> Hash for HcTable<T, M> {
+    /// Hashes the identity of the table, consistently with `eq`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.inner).hash(state);
+    }
+}
+
+#[cfg(feature = "leak-check")]
+impl<T, M> Drop for HcTable<T, M> {
+    /// Reports the values that still have handles when the last `HcTable` of the table is
+    /// dropped.
+    fn drop(&mut self) {
+        if self.inner.tables.fetch_sub(1, Ordering::AcqRel) == 1
+            && !self.inner.leaks_reported.swap(true, Ordering::Relaxed)
+        {
+            let report = self.inner.leak_report();
+            if report.leaked > 0 {
+                eprintln!("{report}");
+            }
+        }
+    }
+}
+
+impl<T, M> std::fmt::Debug for HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Summarizes the `HcTable`: its size and how it identifies and removes values. Use
+    /// `debug_entries` to format the values themselves.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let value = table.hashcons(5);
+    ///
+    /// assert!(format!("{:?}", table).starts_with("HcTable { len: 1, "));
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let table = self.inner.read();
+        let mut debug = f.debug_struct("HcTable");
+        if let Some(name) = self.inner.name {
+            debug.field("name", &name);
+        }
+        debug
+            .field("len", &table.len())
+            .field("capacity", &table.capacity())
+            .field("trusted_hash", &self.inner.trusted_hasher.is_some())
+            .field("prehashed", &self.inner.prehashed)
+            .field("normalized", &self.inner.strategies.normalize.is_some())
+            .field("auto_cleanup", &cfg!(feature = "auto-cleanup"))
+            .finish_non_exhaustive()
+    }
+}
+
+/// # `DebugEntries<'a, T>`
+/// Formats the first values of an `HcTable<T>` that still have handles. Created with
+/// `HcTable::debug_entries()`.
+struct DebugEntries<'a, T, M>
+where
+    T: Hash + Eq,
+{
+    table: &'a HcTable<T, M>,
+    limit: usize,
+}
+
+impl<T, M> std::fmt::Debug for DebugEntries<'_, T, M>
+where
+    T: Hash + Eq + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let table = self.table.inner.read();
+        let live = table
+            .iter()
+            .filter(|entry| entry.weak.strong_count() > 0)
+            .map(|entry| &*entry.key);
+        f.debug_list().entries(live.take(self.limit)).finish()
+    }
+}
+
+#[cfg(not(feature = "auto-cleanup"))]
+/// # `Checkpoint`
+/// The values stored in a `HcTable` at some point. Created with `HcTable::checkpoint()`.
+///
+/// ## Fields
+/// * `seq`: The sequence number of the first value stored after the checkpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    seq: u64,
+}
+
+/// # `Namespace<'a, T>`
+/// A named part of a `HcTable<T>`, created with `HcTable::namespace()`.
+///
+/// Values interned through a namespace are stored in the table like any other value, so that
+/// equivalent values of different namespaces share their handle. The table remembers which
+/// values each namespace interned, until they are removed from it. Views of the same name share
+/// that membership.
+///
+/// ## Fields
+/// * `table`: The table the namespace is part of.
+/// * `name`: The name of the namespace.
+pub struct Namespace<'a, T, M = Structural> {
+    table: &'a HcTable<T, M>,
+    name: String,
+}
+
+impl<T, M> Namespace<'_, T, M>
+where
+    T: Hash + Eq,
+{
+    /// Returns the name of the namespace.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Interns `value` in the table of the namespace, like `HcTable::hashcons()`, and records it as
+    /// a value of the namespace.
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        let hc = self.table.hashcons(value);
+        self.table
+            .inner
+            .namespaces()
+            .entry(self.name.clone())
+            .or_default()
+            .insert(hc.inner.seq, hc.inner.hash);
+        hc
+    }
+
+    /// Returns `true` if `hc` was interned through this namespace of its table.
+    pub fn contains(&self, hc: &Hc<T, M>) -> bool {
+        Arc::ptr_eq(&hc.inner._table, &self.table.inner)
+            && self
+                .table
+                .inner
+                .namespaces()
+                .get(&self.name)
+                .is_some_and(|members| members.contains_key(&hc.inner.seq))
+    }
+
+    /// Returns a handle to every value interned through the namespace that still has one, in
+    /// the order they were stored. Forgets the values that were removed from the table.
+    pub fn handles(&self) -> Vec<Hc<T, M>> {
+        let mut handles = Vec::new();
+        self.visit_members(|weak| handles.extend(weak.upgrade().map(Hc::from_inner)));
+        handles.sort_by_key(|hc| hc.inner.seq);
+        handles
+    }
+
+    /// Returns the number of values interned through the namespace that still have handles.
+    /// Forgets the values that were removed from the table.
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.visit_members(|weak| len += usize::from(weak.strong_count() > 0));
+        len
+    }
+
+    /// Returns `true` if no value interned through the namespace still has a handle.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values that were interned through this namespace and no other one,
+    /// including values released by removing others, and forgets the namespace. Values that
+    /// are still in use are kept, as are values of other namespaces.
+    ///
+    /// ## Returns
+    /// How many entries were removed and remain, and how long the sweep took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let shared = table.namespace("parser").hashcons("ident");
+    /// drop(table.namespace("parser").hashcons("expr"));
+    /// drop(table.namespace("lexer").hashcons("ident"));
+    /// drop(shared);
+    ///
+    /// let report = table.namespace("parser").evict();
+    ///
+    /// assert_eq!((report.removed, report.remaining), (1, 1));
+    /// ```
+    pub fn evict(self) -> CleanupReport {
+        let mut namespaces = self.table.inner.namespaces();
+        let members = namespaces.remove(&self.name).unwrap_or_default();
+        let others: HashSet<u64> = namespaces
+            .values()
+            .flat_map(HashMap::keys)
+            .copied()
+            .collect();
+        drop(namespaces);
+        self.table
+            .inner
+            .sweep(|entry| members.contains_key(&entry.seq) && !others.contains(&entry.seq))
+    }
+
+    /// Calls `visit` with the weak reference of every value interned through the namespace
+    /// that is still stored, looking each one up in the bucket of its hash, and forgets the
+    /// values that were removed from the table.
+    fn visit_members(&self, mut visit: impl FnMut(&Weak<Inner<T>>)) {
+        let mut namespaces = self.table.inner.namespaces();
+        let Some(members) = namespaces.get_mut(&self.name) else {
+            return;
+        };
+        let table = self.table.inner.read();
+        members.retain(|&seq, &mut hash| {
+            let entry = table.bucket(hash).iter().find(|entry| entry.seq == seq);
+            entry.map(|entry| visit(&entry.weak)).is_some()
+        });
+    }
+}
+
+/// # `PinScope<'a, T>`
+/// A guard pinning the values interned in a `HcTable<T>` while it lives, created with
+/// `HcTable::pin_scope()`.
+///
+/// ## Fields
+/// * `table`: The table whose values are pinned.
+/// * `id`: The key of the values pinned by this scope in the table.
+pub struct PinScope<'a, T, M = Structural> {
+    table: &'a HcTable<T, M>,
+    id: u64,
+}
+
+impl<T, M> PinScope<'_, T, M> {
+    /// Returns the number of distinct values pinned by this scope.
+    pub fn len(&self) -> usize {
+        self.table.inner.lock_mutex(&self.table.inner.pins).scopes[&self.id].len()
+    }
+
+    /// Returns `true` if no value was interned since the scope started.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, M> Drop for PinScope<'_, T, M> {
+    /// Unpins the values of the scope. They are released after the pins are unlocked, since
+    /// releasing a value can remove it from the table.
+    fn drop(&mut self) {
+        let mut pins = self.table.inner.lock_mutex(&self.table.inner.pins);
+        let pinned = pins.scopes.remove(&self.id);
+        self.table.inner.pin_scopes.fetch_sub(1, Ordering::Relaxed);
+        drop(pins);
+        drop(pinned);
+    }
+}
+
+/// # `LiveSetDiff<T>`
+/// The values held by only one of two tables, created with `HcTable::diff_live_set()`.
+///
+/// Golden tests compare the table built by the code under test to an expected one, and
+/// report both lists when they differ.
+pub struct LiveSetDiff<T, M = Structural> {
+    /// The values of the first table missing from the second one, in storing order.
+    pub removed: Vec<Hc<T, M>>,
+    /// The values of the second table missing from the first one, in storing order.
+    pub added: Vec<Hc<T, M>>,
+}
+
+impl<T, M> LiveSetDiff<T, M> {
+    /// Returns `true` if both tables hold equivalent values.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+impl<T: std::fmt::Debug, M> std::fmt::Debug for LiveSetDiff<T, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LiveSetDiff")
+            .field("removed", &self.removed)
+            .field("added", &self.added)
+            .finish()
+    }
+}
+
+/// # `HcFork<T>`
+/// A child of a `HcTable<T>` recording its own additions, created with `HcTable::fork()`.
+///
+/// Values stored by the parent before or after the fork are shared with it, as long as the
+/// fork did not store an equivalent value first. Handles of the fork and of its parent can be
+/// mixed freely: with structural semantics they compare by value.
+///
+/// ## Fields
+/// * `parent`: The table the fork was created from.
+/// * `child`: The table holding the values stored by the fork.
+pub struct HcFork<T, M = Structural> {
+    parent: HcTable<T, M>,
+    child: HcTable<T, M>,
+}
+
+impl<T, M> HcFork<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Returns the handle of the value equivalent to `value` in the fork or its parent,
+    /// storing `value` in the fork if neither holds one.
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        let value = self.child.inner.normalize(value);
+        if let Some(hc) = self.child.lookup(&value) {
+            return hc;
+        }
+        if let Some(hc) = self.parent.lookup(&value) {
+            return hc;
+        }
+        self.child.hashcons(value)
+    }
+
+    /// Returns the table the fork was created from.
+    pub fn parent(&self) -> &HcTable<T, M> {
+        &self.parent
+    }
+
+    /// Returns a handle to every value stored by the fork that still has one, in the order
+    /// they were stored.
+    pub fn additions(&self) -> Vec<Hc<T, M>> {
+        self.child
+            .export_entries()
+            .into_iter()
+            .map(|(_, hc)| hc)
+            .collect()
+    }
+
+    /// Returns the id of the value of `hc` in the fork or its parent, or `None` if `hc` is a
+    /// handle of neither.
+    pub fn entry_id(&self, hc: &Hc<T, M>) -> Option<u64> {
+        self.child.entry_id(hc).or_else(|| self.parent.entry_id(hc))
+    }
+
+    /// Returns the number of values stored by the fork, counted like `HcTable::len()`.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Returns `true` if the fork stores no value.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> HcFork<T>
+where
+    T: Hash + Eq + Clone,
+{
+    /// Merges the values stored by the fork back into its parent, in the order they were stored.
+    ///
+    /// Each value is cloned into the parent, where it shares the handle of an equivalent value
+    /// the parent stored meanwhile. Handles held by the values, such as the children of a node,
+    /// are cloned as they are: they keep pointing into the fork and keep those values alive.
+    /// They are not entries of the parent, so its `entry_id` and `export_entries` do not
+    /// report them, and `resolve` does not find them by their tokens.
+    ///
+    /// Merging requires structural semantics, under which a merged node still equals the node
+    /// the parent builds from its own children. Handles of tables built with `identity()`
+    /// compare by address, so a merged node would never match it and the parent would store
+    /// both. To rebuild the merged nodes from children stored in the parent, use a
+    /// `dag::Transform` into the parent instead.
+    ///
+    /// ## Returns
+    /// The handles of the merged values in the parent, in the order they were stored.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::new();
+    /// let fork = table.fork();
+    /// let token = fork.hashcons("token".to_string());
+    ///
+    /// let merged = fork.merge();
+    ///
+    /// assert_eq!(merged, [token]);
+    /// assert!(table.hashcons("token".to_string()) == merged[0]);
+    /// ```
+    pub fn merge(self) -> Vec<Hc<T>> {
+        self.additions()
+            .iter()
+            .map(|hc| self.parent.hashcons(hc.get().clone()))
+            .collect()
+    }
+}
+
+impl<T, M> std::fmt::Debug for HcFork<T, M>
+where
+    T: Hash + Eq,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HcFork")
+            .field("parent", &self.parent)
+            .field("additions", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "tokio")]
+/// # `MaintenanceHandle`
+/// The task spawned by `HcTable::spawn_maintenance()`. Dropping the handle stops the task.
+///
+/// ## Fields
+/// * `task`: The maintenance task.
+/// * `stats`: The counters of the table as of the last sweep.
+/// * `poison_policy`: The poison policy of the table, also used for `stats`.
+/// * `prefix`: The prefix of messages about the table.
+pub struct MaintenanceHandle {
+    task: tokio::task::JoinHandle<()>,
+    stats: Arc<Mutex<TableStats>>,
+    poison_policy: PoisonPolicy,
+    prefix: String,
+}
+
+#[cfg(feature = "tokio")]
+impl MaintenanceHandle {
+    /// Returns the counters of the table as of the last sweep, or as of spawning the task if
+    /// it did not sweep yet.
+    pub fn stats(&self) -> TableStats {
+        *self.stats.lock().unwrap_or_else(|poisoned| {
+            self.poison_policy
+                .recover(&self.prefix, poisoned, || self.stats.clear_poison())
+        })
+    }
+
+    /// Returns `true` if the task stopped, because the table was dropped or the runtime shut
+    /// down.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl std::fmt::Debug for MaintenanceHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MaintenanceHandle")
+            .field("finished", &self.is_finished())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// # `Iter<T>`
+/// An iterator over a snapshot of the values of a `HcTable`, created with `HcTable::iter()`.
+///
+/// ## Fields
+/// * `handles`: The ids and handles of the values not visited yet, in storing order.
+pub struct Iter<T, M = Structural> {
+    handles: std::vec::IntoIter<(u64, Hc<T, M>)>,
+}
+
+impl<T, M> Iterator for Iter<T, M> {
+    type Item = Hc<T, M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.handles.next().map(|(_, hc)| hc)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.handles.size_hint()
+    }
+}
+
+impl<T, M> ExactSizeIterator for Iter<T, M> {}
+
+impl<T, M> std::fmt::Debug for Iter<T, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Iter")
+            .field("remaining", &self.handles.len())
+            .finish()
+    }
+}
+
+impl<T, M> IntoIterator for &HcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    type Item = Hc<T, M>;
+    type IntoIter = Iter<T, M>;
+
+    /// Iterates over a snapshot of the values of the table, see `HcTable::iter()`.
+    fn into_iter(self) -> Iter<T, M> {
+        self.iter()
+    }
+}
+
+/// The handles of a frozen table, grouped by hash.
+type HandleMap<T, M> = HashMap<u128, Vec<Hc<T, M>>, BuildHasherDefault<IdentityHasher>>;
+
+/// # `FrozenHcTable<T>`
+/// A read-only snapshot of a `HcTable<T>`, created with `HcTable::freeze()`.
+///
+/// The snapshot owns a handle to each of its values and looks them up with the strategies of
+/// the table it was taken from, without locking. Clones share the snapshot.
+///
+/// ## Fields
+/// * `table`: The table the snapshot was taken from, whose strategies hash and compare values.
+/// * `map`: The handles of the values, grouped by hash.
+/// * `len`: The number of values.
+pub struct FrozenHcTable<T, M = Structural> {
+    table: Arc<InnerTable<T>>,
+    map: Arc<HandleMap<T, M>>,
+    len: usize,
+}
+
+impl<T, M> FrozenHcTable<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Returns the handle of the value equivalent to `value`, if the snapshot holds one.
+    ///
+    /// ## Note
+    /// `value` is looked up as given. A normalization configured for the table is not applied,
+    /// as it takes values by ownership.
+    pub fn get(&self, value: &T) -> Option<&Hc<T, M>> {
+        let hash = self.table.hash(value);
+        self.map
+            .get(&hash)?
+            .iter()
+            .find(|hc| self.table.matches(&hc.inner.elem, value))
+    }
+
+    /// Returns `true` if the snapshot holds a value equivalent to `value`.
+    pub fn contains(&self, value: &T) -> bool {
+        self.get(value).is_some()
+    }
+
+    /// Returns the handles of all values in the snapshot, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Hc<T, M>> {
+        self.map.values().flatten()
+    }
+
+    /// Returns the number of values in the snapshot.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the snapshot holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T, M> Clone for FrozenHcTable<T, M> {
+    fn clone(&self) -> Self {
+        FrozenHcTable {
+            table: self.table.clone(),
+            map: self.map.clone(),
+            len: self.len,
+        }
+    }
+}
+
+/// # `HcPairTable<T, R>`
+/// A memo cache of a binary operation on handles, such as the apply operation of binary
+/// decision diagrams or the unifier of two terms.
+///
+/// Results are cached under the ids of the operand handles, so that a lookup hashes two integers
+/// however large the operands. The cache only holds weak references: it keeps neither operands
+/// nor results alive, and a result is forgotten once the operands or the result are dropped.
+/// Forgotten entries are purged as the cache grows, or by `purge`.
+///
+/// ## Type Parameters
+/// * `T` - The type of the operands.
+/// * `R` - The type of the results.
+/// * `M` - The semantics of the handles, see `HcTableBuilder::identity()`.
+///
+/// ## Fields
+/// * `cache`: The cached results, and the number of entries after which the cache is purged.
+/// * `poison_policy`: How the cache handles its lock being poisoned, see `with_poison_policy`.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{Hc, HcPairTable, HcTable};
+///
+/// let table = HcTable::new();
+/// let sums = HcPairTable::new();
+/// let (a, b) = (table.hashcons(2u64), table.hashcons(3u64));
+///
+/// let add = |a: &Hc<u64>, b: &Hc<u64>| sums.get_or_insert_with(a, b, || table.hashcons(**a + **b));
+/// let sum = add(&a, &b);
+///
+/// assert_eq!(*sum, 5);
+/// assert!(sums.get(&a, &b) == Some(sum));
+/// assert!(sums.get(&b, &a).is_none());
+/// ```
+pub struct HcPairTable<T, R = T, M = Structural> {
+    cache: Mutex<PairCache<T, R>>,
+    poison_policy: PoisonPolicy,
+    _semantics: PhantomData<fn() -> M>,
+}
+
+/// The cached results of a `HcPairTable`, by the ids of their operands.
+///
+/// ## Fields
+/// * `map`: The operands and result of each entry.
+/// * `purge_at`: The number of entries at which the next insertion purges the cache.
+struct PairCache<T, R> {
+    map: HashMap<(u64, u64), PairEntry<T, R>>,
+    purge_at: usize,
+}
+
+/// The weak references to the operands and the result of an operation.
+struct PairEntry<T, R> {
+    left: Weak<Inner<T>>,
+    right: Weak<Inner<T>>,
+    result: Weak<Inner<R>>,
+}
+
+impl<T, R> PairEntry<T, R> {
+    /// Returns `true` if the entry was stored for these operands. Ids are only unique within a
+    /// table, so the operands of entries sharing them are told apart by their addresses.
+    fn is_for(&self, left: &Arc<Inner<T>>, right: &Arc<Inner<T>>) -> bool {
+        self.left.as_ptr() == Arc::as_ptr(left) && self.right.as_ptr() == Arc::as_ptr(right)
+    }
+
+    /// Returns `true` if the operands and result of the entry are alive.
+    fn is_alive(&self) -> bool {
+        self.left.strong_count() > 0
+            && self.right.strong_count() > 0
+            && self.result.strong_count() > 0
+    }
+}
+
+/// The least number of entries at which a `HcPairTable` is purged.
+const PAIR_PURGE_MIN: usize = 64;
+
+impl<T, R, M> HcPairTable<T, R, M> {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::with_poison_policy(PoisonPolicy::default())
+    }
+
+    /// Creates an empty cache handling its lock being poisoned as `policy` says. The cached
+    /// entries stay consistent when a holder of the lock panics, so continuing is sound.
+    pub fn with_poison_policy(policy: PoisonPolicy) -> Self {
+        HcPairTable {
+            cache: Mutex::new(PairCache {
+                map: HashMap::new(),
+                purge_at: PAIR_PURGE_MIN,
+            }),
+            poison_policy: policy,
+            _semantics: PhantomData,
+        }
+    }
+
+    /// Acquires the lock of the cache, handling a poisoned one as the poison policy says.
+    fn lock(&self) -> MutexGuard<'_, PairCache<T, R>> {
+        self.cache.lock().unwrap_or_else(|poisoned| {
+            self.poison_policy
+                .recover(&message_prefix(None), poisoned, || {
+                    self.cache.clear_poison()
+                })
+        })
+    }
+
+    /// Returns the cached result of the operation on `left` and `right`, if it is still alive.
+    pub fn get(&self, left: &Hc<T, M>, right: &Hc<T, M>) -> Option<Hc<R, M>> {
+        let mut cache = self.lock();
+        let key = (left.seq(), right.seq());
+        let entry = cache.map.get(&key)?;
+        if !entry.is_for(&left.inner, &right.inner) {
+            return None;
+        }
+        match entry.result.upgrade() {
+            Some(result) => Some(Hc::from_inner(result)),
+            None => {
+                cache.map.remove(&key);
+                None
+            }
+        }
+    }
+
+    /// Caches `result` as the result of the operation on `left` and `right`.
+    pub fn insert(&self, left: &Hc<T, M>, right: &Hc<T, M>, result: &Hc<R, M>) {
+        let entry = PairEntry {
+            left: Arc::downgrade(&left.inner),
+            right: Arc::downgrade(&right.inner),
+            result: Arc::downgrade(&result.inner),
+        };
+        let mut cache = self.lock();
+        cache.map.insert((left.seq(), right.seq()), entry);
+        if cache.map.len() >= cache.purge_at {
+            cache.map.retain(|_, entry| entry.is_alive());
+            cache.purge_at = (cache.map.len() * 2).max(PAIR_PURGE_MIN);
+        }
+    }
+
+    /// Returns the cached result of the operation on `left` and `right`, computing and caching it
+    /// with `compute` if there is none.
+    ///
+    /// `compute` runs without the cache being locked, so that recursive operations can use the
+    /// cache for their subproblems.
+    pub fn get_or_insert_with(
+        &self,
+        left: &Hc<T, M>,
+        right: &Hc<T, M>,
+        compute: impl FnOnce() -> Hc<R, M>,
+    ) -> Hc<R, M> {
+        if let Some(result) = self.get(left, right) {
+            return result;
+        }
+        let result = compute();
+        self.insert(left, right, &result);
+        result
+    }
+
+    /// Removes the entries whose operands or result were dropped.
+    ///
+    /// ## Returns
+    /// The number of removed entries.
+    pub fn purge(&self) -> usize {
+        let mut cache = self.lock();
+        let before = cache.map.len();
+        cache.map.retain(|_, entry| entry.is_alive());
+        cache.purge_at = (cache.map.len() * 2).max(PAIR_PURGE_MIN);
+        before - cache.map.len()
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) {
+        let mut cache = self.lock();
+        cache.map.clear();
+        cache.purge_at = PAIR_PURGE_MIN;
+    }
+
+    /// Returns the number of entries, including forgotten ones that were not purged yet.
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, R, M> Default for HcPairTable<T, R, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R, M> std::fmt::Debug for HcPairTable<T, R, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HcPairTable")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// An operation registered with a `ConsEngine`, and the cache of its results.
+type EngineOp<T, M> = (
+    Box<dyn Fn(&ConsEngine<T, M>, &Hc<T, M>, &Hc<T, M>) -> Hc<T, M> + Send + Sync>,
+    HcPairTable<T, T, M>,
+);
+
+/// # `Op`
+/// A binary operation registered with a `ConsEngine`, see `ConsEngine::register()`.
+///
+/// ## Fields
+/// * `index`: The position of the operation in the engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Op {
+    index: usize,
+}
+
+/// # `ConsEngine<T>`
+/// A `HcTable<T>` bundled with a memo cache for each of the binary operations on its values, in
+/// the way the unique table and the computed table of a decision diagram package work together.
+///
+/// Operations are registered once and applied by `apply`, which returns the cached result if
+/// there is one and computes it otherwise. Operations receive the engine, so that they can
+/// intern values and apply operations to subproblems. The caches only hold weak references,
+/// so they never keep values alive or return values the table removed, and `cleanup` and
+/// `purge` drop their stale entries together with the values of the table.
+///
+/// ## Fields
+/// * `table`: The table the values are interned in.
+/// * `ops`: The registered operations with their caches, indexed by `Op`.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{ConsEngine, Hc};
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum Nat {
+///     Zero,
+///     Succ(Hc<Nat>),
+/// }
+///
+/// let mut engine = ConsEngine::new();
+/// let add = engine.register(|engine, a, b: &Hc<Nat>| match b.get() {
+///     Nat::Zero => a.clone(),
+///     Nat::Succ(pred) => {
+///         let sum = engine.apply(engine.op(0), a, pred);
+///         engine.hashcons(Nat::Succ(sum))
+///     }
+/// });
+///
+/// let zero = engine.hashcons(Nat::Zero);
+/// let one = engine.hashcons(Nat::Succ(zero.clone()));
+/// let two = engine.apply(add, &one, &one);
+///
+/// assert!(engine.apply(add, &one, &one) == two);
+/// assert!(matches!(two.get(), Nat::Succ(pred) if *pred == one));
+/// assert_eq!(engine.cached(), 2);
+/// ```
+pub struct ConsEngine<T, M = Structural> {
+    table: HcTable<T, M>,
+    ops: Vec<EngineOp<T, M>>,
+}
+
+impl<T> ConsEngine<T>
+where
+    T: Hash + Eq,
+{
+    /// Creates an engine with a new table and no operations.
+    pub fn new() -> Self {
+        Self::with_table(HcTable::new())
+    }
+}
+
+impl<T, M> ConsEngine<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Creates an engine interning values in `table`, e.g. a table built with custom strategies.
+    pub fn with_table(table: HcTable<T, M>) -> Self {
+        ConsEngine {
+            table,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Returns the table of the engine.
+    pub fn table(&self) -> &HcTable<T, M> {
+        &self.table
+    }
+
+    /// Interns `value` in the table of the engine, see `HcTable::hashcons()`.
+    pub fn hashcons(&self, value: T) -> Hc<T, M> {
+        self.table.hashcons(value)
+    }
+
+    /// Registers a binary operation, with an empty cache.
+    ///
+    /// The operation must be a function of its operands: its results are cached by operands and
+    /// reused for as long as they are alive.
+    ///
+    /// ## Returns
+    /// The `Op` to apply the operation with. Operations are numbered in registration order,
+    /// see `op`.
+    pub fn register(
+        &mut self,
+        op: impl Fn(&Self, &Hc<T, M>, &Hc<T, M>) -> Hc<T, M> + Send + Sync + 'static,
+    ) -> Op {
+        let cache = HcPairTable::with_poison_policy(self.table.inner.poison_policy);
+        self.ops.push((Box::new(op), cache));
+        Op {
+            index: self.ops.len() - 1,
+        }
+    }
+
+    /// Returns the `Op` of the operation registered `index`-th, e.g. for operations that apply
+    /// themselves recursively.
+    ///
+    /// ## Panics
+    /// If fewer than `index + 1` operations are registered.
+    pub fn op(&self, index: usize) -> Op {
+        assert!(index < self.ops.len(), "no operation {index} is registered");
+        Op { index }
+    }
+
+    /// Applies `op` to `left` and `right`, returning the cached result if there is one.
+    ///
+    /// ## Panics
+    /// If `op` was registered with another engine that has more operations.
+    pub fn apply(&self, op: Op, left: &Hc<T, M>, right: &Hc<T, M>) -> Hc<T, M> {
+        let (compute, cache) = &self.ops[op.index];
+        cache.get_or_insert_with(left, right, || compute(self, left, right))
+    }
+
+    /// Returns the number of cached results of all operations, including forgotten ones that
+    /// were not purged yet.
+    pub fn cached(&self) -> usize {
+        self.ops.iter().map(|(_, cache)| cache.len()).sum()
+    }
+
+    /// Removes the cached results whose operands or result were dropped, see
+    /// `HcPairTable::purge()`.
+    ///
+    /// ## Returns
+    /// The number of removed results.
+    pub fn purge(&self) -> usize {
+        self.ops.iter().map(|(_, cache)| cache.purge()).sum()
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the table, see `HcTable::cleanup()`, and then removes the cached results of the
+    /// removed values.
+    ///
+    /// ## Returns
+    /// How many entries of the table were removed and remain, and how long the cleanup took.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::ConsEngine;
+    ///
+    /// let mut engine = ConsEngine::new();
+    /// let add = engine.register(|engine, a, b| engine.hashcons(**a + **b));
+    /// let (one, two) = (engine.hashcons(1u32), engine.hashcons(2u32));
+    /// drop(engine.apply(add, &one, &two));
+    ///
+    /// let report = engine.cleanup();
+    ///
+    /// assert_eq!((report.removed, report.remaining), (1, 2));
+    /// assert_eq!(engine.cached(), 0);
+    /// ```
+    pub fn cleanup(&self) -> CleanupReport {
+        let report = self.table.cleanup();
+        self.purge();
+        report
+    }
+}
+
+impl<T> Default for ConsEngine<T>
+where
+    T: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # `Migration<'a, S, T>`
+/// Moves values from single-threaded handles into a `HcTable<T>`, e.g. once a build phase goes
+/// parallel, and maps every migrated handle to the handle of its copy.
+///
+/// Values are converted by a function that migrates the handles they hold through the same
+/// `Migration`, so that shared subterms stay shared in the target table. Each source handle is
+/// converted once, and kept alive until the `Migration` is dropped so that its address cannot
+/// be reused by another value.
+///
+/// ## Type Parameters
+/// * `S` - The type of values in the single-threaded table.
+/// * `T` - The type of values in the target table.
+///
+/// ## Fields
+/// * `table`: The table values are migrated into.
+/// * `handles`: The migrated handles and their copies, by address of the migrated handle.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{single_threaded, thread_safe};
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum Expr {
+///     Leaf(u32),
+///     Neg(single_threaded::Hc<Expr>),
+/// }
+///
+/// #[derive(Hash, PartialEq, Eq)]
+/// enum SyncExpr {
+///     Leaf(u32),
+///     Neg(thread_safe::Hc<SyncExpr>),
+/// }
+///
+/// fn convert(expr: &Expr, migration: &mut thread_safe::Migration<Expr, SyncExpr>) -> SyncExpr {
+///     match expr {
+///         Expr::Leaf(n) => SyncExpr::Leaf(*n),
+///         Expr::Neg(child) => SyncExpr::Neg(migration.migrate(child, convert)),
+///     }
+/// }
+///
+/// let source = single_threaded::HcTable::new();
+/// let leaf = source.hashcons(Expr::Leaf(1));
+/// let neg = source.hashcons(Expr::Neg(leaf.clone()));
+///
+/// let target = thread_safe::HcTable::new();
+/// let mut migration = thread_safe::Migration::new(&target);
+/// assert_eq!(migration.migrate_table(&source, convert), 2);
+///
+/// let sync_leaf = migration.get(&leaf).unwrap();
+/// let sync_neg = migration.get(&neg).unwrap();
+/// assert!(matches!(&*sync_neg, SyncExpr::Neg(child) if *child == sync_leaf));
+/// assert_eq!(target.len(), 2);
+/// ```
+pub struct Migration<'a, S, T>
+where
+    S: Hash + Eq,
+    T: Hash + Eq,
+{
+    table: &'a HcTable<T>,
+    handles: HashMap<usize, (single_threaded::Hc<S>, Hc<T>)>,
+}
+
+impl<'a, S, T> Migration<'a, S, T>
+where
+    S: Hash + Eq,
+    T: Hash + Eq,
+{
+    /// Creates a `Migration` into `table`.
+    pub fn new(table: &'a HcTable<T>) -> Self {
+        Migration {
+            table,
+            handles: HashMap::new(),
+        }
+    }
+
+    /// Returns the copy of the value of `handle`, converting it with `convert` unless it was
+    /// migrated before.
+    ///
+    /// ## Parameters
+    /// * `handle`: The handle to migrate.
+    /// * `convert`: Converts a value, migrating the handles it holds with this `Migration`.
+    ///
+    /// ## Note
+    /// Children are migrated recursively, so very deep values can exhaust the stack.
+    pub fn migrate(
+        &mut self,
+        handle: &single_threaded::Hc<S>,
+        convert: fn(&S, &mut Self) -> T,
+    ) -> Hc<T> {
+        if let Some((_, migrated)) = self.handles.get(&handle.addr()) {
+            return migrated.clone();
+        }
+        let value = convert(handle.get(), self);
+        let migrated = self.table.hashcons(value);
+        self.handles
+            .insert(handle.addr(), (handle.clone(), migrated.clone()));
+        migrated
+    }
+
+    /// Migrates every value of `source` that still has a handle.
+    ///
+    /// ## Returns
+    /// The number of values that were not migrated before.
+    pub fn migrate_table(
+        &mut self,
+        source: &single_threaded::HcTable<S>,
+        convert: fn(&S, &mut Self) -> T,
+    ) -> usize {
+        let migrated = self.handles.len();
+        for handle in source.handles() {
+            self.migrate(&handle, convert);
+        }
+        self.handles.len() - migrated
+    }
+
+    /// Returns the copy of the value of `handle`, if it was migrated.
+    pub fn get(&self, handle: &single_threaded::Hc<S>) -> Option<Hc<T>> {
+        self.handles
+            .get(&handle.addr())
+            .map(|(_, migrated)| migrated.clone())
+    }
+
+    /// Returns the number of migrated handles.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Returns `true` if no handle was migrated yet.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+}
+
+/// # `HcTableBuilder<T>`
+/// Configures the strategies of a new `HcTable<T>`. Created with `HcTable::builder()`.
+///
+/// ## Fields
+/// * `eq`: The equivalence deciding whether two values share a handle.
+/// * `hash`: The hash function grouping candidate values.
+/// * `normalize`: The canonicalization applied before lookup, if any.
+/// * `weight`: The weight function of values, if any.
+/// * `custom_strategies`: Whether the equivalence, hash function or normalization was set.
+/// * `trusted_hash`: Whether values are identified by a 128-bit hash alone.
+/// * `prehashed`: Whether the data written by `hash` is used as the hash without rehashing it.
+/// * `seed`: The seed of the hasher, or `None` for a random one.
+/// * `deferred_reclamation`: Whether dropping the last handle only queues the entry for removal.
+/// * `collision_warning`: The hook called when too many values share a hash, if any.
+/// * `drift_check`: The hook called with the values whose hash changed, if any.
+/// * `ids`: The allocator of the ids of the table.
+/// * `stall`: The hook called when an operation waits too long for the lock, if any.
+/// * `_semantics`: The semantics of the handles of the built table.
+///
+pub struct HcTableBuilder<T, M = Structural> {
+    eq: Box<dyn ConsEq<T> + Send + Sync>,
+    hash: Box<dyn ConsHash<T> + Send + Sync>,
+    normalize: Option<Box<dyn ConsNormalize<T> + Send + Sync>>,
+    weight: Option<Box<dyn ConsWeight<T> + Send + Sync>>,
+    custom_strategies: bool,
+    trusted_hash: bool,
+    prehashed: bool,
+    seed: Option<u64>,
+    #[cfg(feature = "auto-cleanup")]
+    deferred_reclamation: bool,
+    collision_warning: Option<CollisionWarning>,
+    drift_check: Option<DriftCheck<T>>,
+    ids: Box<dyn IdAllocator>,
+    stall: Option<Stall>,
+    on_evict: Option<EvictObserver<T>>,
+    name: Option<&'static str>,
+    poison_policy: PoisonPolicy,
+    _semantics: PhantomData<fn() -> M>,
+}
+
+impl<T, M> HcTableBuilder<T, M>
+where
+    T: Hash + Eq,
+{
+    /// Sets the equivalence deciding whether two values share a handle, `T::eq` by default.
+    ///
+    /// ## Parameters
+    /// * `eq`: The equivalence, which must be consistent with the hash function, see `ConsEq`.
+    pub fn equivalence(mut self, eq: impl ConsEq<T> + Send + Sync + 'static) -> Self {
+        self.eq = Box::new(eq);
+        self.custom_strategies = true;
+        self
+    }
+
+    /// Sets the hash function grouping candidate values, `T::hash` by default.
+    ///
+    /// ## Parameters
+    /// * `hash`: The hash function, which must hash equivalent values equally, see `ConsHash`.
+    pub fn hashing(mut self, hash: impl ConsHash<T> + Send + Sync + 'static) -> Self {
+        self.hash = Box::new(hash);
+        self.custom_strategies = true;
+        self
+    }
+
+    /// Sets a canonicalization applied to every value before it is looked up.
+    ///
+    /// ## Parameters
+    /// * `normalize`: The canonicalization, see `ConsNormalize`.
+    pub fn normalize(mut self, normalize: impl ConsNormalize<T> + Send + Sync + 'static) -> Self {
+        self.normalize = Some(Box::new(normalize));
+        self.custom_strategies = true;
+        self
+    }
+
+    /// Sets the weight function of values, used by `evict_until_weight`. Every value weighs
+    /// `1` by default.
+    ///
+    /// ## Parameters
+    /// * `weight`: The weight function, see `ConsWeight`.
+    pub fn weight(mut self, weight: impl ConsWeight<T> + Send + Sync + 'static) -> Self {
+        self.weight = Some(Box::new(weight));
+        self
+    }
+
+    /// Identifies values by a 128-bit hash alone, without ever comparing them.
+    ///
+    /// This avoids a structural comparison on every lookup of an existing value, which is
+    /// worthwhile for very large values. Interning and dropping a value hash it twice instead.
+    ///
+    /// ## Note
+    /// Two different values whose hashes collide are merged into one handle, and the second
+    /// one silently becomes the first. How likely that is depends on the hash function, see
+    /// the `hasher` module:
+    /// * SipHash, the default, and `ahash` are keyed, randomly unless the table is built with
+    ///   `seed`. Among `n` values, two collide with a probability of about `n² / 2^129`, also
+    ///   for values chosen by someone who does not know the keys.
+    /// * With the `fxhash` feature, both halves of the hash are FxHash of the value with
+    ///   different salts. FxHash is not collision-resistant, so values crafted to collide, and
+    ///   possibly regularly structured ones, collide in both halves. Do not use this mode with
+    ///   `fxhash` unless merging distinct values is acceptable.
+    /// * Tables built with `prehashed` use the 128-bit fingerprint of the values as it is, so
+    ///   the risk is that of the fingerprint.
+    ///
+    /// A `ConsHash` that ignores parts of the value merges every value that only differs in
+    /// those parts. The configured `ConsEq` is not used in this mode.
+    pub fn trusted_hash(mut self) -> Self {
+        self.trusted_hash = true;
+        self
+    }
+
+    /// Uses the fingerprint written by the hash function as the hash, without rehashing it.
+    ///
+    /// For values that carry a high-quality hash already, e.g. a content fingerprint, this
+    /// skips hashing entirely. The hash function should write exactly one `u64`, or a `u128` in
+    /// trusted-hash mode, see `IdentityHasher`.
+    ///
+    /// ## Note
+    /// The table does not randomize fingerprints, so a poor fingerprint degrades every lookup.
+    /// `hash_diagnostics` shows whether values collide.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    /// use std::hash::Hasher;
+    ///
+    /// let table = HcTable::builder()
+    ///     .hashing(|value: &(u64, String), state: &mut dyn Hasher| state.write_u64(value.0))
+    ///     .prehashed()
+    ///     .build();
+    ///
+    /// let file = table.hashcons((0x5eed_f00d, "contents".to_string()));
+    ///
+    /// assert_eq!(table.hashcons((0x5eed_f00d, "contents".to_string())), file);
+    /// ```
+    pub fn prehashed(mut self) -> Self {
+        self.prehashed = true;
+        self
+    }
+
+    /// Seeds the hasher of the table with `seed` instead of a random value.
+    ///
+    /// Tables built with the same seed hash every value the same way and lay out their entries
+    /// identically, so that anything depending on the order of the entries is reproducible
+    /// across runs. This holds for one build of the crate on one platform, since the hash
+    /// functions do not promise stable output across versions.
+    ///
+    /// ## Note
+    /// Randomly seeded tables resist inputs crafted to collide. Only pin the seed if the values
+    /// do not come from an untrusted source.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let first = HcTable::builder().seed(42).build();
+    /// let second = HcTable::builder().seed(42).build();
+    /// let values: Vec<_> = (0..100)
+    ///     .flat_map(|i| [first.hashcons(i), second.hashcons(i)])
+    ///     .collect();
+    ///
+    /// assert_eq!(
+    ///     format!("{:?}", first.debug_entries(100)),
+    ///     format!("{:?}", second.debug_entries(100))
+    /// );
+    /// ```
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    #[cfg(feature = "auto-cleanup")]
+    /// Leaves the entries of dropped values queued until `collect_retired` is called or the
+    /// queue fills up.
+    ///
+    /// Dropping the last handle of a value never takes the write lock of the table, it only
+    /// queues the entry. By default the next intern or query removes the queued entries first,
+    /// so that their cost falls on the threads using the table. With this option they are
+    /// removed in batches instead, e.g. by a background thread, and still count towards `len`
+    /// until then.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::builder().deferred_reclamation().build();
+    /// drop(table.hashcons(5));
+    ///
+    /// assert_eq!(table.len(), 1);
+    /// assert_eq!(table.collect_retired(), 1);
+    /// assert!(table.is_empty());
+    /// ```
+    pub fn deferred_reclamation(mut self) -> Self {
+        self.deferred_reclamation = true;
+        self
+    }
+
+    /// Calls `report` with the number of values sharing a hash the first time more than
+    /// `threshold` distinct values share one, which points to a `Hash` implementation that
+    /// ignores most of the value.
+    ///
+    /// The hook is called after the lock of the table is released.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::thread_safe::HcTable;
     ///
-    /// drop(value);
-    /// table.cleanup();
+    /// let table = HcTable::builder()
+    ///     .collision_warning(64, |shared| {
+    ///         eprintln!("{shared} values share one hash, check the `Hash` implementation")
+    ///     })
+    ///     .build();
+    ///
+    /// assert_eq!(*table.hashcons(5), 5);
+    /// ```
+    pub fn collision_warning(
+        mut self,
+        threshold: usize,
+        report: impl Fn(usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.collision_warning = Some(CollisionWarning {
+            threshold,
+            report: Box::new(report),
+        });
+        self
+    }
+
+    /// Checks the hashes of the next `every` stored values every time `every` new values were
+    /// stored, and calls `report` with each value whose hash changed, see `hash_drift`.
+    ///
+    /// The checks visit the stored values in rounds, so each of them is checked once every
+    /// time the table stored about as many new values as it holds. The values are hashed and
+    /// reported after the lock of the table is released.
+    ///
+    /// ## Example
     /// ```
+    /// use hash_cons::thread_safe::HcTable;
+    ///
+    /// let table = HcTable::builder()
+    ///     .hash_drift_check(64, |_: &String| eprintln!("the hash of a value changed"))
+    ///     .build();
     ///
-    pub fn cleanup(&self) {
-        self.inner.cleanup();
+    /// assert_eq!(*table.hashcons("hash".to_string()), "hash");
+    /// ```
+    pub fn hash_drift_check(
+        mut self,
+        every: usize,
+        report: impl Fn(&T) + Send + Sync + 'static,
+    ) -> Self {
+        self.drift_check = Some(DriftCheck {
+            every: every.max(1),
+            report: Box::new(report),
+        });
+        self
     }
 
-    /// Returns the number of elements currently stored in the `HcTable`.
+    /// Calls `hook` with the time waited whenever an operation waited `timeout` for the lock of
+    /// the table, and keeps waiting.
     ///
-    /// ## Returns
-    /// The number of elements in the `HcTable`.
+    /// This makes a table stuck in a deadlock, or starved by a thread that holds its lock for
+    /// too long, visible. `HcTable::hashcons_timeout` gives up instead.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::thread_safe::HcTable;
+    /// use std::time::Duration;
+    ///
+    /// let table = HcTable::builder()
+    ///     .on_stall(Duration::from_secs(1), |waited| {
+    ///         eprintln!("waited {waited:?} for the lock of the table")
+    ///     })
+    ///     .build();
+    ///
+    /// assert_eq!(*table.hashcons(5), 5);
+    /// ```
+    pub fn on_stall(
+        mut self,
+        timeout: Duration,
+        hook: impl Fn(Duration) + Send + Sync + 'static,
+    ) -> Self {
+        self.stall = Some(Stall {
+            timeout,
+            hook: Box::new(hook),
+        });
+        self
+    }
+
+    /// Calls `observe` with each value evicted from the table, just before it is dropped.
+    ///
+    /// Entries removed by sweeps, by dropping their last handle, or by an equivalent value
+    /// taking over their entry are observed, so that their values can be written behind to disk
+    /// or to an external cache. Values taken back with `remove` or dropped with the table are
+    /// not. `observe` runs after the lock of the table is released, so it may use the table.
     ///
     /// ## Example
     /// ```
     /// use hash_cons::HcTable;
+    /// use std::sync::{Arc, Mutex};
     ///
-    /// let table = HcTable::new();
-    /// let value = table.hashcons(5);
+    /// let spilled = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = spilled.clone();
+    /// let table = HcTable::builder()
+    ///     .on_evict(move |value: &String| sink.lock().unwrap().push(value.clone()))
+    ///     .build();
     ///
-    /// assert_eq!(table.len(), 1);
+    /// drop(table.hashcons("cold".to_string()));
+    /// # #[cfg(not(feature = "auto-cleanup"))]
+    /// # table.cleanup();
+    ///
+    /// assert!(table.is_empty());
+    /// assert_eq!(*spilled.lock().unwrap(), ["cold"]);
     /// ```
+    pub fn on_evict(mut self, observe: impl Fn(&T) + Send + Sync + 'static) -> Self {
+        self.on_evict = Some(EvictObserver {
+            observe: Box::new(observe),
+        });
+        self
+    }
+
+    /// Names the table, so that its `Debug` output, `stats`, leak reports and warnings tell it
+    /// apart from the other tables of the process. Forks keep the name of their table.
     ///
-    pub fn len(&self) -> usize {
-        self.inner.len()
+    /// ## Example
+    /// ```
+    /// use hash_cons::HcTable;
+    ///
+    /// let table = HcTable::builder().name("types").build();
+    /// let _value = table.hashcons(5);
+    ///
+    /// assert_eq!(table.name(), Some("types"));
+    /// assert!(table.stats().to_string().starts_with("types: 1 entries"));
+    /// ```
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
     }
-}
 
-impl<T> Clone for HcTable<T>
-where
-    T: Hash + Eq,
-{
-    /// Provides the functionality to clone `HcTable<T>` instances.
+    /// Sets how the table handles its locks being poisoned by a panic, see [`PoisonPolicy`].
+    /// The policy covers the lock on the stored values and the locks guarding pins,
+    /// namespaces and maintenance statistics.
     ///
-    /// ## Returns
-    /// A new `HcTable<T>` instance with the same values as the original.
+    /// By default, the table warns and continues with the lock. `PoisonPolicy::Panic` makes
+    /// every operation on a poisoned table panic instead, for code that must not continue
+    /// with a table a panic interrupted.
+    ///
+    /// ## Note
+    /// An operation called while unwinding, e.g. by dropping a handle, aborts the process if it
+    /// panics, so strict tables are best dropped together with their handles.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::thread_safe::HcTable;
+    /// use hash_cons::PoisonPolicy;
+    ///
+    /// let table = HcTable::builder().poison_policy(PoisonPolicy::Panic).build();
+    ///
+    /// assert_eq!(*table.hashcons(1u32), 1);
+    /// ```
+    pub fn poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.poison_policy = policy;
+        self
+    }
+
+    /// Makes the ids of the table start at `prefix << 48`, so that tables given distinct
+    /// prefixes, e.g. one per process of a cluster, never assign the same id.
+    ///
+    /// The ids reported by `export_entries` can then be collected from several processes into
+    /// one map without renumbering them, and the upper 16 bits of an id tell which table
+    /// assigned it. Ids still increase in storing order.
+    ///
+    /// ## Panics
+    /// The table panics when storing a value after it assigned all 2^48 ids with the prefix,
+    /// instead of assigning ids with the next prefix.
     ///
     /// ## Example
     /// ```
     /// use hash_cons::HcTable;
     ///
-    /// let table = HcTable::new();
-    /// let value = table.hashcons(5);
-    /// let table_clone = table.clone();
+    /// let first = HcTable::builder().id_prefix(1).build();
+    /// let second = HcTable::builder().id_prefix(2).build();
+    /// let (a, b) = (first.hashcons("x"), second.hashcons("x"));
     ///
-    /// assert_eq!(table.len(), table_clone.len());
+    /// let (a, b) = (first.entry_id(&a).unwrap(), second.entry_id(&b).unwrap());
+    /// assert_ne!(a, b);
+    /// assert_eq!((a >> 48, b >> 48), (1, 2));
     /// ```
+    pub fn id_prefix(self, prefix: u16) -> Self {
+        let first = (prefix as u64) << 48;
+        self.ids(SequentialIds::within(first..=first | ((1 << 48) - 1)))
+    }
+
+    /// Sets the allocator of the ids of the table, [`SequentialIds`] from `0` by default.
     ///
-    fn clone(&self) -> Self {
+    /// Forks of the table draw their ids from the same allocator.
+    pub fn ids(mut self, ids: impl IdAllocator + 'static) -> Self {
+        self.ids = Box::new(ids);
+        self
+    }
+
+    /// Makes the handles of the table compare and hash by the address of their value instead
+    /// of the value itself, see [`Identity`].
+    pub fn identity(self) -> HcTableBuilder<T, Identity> {
+        HcTableBuilder {
+            eq: self.eq,
+            hash: self.hash,
+            normalize: self.normalize,
+            weight: self.weight,
+            custom_strategies: self.custom_strategies,
+            trusted_hash: self.trusted_hash,
+            prehashed: self.prehashed,
+            seed: self.seed,
+            #[cfg(feature = "auto-cleanup")]
+            deferred_reclamation: self.deferred_reclamation,
+            collision_warning: self.collision_warning,
+            drift_check: self.drift_check,
+            ids: self.ids,
+            stall: self.stall,
+            on_evict: self.on_evict,
+            name: self.name,
+            poison_policy: self.poison_policy,
+            _semantics: PhantomData,
+        }
+    }
+
+    /// Creates the configured `HcTable`.
+    pub fn build(self) -> HcTable<T, M> {
         HcTable {
-            inner: self.inner.clone(),
+            _semantics: PhantomData,
+            inner: Arc::new(InnerTable::new(self)),
         }
     }
 }
@@ -526,25 +4301,33 @@ where
 ///
 /// ## Fields
 /// * `elem`: The actual stored value.
+/// * `seq`: The sequence number of the entry of the value.
+/// * `generation`: The generation of the table when the value was stored.
+/// * `hash`: The hash the value is stored under.
+/// * `created_at`: Where the value was stored, if backtraces are enabled.
 /// * `_table`: An atomic reference counted pointer to the `HcTable` that contains this value.
 ///
-struct Inner<T>
-where
-    T: Hash + Eq,
-{
+struct Inner<T> {
     elem: Arc<T>,
+    seq: u64,
+    generation: u64,
+    hash: u128,
+    #[cfg(feature = "leak-check")]
+    created_at: std::backtrace::Backtrace,
 
     _table: Arc<InnerTable<T>>,
 }
 
 #[cfg(feature = "auto-cleanup")]
-impl<T> Drop for Inner<T>
-where
-    T: Hash + Eq,
-{
+impl<T> Drop for Inner<T> {
     /// Provides the functionality to drop `Inner<T>` instances.
     /// This method is useful for managing the lifecycle of `Hc<T>` instances.
     ///
+    /// The entry is not removed right away, which would make every drop wait for the write lock
+    /// of the table. Instead it is pushed to a lock-free queue, which the next thread to intern
+    /// or query the table drains. Only the entry of this allocation is removed then: an
+    /// equivalent value interned after this one died may already have taken over the entry.
+    ///
     /// ## Example
     /// ```
     /// use hash_cons::HcTable;
@@ -557,19 +4340,103 @@ where
     /// ```
     ///
     fn drop(&mut self) {
-        let arc_table = self._table.clone();
-
-        let key = self.elem.clone();
-        let mut_table_result = arc_table.table.write();
-        let mut mut_table = match mut_table_result {
-            Ok(guard) => guard,
-            Err(poisoned) => {
-                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
-                poisoned.into_inner() // continues, because we are not using
-                                      // any inconsistent value(if any)
-            }
-        };
-        mut_table.remove_entry(&key);
+        let queued = self
+            ._table
+            .retired
+            .push((self.hash, Arc::as_ptr(&self.elem) as usize));
+        if queued >= RETIRED_BATCH {
+            self._table.collect_retired();
+        }
+    }
+}
+
+/// # Stall
+/// The hook of a table called when an operation waited too long for its lock.
+///
+/// ## Fields
+/// * `timeout`: How long an operation waits before the hook is called.
+/// * `hook`: The hook, called with the time waited.
+struct Stall {
+    timeout: Duration,
+    hook: Box<dyn Fn(Duration) + Send + Sync>,
+}
+
+/// The hook of a table called when too many values share a hash.
+///
+/// ## Fields
+/// * `threshold`: The number of values sharing a hash above which the hook is called.
+/// * `report`: The hook, called with the number of values sharing the hash.
+struct CollisionWarning {
+    threshold: usize,
+    report: Box<dyn Fn(usize) + Send + Sync>,
+}
+
+/// The check of a table for values whose hash changed, see `HcTableBuilder::hash_drift_check`.
+///
+/// ## Fields
+/// * `every`: The number of new values after which the next values are checked.
+/// * `report`: The hook, called with each value whose hash changed.
+struct DriftCheck<T> {
+    every: usize,
+    report: Box<dyn Fn(&T) + Send + Sync>,
+}
+
+/// The observer of the values evicted from a table, see `HcTableBuilder::on_evict`.
+struct EvictObserver<T> {
+    observe: Box<dyn Fn(&T) + Send + Sync>,
+}
+
+/// The values pinned by the live pin scopes of a table, by scope and address, see
+/// `HcTable::pin_scope`.
+struct Pins<T> {
+    next_scope: u64,
+    scopes: HashMap<u64, HashMap<usize, Arc<Inner<T>>>>,
+}
+
+impl<T> Default for Pins<T> {
+    fn default() -> Self {
+        Pins {
+            next_scope: 0,
+            scopes: HashMap::new(),
+        }
+    }
+}
+
+/// # Strategies<T>
+/// The strategies a table hashes, compares, normalizes and weighs values with.
+///
+/// ## Fields
+/// * `eq`: The equivalence deciding whether two values share a handle.
+/// * `hash`: The hash function grouping candidate values.
+/// * `normalize`: The canonicalization applied before lookup, if any.
+/// * `weight`: The weight function of values, if any.
+struct Strategies<T> {
+    eq: Box<dyn ConsEq<T> + Send + Sync>,
+    hash: Box<dyn ConsHash<T> + Send + Sync>,
+    normalize: Option<Box<dyn ConsNormalize<T> + Send + Sync>>,
+    weight: Option<Box<dyn ConsWeight<T> + Send + Sync>>,
+}
+
+/// The buckets of a table, holding each value and the handles of its entry.
+type Storage<T> = Buckets<Arc<T>, Weak<Inner<T>>>;
+
+/// # Candidate<T>
+/// A stored value sharing the hash of a looked up value, held so that the two can be compared
+/// after the lock on the table is released.
+enum Candidate<T> {
+    /// A value with handles.
+    Live(Arc<Inner<T>>),
+    /// A value without handles whose entry was not removed yet.
+    Dead(Arc<T>),
+}
+
+impl<T> Candidate<T> {
+    /// Returns the stored value.
+    fn value(&self) -> &T {
+        match self {
+            Candidate::Live(inner) => &inner.elem,
+            Candidate::Dead(value) => value,
+        }
     }
 }
 
@@ -581,27 +4448,493 @@ where
 /// * `T` - The type of values stored in the `HcTable`.
 ///
 /// ## Fields
-/// * `table`: The actual HashMap that stores the `Hc<T>` instances.
+/// * `id`: The id of the table, unique within the process.
+/// * `table`: The buckets that store the `Hc<T>` instances, grouped by hash.
+/// * `hasher`: The hasher state used to hash values.
+/// * `trusted_hasher`: The hasher state of the upper 64 bits of the hash in trusted-hash mode.
+/// * `strategies`: The strategies of the table, shared with the tables forked from it.
+/// * `borrowed_lookup`: Whether values are stored under the hash of the keys they borrow as.
+/// * `in_flight`: The values being constructed by `get_or_insert_with`.
+/// * `namespaces`: The hashes of the values interned through each namespace, by their
+///   sequence numbers.
+/// * `prehashed`: Whether the data written by `hash` is used as the hash without rehashing it.
+/// * `collision_warning`: The hook called when too many values share a hash, if any.
+/// * `collision_warned`: Whether the collision hook was called already.
+/// * `drift_check`: The hook called with the values whose hash changed, if any.
+/// * `stall`: The hook called when an operation waits too long for the lock, if any.
+/// * `tables`: The number of `HcTable`s of this table.
+/// * `leaks_reported`: Whether the leaked values were reported already.
+/// * `retired`: The hashes and addresses of the entries queued for removal by dropped handles.
+/// * `defer_collection`: Whether `retired` is only collected explicitly or once it is full.
+/// * `last_activity`: The number of lookups when `cleanup_when_idle` last saw them change, and
+///   when that was.
 ///
-struct InnerTable<T>
-where
-    T: Hash + Eq,
-{
-    table: RwLock<HashMap<Arc<T>, Weak<Inner<T>>>>,
+struct InnerTable<T> {
+    id: usize,
+    table: RwLock<Storage<T>>,
+    hasher: HashState,
+    trusted_hasher: Option<HashState>,
+    strategies: Arc<Strategies<T>>,
+    borrowed_lookup: bool,
+    in_flight: InFlight,
+    namespaces: Mutex<HashMap<String, HashMap<u64, u128>>>,
+    pins: Mutex<Pins<T>>,
+    pin_scopes: AtomicUsize,
+    prehashed: bool,
+    collision_warning: Option<Arc<CollisionWarning>>,
+    collision_warned: AtomicBool,
+    drift_check: Option<Arc<DriftCheck<T>>>,
+    stall: Option<Arc<Stall>>,
+    on_evict: Option<Arc<EvictObserver<T>>>,
+    name: Option<&'static str>,
+    poison_policy: PoisonPolicy,
+    #[cfg(feature = "leak-check")]
+    tables: AtomicUsize,
+    #[cfg(feature = "leak-check")]
+    leaks_reported: AtomicBool,
+    #[cfg(feature = "auto-cleanup")]
+    retired: RetireQueue<(u128, usize)>,
+    #[cfg(feature = "auto-cleanup")]
+    defer_collection: bool,
+    #[cfg(not(feature = "auto-cleanup"))]
+    last_activity: Mutex<(u64, Instant)>,
 }
 
-impl<T> InnerTable<T>
-where
-    T: Hash + Eq,
-{
+impl<T> InnerTable<T> {
     /// Creates a new `InnerTable<T>`.
     ///
     /// ## Returns
     /// A new instance of `InnerTable<T>`.
     ///
-    fn new() -> Self {
+    fn new<M>(builder: HcTableBuilder<T, M>) -> Self {
+        let HcTableBuilder {
+            eq,
+            hash,
+            normalize,
+            weight,
+            custom_strategies,
+            trusted_hash,
+            prehashed,
+            seed,
+            #[cfg(feature = "auto-cleanup")]
+            deferred_reclamation,
+            collision_warning,
+            drift_check,
+            ids,
+            stall,
+            on_evict,
+            name,
+            poison_policy,
+            _semantics,
+        } = builder;
+        InnerTable {
+            id: next_table_id(),
+            table: RwLock::new(Buckets::with_ids(ids)),
+            hasher: seed.map_or_else(HashState::new, HashState::with_seed),
+            // The upper half of a trusted hash needs a state independent of the lower half
+            trusted_hasher: trusted_hash
+                .then(|| seed.map_or_else(HashState::new, |seed| HashState::with_seed(!seed))),
+            strategies: Arc::new(Strategies {
+                eq,
+                hash,
+                normalize,
+                weight,
+            }),
+            borrowed_lookup: !custom_strategies,
+            prehashed,
+            collision_warning: collision_warning.map(Arc::new),
+            collision_warned: AtomicBool::new(false),
+            drift_check: drift_check.map(Arc::new),
+            stall: stall.map(Arc::new),
+            on_evict: on_evict.map(Arc::new),
+            name,
+            poison_policy,
+            #[cfg(feature = "leak-check")]
+            tables: AtomicUsize::new(1),
+            #[cfg(feature = "leak-check")]
+            leaks_reported: AtomicBool::new(false),
+            in_flight: InFlight::new(poison_policy, message_prefix(name)),
+            namespaces: Mutex::new(HashMap::new()),
+            pins: Mutex::new(Pins::default()),
+            pin_scopes: AtomicUsize::new(0),
+            #[cfg(feature = "auto-cleanup")]
+            retired: RetireQueue::new(),
+            #[cfg(feature = "auto-cleanup")]
+            defer_collection: deferred_reclamation,
+            #[cfg(not(feature = "auto-cleanup"))]
+            last_activity: Mutex::new((0, Instant::now())),
+        }
+    }
+
+    /// Creates an empty table configured like this one, sharing its strategies and the
+    /// allocator of its ids.
+    fn fork(&self) -> Self {
         InnerTable {
-            table: RwLock::new(HashMap::new()),
+            id: next_table_id(),
+            table: RwLock::new(self.read().sharing_ids()),
+            hasher: self.hasher.clone(),
+            trusted_hasher: self.trusted_hasher.clone(),
+            strategies: self.strategies.clone(),
+            borrowed_lookup: self.borrowed_lookup,
+            prehashed: self.prehashed,
+            collision_warning: self.collision_warning.clone(),
+            collision_warned: AtomicBool::new(false),
+            drift_check: self.drift_check.clone(),
+            stall: self.stall.clone(),
+            on_evict: self.on_evict.clone(),
+            name: self.name,
+            poison_policy: self.poison_policy,
+            #[cfg(feature = "leak-check")]
+            tables: AtomicUsize::new(1),
+            #[cfg(feature = "leak-check")]
+            leaks_reported: AtomicBool::new(false),
+            in_flight: InFlight::new(self.poison_policy, self.prefix()),
+            namespaces: Mutex::new(HashMap::new()),
+            pins: Mutex::new(Pins::default()),
+            pin_scopes: AtomicUsize::new(0),
+            #[cfg(feature = "auto-cleanup")]
+            retired: RetireQueue::new(),
+            #[cfg(feature = "auto-cleanup")]
+            defer_collection: self.defer_collection,
+            #[cfg(not(feature = "auto-cleanup"))]
+            last_activity: Mutex::new((0, Instant::now())),
+        }
+    }
+
+    #[cfg(feature = "leak-check")]
+    /// Lists the values that still have handles, with their creation backtraces if captured.
+    fn leak_report(&self) -> LeakReport {
+        let table = self.read();
+        let mut report = LeakReport {
+            type_name: std::any::type_name::<T>(),
+            table_name: self.name,
+            ..LeakReport::default()
+        };
+        for inner in table.iter().filter_map(|entry| entry.weak.upgrade()) {
+            report.leaked += 1;
+            if inner.created_at.status() == std::backtrace::BacktraceStatus::Captured {
+                report.backtraces.push(inner.created_at.to_string());
+            }
+        }
+        report
+    }
+
+    /// Returns the canonical form of `value`.
+    fn normalize(&self, value: T) -> T {
+        match &self.strategies.normalize {
+            Some(normalize) => normalize.normalize(value),
+            None => value,
+        }
+    }
+
+    /// Returns the hash a value is stored under, given the hash computed by the caller.
+    fn given_hash(&self, hash: u64) -> u128 {
+        assert!(
+            self.trusted_hasher.is_none(),
+            "hash_cons: values cannot be found by a 64-bit hash in trusted-hash mode"
+        );
+        hash as u128
+    }
+
+    /// Returns the hash `value` is stored under.
+    fn hash(&self, value: &T) -> u128 {
+        self.hash_data(|state| self.strategies.hash.write_hash(value, state))
+    }
+
+    /// Returns the hash the value `key` borrows as is stored under, if `borrowed_lookup` is set.
+    fn hash_key<Q>(&self, key: &Q) -> u128
+    where
+        Q: Hash + ?Sized,
+    {
+        self.hash_data(|mut state| key.hash(&mut state))
+    }
+
+    /// Returns the hash of the data `write` writes.
+    fn hash_data(&self, write: impl Fn(&mut dyn Hasher)) -> u128 {
+        if self.prehashed {
+            let mut state = IdentityHasher::default();
+            write(&mut state);
+            return match &self.trusted_hasher {
+                Some(_) => state.finish_u128(),
+                None => state.finish() as u128,
+            };
+        }
+        let low = Self::hash_with(&self.hasher, &write);
+        match &self.trusted_hasher {
+            Some(hasher) => (Self::hash_with(hasher, &write) as u128) << 64 | low as u128,
+            None => low as u128,
+        }
+    }
+
+    /// Hashes the data `write` writes, starting from `hasher`.
+    fn hash_with(hasher: &HashState, write: &impl Fn(&mut dyn Hasher)) -> u64 {
+        let mut state = hasher.build_hasher();
+        write(&mut state);
+        state.finish()
+    }
+
+    /// Returns the counters of the table, see `HcTable::stats`.
+    fn stats(&self) -> TableStats {
+        TableStats {
+            name: self.name,
+            ..self.read().stats()
+        }
+    }
+
+    /// Returns the prefix of the messages printed about the table, naming it if it has a name.
+    fn prefix(&self) -> String {
+        message_prefix(self.name)
+    }
+
+    /// Calls the collision hook of the table if `shared` values share a hash and its threshold
+    /// is exceeded for the first time. Called after the lock is released.
+    fn check_collisions(&self, shared: usize) {
+        if let Some(warning) = &self.collision_warning {
+            if shared > warning.threshold && !self.collision_warned.swap(true, Ordering::Relaxed) {
+                (warning.report)(shared);
+            }
+        }
+    }
+
+    /// Returns the next stored values to check for a changed hash with their stored hashes, if
+    /// a check is due after a value was stored in `table`.
+    fn drift_batch(&self, table: &mut Storage<T>) -> Vec<(u128, Arc<T>)> {
+        match &self.drift_check {
+            Some(check) if table.stats.created.is_multiple_of(check.every as u64) => table
+                .drift_batch(check.every)
+                .into_iter()
+                .map(|(hash, entry)| (hash, entry.key.clone()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Reports the values of `batch` whose hash changed to the drift check of the table. Called
+    /// after the lock is released, since their `Hash` may use the table.
+    fn check_drift(&self, batch: Vec<(u128, Arc<T>)>) {
+        if let Some(check) = &self.drift_check {
+            for (hash, value) in batch {
+                if self.hash(&value) != hash {
+                    (check.report)(&value);
+                }
+            }
+        }
+    }
+
+    /// Collects the entries stored under `hash` whose sequence numbers are not in `checked`, and
+    /// adds their sequence numbers to it.
+    fn unchecked(
+        table: &Storage<T>,
+        hash: u128,
+        checked: &mut Vec<u64>,
+    ) -> Vec<(u64, Candidate<T>)> {
+        let candidates: Vec<_> = table
+            .bucket(hash)
+            .iter()
+            .filter(|entry| !checked.contains(&entry.seq))
+            .map(|entry| {
+                let candidate = match entry.weak.upgrade() {
+                    Some(inner) => Candidate::Live(inner),
+                    None => Candidate::Dead(entry.key.clone()),
+                };
+                (entry.seq, candidate)
+            })
+            .collect();
+        checked.extend(candidates.iter().map(|&(seq, _)| seq));
+        candidates
+    }
+
+    /// Returns every stored value, whether it still has handles, and its generation, so that
+    /// the values can be measured after the lock is released.
+    fn stored(&self) -> Vec<(Arc<T>, bool, u64)> {
+        let table = self.read();
+        table
+            .iter()
+            .map(|entry| {
+                let alive = entry.weak.strong_count() > 0;
+                (entry.key.clone(), alive, entry.generation)
+            })
+            .collect()
+    }
+
+    /// Returns the values with handles stored under `hash`.
+    fn live(table: &Storage<T>, hash: u128) -> Vec<Arc<Inner<T>>> {
+        table
+            .bucket(hash)
+            .iter()
+            .filter_map(|entry| entry.weak.upgrade())
+            .collect()
+    }
+
+    /// Returns the weight of `value`.
+    fn weight_of(&self, value: &T) -> usize {
+        self.strategies
+            .weight
+            .as_ref()
+            .map_or(1, |weight| weight.weight(value))
+    }
+
+    /// Returns `true` if `value` should share the handle of the stored value `key`, given that
+    /// their hashes are equal.
+    fn matches(&self, key: &T, value: &T) -> bool {
+        self.trusted_hasher.is_some() || self.strategies.eq.equivalent(key, value)
+    }
+
+    /// Acquires the write lock on the stored values, after collecting the retired entries.
+    fn write(&self) -> RwLockWriteGuard<'_, Storage<T>> {
+        #[cfg(feature = "auto-cleanup")]
+        self.collect_pending();
+        self.lock_write()
+    }
+
+    /// Acquires the write lock on the stored values like `write`, giving up after `timeout`.
+    ///
+    /// The retired entries are left to the next operation, since collecting them waits for the
+    /// lock without a timeout.
+    fn write_within(&self, timeout: Duration) -> Result<RwLockWriteGuard<'_, Storage<T>>, Timeout> {
+        self.lock_within(timeout, || self.table.try_write())
+            .map_err(|waited| Timeout { waited })
+    }
+
+    /// Acquires the write lock on the stored values.
+    fn lock_write(&self) -> RwLockWriteGuard<'_, Storage<T>> {
+        self.lock(|| self.table.try_write(), || self.table.write())
+    }
+
+    /// Acquires the read lock on the stored values, after collecting the retired entries.
+    fn read(&self) -> RwLockReadGuard<'_, Storage<T>> {
+        #[cfg(feature = "auto-cleanup")]
+        self.collect_pending();
+        self.lock(|| self.table.try_read(), || self.table.read())
+    }
+
+    /// Acquires a lock on the stored values with `lock`, calling the stall hook of the table if
+    /// it is not acquired with `try_lock` in time.
+    fn lock<G>(
+        &self,
+        try_lock: impl Fn() -> TryLockResult<G>,
+        lock: impl FnOnce() -> LockResult<G>,
+    ) -> G {
+        if let Some(stall) = &self.stall {
+            match self.lock_within(stall.timeout, try_lock) {
+                Ok(guard) => return guard,
+                Err(waited) => (stall.hook)(waited),
+            }
+        }
+        lock().unwrap_or_else(|poisoned| self.recover(poisoned))
+    }
+
+    /// Tries to acquire a lock on the stored values with `try_lock` until `timeout` passed.
+    ///
+    /// ## Returns
+    /// The guard, or the time waited if the lock stayed taken.
+    fn lock_within<G>(
+        &self,
+        timeout: Duration,
+        try_lock: impl Fn() -> TryLockResult<G>,
+    ) -> Result<G, Duration> {
+        let start = Instant::now();
+        let mut attempts = 0u32;
+        loop {
+            match try_lock() {
+                Ok(guard) => return Ok(guard),
+                Err(TryLockError::Poisoned(poisoned)) => return Ok(self.recover(poisoned)),
+                Err(TryLockError::WouldBlock) => {}
+            }
+            let waited = start.elapsed();
+            if waited >= timeout {
+                return Err(waited);
+            }
+            // Most critical sections are short, so the lock is polled a few times before sleeping
+            attempts += 1;
+            if attempts < 64 {
+                std::thread::yield_now();
+            } else {
+                std::thread::sleep((timeout - waited).min(Duration::from_millis(1)));
+            }
+        }
+    }
+
+    /// Pins `inner` in every live pin scope of the table and returns it.
+    fn pinned(&self, inner: Arc<Inner<T>>) -> Arc<Inner<T>> {
+        if self.pin_scopes.load(Ordering::Relaxed) > 0 {
+            let addr = Arc::as_ptr(&inner) as usize;
+            for pinned in self.lock_mutex(&self.pins).scopes.values_mut() {
+                pinned.entry(addr).or_insert_with(|| inner.clone());
+            }
+        }
+        inner
+    }
+
+    /// Passes the values of evicted entries to the observer of the table, if any. Called after
+    /// the lock is released.
+    fn observe_evicted<'a>(&self, values: impl IntoIterator<Item = &'a Arc<T>>)
+    where
+        T: 'a,
+    {
+        if let Some(observer) = &self.on_evict {
+            values
+                .into_iter()
+                .for_each(|value| (observer.observe)(value));
+        }
+    }
+
+    /// Handles a poisoned lock on the stored values as the poison policy of the table says.
+    ///
+    /// Continuing is sound, because user code only runs before a write changes the buckets, so
+    /// a panic leaves them consistent.
+    fn recover<G>(&self, poisoned: std::sync::PoisonError<G>) -> G {
+        self.poison_policy
+            .recover(&self.prefix(), poisoned, || self.table.clear_poison())
+    }
+
+    /// Acquires `mutex` of the table, handling a poisoned one as the poison policy of the table
+    /// says. Every value guarded by a mutex of the table stays consistent when a holder panics.
+    fn lock_mutex<'a, V>(&self, mutex: &'a Mutex<V>) -> MutexGuard<'a, V> {
+        mutex.lock().unwrap_or_else(|poisoned| {
+            self.poison_policy
+                .recover(&self.prefix(), poisoned, || mutex.clear_poison())
+        })
+    }
+
+    #[cfg(feature = "auto-cleanup")]
+    /// Removes the entries queued by dropped handles.
+    ///
+    /// ## Returns
+    /// The number of entries removed.
+    fn collect_retired(&self) -> usize {
+        let mut collected = 0;
+        while !self.retired.is_empty() {
+            let retired = self.retired.take_all();
+
+            // An entry is only removed if it is still dead: a new value may have taken it over,
+            // possibly at the address of the retired one, before the queue was collected.
+            let mut mut_table = self.lock_write();
+            let removed: Vec<_> = retired
+                .into_iter()
+                .filter_map(|(hash, addr)| {
+                    mut_table.remove(hash, |entry| {
+                        Arc::as_ptr(&entry.key) as usize == addr && entry.weak.strong_count() == 0
+                    })
+                })
+                .collect();
+            drop(mut_table);
+            collected += removed.len();
+
+            // Dropping the removed values may retire their children, which the next round
+            // collects, so the lock was released first.
+            self.observe_evicted(removed.iter().map(|entry| &entry.key));
+            drop(removed);
+        }
+        collected
+    }
+
+    #[cfg(feature = "auto-cleanup")]
+    /// Removes the entries queued by dropped handles, unless the table only collects them
+    /// explicitly. Called before every lookup and query, so that they see no retired entries.
+    fn collect_pending(&self) {
+        if !self.defer_collection {
+            self.collect_retired();
         }
     }
 
@@ -611,15 +4944,22 @@ where
     /// The number of elements in the `InnerTable`.
     ///
     fn len(&self) -> usize {
-        let table_result = self.table.read();
-        let table = match table_result {
-            Ok(guard) => guard,
-            Err(poisoned) => {
-                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
-                poisoned.into_inner() // continues, because we don't need the value(even if inconsistent)
-            }
-        };
-        table.len()
+        self.read().len()
+    }
+
+    #[cfg(feature = "tokio")]
+    /// Removes the dead entries of the `InnerTable`: the retired ones with `auto-cleanup`, and
+    /// the values without handles otherwise.
+    fn maintain(&self) {
+        #[cfg(feature = "auto-cleanup")]
+        self.collect_retired();
+        #[cfg(not(feature = "auto-cleanup"))]
+        self.cleanup();
+    }
+
+    /// Acquires the values interned through each namespace.
+    fn namespaces(&self) -> MutexGuard<'_, HashMap<String, HashMap<u64, u128>>> {
+        self.lock_mutex(&self.namespaces)
     }
 
     #[cfg(not(feature = "auto-cleanup"))]
@@ -632,48 +4972,153 @@ where
     /// It removes any values that have a `strong_count()` of 0.
     /// This is the desired behavior for hash consing.
     ///
-    fn cleanup(&self) {
-        loop {
-            let mut_table_result = self.table.write();
+    fn cleanup(&self) -> CleanupReport {
+        self.sweep(|_| true)
+    }
 
-            let mut mut_table = match mut_table_result {
-                Ok(guard) => guard,
-                Err(poisoned) => {
-                    eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
-                    poisoned.into_inner() // continues, because we are removing the value
-                }
-            };
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `InnerTable` stored before `generation`.
+    fn cleanup_older_than(&self, generation: u64) -> CleanupReport {
+        self.sweep(|entry| entry.generation < generation)
+    }
 
-            // Flag to check if any weak references are dropped in this iteration
-            let mut dropped = false;
+    #[cfg(all(feature = "access-time", not(feature = "auto-cleanup")))]
+    /// Removes the unused values of the `InnerTable` last interned `idle` or longer ago.
+    fn cleanup_idle_for(&self, idle: Duration) -> CleanupReport {
+        let now = self.read().now();
+        self.sweep(|entry| u64::from(now.saturating_sub(entry.last_access)) >= idle.as_secs())
+    }
 
-            mut_table.retain(|_, weak_hc: &mut Weak<Inner<T>>| {
-                if weak_hc.strong_count() == 0 {
-                    dropped = true; // A weak reference was dropped
-                    false // Remove this entry
-                } else {
-                    true // Keep this entry
-                }
-            });
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values of the `InnerTable` for which `evict` returns `true`,
+    /// including values released by removing others.
+    fn sweep(
+        &self,
+        mut evict: impl FnMut(&Entry<Arc<T>, Weak<Inner<T>>>) -> bool,
+    ) -> CleanupReport {
+        let start = Instant::now();
+        let mut report = CleanupReport::default();
+        loop {
+            let mut mut_table = self.write();
+            let removed = mut_table.retain(|entry| entry.weak.strong_count() > 0 || !evict(entry));
+            drop(mut_table);
+            self.observe_evicted(removed.iter().map(|entry| &entry.key));
 
-            // Break the loop if no weak references were dropped in this iteration
-            if !dropped {
+            report.removed += removed.len();
+            // Break the loop if no weak references were dropped in this iteration. Otherwise,
+            // dropping the removed values may have released their children.
+            if removed.is_empty() {
                 break;
             }
         }
+        report.remaining = self.len();
+        report.duration = start.elapsed();
+        report
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes unused values of the `InnerTable` in the given order until the stored values
+    /// weigh at most `budget`, or no unused value is left.
+    fn evict_until_weight(&self, budget: usize, order: EvictionOrder) -> CleanupReport {
+        self.evict_until(budget, order, |value| self.weight_of(value))
     }
 
-    /*fn cleanup(&self) {
-        let mut_table_result = self.table.write();
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Like `evict_until_weight`, but weighs the stored values with `weigh`.
+    fn evict_until(
+        &self,
+        budget: usize,
+        order: EvictionOrder,
+        weigh: impl Fn(&T) -> usize,
+    ) -> CleanupReport {
+        let start = Instant::now();
+        let mut report = CleanupReport::default();
+        loop {
+            // The values are weighed without the lock, and kept alive until the evicted ones
+            // were removed, so that their addresses identify them.
+            let stored = self.stored();
+            let mut total = 0usize;
+            let mut candidates = Vec::new();
+            for (value, alive, generation) in &stored {
+                let weight = weigh(value);
+                total = total.saturating_add(weight);
+                if !alive {
+                    candidates.push((weight, *generation, Arc::as_ptr(value)));
+                }
+            }
+            if total <= budget || candidates.is_empty() {
+                break;
+            }
 
-        let mut mut_table = match mut_table_result {
-            Ok(guard) => guard,
-            Err(poisoned) => {
-                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
-                poisoned.into_inner() // continues, because we are removing the value
+            match order {
+                EvictionOrder::Heaviest => {
+                    candidates.sort_by_key(|&(weight, _, _)| std::cmp::Reverse(weight))
+                }
+                EvictionOrder::Oldest => candidates.sort_by_key(|&(_, generation, _)| generation),
             }
-        };
+            let mut evicted = HashSet::new();
+            for (weight, _, value) in candidates {
+                if total <= budget {
+                    break;
+                }
+                total -= weight;
+                evicted.insert(value);
+            }
+            let removed = self
+                .write()
+                .retain(|entry| !evicted.contains(&Arc::as_ptr(&entry.key)));
+            drop(stored);
+            self.observe_evicted(removed.iter().map(|entry| &entry.key));
+
+            // Dropping the removed values may have released their children, which are
+            // candidates of the next round.
+            report.removed += removed.len();
+        }
+        report.remaining = self.len();
+        report.duration = start.elapsed();
+        report
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Removes the unused values among the next `max_entries` entries of the `InnerTable`.
+    fn cleanup_batch(&self, max_entries: usize) -> CleanupReport {
+        let start = Instant::now();
+        // The lock is released before the removed values are observed and dropped.
+        let removed = self
+            .write()
+            .retain_batch(max_entries, |entry| entry.weak.strong_count() > 0);
+        self.observe_evicted(removed.iter().map(|entry| &entry.key));
+        CleanupReport {
+            removed: removed.len(),
+            remaining: self.len(),
+            duration: start.elapsed(),
+        }
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Cleans up the `InnerTable` if the number of lookups did not change since the call that
+    /// last saw it change, at least `window` ago.
+    fn cleanup_when_idle(&self, window: Duration) -> Option<CleanupReport> {
+        let lookups = self.read().stats.lookups();
+        let now = Instant::now();
+        let mut last_activity = self.lock_mutex(&self.last_activity);
+        if last_activity.0 != lookups {
+            *last_activity = (lookups, now);
+            return None;
+        }
+        if now.duration_since(last_activity.1) < window {
+            return None;
+        }
+        // Concurrent calls wait here instead of sweeping the table again.
+        let report = self.cleanup();
+        *last_activity = (self.read().stats.lookups(), Instant::now());
+        Some(report)
+    }
+}
 
-        mut_table.retain(|_, weak_Hc: &mut Weak<Inner<T>>| weak_Hc.strong_count() > 0);
-    }*/
-}
\ No newline at end of file
+/// Returns the bytes held by the entry of `value`: the allocation of the value and that of its
+/// handle, both of which start with a strong and a weak reference count, and the heap memory of
+/// the value.
+fn entry_bytes<T: MemSize>(value: &T) -> usize {
+    4 * size_of::<usize>() + size_of::<T>() + size_of::<Inner<T>>() + value.heap_size()
+}
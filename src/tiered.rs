@@ -0,0 +1,369 @@
+//! # Tiered Storage
+//!
+//! **Experimental.** A table whose values live in one of two tiers. Interned values start in
+//! the hot tier, in memory. Values nothing outside the table refers to anymore can be spilled
+//! to the cold tier, an append-only log file of `serde_json` records, which frees their memory.
+//! Interning an equal value, or resolving the id of a cold value, reads it back and moves it to
+//! the hot tier again, so that term sets larger than memory only keep their working set loaded.
+//!
+//! Every value keeps the id the [`TieredTable`] gave it when it was first interned, in
+//! whichever tier it is. The log is truncated when the table is opened and grows with every
+//! spill; records of values that became hot again are not reclaimed.
+//!
+//! With the `compression` feature, [`TieredTable::compressed`] keeps the cold tier in memory
+//! instead, each value serialized and compressed with LZ4. Bursty workloads whose large values
+//! go unused between bursts then trade the CPU time of compressing them for resident memory,
+//! without touching the disk.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::tiered::TieredTable;
+//!
+//! let path = std::env::temp_dir().join("hash_cons_tiered_doc.log");
+//! let table = TieredTable::open(&path).unwrap();
+//! let hot = table.hashcons("hot".to_string()).unwrap();
+//! let id = table.id(&table.hashcons("cold".to_string()).unwrap()).unwrap();
+//!
+//! assert_eq!(table.spill(0).unwrap(), 1);
+//! assert_eq!((table.hot_len(), table.cold_len()), (1, 1));
+//!
+//! let cold = table.resolve(id).unwrap().unwrap();
+//! assert_eq!((cold.get().as_str(), hot.get().as_str()), ("cold", "hot"));
+//! assert_eq!(table.cold_len(), 0);
+//! ```
+
+use crate::thread_safe::{Hc, HcTable};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// A value of the hot tier, with the tick of its last use.
+struct Hot<T> {
+    hc: Hc<T>,
+    last_use: u64,
+}
+
+/// A value of the cold tier, with the hash it was interned under.
+struct Cold {
+    record: Record,
+    hash: u64,
+}
+
+/// The serialized form of a cold value.
+enum Record {
+    /// A line of the log, without its newline.
+    Log { offset: u64, len: usize },
+    #[cfg(feature = "compression")]
+    /// LZ4-compressed bytes, prefixed with their uncompressed size.
+    Compressed(Box<[u8]>),
+}
+
+impl Record {
+    /// Returns the number of bytes the record takes.
+    fn size(&self) -> usize {
+        match self {
+            Record::Log { len, .. } => *len,
+            #[cfg(feature = "compression")]
+            Record::Compressed(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// The tiers of a table, guarded by a single lock.
+struct Tiers<T> {
+    /// Hot values by id.
+    hot: HashMap<u64, Hot<T>>,
+    /// Ids of hot values by handle address.
+    ids: HashMap<usize, u64>,
+    /// Cold values by id.
+    cold: HashMap<u64, Cold>,
+    /// Ids of cold values by hash.
+    cold_ids: HashMap<u64, Vec<u64>>,
+    next_id: u64,
+    clock: u64,
+    /// The log of the cold tier, or `None` if it is compressed in memory.
+    log: Option<File>,
+    end: u64,
+}
+
+/// # `TieredTable<T>`
+/// A hash consing table that spills unused values to an on-disk log and interns them again on
+/// demand. See the [module documentation](self).
+pub struct TieredTable<T> {
+    table: HcTable<T>,
+    tiers: Mutex<Tiers<T>>,
+}
+
+impl<T> TieredTable<T>
+where
+    T: Hash + Eq + Serialize + DeserializeOwned,
+{
+    /// Creates a table whose cold tier is stored at `path`, creating or truncating that file.
+    ///
+    /// ## Errors
+    /// Returns the error of opening the file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let log = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self::with_log(Some(log)))
+    }
+
+    #[cfg(feature = "compression")]
+    /// Creates a table whose cold tier is kept in memory, each value serialized and compressed
+    /// with LZ4.
+    ///
+    /// ## Example
+    /// ```
+    /// use hash_cons::tiered::TieredTable;
+    ///
+    /// let table = TieredTable::compressed();
+    /// let id = table.id(&table.hashcons("ab".repeat(1000)).unwrap()).unwrap();
+    ///
+    /// assert_eq!(table.spill(0).unwrap(), 1);
+    /// assert!(table.cold_bytes() < 100);
+    /// assert_eq!(table.resolve(id).unwrap().unwrap().len(), 2000);
+    /// ```
+    pub fn compressed() -> Self {
+        Self::with_log(None)
+    }
+
+    fn with_log(log: Option<File>) -> Self {
+        TieredTable {
+            table: HcTable::new(),
+            tiers: Mutex::new(Tiers {
+                hot: HashMap::new(),
+                ids: HashMap::new(),
+                cold: HashMap::new(),
+                cold_ids: HashMap::new(),
+                next_id: 0,
+                clock: 0,
+                log,
+                end: 0,
+            }),
+        }
+    }
+
+    /// Interns `value` in the hot tier and returns its handle.
+    ///
+    /// If an equal value was spilled, it is read back from the cold tier and keeps its id.
+    ///
+    /// ## Errors
+    /// Returns the error of reading or decoding a cold value with the same hash.
+    pub fn hashcons(&self, value: T) -> io::Result<Hc<T>> {
+        let hc = self.table.hashcons(value);
+        let mut tiers = self.lock();
+        if let Some(&id) = tiers.ids.get(&hc.addr()) {
+            tiers.touch(id);
+            return Ok(hc);
+        }
+        let id = match tiers.take_cold(&hc)? {
+            Some(id) => id,
+            None => {
+                tiers.next_id += 1;
+                tiers.next_id - 1
+            }
+        };
+        tiers.insert_hot(id, hc.clone());
+        Ok(hc)
+    }
+
+    /// Returns the id of the value of `hc`, or `None` if it was not interned by this table.
+    pub fn id(&self, hc: &Hc<T>) -> Option<u64> {
+        self.lock().ids.get(&hc.addr()).copied()
+    }
+
+    /// Returns a handle to the value with the given id, reading it back from the cold tier if
+    /// needed, or `None` if no value has that id.
+    ///
+    /// ## Errors
+    /// Returns the error of reading or decoding the cold value.
+    pub fn resolve(&self, id: u64) -> io::Result<Option<Hc<T>>> {
+        let mut tiers = self.lock();
+        if let Some(hot) = tiers.hot.get(&id) {
+            let hc = hot.hc.clone();
+            tiers.touch(id);
+            return Ok(Some(hc));
+        }
+        if !tiers.cold.contains_key(&id) {
+            return Ok(None);
+        }
+        let hc = self.table.hashcons(tiers.read(id)?);
+        if let Some(cold) = tiers.cold.remove(&id) {
+            tiers.forget_cold(cold.hash, id);
+        }
+        tiers.insert_hot(id, hc.clone());
+        Ok(Some(hc))
+    }
+
+    /// Spills the least recently used hot values that have no handle outside the table to the
+    /// cold tier, until at most `keep_hot` values are hot or none is left to spill.
+    ///
+    /// ## Returns
+    /// The number of values spilled.
+    ///
+    /// ## Errors
+    /// Returns the error of encoding or writing a value. The values spilled before it stay cold.
+    pub fn spill(&self, keep_hot: usize) -> io::Result<usize> {
+        let mut tiers = self.lock();
+        let excess = tiers.hot.len().saturating_sub(keep_hot);
+        let mut unused: Vec<(u64, u64)> = tiers
+            .hot
+            .iter()
+            .filter(|(_, hot)| hot.hc.handle_count() == 1)
+            .map(|(&id, hot)| (hot.last_use, id))
+            .collect();
+        unused.sort_unstable();
+        unused.truncate(excess);
+
+        for &(_, id) in &unused {
+            let hc = &tiers.hot[&id].hc;
+            let (hash, addr) = (hc.hash_value(), hc.addr());
+            let bytes = serde_json::to_vec(hc.get())?;
+            let record = tiers.write(bytes)?;
+
+            tiers.hot.remove(&id);
+            tiers.ids.remove(&addr);
+            tiers.cold.insert(id, Cold { record, hash });
+            tiers.cold_ids.entry(hash).or_default().push(id);
+        }
+        if let Some(log) = &mut tiers.log {
+            log.flush()?;
+        }
+        Ok(unused.len())
+    }
+
+    /// Returns the number of values in the hot tier.
+    pub fn hot_len(&self) -> usize {
+        self.lock().hot.len()
+    }
+
+    /// Returns the number of values in the cold tier.
+    pub fn cold_len(&self) -> usize {
+        self.lock().cold.len()
+    }
+
+    /// Returns the number of bytes the serialized values of the cold tier take, in the log or
+    /// compressed in memory.
+    pub fn cold_bytes(&self) -> usize {
+        self.lock()
+            .cold
+            .values()
+            .map(|cold| cold.record.size())
+            .sum()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tiers<T>> {
+        match self.tiers.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                self.tiers.clear_poison();
+                poisoned.into_inner()
+            }
+        }
+    }
+}
+
+impl<T> Tiers<T>
+where
+    T: Eq + DeserializeOwned,
+{
+    fn touch(&mut self, id: u64) {
+        self.clock += 1;
+        if let Some(hot) = self.hot.get_mut(&id) {
+            hot.last_use = self.clock;
+        }
+    }
+
+    fn insert_hot(&mut self, id: u64, hc: Hc<T>) {
+        self.clock += 1;
+        self.ids.insert(hc.addr(), id);
+        let last_use = self.clock;
+        self.hot.insert(id, Hot { hc, last_use });
+    }
+
+    /// Removes the cold value equal to the value of `hc` and returns its id, if there is one.
+    fn take_cold(&mut self, hc: &Hc<T>) -> io::Result<Option<u64>> {
+        let hash = hc.hash_value();
+        let candidates = self.cold_ids.get(&hash).cloned().unwrap_or_default();
+        for id in candidates {
+            if self.read(id)? == *hc.get() {
+                self.cold.remove(&id);
+                self.forget_cold(hash, id);
+                return Ok(Some(id));
+            }
+        }
+        Ok(None)
+    }
+
+    fn forget_cold(&mut self, hash: u64, id: u64) {
+        if let Some(ids) = self.cold_ids.get_mut(&hash) {
+            ids.retain(|&cold| cold != id);
+            if ids.is_empty() {
+                self.cold_ids.remove(&hash);
+            }
+        }
+    }
+
+    /// Stores the serialized form of a value in the cold tier.
+    fn write(&mut self, mut bytes: Vec<u8>) -> io::Result<Record> {
+        match &mut self.log {
+            Some(log) => {
+                let (offset, len) = (self.end, bytes.len());
+                bytes.push(b'\n');
+                log.seek(SeekFrom::Start(offset))?;
+                log.write_all(&bytes)?;
+                self.end += bytes.len() as u64;
+                Ok(Record::Log { offset, len })
+            }
+            #[cfg(feature = "compression")]
+            None => Ok(Record::Compressed(
+                lz4_flex::compress_prepend_size(&bytes).into_boxed_slice(),
+            )),
+            #[cfg(not(feature = "compression"))]
+            None => unreachable!("tables without a log are only created with compression"),
+        }
+    }
+
+    /// Reads back the value of the cold tier with the given id.
+    fn read(&mut self, id: u64) -> io::Result<T> {
+        let bytes = match &self.cold[&id].record {
+            &Record::Log { offset, len } => {
+                let log = self
+                    .log
+                    .as_mut()
+                    .expect("log records are only written to a log");
+                let mut bytes = vec![0; len];
+                log.seek(SeekFrom::Start(offset))?;
+                log.read_exact(&mut bytes)?;
+                bytes
+            }
+            #[cfg(feature = "compression")]
+            Record::Compressed(bytes) => lz4_flex::decompress_size_prepended(bytes)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?,
+        };
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+impl<T> std::fmt::Debug for TieredTable<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tiers = self
+            .tiers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f.debug_struct("TieredTable")
+            .field("hot", &tiers.hot.len())
+            .field("cold", &tiers.cold.len())
+            .finish()
+    }
+}
@@ -0,0 +1,42 @@
+//! # Lock Timeouts
+//!
+//! A thread-safe `HcTable` guards its values with a lock. A thread that never releases it, e.g.
+//! one stuck in a deadlock inside a `Hash` implementation, makes every other thread using the
+//! table hang without a trace. `HcTable::hashcons_timeout` gives up with a [`Timeout`] after a
+//! given wait instead, and `HcTableBuilder::on_stall` reports the operations that keep waiting.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::thread_safe::HcTable;
+//! use std::time::Duration;
+//!
+//! let table = HcTable::new();
+//!
+//! match table.hashcons_timeout(5, Duration::from_secs(1)) {
+//!     Ok(value) => assert_eq!(*value, 5),
+//!     Err(timeout) => panic!("{timeout}"),
+//! }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+/// # `Timeout`
+/// The error of `HcTable::hashcons_timeout` when the lock of the table stayed taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeout {
+    /// How long the lock was waited for.
+    pub waited: Duration,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hash_cons: gave up on the lock of a table after {:?}",
+            self.waited
+        )
+    }
+}
+
+impl std::error::Error for Timeout {}
@@ -0,0 +1,48 @@
+//! # Handle Tokens
+//!
+//! An [`HcToken`] names an interned value by the table and the entry it was stored in. It is
+//! `Copy` and `Send` whatever the value, and taking or passing one leaves the reference count of
+//! the value alone. Actor systems sending the same nodes to many receivers can pass tokens
+//! through their channels instead of cloning handles, which keeps the threads from contending
+//! for the cache line of the count, and resolve them with `HcTable::resolve` where a handle is
+//! needed.
+//!
+//! A token does not keep its value alive: it resolves to `None` once every handle to the value
+//! is dropped and the table removed it, and in any table but the one it was taken from.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::thread_safe::HcTable;
+//! use std::sync::mpsc;
+//!
+//! let table = HcTable::new();
+//! let node = table.hashcons("node");
+//! let (sender, receiver) = mpsc::channel();
+//!
+//! let worker = std::thread::spawn({
+//!     let table = table.clone();
+//!     move || table.resolve(receiver.recv().unwrap()).map(|hc| *hc)
+//! });
+//! sender.send(node.token()).unwrap();
+//! assert_eq!(worker.join().unwrap(), Some("node"));
+//! ```
+
+use crate::sync::{AtomicUsize, Ordering};
+
+/// The id of the next table created.
+static NEXT_TABLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns an id that no other table of the process has.
+pub(crate) fn next_table_id() -> usize {
+    NEXT_TABLE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// # `HcToken`
+/// A copyable reference to an interned value, resolved back to a handle through its table. See
+/// the [module documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HcToken {
+    pub(crate) table: usize,
+    pub(crate) seq: u64,
+    pub(crate) hash: u128,
+}
@@ -0,0 +1,210 @@
+//! # Write-Ahead Log
+//!
+//! A table that appends every value it stores to a log file before returning its handle, so
+//! that a process that crashes mid-session can rebuild its table by replaying the log on
+//! restart, instead of losing everything since its last whole-table save.
+//!
+//! Each record is one line: the value as `serde_json`, preceded by its content address, the
+//! 64-bit FNV-1a hash of its bytes in hex. Handles within values are written
+//! through [`serde_hc`](crate::serde_hc) and interned again on replay, which restores their
+//! sharing. Replay stops at the first record whose content does not match its address, or that
+//! lacks its newline, and truncates the log there: that is the record a crash interrupted.
+//!
+//! Values stored by a [`WalTable`] are kept until it is dropped, so that the log and the table
+//! always hold the same values.
+//!
+//! ## Example
+//! ```
+//! use hash_cons::wal::WalTable;
+//!
+//! let path = std::env::temp_dir().join("hash_cons_wal_doc.log");
+//! # let _ = std::fs::remove_file(&path);
+//! let table = WalTable::open(&path).unwrap();
+//! let hello = table.hashcons("hello".to_string()).unwrap();
+//! table.hashcons("world".to_string()).unwrap();
+//! drop(table);
+//!
+//! let table = WalTable::<String>::open(&path).unwrap();
+//! assert_eq!(table.len(), 2);
+//! assert_eq!(*table.hashcons("hello".to_string()).unwrap(), *hello);
+//! assert_eq!(table.len(), 2);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::{serde_hc, Hc, HcTable};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// The log of a table and the values written to it.
+struct Log<T> {
+    file: File,
+    /// Length of the intact records.
+    end: u64,
+    /// Addresses of the logged values.
+    logged: HashSet<usize>,
+    /// Handles keeping the logged values alive, in logging order.
+    values: Vec<Hc<T>>,
+}
+
+/// # `WalTable<T>`
+/// A hash consing table backed by an append-only log of its values. See the
+/// [module documentation](self).
+pub struct WalTable<T> {
+    table: HcTable<T>,
+    log: Mutex<Log<T>>,
+}
+
+impl<T> WalTable<T>
+where
+    T: Hash + Eq + Serialize + DeserializeOwned + 'static,
+{
+    /// Opens the log at `path`, creating it if needed, and interns the values it records.
+    ///
+    /// An incomplete or corrupted record at the end of the log, left by a crash while it was
+    /// written, is discarded along with everything after it.
+    ///
+    /// ## Errors
+    /// Returns the error of opening, reading or truncating the file, or `InvalidData` if an
+    /// intact record does not deserialize as a `T`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let table = HcTable::new();
+        let mut log = Log {
+            file,
+            end: 0,
+            logged: HashSet::new(),
+            values: Vec::new(),
+        };
+        let mut end = 0;
+        while let Some(len) = bytes[end..].iter().position(|&byte| byte == b'\n') {
+            let Some(record) = parse_record(&bytes[end..end + len]) else {
+                break;
+            };
+            let value = serde_hc::with_table(&table, || serde_json::from_slice::<T>(record))?;
+            let hc = table.hashcons(value);
+            if log.logged.insert(hc.as_ptr() as usize) {
+                log.values.push(hc);
+            }
+            end += len + 1;
+        }
+        if end < bytes.len() {
+            log.file.set_len(end as u64)?;
+        }
+        log.file.seek(SeekFrom::Start(end as u64))?;
+        log.end = end as u64;
+
+        Ok(WalTable {
+            table,
+            log: Mutex::new(log),
+        })
+    }
+
+    /// Interns `value`, appending it to the log first if the table did not store it yet.
+    ///
+    /// The record is handed to the operating system before the handle is returned, which
+    /// survives a crash of the process. Call [`sync`](Self::sync) to also survive a crash of
+    /// the machine.
+    ///
+    /// ## Errors
+    /// Returns the error of serializing or writing the record. The value is not stored then.
+    pub fn hashcons(&self, value: T) -> io::Result<Hc<T>> {
+        let hc = self.table.hashcons(value);
+        let mut log = self.lock();
+        if log.logged.contains(&(hc.as_ptr() as usize)) {
+            return Ok(hc);
+        }
+
+        let json = serde_json::to_vec(hc.get())?;
+        let mut record = format!("{:016x} ", fnv1a(&json)).into_bytes();
+        record.extend_from_slice(&json);
+        record.push(b'\n');
+        if let Err(error) = log.file.write_all(&record) {
+            // Drops the part of the record that was written, so that later records stay
+            // reachable by replay.
+            let end = log.end;
+            log.file.set_len(end)?;
+            log.file.seek(SeekFrom::Start(end))?;
+            return Err(error);
+        }
+        log.end += record.len() as u64;
+
+        log.logged.insert(hc.as_ptr() as usize);
+        log.values.push(hc.clone());
+        Ok(hc)
+    }
+
+    /// Flushes the log to the storage device.
+    ///
+    /// ## Errors
+    /// Returns the error of syncing the file.
+    pub fn sync(&self) -> io::Result<()> {
+        self.lock().file.sync_data()
+    }
+
+    /// Returns the table the values are interned into.
+    pub fn table(&self) -> &HcTable<T> {
+        &self.table
+    }
+
+    /// Returns the number of values in the log.
+    pub fn len(&self) -> usize {
+        self.lock().values.len()
+    }
+
+    /// Returns `true` if the log holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Log<T>> {
+        match self.log.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                eprintln!("Mutex is poisoned. Continuing with the poisoned lock.");
+                self.log.clear_poison();
+                poisoned.into_inner()
+            }
+        }
+    }
+}
+
+/// Returns the value of a record line whose address matches its content, or `None` if the
+/// line is malformed.
+fn parse_record(line: &[u8]) -> Option<&[u8]> {
+    let (address, json) = (line.get(..16)?, line.get(17..)?);
+    let address = u64::from_str_radix(std::str::from_utf8(address).ok()?, 16).ok()?;
+    (line[16] == b' ' && address == fnv1a(json)).then_some(json)
+}
+
+/// Hashes `bytes` with 64-bit FNV-1a, which is the same on every platform and release.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl<T> std::fmt::Debug for WalTable<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let log = self
+            .log
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f.debug_struct("WalTable")
+            .field("values", &log.values.len())
+            .finish()
+    }
+}
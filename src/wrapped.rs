@@ -0,0 +1,170 @@
+//! # Newtype Handles
+//!
+//! A domain can brand the handles of a table with its own newtype, e.g. `Meters(f64)`, without
+//! a second table holding copies of the values. A handle is wrapped into a [`Wrapped`] handle
+//! that dereferences to the newtype, which costs nothing since a `#[repr(transparent)]` newtype
+//! has the layout of the value it wraps.
+
+use crate::{single_threaded, thread_safe};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::ops::Deref;
+
+/// # `Transparent<T>`
+/// Marks a `#[repr(transparent)]` newtype over `T`, whose references can be cast from
+/// references to `T`.
+///
+/// ## Safety
+/// `Self` must be a `#[repr(transparent)]` struct whose only field that is not zero-sized has
+/// type `T`, and must accept every value of `T`, since wrapped handles view the stored values
+/// as `Self` without checking them.
+///
+/// ## Example
+/// ```
+/// use hash_cons::Transparent;
+///
+/// #[repr(transparent)]
+/// struct Meters(u64);
+///
+/// // SAFETY: `Meters` is a transparent newtype over any `u64`.
+/// unsafe impl Transparent<u64> for Meters {}
+///
+/// assert_eq!(Meters::wrap_ref(&5).0, 5);
+/// ```
+pub unsafe trait Transparent<T>: Sized {
+    /// Views `value` as the newtype wrapping it.
+    fn wrap_ref(value: &T) -> &Self {
+        assert!(size_of::<Self>() == size_of::<T>() && align_of::<Self>() == align_of::<T>());
+        // SAFETY: The implementation promises that `Self` has the layout of `T` and accepts
+        // every value of it.
+        unsafe { &*(value as *const T as *const Self) }
+    }
+}
+
+/// # `Wrapped<W, H>`
+/// A handle `H` to a value viewed as the newtype `W` wrapping it. Created with `Hc::wrap`.
+///
+/// Wrapped handles share the table entry of their handle, and compare and hash like it. The
+/// handle is given back by `into_inner`.
+///
+/// ## Type Parameters
+/// * `W` - The newtype the value is viewed as.
+/// * `H` - The handle to the value, a thread-safe or single-threaded `Hc<T, M>`.
+///
+/// ## Example
+/// ```
+/// use hash_cons::{HcTable, Transparent, Wrapped};
+///
+/// #[repr(transparent)]
+/// struct Meters(u64);
+///
+/// // SAFETY: `Meters` is a transparent newtype over any `u64`.
+/// unsafe impl Transparent<u64> for Meters {}
+///
+/// let table = HcTable::new();
+/// let distance = table.hashcons(42u64).wrap::<Meters>();
+///
+/// assert_eq!(distance.0, 42);
+/// assert_eq!(distance.into_inner(), table.hashcons(42));
+/// ```
+pub struct Wrapped<W, H> {
+    handle: H,
+    _wrapper: PhantomData<fn() -> W>,
+}
+
+impl<W, H> Wrapped<W, H> {
+    /// Returns the handle to the unwrapped value.
+    pub fn handle(&self) -> &H {
+        &self.handle
+    }
+
+    /// Gives back the handle to the unwrapped value.
+    pub fn into_inner(self) -> H {
+        self.handle
+    }
+}
+
+impl<W, H> Wrapped<W, H>
+where
+    H: Deref,
+    H::Target: Sized,
+    W: Transparent<H::Target>,
+{
+    /// Wraps `handle` to view its value as `W`.
+    pub fn wrap(handle: H) -> Self {
+        Wrapped {
+            handle,
+            _wrapper: PhantomData,
+        }
+    }
+}
+
+impl<W, H> Deref for Wrapped<W, H>
+where
+    H: Deref,
+    H::Target: Sized,
+    W: Transparent<H::Target>,
+{
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        W::wrap_ref(&self.handle)
+    }
+}
+
+impl<W, H> AsRef<W> for Wrapped<W, H>
+where
+    H: Deref,
+    H::Target: Sized,
+    W: Transparent<H::Target>,
+{
+    fn as_ref(&self) -> &W {
+        self
+    }
+}
+
+impl<W, H: Clone> Clone for Wrapped<W, H> {
+    fn clone(&self) -> Self {
+        Wrapped {
+            handle: self.handle.clone(),
+            _wrapper: PhantomData,
+        }
+    }
+}
+
+impl<W, H: PartialEq> PartialEq for Wrapped<W, H> {
+    /// Compares the handles of the values, see `Hc::eq`.
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl<W, H: Eq> Eq for Wrapped<W, H> {}
+
+impl<W, H: Hash> Hash for Wrapped<W, H> {
+    /// Hashes the handle of the value, consistently with `eq`.
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        self.handle.hash(state);
+    }
+}
+
+impl<W, H: std::fmt::Debug> std::fmt::Debug for Wrapped<W, H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Wrapped").field(&self.handle).finish()
+    }
+}
+
+impl<T, M> thread_safe::Hc<T, M> {
+    /// Views the value of this handle as the newtype `W`, without interning it again.
+    pub fn wrap<W: Transparent<T>>(self) -> Wrapped<W, Self> {
+        Wrapped::wrap(self)
+    }
+}
+
+impl<T, M> single_threaded::Hc<T, M> {
+    /// Views the value of this handle as the newtype `W`, without interning it again.
+    pub fn wrap<W: Transparent<T>>(self) -> Wrapped<W, Self> {
+        Wrapped::wrap(self)
+    }
+}
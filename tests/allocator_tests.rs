@@ -0,0 +1,98 @@
+#[cfg(feature = "allocator-api2")]
+#[cfg(test)]
+mod allocator_tests {
+    use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+    use hash_cons::allocator::{AllocHc, AllocHcTable};
+    use std::cell::Cell;
+    use std::ptr::NonNull;
+    use std::rc::Rc;
+
+    /// An allocator that forwards to the global allocator and counts the live allocations.
+    #[derive(Clone, Default)]
+    struct Counting {
+        live: Rc<Cell<usize>>,
+        bytes: Rc<Cell<usize>>,
+    }
+
+    unsafe impl Allocator for Counting {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let block = Global.allocate(layout)?;
+            self.live.set(self.live.get() + 1);
+            self.bytes.set(self.bytes.get() + layout.size());
+            Ok(block)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.live.set(self.live.get() - 1);
+            self.bytes.set(self.bytes.get() - layout.size());
+            Global.deallocate(ptr, layout);
+        }
+    }
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum BoolExpr {
+        Const(bool),
+        And(AllocHc<BoolExpr, Counting>, AllocHc<BoolExpr, Counting>),
+        Not(AllocHc<BoolExpr, Counting>),
+    }
+
+    /// Equal values share their handle, and the map and nodes are allocated in the allocator.
+    #[test]
+    fn test_alloc_hashconsing() {
+        let alloc = Counting::default();
+        let table = AllocHcTable::new_in(alloc.clone());
+        let empty = alloc.bytes.get();
+
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let and = table.hashcons(BoolExpr::And(t.clone(), f.clone()));
+        let and_v2 = table.hashcons(BoolExpr::And(
+            table.hashcons(BoolExpr::Const(true)),
+            table.hashcons(BoolExpr::Const(false)),
+        ));
+
+        assert_eq!(and, and_v2, "Equal values should share the same handle");
+        assert_ne!(table.hashcons(BoolExpr::Not(t.clone())), and);
+        assert_eq!(*and, BoolExpr::And(t, f));
+        assert_eq!(table.len(), 3);
+        assert!(
+            alloc.bytes.get() > empty,
+            "The map and the nodes should be allocated in the allocator"
+        );
+    }
+
+    /// Dropping the last handle of a value frees it and the handles it owns.
+    #[test]
+    fn test_drop_cascades() {
+        let alloc = Counting::default();
+        let table = AllocHcTable::new_in(alloc.clone());
+
+        let not_t = table.hashcons(BoolExpr::Not(table.hashcons(BoolExpr::Const(true))));
+        let nodes = alloc.live.get();
+        assert_eq!(table.len(), 2);
+
+        drop(not_t);
+        assert!(
+            table.is_empty(),
+            "Children should be removed with their parent"
+        );
+        assert_eq!(alloc.live.get(), nodes - 2, "Both nodes should be freed");
+    }
+
+    /// Handles outlive the table, and everything is freed once the last of them is dropped.
+    #[test]
+    fn test_handles_outlive_table() {
+        let alloc = Counting::default();
+        let leaf = {
+            let table = AllocHcTable::new_in(alloc.clone());
+            table.hashcons(BoolExpr::Const(true))
+        };
+
+        assert_eq!(*leaf, BoolExpr::Const(true));
+        assert!(alloc.live.get() > 0);
+
+        drop(leaf);
+        assert_eq!(alloc.live.get(), 0, "Every allocation should be freed");
+        assert_eq!(alloc.bytes.get(), 0);
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(feature = "arena")]
+#[cfg(test)]
+mod arena_tests {
+    use bumpalo::Bump;
+    use hash_cons::arena::{ArenaHc, ArenaHcTable};
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum BoolExpr<'a> {
+        Const(bool),
+        And(ArenaHc<'a, BoolExpr<'a>>, ArenaHc<'a, BoolExpr<'a>>),
+        Not(ArenaHc<'a, BoolExpr<'a>>),
+    }
+
+    /// Equal values are allocated once and share their handle.
+    #[test]
+    fn test_arena_hashconsing() {
+        let arena = Bump::new();
+        let table = ArenaHcTable::new(&arena);
+
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let and = table.hashcons(BoolExpr::And(t, f));
+        let and_v2 = table.hashcons(BoolExpr::And(
+            table.hashcons(BoolExpr::Const(true)),
+            table.hashcons(BoolExpr::Const(false)),
+        ));
+
+        assert_eq!(and, and_v2, "Equal values should share the same handle");
+        assert_ne!(table.hashcons(BoolExpr::Not(t)), and);
+        assert_eq!(table.len(), 4);
+        assert_eq!(*and.get(), BoolExpr::And(t, f));
+    }
+
+    /// Handles outlive the table as long as the arena is alive.
+    #[test]
+    fn test_handles_outlive_table() {
+        let arena = Bump::new();
+        let leaf = {
+            let table = ArenaHcTable::new(&arena);
+            table.hashcons(BoolExpr::Const(true))
+        };
+
+        assert_eq!(*leaf, BoolExpr::Const(true));
+    }
+}
@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod backend_tests {
+    use hash_cons::backend::{ConsTable, NullTable};
+    use hash_cons::{single_threaded, thread_safe};
+    use std::collections::HashSet;
+
+    /// Interns a list of words with any backend, keeping the handles.
+    fn words<B: ConsTable<String>>(table: &B) -> Vec<B::Handle> {
+        "the cat saw the dog"
+            .split(' ')
+            .map(|word| table.hashcons(word.to_string()))
+            .collect()
+    }
+
+    /// The hash consing backends deduplicate the same workload the null backend stores as is.
+    #[test]
+    fn test_backends_share_workload() {
+        let concurrent = thread_safe::HcTable::new();
+        let local = single_threaded::HcTable::new();
+        let null = NullTable::new();
+        assert!(ConsTable::is_empty(&null));
+
+        let (a, b, c) = (words(&concurrent), words(&local), words(&null));
+        assert_eq!((concurrent.len(), local.len(), null.len()), (4, 4, 5));
+        assert!(a[0].as_ptr() == a[3].as_ptr());
+        assert!(b[0].as_ptr() == b[3].as_ptr());
+        assert_eq!(c[0], c[3]);
+        assert!(!std::ptr::eq(&*c[0], &*c[3]));
+    }
+
+    /// Null handles compare, hash and print by value.
+    #[test]
+    fn test_null_handles() {
+        let table = NullTable::default();
+        let first = table.hashcons(5);
+        let second = table.hashcons(5);
+
+        assert_eq!(first, second.clone());
+        assert_eq!(HashSet::from([first, second]).len(), 1);
+        assert_eq!(format!("{:?}", table.hashcons(7)), "7");
+        assert_eq!(format!("{table:?}"), "NullTable { stored: 3 }");
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(feature = "bytes")]
+#[cfg(test)]
+mod bytes_tests {
+    use bytes::{BufMut, Bytes, BytesMut};
+    use hash_cons::bytes::{get_slice, hashcons_bytes_mut, hashcons_slice};
+    use hash_cons::HcTable;
+
+    /// Interned buffers keep their data where it was, and are handed back without copying.
+    #[test]
+    fn test_hashcons_bytes_zero_copy() {
+        let table = HcTable::new();
+        let payload = Bytes::from(vec![7u8; 256]);
+        let fragment = payload.slice(16..48);
+
+        let hc = table.hashcons(fragment.clone());
+        assert_eq!(hc.get().as_ptr(), fragment.as_ptr());
+        assert_eq!(Bytes::from(&hc).as_ptr(), payload[16..].as_ptr());
+
+        let mut buffer = BytesMut::with_capacity(32);
+        buffer.put_slice(&[7u8; 32]);
+        let address = buffer.as_ptr();
+        let again = hashcons_bytes_mut(&table, buffer);
+        assert_eq!(again, hc);
+        assert_ne!(again.get().as_ptr(), address);
+
+        let other = hashcons_bytes_mut(&table, BytesMut::from(&b"header"[..]));
+        assert_eq!(Bytes::from(other), Bytes::from_static(b"header"));
+    }
+
+    /// Borrowed lookups find stored buffers and copy only missing ones.
+    #[test]
+    fn test_borrowed_lookup() {
+        let table = HcTable::new();
+        let value = table.hashcons(Bytes::from_static(b"keep-alive"));
+
+        assert_eq!(get_slice(&table, b"keep-alive"), Some(value.clone()));
+        assert!(get_slice(&table, b"close").is_none());
+        assert_eq!(table.len(), 1);
+
+        assert_eq!(hashcons_slice(&table, b"keep-alive"), value);
+        let close = hashcons_slice(&table, b"close");
+        assert_eq!(&close[..], b"close");
+        assert_eq!(get_slice(&table, b"close"), Some(close));
+    }
+}
@@ -0,0 +1,218 @@
+#[cfg(test)]
+mod dag_tests {
+    use hash_cons::dag::{diff, display_shared, CanonicalOrder, Transform};
+    use hash_cons::{HashConsed, Hc, HcTable};
+    use std::cmp::Ordering;
+    use std::fmt;
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum BoolExpr {
+        Const(bool),
+        Var(u32),
+        And(Hc<BoolExpr>, Hc<BoolExpr>),
+        Not(Hc<BoolExpr>),
+    }
+
+    impl HashConsed for BoolExpr {
+        fn children(&self) -> Vec<&Hc<BoolExpr>> {
+            match self {
+                BoolExpr::Const(_) | BoolExpr::Var(_) => vec![],
+                BoolExpr::And(lhs, rhs) => vec![lhs, rhs],
+                BoolExpr::Not(inner) => vec![inner],
+            }
+        }
+    }
+
+    impl fmt::Display for BoolExpr {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                BoolExpr::Const(value) => write!(f, "{value}"),
+                BoolExpr::Var(index) => write!(f, "v{index}"),
+                BoolExpr::And(..) => f.write_str("And"),
+                BoolExpr::Not(_) => f.write_str("Not"),
+            }
+        }
+    }
+
+    /// Shared nodes are bound once, so the output stays linear in the size of the DAG.
+    #[test]
+    fn test_display_shared() {
+        let table = HcTable::new();
+        let constant = table.hashcons(BoolExpr::Const(true));
+        assert_eq!(display_shared(&constant).to_string(), "true");
+        let var = table.hashcons(BoolExpr::Var(0));
+        assert_eq!(display_shared(&var).to_string(), "v0");
+
+        let not = table.hashcons(BoolExpr::Not(var.clone()));
+        let tree = table.hashcons(BoolExpr::And(not, var));
+        assert_eq!(display_shared(&tree).to_string(), "And(Not(v0), v0)");
+
+        // Doubling the term 12 times would print 2^12 leaves without sharing.
+        let mut term = tree.clone();
+        for _ in 0..12 {
+            term = table.hashcons(BoolExpr::And(term.clone(), term));
+        }
+        let printed = display_shared(&term).to_string();
+        assert_eq!(printed.lines().count(), 13);
+        assert!(printed.starts_with("let x0 = And(Not(v0), v0);\nlet x1 = And(x0, x0);\n"));
+        assert!(printed.ends_with("\nAnd(x11, x11)"));
+    }
+
+    /// Identical subterms are skipped, and each differing pair is reported once.
+    #[test]
+    fn test_diff() {
+        let table = HcTable::new();
+        let build = |var: u32| {
+            let var = table.hashcons(BoolExpr::Var(var));
+            let mut term = table.hashcons(BoolExpr::And(
+                table.hashcons(BoolExpr::Not(var.clone())),
+                var,
+            ));
+            for _ in 0..12 {
+                term = table.hashcons(BoolExpr::And(term.clone(), term));
+            }
+            term
+        };
+        let old = build(0);
+        let new = build(1);
+        assert!(diff(&old, &old.clone()).is_empty());
+
+        let differences = diff(&old, &new);
+        let printed: Vec<String> = differences.iter().map(ToString::to_string).collect();
+        // The variables also differ at `[0, .., 0, 1]`, which is not reported again.
+        let mut path = vec![0; 12];
+        path.extend([0, 0]);
+        assert_eq!(printed, [format!("{path:?}: v0 -> v1")]);
+
+        // Nodes of different shapes are reported as a whole.
+        let var = table.hashcons(BoolExpr::Var(0));
+        let not = table.hashcons(BoolExpr::Not(var.clone()));
+        let and = table.hashcons(BoolExpr::And(var.clone(), var));
+        let differences = diff(&not, &and);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].to_string(), "[]: Not(v0) -> And(v0, v0)");
+    }
+
+    /// Handles of different tables are compared structurally, each pair of nodes once.
+    #[test]
+    fn test_deep_eq_across_tables() {
+        let build = |table: &HcTable<BoolExpr>, var: u32| {
+            let var = table.hashcons(BoolExpr::Var(var));
+            let mut term = table.hashcons(BoolExpr::And(
+                table.hashcons(BoolExpr::Not(var.clone())),
+                var,
+            ));
+            for _ in 0..12 {
+                term = table.hashcons(BoolExpr::And(term.clone(), term));
+            }
+            term
+        };
+        let (first, second) = (HcTable::new(), HcTable::new());
+        let term = build(&first, 0);
+
+        assert!(term.deep_eq_across_tables(&term));
+        assert!(term.deep_eq_across_tables(&build(&second, 0)));
+        assert!(!term.deep_eq_across_tables(&build(&second, 1)));
+
+        let var = second.hashcons(BoolExpr::Var(0));
+        let not = second.hashcons(BoolExpr::Not(var.clone()));
+        assert!(!term.deep_eq_across_tables(&not));
+        assert!(!not.deep_eq_across_tables(&var));
+    }
+
+    /// Every source node is mapped once, so the image shares like the source.
+    #[test]
+    fn test_transform() {
+        let source = HcTable::new();
+        let var = source.hashcons(BoolExpr::Var(0));
+        let base = source.hashcons(BoolExpr::And(
+            source.hashcons(BoolExpr::Not(var.clone())),
+            var,
+        ));
+        let mut term = base.clone();
+        for _ in 0..12 {
+            term = source.hashcons(BoolExpr::And(term.clone(), term));
+        }
+
+        let target = HcTable::new();
+        let mut rename = Transform::new(&target);
+        let mut mapped = 0;
+        let mut map = |node: &BoolExpr, children: &[Hc<BoolExpr>]| {
+            mapped += 1;
+            match node {
+                BoolExpr::Const(value) => BoolExpr::Const(*value),
+                BoolExpr::Var(index) => BoolExpr::Var(index + 1),
+                BoolExpr::And(..) => BoolExpr::And(children[0].clone(), children[1].clone()),
+                BoolExpr::Not(_) => BoolExpr::Not(children[0].clone()),
+            }
+        };
+        let image = rename.apply(&term, &mut map);
+        assert_eq!(
+            display_shared(&image).to_string().lines().next(),
+            Some("let x0 = And(Not(v1), v1);")
+        );
+        assert_eq!(target.len(), 15);
+
+        // Nodes mapped before are not mapped again.
+        let base_image = rename.apply(&base, &mut map);
+        assert_eq!(mapped, 15);
+        assert_eq!(rename.len(), 15);
+        assert!(rename.get(&base) == Some(base_image));
+    }
+
+    /// Orders the labels of `BoolExpr`, by variant and then by their leaves.
+    fn cmp_label(a: &BoolExpr, b: &BoolExpr) -> Ordering {
+        let rank = |node: &BoolExpr| match node {
+            BoolExpr::Const(value) => (0, *value as u32),
+            BoolExpr::Var(index) => (1, *index),
+            BoolExpr::And(..) => (2, 0),
+            BoolExpr::Not(_) => (3, 0),
+        };
+        rank(a).cmp(&rank(b))
+    }
+
+    /// Terms are ordered by label and then lexicographically by children, comparing each pair
+    /// of nodes once, also if the terms have exponentially many paths.
+    #[test]
+    fn test_canonical_order() {
+        let table = HcTable::new();
+        let (x, y) = (
+            table.hashcons(BoolExpr::Var(0)),
+            table.hashcons(BoolExpr::Var(1)),
+        );
+        let not_x = table.hashcons(BoolExpr::Not(x.clone()));
+        let and_xy = table.hashcons(BoolExpr::And(x.clone(), y.clone()));
+        let and_yx = table.hashcons(BoolExpr::And(y.clone(), x.clone()));
+
+        let mut order = CanonicalOrder::new(cmp_label);
+        let mut terms = vec![
+            not_x.clone(),
+            and_yx.clone(),
+            y.clone(),
+            and_xy.clone(),
+            x.clone(),
+        ];
+        order.sort(&mut terms);
+        assert!(terms == [x.clone(), y.clone(), and_xy, and_yx, not_x]);
+
+        // Two terms differing in their deepest leaf only
+        let (mut left, mut right) = (x.clone(), y.clone());
+        for _ in 0..12 {
+            left = table.hashcons(BoolExpr::And(left.clone(), left));
+            right = table.hashcons(BoolExpr::And(right.clone(), right));
+        }
+        let compared = order.len();
+        assert_eq!(order.cmp(&left, &right), Ordering::Less);
+        assert_eq!(order.cmp(&right, &left), Ordering::Greater);
+        assert_eq!(order.cmp(&left, &left), Ordering::Equal);
+        assert_eq!(order.len() - compared, 12);
+
+        // Equal terms of different tables compare equal
+        let other = HcTable::new();
+        let mut copy = other.hashcons(BoolExpr::Var(0));
+        for _ in 0..12 {
+            copy = other.hashcons(BoolExpr::And(copy.clone(), copy));
+        }
+        assert_eq!(order.cmp(&left, &copy), Ordering::Equal);
+    }
+}
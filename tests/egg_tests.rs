@@ -0,0 +1,79 @@
+#[cfg(feature = "egg")]
+#[cfg(test)]
+mod egg_tests {
+    use egg::{define_language, AstSize, EGraph, Extractor, Id, RecExpr};
+    use hash_cons::egg::{add_to_egraph, from_rec_expr, to_rec_expr};
+    use hash_cons::{HashConsed, Hc, HcTable};
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum Arith {
+        Num(i32),
+        Add(Hc<Arith>, Hc<Arith>),
+        Mul(Hc<Arith>, Hc<Arith>),
+    }
+
+    impl HashConsed for Arith {
+        fn children(&self) -> Vec<&Hc<Arith>> {
+            match self {
+                Arith::Num(_) => vec![],
+                Arith::Add(lhs, rhs) | Arith::Mul(lhs, rhs) => vec![lhs, rhs],
+            }
+        }
+    }
+
+    define_language! {
+        enum Lang {
+            Num(i32),
+            "+" = Add([Id; 2]),
+            "*" = Mul([Id; 2]),
+        }
+    }
+
+    fn to_lang(node: &Arith, children: &[Id]) -> Lang {
+        match node {
+            Arith::Num(n) => Lang::Num(*n),
+            Arith::Add(..) => Lang::Add([children[0], children[1]]),
+            Arith::Mul(..) => Lang::Mul([children[0], children[1]]),
+        }
+    }
+
+    fn from_lang(node: &Lang, children: &[Hc<Arith>]) -> Arith {
+        match node {
+            Lang::Num(n) => Arith::Num(*n),
+            Lang::Add(_) => Arith::Add(children[0].clone(), children[1].clone()),
+            Lang::Mul(_) => Arith::Mul(children[0].clone(), children[1].clone()),
+        }
+    }
+
+    /// Shared subterms become a single `RecExpr` entry and are shared again on the way back.
+    #[test]
+    fn test_rec_expr_round_trip_preserves_sharing() {
+        let table = HcTable::new();
+        let two = table.hashcons(Arith::Num(2));
+        let sum = table.hashcons(Arith::Add(two.clone(), two.clone()));
+        let root = table.hashcons(Arith::Mul(sum.clone(), sum.clone()));
+
+        let expr: RecExpr<Lang> = to_rec_expr(&root, to_lang);
+        assert_eq!(expr.len(), 3, "Each distinct node should appear once");
+        assert_eq!(expr.to_string(), "(* (+ 2 2) (+ 2 2))");
+
+        let back = from_rec_expr(&expr, &table, from_lang);
+        assert_eq!(back, root);
+        assert_eq!(table.len(), 3);
+    }
+
+    /// A DAG added to an e-graph can be extracted and interned again.
+    #[test]
+    fn test_egraph_round_trip() {
+        let table = HcTable::new();
+        let one = table.hashcons(Arith::Num(1));
+        let root = table.hashcons(Arith::Add(one.clone(), one));
+
+        let mut egraph: EGraph<Lang, ()> = EGraph::default();
+        let id = add_to_egraph(&mut egraph, &root, to_lang);
+        egraph.rebuild();
+
+        let (_, best) = Extractor::new(&egraph, AstSize).find_best(id);
+        assert_eq!(from_rec_expr(&best, &table, from_lang), root);
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(feature = "heapless")]
+#[cfg(test)]
+mod heapless_tests {
+    use hash_cons::heapless::{HeaplessHc, HeaplessHcTable, HeaplessStorage};
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum BoolExpr<'a> {
+        Const(bool),
+        And(HeaplessHc<'a, BoolExpr<'a>>, HeaplessHc<'a, BoolExpr<'a>>),
+        Not(HeaplessHc<'a, BoolExpr<'a>>),
+    }
+
+    /// Equal values take one slot and share their handle.
+    #[test]
+    fn test_heapless_hashconsing() {
+        let mut storage = HeaplessStorage::<BoolExpr, 8>::new();
+        let table = HeaplessHcTable::new(&mut storage);
+
+        let t = table.hashcons(BoolExpr::Const(true)).unwrap();
+        let f = table.hashcons(BoolExpr::Const(false)).unwrap();
+        let and = table.hashcons(BoolExpr::And(t, f)).unwrap();
+        let and_v2 = table
+            .hashcons(BoolExpr::And(
+                table.hashcons(BoolExpr::Const(true)).unwrap(),
+                table.hashcons(BoolExpr::Const(false)).unwrap(),
+            ))
+            .unwrap();
+
+        assert_eq!(and, and_v2, "Equal values should share the same handle");
+        assert_eq!(table.get(&BoolExpr::Const(true)), Some(t));
+        assert!(!table.contains(&BoolExpr::Not(t)));
+        assert_eq!((table.len(), table.capacity()), (3, 8));
+    }
+
+    /// A full table still finds interned values, and hands new ones back.
+    #[test]
+    fn test_heapless_table_full() {
+        let mut storage = HeaplessStorage::<&str, 2>::new();
+        let table = HeaplessHcTable::new(&mut storage);
+
+        let a = table.hashcons("a").unwrap();
+        table.hashcons("b").unwrap();
+        let full = table.hashcons("c").unwrap_err();
+
+        assert_eq!(full.into_inner(), "c");
+        assert_eq!(table.hashcons("a").unwrap(), a);
+        assert!(table.get(&"c").is_none());
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            format!("{table:?}"),
+            "HeaplessHcTable { len: 2, capacity: 2 }"
+        );
+    }
+
+    /// Empty storage holds nothing, and values are dropped with the storage.
+    #[test]
+    fn test_heapless_zero_capacity() {
+        let mut storage = HeaplessStorage::<String, 0>::default();
+        let table = HeaplessHcTable::new(&mut storage);
+
+        assert!(table.hashcons(String::from("x")).is_err());
+        assert!(table.is_empty());
+
+        let counter = std::rc::Rc::new(());
+        let mut storage = HeaplessStorage::<std::rc::Rc<()>, 1>::new();
+        HeaplessHcTable::new(&mut storage)
+            .hashcons(counter.clone())
+            .unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&counter), 2);
+        drop(storage);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(feature = "history")]
+#[cfg(test)]
+mod history_tests {
+    use hash_cons::history::{HistoryKind, DEFAULT_HISTORY_CAPACITY};
+    use hash_cons::{single_threaded, thread_safe};
+
+    /// Stores and removals are recorded with the id of the entry and the thread.
+    #[test]
+    fn test_history_events() {
+        let table = thread_safe::HcTable::new();
+        let main = table.hashcons(1);
+        let spawned = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let value = table.hashcons(2);
+                    (table.entry_id(&value).unwrap(), std::thread::current().id())
+                })
+                .join()
+                .unwrap()
+        });
+        let main_id = table.entry_id(&main).unwrap();
+        table.remove(main).unwrap();
+
+        let history = table.history();
+        let created = |id| {
+            history
+                .iter()
+                .find(|event| event.id == id && event.kind == HistoryKind::Interned)
+                .unwrap()
+        };
+        assert_eq!(created(main_id).thread, std::thread::current().id());
+        assert_eq!(created(spawned.0).thread, spawned.1);
+        assert!(history
+            .iter()
+            .any(|event| event.id == main_id && event.kind == HistoryKind::Evicted));
+        assert!(history.windows(2).all(|pair| pair[0].time <= pair[1].time));
+    }
+
+    /// The history keeps the most recent events only.
+    #[test]
+    fn test_history_capacity() {
+        let table = single_threaded::HcTable::new();
+        let values: Vec<_> = (0..DEFAULT_HISTORY_CAPACITY as u32 + 10)
+            .map(|i| table.hashcons(i))
+            .collect();
+        let history = table.history();
+        assert_eq!(history.len(), DEFAULT_HISTORY_CAPACITY);
+        assert_eq!(history[0].id, 10);
+
+        table.set_history_capacity(2);
+        assert_eq!(table.history().len(), 2);
+        let last = table.entry_id(values.last().unwrap()).unwrap();
+        assert_eq!(table.history()[1].id, last);
+
+        table.set_history_capacity(0);
+        let _more = table.hashcons(u32::MAX);
+        assert!(table.history().is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod interner_tests {
+    use hash_cons::interner::Interner;
+    use hash_cons::strong::StrongHcTable;
+    use hash_cons::{single_threaded, thread_safe};
+    use std::collections::HashSet;
+
+    /// Interns a list of words with any interner, returning the distinct ids in order.
+    fn word_ids<I: Interner<String>>(interner: &I) -> Vec<I::Id> {
+        let words: Vec<_> = "the cat saw the dog"
+            .split(' ')
+            .map(|word| interner.intern(word.to_string()))
+            .collect();
+        assert!(words[0] == words[3] && words[0] != words[1]);
+        assert_eq!(words.iter().cloned().collect::<HashSet<_>>().len(), 4);
+        assert_eq!(
+            interner.lookup(&"cat".to_string()).as_deref(),
+            Some(&words[1] as &String)
+        );
+        assert!(interner.lookup(&"bird".to_string()).is_none());
+
+        let mut ids = Vec::new();
+        for word in &words {
+            let id = interner.id(word);
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    /// Every interner dedupes the same words, and hands out one id per distinct word.
+    #[test]
+    fn test_interners_share_workload() {
+        let concurrent = thread_safe::HcTable::new();
+        let local = single_threaded::HcTable::new();
+        let strong = StrongHcTable::new();
+
+        assert_eq!(word_ids(&concurrent), [0, 1, 2, 3]);
+        assert_eq!(word_ids(&local), [0, 1, 2, 3]);
+        let ids = word_ids(&strong);
+        assert_eq!(
+            ids.iter().map(|id| id.index()).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+    }
+
+    /// Values interned in a strong table are copyable references into it.
+    #[test]
+    fn test_strong_interned() {
+        let table = StrongHcTable::new();
+        let cat = Interner::intern(&table, "cat");
+        let copy = cat;
+
+        assert_eq!(cat, copy);
+        assert!(std::ptr::eq(cat.get(), &table[cat.id()]));
+        assert_eq!(format!("{cat:?}"), "\"cat\"");
+    }
+}
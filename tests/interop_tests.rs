@@ -0,0 +1,70 @@
+#[cfg(feature = "internment")]
+#[cfg(test)]
+mod internment_tests {
+    use hash_cons::internment::{hashcons_arc_intern, hashcons_intern};
+    use hash_cons::HcTable;
+    use internment::{ArcIntern, Intern};
+
+    /// Values survive a round trip through `ArcIntern` and come back as the same handle.
+    #[test]
+    fn test_arc_intern_round_trip() {
+        let table = HcTable::new();
+        let hc = table.hashcons(vec![1u8, 2, 3]);
+
+        let intern = ArcIntern::from(hc.clone());
+        assert_eq!(*intern, vec![1, 2, 3]);
+        assert_eq!(
+            ArcIntern::from(&hc),
+            intern,
+            "Conversions should reuse the pooled value"
+        );
+
+        assert_eq!(hashcons_arc_intern(&table, &intern), hc);
+        assert_eq!(table.len(), 1);
+    }
+
+    /// Values survive a round trip through the leaked `Intern` pool.
+    #[test]
+    fn test_intern_round_trip() {
+        let table = HcTable::new();
+        let hc = table.hashcons(("interop", 7u32));
+
+        let intern: Intern<(&str, u32)> = Intern::from(&hc);
+        assert_eq!(hashcons_intern(&table, intern), hc);
+    }
+}
+
+#[cfg(feature = "lasso")]
+#[cfg(test)]
+mod lasso_tests {
+    use hash_cons::lasso::{hashcons_key, to_key, try_hashcons_key};
+    use hash_cons::HcTable;
+    use lasso::Rodeo;
+
+    /// Strings map to the same key in both directions.
+    #[test]
+    fn test_rodeo_round_trip() {
+        let table = HcTable::new();
+        let mut rodeo = Rodeo::default();
+
+        let foo = table.hashcons("foo".to_string());
+        let key = to_key(&foo, &mut rodeo);
+        assert_eq!(to_key(&table.hashcons("foo".to_string()), &mut rodeo), key);
+        assert_eq!(hashcons_key(&table, &rodeo, &key), foo);
+
+        let reader = rodeo.into_reader();
+        assert_eq!(try_hashcons_key(&table, &reader, &key), Some(foo));
+    }
+
+    /// Keys unknown to the resolver are reported instead of panicking.
+    #[test]
+    fn test_unknown_key() {
+        let table = HcTable::new();
+        let resolver = Rodeo::default().into_resolver();
+        let mut other = Rodeo::default();
+        let key = other.get_or_intern("elsewhere");
+
+        assert_eq!(try_hashcons_key(&table, &resolver, &key), None);
+        assert!(table.is_empty());
+    }
+}
@@ -0,0 +1,44 @@
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod json_tests {
+    use hash_cons::json::{hashcons_value, to_value, JsonNode};
+    use hash_cons::HcTable;
+    use serde_json::{json, Value};
+
+    /// Equal subtrees are stored once, within and across documents.
+    #[test]
+    fn test_json_sharing() {
+        let table: HcTable<JsonNode> = HcTable::new();
+        let record = |id: u64| json!({"id": id, "host": {"name": "web", "port": 80}, "ok": true});
+
+        let first = hashcons_value(&table, record(1));
+        let len = table.len();
+        let second = hashcons_value(&table, record(2));
+        let again = hashcons_value(&table, record(1));
+
+        assert_eq!(first, again);
+        assert_ne!(first, second);
+        // Only the id and the record holding it are new.
+        assert_eq!(table.len(), len + 2);
+
+        let array = hashcons_value(&table, json!([null, null, [1.5, "web"]]));
+        let JsonNode::Array(elements) = array.get() else {
+            panic!("expected an array")
+        };
+        assert_eq!(elements[0], elements[1]);
+        assert_eq!(table.hashcons(JsonNode::Null), elements[0]);
+    }
+
+    /// Interned documents serialize and convert back to the original value.
+    #[test]
+    fn test_json_round_trip() {
+        let table = HcTable::new();
+        let text = r#"{"a":[1,-2,3.25,{"b":null}],"c":"text","d":false,"e":{}}"#;
+        let value: Value = serde_json::from_str(text).unwrap();
+
+        let node = hashcons_value(&table, value.clone());
+
+        assert_eq!(to_value(&node), value);
+        assert_eq!(serde_json::to_string(node.get()).unwrap(), text);
+    }
+}
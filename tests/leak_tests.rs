@@ -0,0 +1,47 @@
+#[cfg(feature = "leak-check")]
+#[cfg(test)]
+mod leak_tests {
+    use hash_cons::{single_threaded, thread_safe, MemoryPressure};
+
+    /// Reports count the values that still have handles, also after the table is dropped.
+    #[test]
+    fn test_leak_report() {
+        let table = thread_safe::HcTable::new();
+        let kept = table.hashcons("kept".to_string());
+        drop(table.hashcons("dropped".to_string()));
+
+        let report = table.leak_report();
+        assert_eq!(report.leaked, 1);
+        assert!(report.type_name.contains("String"));
+        assert!(report.backtraces.len() <= 1);
+        assert!(report
+            .to_string()
+            .contains("1 of its values still have handles"));
+
+        let pressure = MemoryPressure::new();
+        pressure.register(&table);
+        let clone = table.clone();
+        drop(table);
+        assert_eq!(clone.leak_report().leaked, 1);
+        drop(clone);
+        pressure.release(usize::MAX);
+        assert_eq!(*kept, "kept");
+    }
+
+    /// Clones and forks count as tables until the last of them is dropped.
+    #[test]
+    fn test_leak_report_single_threaded() {
+        let table = single_threaded::HcTable::new();
+        let clone = table.clone();
+        let fork = table.fork();
+        let added = fork.hashcons(1);
+        let kept = table.hashcons(2);
+
+        assert_eq!(fork.parent().leak_report().leaked, 1);
+        drop(table);
+        drop(fork);
+        assert_eq!(clone.leak_report().leaked, 1);
+        drop(clone);
+        assert_eq!((*added, *kept), (1, 2));
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod normalize_tests {
+    use hash_cons::normalize::{FlattenAssociative, Operator, SortOperands, StripDoubleNegation};
+    use hash_cons::{HashConsed, Hc, HcTable};
+
+    #[derive(Hash, PartialEq, Eq, Clone, Debug)]
+    enum Expr {
+        Var(char),
+        Add(Vec<Hc<Expr>>),
+        Mul(Vec<Hc<Expr>>),
+        Sub(Hc<Expr>, Hc<Expr>),
+        Neg(Hc<Expr>),
+    }
+
+    impl HashConsed for Expr {
+        fn children(&self) -> Vec<&Hc<Expr>> {
+            match self {
+                Expr::Var(_) => vec![],
+                Expr::Add(operands) | Expr::Mul(operands) => operands.iter().collect(),
+                Expr::Sub(lhs, rhs) => vec![lhs, rhs],
+                Expr::Neg(operand) => vec![operand],
+            }
+        }
+    }
+
+    impl Operator for Expr {
+        fn with_children(self, mut children: Vec<Hc<Expr>>) -> Expr {
+            match self {
+                Expr::Var(_) => self,
+                Expr::Add(_) => Expr::Add(children),
+                Expr::Mul(_) => Expr::Mul(children),
+                Expr::Sub(..) => {
+                    let rhs = children.pop().unwrap();
+                    Expr::Sub(children.pop().unwrap(), rhs)
+                }
+                Expr::Neg(_) => Expr::Neg(children.remove(0)),
+            }
+        }
+
+        fn is_commutative(&self) -> bool {
+            matches!(self, Expr::Add(_) | Expr::Mul(_))
+        }
+
+        fn is_associative(&self) -> bool {
+            matches!(self, Expr::Add(_) | Expr::Mul(_))
+        }
+
+        fn is_negation(&self) -> bool {
+            matches!(self, Expr::Neg(_))
+        }
+    }
+
+    /// Interns the variables `a`, `b` and `c`, in this order.
+    fn vars(table: &HcTable<Expr>) -> (Hc<Expr>, Hc<Expr>, Hc<Expr>) {
+        (
+            table.hashcons(Expr::Var('a')),
+            table.hashcons(Expr::Var('b')),
+            table.hashcons(Expr::Var('c')),
+        )
+    }
+
+    /// Operands of commutative operators are ordered by the ids of their handles.
+    #[test]
+    fn test_sort_operands() {
+        let table = HcTable::builder().normalize(SortOperands).build();
+        let (a, b, c) = vars(&table);
+
+        let sum = table.hashcons(Expr::Add(vec![c.clone(), a.clone(), b.clone()]));
+        assert_eq!(*sum, Expr::Add(vec![a.clone(), b.clone(), c.clone()]));
+        assert_eq!(
+            sum,
+            table.hashcons(Expr::Add(vec![b.clone(), c, a.clone()]))
+        );
+        assert_ne!(
+            table.hashcons(Expr::Sub(a.clone(), b.clone())),
+            table.hashcons(Expr::Sub(b, a))
+        );
+    }
+
+    /// Nested applications of an associative operator are inlined, but not across operators.
+    #[test]
+    fn test_flatten_associative() {
+        let table = HcTable::builder()
+            .normalize(FlattenAssociative.then(SortOperands))
+            .build();
+        let (a, b, c) = vars(&table);
+
+        let ab = table.hashcons(Expr::Add(vec![a.clone(), b.clone()]));
+        let bc = table.hashcons(Expr::Add(vec![b.clone(), c.clone()]));
+        let left = table.hashcons(Expr::Add(vec![ab.clone(), c.clone()]));
+        let right = table.hashcons(Expr::Add(vec![bc, a.clone()]));
+        assert_eq!(left, right);
+        assert_eq!(left.children().len(), 3);
+
+        let product = table.hashcons(Expr::Mul(vec![ab.clone(), c]));
+        assert!(product.children().contains(&&ab));
+        let nested = table.hashcons(Expr::Add(vec![left.clone(), ab]));
+        assert_eq!(nested.children().len(), 5);
+    }
+
+    /// Double negations intern to the handle of the negated value.
+    #[test]
+    fn test_strip_double_negation() {
+        let table = HcTable::builder().normalize(StripDoubleNegation).build();
+        let (a, _b, _c) = vars(&table);
+
+        let neg = table.hashcons(Expr::Neg(a.clone()));
+        let double = table.hashcons(Expr::Neg(neg.clone()));
+        assert!(double.as_ptr() == a.as_ptr());
+        assert_eq!(table.hashcons(Expr::Neg(double)), neg);
+        assert_eq!(table.len(), 4);
+    }
+}
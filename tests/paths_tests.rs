@@ -0,0 +1,39 @@
+#[cfg(test)]
+mod paths_tests {
+    use hash_cons::paths::{get_os_str, get_path, hashcons_os_str, hashcons_path};
+    use hash_cons::HcTable;
+    use std::ffi::{OsStr, OsString};
+    use std::path::{Path, PathBuf};
+
+    /// Borrowed paths find the stored one and are copied only when missing.
+    #[test]
+    fn test_path_interning() {
+        let table: HcTable<PathBuf> = HcTable::new();
+        let lib = table.hashcons(PathBuf::from("src/lib.rs"));
+
+        assert_eq!(hashcons_path(&table, Path::new("src/lib.rs")), lib);
+        let dotted = hashcons_path(&table, Path::new("./src/lib.rs"));
+        assert_ne!(dotted, lib);
+        assert_eq!(get_path(&table, Path::new("src/lib.rs")), Some(lib.clone()));
+        assert!(get_path(&table, Path::new("src/main.rs")).is_none());
+        assert_eq!(table.len(), 2);
+
+        let as_ref: &Path = lib.as_ref();
+        assert_eq!(as_ref.file_name(), Some(OsStr::new("lib.rs")));
+    }
+
+    /// Borrowed OS strings find the stored one and are copied only when missing.
+    #[test]
+    fn test_os_str_interning() {
+        let table: HcTable<OsString> = HcTable::new();
+        let flag = hashcons_os_str(&table, OsStr::new("--release"));
+
+        assert_eq!(table.hashcons(OsString::from("--release")), flag);
+        assert_eq!(
+            get_os_str(&table, OsStr::new("--release")),
+            Some(flag.clone())
+        );
+        assert!(get_os_str(&table, OsStr::new("--debug")).is_none());
+        assert_eq!(table.len(), 1);
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(feature = "persistent")]
+#[cfg(test)]
+mod persistent_tests {
+    use hash_cons::persistent::{PersistentHc, PersistentHcTable};
+    use std::thread;
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum BoolExpr {
+        Const(bool),
+        And(PersistentHc<BoolExpr>, PersistentHc<BoolExpr>),
+    }
+
+    /// Equal values share their handle, and snapshots keep the values interned before them.
+    #[test]
+    fn test_persistent_snapshot() {
+        let table = PersistentHcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let snapshot = table.snapshot();
+        let and = table.hashcons(BoolExpr::And(t.clone(), f.clone()));
+
+        assert_eq!(table.hashcons(BoolExpr::Const(true)), t);
+        assert_eq!(table.hashcons(BoolExpr::And(t.clone(), f.clone())), and);
+        assert_eq!(snapshot.get(&BoolExpr::Const(false)), Some(f.clone()));
+        assert!(!snapshot.contains(&BoolExpr::And(t.clone(), f)));
+        assert_eq!(snapshot.iter().count(), 2);
+        assert_eq!((snapshot.len(), table.len()), (2, 3));
+        assert!(table.snapshot().iter().any(|hc| hc == and));
+    }
+
+    /// Snapshots are iterated while other threads keep interning.
+    #[test]
+    fn test_persistent_concurrent_snapshots() {
+        let table = PersistentHcTable::new();
+        let writers: Vec<_> = (0..4u32)
+            .map(|worker| {
+                let table = table.clone();
+                thread::spawn(move || {
+                    for i in 0..500u32 {
+                        table.hashcons((worker * 250 + i) % 1000);
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..50 {
+            let snapshot = table.snapshot();
+            assert_eq!(snapshot.iter().count(), snapshot.len());
+        }
+        for writer in writers {
+            writer.join().expect("Writers should not panic");
+        }
+
+        assert_eq!(table.len(), 1000);
+        let snapshot = table.snapshot();
+        assert!((0..1000u32).all(|i| snapshot.get(&i).map(|hc| *hc) == Some(i)));
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod pressure_tests {
+    use hash_cons::{single_threaded, thread_safe, MemoryPressure, TryRelease};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Releasing drops dead entries and spare capacity, but never values with handles.
+    #[test]
+    fn test_try_release() {
+        let table = thread_safe::HcTable::new();
+        let kept = table.hashcons(vec![0u8; 64]);
+        let unused: Vec<_> = (1..100u8).map(|i| table.hashcons(vec![i; 64])).collect();
+        drop(unused);
+
+        let before = table.memory_usage().total();
+        let released = table.try_release(usize::MAX);
+        assert!(released > 0);
+        assert_eq!(table.memory_usage().total(), before - released);
+        assert_eq!((table.len(), table.dead_count()), (1, 0));
+        assert_eq!(*kept, vec![0u8; 64]);
+
+        let single = single_threaded::HcTable::new();
+        let kept = single.hashcons(String::from("kept"));
+        drop(single.hashcons(String::from("unused")));
+        single.try_release(usize::MAX);
+        assert_eq!(single.len(), 1);
+        assert_eq!(*kept, "kept");
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Only as many unused values as needed are evicted, heaviest first.
+    #[test]
+    fn test_try_release_evicts_heaviest() {
+        let table = thread_safe::HcTable::new();
+        drop(table.hashcons(vec![0u8; 16]));
+        drop(table.hashcons(vec![1u8; 4096]));
+
+        assert!(table.try_release(1024) >= 4096);
+        assert_eq!(table.len(), 1);
+        assert!(table.memory_usage().dead < 4096);
+    }
+
+    /// Holders are asked in registration order until enough was released, and dropped tables
+    /// are forgotten.
+    #[test]
+    fn test_memory_pressure() {
+        let pressure = MemoryPressure::new();
+        let asked = Arc::new(AtomicUsize::new(0));
+        let first = asked.clone();
+        pressure.register_with(move |bytes| {
+            first.fetch_add(1, Ordering::Relaxed);
+            bytes.min(100)
+        });
+        let table = thread_safe::HcTable::new();
+        drop(table.hashcons(String::from("unused")));
+        pressure.register(&table);
+
+        assert_eq!(pressure.release(50), 50);
+        assert_eq!(asked.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            table.len(),
+            usize::from(cfg!(not(feature = "auto-cleanup")))
+        );
+
+        assert!(pressure.release(1 << 20) > 100);
+        assert_eq!(table.len(), 0);
+
+        drop(table);
+        pressure.release(1);
+        pressure.clone().try_release(1 << 20);
+        assert_eq!(format!("{pressure:?}"), "MemoryPressure { holders: 1 }");
+    }
+}
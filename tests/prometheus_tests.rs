@@ -0,0 +1,42 @@
+#[cfg(feature = "prometheus")]
+#[cfg(test)]
+mod prometheus_tests {
+    use hash_cons::prometheus::TableMetrics;
+    use hash_cons::thread_safe::HcTable;
+    use prometheus_client::encoding::text::encode;
+    use prometheus_client::registry::Registry;
+
+    /// Every family is described once, with a labelled metric per table.
+    #[test]
+    fn test_table_metrics() {
+        let types = HcTable::new();
+        let symbols = HcTable::new();
+        let metrics = TableMetrics::new();
+        let mut registry = Registry::default();
+        registry.register_collector(Box::new(metrics.clone()));
+        metrics.register("types", &types);
+        metrics.register("symbols", &symbols);
+
+        let int = types.hashcons("int");
+        let again = types.hashcons("int");
+        let _main = symbols.hashcons(String::from("main"));
+        drop(symbols.hashcons(String::from("tmp")));
+
+        let mut body = String::new();
+        encode(&mut body, &registry).unwrap();
+        assert_eq!(
+            body.matches("# TYPE hashcons_table_entries gauge").count(),
+            1
+        );
+        assert!(body.contains("hashcons_table_entries{table=\"types\"} 1\n"));
+        assert!(body.contains("hashcons_table_created_total{table=\"symbols\"} 2\n"));
+        assert!(body.contains("hashcons_table_reused_total{table=\"types\"} 1\n"));
+        assert!(body.contains("hashcons_table_reuse_ratio{table=\"types\"} 0.5\n"));
+        assert_eq!(body, metrics.render());
+
+        // Tables are forgotten once they and their handles are dropped.
+        drop((types, int, again));
+        assert_eq!(metrics.tables(), vec!["symbols".to_string()]);
+        assert!(!metrics.render().contains("types"));
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod remote_tests {
+    use hash_cons::remote::{Decoder, Encoder, Message, UnknownFingerprint};
+    use hash_cons::{HashConsed, Hc, HcTable};
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum Term {
+        Var(String),
+        App(Hc<Term>, Hc<Term>),
+    }
+
+    impl HashConsed for Term {
+        fn children(&self) -> Vec<&Hc<Term>> {
+            match self {
+                Term::Var(_) => vec![],
+                Term::App(fun, arg) => vec![fun, arg],
+            }
+        }
+    }
+
+    fn data(term: &Term) -> Option<String> {
+        match term {
+            Term::Var(name) => Some(name.clone()),
+            Term::App(..) => None,
+        }
+    }
+
+    fn node(data: &Option<String>, children: &[Hc<Term>]) -> Term {
+        match data {
+            Some(name) => Term::Var(name.clone()),
+            None => Term::App(children[0].clone(), children[1].clone()),
+        }
+    }
+
+    /// Builds `f (f (... (f x)))` with `depth` applications.
+    fn tower(table: &HcTable<Term>, depth: usize) -> Hc<Term> {
+        let f = table.hashcons(Term::Var("f".to_string()));
+        (0..depth).fold(table.hashcons(Term::Var("x".to_string())), |term, _| {
+            table.hashcons(Term::App(f.clone(), term))
+        })
+    }
+
+    /// Later messages only carry the nodes the receiver does not have, and the receiver
+    /// rebuilds the same sharing in its table.
+    #[test]
+    fn test_incremental_messages() {
+        let sender = HcTable::new();
+        let receiver = HcTable::new();
+        let mut encoder = Encoder::new();
+        let mut decoder = Decoder::new(&receiver);
+
+        let small = tower(&sender, 100);
+        let message = encoder.encode(&small, data);
+        assert_eq!(message.nodes.len(), 102);
+        let received = decoder.decode(&message, node).unwrap();
+
+        let large = tower(&sender, 110);
+        let message = encoder.encode(&large, data);
+        assert_eq!(message.nodes.len(), 10);
+        let received_large = decoder.decode(&message, node).unwrap();
+        let mut below = received_large.clone();
+        for _ in 0..10 {
+            let Term::App(_, arg) = below.get() else {
+                panic!("expected an application")
+            };
+            below = arg.clone();
+        }
+        assert_eq!(below, received);
+        assert_eq!(
+            (receiver.len(), decoder.len(), encoder.len()),
+            (112, 112, 112)
+        );
+    }
+
+    /// Equal terms of different sender tables get equal fingerprints.
+    #[test]
+    fn test_fingerprints_are_structural() {
+        let (first, second) = (HcTable::new(), HcTable::new());
+        let a = Encoder::new().encode(&tower(&first, 5), data);
+        let b = Encoder::new().encode(&tower(&second, 5), data);
+        assert_eq!(a, b);
+        assert_ne!(Encoder::new().encode(&tower(&first, 6), data).root, a.root);
+    }
+
+    /// A decoder that missed a message reports the first node it cannot resolve, and a cleared
+    /// encoder sends everything again.
+    #[test]
+    fn test_missing_nodes() {
+        let sender = HcTable::new();
+        let receiver = HcTable::new();
+        let mut encoder = Encoder::new();
+        let term = tower(&sender, 3);
+        let lost = encoder.encode(&term, data);
+
+        let mut decoder = Decoder::new(&receiver);
+        let again: Message<Option<String>> = encoder.encode(&term, data);
+        assert_eq!(
+            decoder.decode(&again, node),
+            Err(UnknownFingerprint(lost.root))
+        );
+
+        encoder.clear();
+        let complete = encoder.encode(&term, data);
+        assert_eq!(complete, lost);
+        assert_eq!(*decoder.decode(&complete, node).unwrap(), *term);
+    }
+}
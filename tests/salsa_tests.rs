@@ -0,0 +1,64 @@
+#[cfg(feature = "salsa")]
+#[cfg(test)]
+mod salsa_tests {
+    use hash_cons::salsa::{Durability, InternId, InternKey, SalsaInterner};
+    use hash_cons::HcTable;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct FunctionId(InternId);
+
+    impl InternKey for FunctionId {
+        fn from_intern_id(id: InternId) -> Self {
+            FunctionId(id)
+        }
+
+        fn as_intern_id(&self) -> InternId {
+            self.0
+        }
+    }
+
+    /// Ids are dense and handed out in interning order.
+    #[test]
+    fn test_dense_ids() {
+        let interner: SalsaInterner<&'static str> = SalsaInterner::new();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        let a_again = interner.intern("a");
+        let c = interner.intern("c");
+
+        assert_eq!(a.as_u32(), 0);
+        assert_eq!(b.as_u32(), 1);
+        assert_eq!(c.as_u32(), 2);
+        assert_eq!(a, a_again, "Equal values should receive the same id");
+        assert_eq!(interner.len(), 3);
+        assert_eq!(*interner.lookup(b), "b");
+        assert!(interner.try_lookup(InternId::from_u32(3)).is_none());
+    }
+
+    /// Lookups return the handle shared with the backing table.
+    #[test]
+    fn test_lookup_shares_table_handles() {
+        let table = HcTable::new();
+        let existing = table.hashcons(42u64);
+        let interner: SalsaInterner<u64, FunctionId> = SalsaInterner::with_table(table.clone());
+
+        let key = interner.intern(42);
+        assert_eq!(interner.lookup(key), existing);
+        assert_eq!(interner.key_of(&existing), Some(key));
+        assert_eq!(table.len(), 1);
+    }
+
+    /// Durability is only ever raised by re-interning.
+    #[test]
+    fn test_durability_hints() {
+        let interner: SalsaInterner<String> = SalsaInterner::new();
+        let id = interner.intern_with_durability("std".to_string(), Durability::Medium);
+        assert_eq!(interner.durability(id), Some(Durability::Medium));
+
+        interner.intern_with_durability("std".to_string(), Durability::Low);
+        assert_eq!(interner.durability(id), Some(Durability::Medium));
+
+        interner.intern_with_durability("std".to_string(), Durability::High);
+        assert_eq!(interner.durability(id), Some(Durability::High));
+    }
+}
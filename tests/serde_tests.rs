@@ -0,0 +1,82 @@
+#[cfg(feature = "serde")]
+#[cfg(test)]
+mod serde_tests {
+    use hash_cons::{serde_hc, Hc, HcTable};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Hash, PartialEq, Eq, Debug, Serialize, Deserialize)]
+    enum BoolExpr {
+        Const(bool),
+        And(
+            #[serde(with = "serde_hc")] Hc<BoolExpr>,
+            #[serde(with = "serde_hc")] Hc<BoolExpr>,
+        ),
+    }
+
+    /// Handles serialize as their values and deserialize to the handles of the installed table.
+    #[test]
+    fn test_serde_hc_round_trip() {
+        let table = HcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let and = table.hashcons(BoolExpr::And(t.clone(), t.clone()));
+
+        let json = serde_json::to_string(&*and).unwrap();
+        assert_eq!(json, r#"{"And":[{"Const":true},{"Const":true}]}"#);
+
+        let copy: BoolExpr = serde_hc::with_table(&table, || serde_json::from_str(&json)).unwrap();
+        assert_eq!(table.hashcons(copy), and);
+        assert_eq!(table.len(), 2);
+
+        // Deserializing into another table interns there, and shares equal values again.
+        let other: HcTable<BoolExpr> = HcTable::new();
+        let copy: BoolExpr = serde_hc::with_table(&other, || serde_json::from_str(&json)).unwrap();
+        assert_eq!(other.len(), 1);
+        assert_eq!(*other.hashcons(copy), *and);
+    }
+
+    /// Without an installed table, deserializing a handle fails instead of panicking.
+    #[test]
+    fn test_serde_hc_without_table() {
+        let json = r#"{"And":[{"Const":true},{"Const":false}]}"#;
+        let error = serde_json::from_str::<BoolExpr>(json).unwrap_err();
+        assert!(error.to_string().contains("serde_hc::with_table"));
+
+        // Tables are uninstalled when `with_table` returns.
+        let table: HcTable<BoolExpr> = HcTable::new();
+        serde_hc::with_table(&table, || ());
+        assert!(serde_json::from_str::<BoolExpr>(json).is_err());
+    }
+
+    /// Tables serialize their live values in storing order, with shared handles as positions.
+    #[test]
+    fn test_serialize_table() {
+        let table = HcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let and = table.hashcons(BoolExpr::And(t.clone(), f.clone()));
+        drop(table.hashcons(BoolExpr::And(f.clone(), f.clone())));
+        let nested = table.hashcons(BoolExpr::And(and.clone(), t.clone()));
+
+        assert_eq!(
+            serde_json::to_string(&table).unwrap(),
+            r#"[{"Const":true},{"Const":false},{"And":[0,1]},{"And":[2,0]}]"#
+        );
+
+        // Outside of a table, handles are serialized in full again.
+        assert_eq!(
+            serde_json::to_string(&*nested).unwrap(),
+            r#"{"And":[{"And":[{"Const":true},{"Const":false}]},{"Const":true}]}"#
+        );
+
+        // Handles of another table are serialized in full.
+        let other = HcTable::new();
+        drop(other.hashcons(BoolExpr::And(t, f)));
+        assert_eq!(serde_json::to_string(&other).unwrap(), "[]");
+        let kept = other.hashcons(BoolExpr::And(and.clone(), and));
+        assert_eq!(
+            serde_json::to_string(&other).unwrap(),
+            r#"[{"And":[{"And":[{"Const":true},{"Const":false}]},{"And":[{"Const":true},{"Const":false}]}]}]"#
+        );
+        drop(kept);
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(feature = "single-threaded")]
 #[cfg(test)]
 mod single_threaded_tests {
-    use hash_cons::{Hc, HcTable};
+    use hash_cons::{Hc, HcDyn, HcTable};
     use rand::Rng;
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
@@ -329,4 +329,1829 @@ mod single_threaded_tests {
             "Data length should always be greater than length of the table"
         );
     }
-}
\ No newline at end of file
+
+    /// Tables built with a custom equivalence merge values that are not `Eq`.
+    #[test]
+    fn test_custom_equivalence() {
+        #[derive(PartialEq, Eq)]
+        struct Labeled {
+            id: u32,
+            label: Option<Hc<Labeled>>,
+        }
+
+        impl Hash for Labeled {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.id.hash(state);
+            }
+        }
+
+        let table = HcTable::builder()
+            .equivalence(|a: &Labeled, b: &Labeled| a.id == b.id)
+            .build();
+        let first = table.hashcons(Labeled { id: 1, label: None });
+        let label = table.hashcons(Labeled { id: 2, label: None });
+
+        // The rejected value owns the last handle of `label`, which is released while
+        // interning.
+        let second = table.hashcons(Labeled {
+            id: 1,
+            label: Some(label),
+        });
+
+        assert!(
+            first == second,
+            "Equivalent values should share the same handle"
+        );
+        assert!(
+            second.label.is_none(),
+            "The value interned first should be kept"
+        );
+
+        #[cfg(feature = "auto-cleanup")]
+        assert_eq!(table.len(), 1);
+        #[cfg(not(feature = "auto-cleanup"))]
+        {
+            table.cleanup();
+            assert_eq!(table.len(), 1);
+        }
+    }
+
+    /// One type can be interned with different identities in different tables.
+    #[test]
+    fn test_custom_hashing() {
+        let exact = HcTable::new();
+        let by_parity = HcTable::builder()
+            .equivalence(|a: &u64, b: &u64| a % 2 == b % 2)
+            .hashing(|value: &u64, state: &mut dyn Hasher| state.write_u64(value % 2))
+            .build();
+
+        let values: Vec<u64> = (0..10).collect();
+        let exact_hcs: Vec<_> = values.iter().map(|&v| exact.hashcons(v)).collect();
+        let parity_hcs: Vec<_> = values.iter().map(|&v| by_parity.hashcons(v)).collect();
+
+        assert_eq!(exact.len(), 10);
+        assert_eq!(by_parity.len(), 2, "Only the parity should identify values");
+        assert_eq!(*parity_hcs[7], 1, "The value interned first should be kept");
+        assert!(exact_hcs[7] != exact_hcs[9]);
+    }
+
+    /// Values are normalized before they are looked up.
+    #[test]
+    fn test_normalize() {
+        let table = HcTable::builder()
+            .normalize(|expr: BoolExpr| match expr {
+                BoolExpr::Not(inner) => match inner.get() {
+                    BoolExpr::Not(double) => double.get().clone(),
+                    _ => BoolExpr::Not(inner),
+                },
+                expr => expr,
+            })
+            .build();
+
+        let ahc_true = table.hashcons(BoolExpr::Const(true));
+        let ahc_not = table.hashcons(BoolExpr::Not(ahc_true.clone()));
+        let ahc_not_not = table.hashcons(BoolExpr::Not(ahc_not.clone()));
+
+        assert!(
+            ahc_not_not == ahc_true,
+            "Double negations should be interned as their operand"
+        );
+        assert_eq!(table.len(), 2);
+    }
+
+    /// Trusted-hash tables identify values without comparing them.
+    #[test]
+    fn test_trusted_hash() {
+        #[derive(Eq)]
+        struct Blob(Vec<u8>);
+
+        impl Hash for Blob {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+
+        impl PartialEq for Blob {
+            fn eq(&self, _: &Self) -> bool {
+                panic!("Trusted-hash tables should not compare values");
+            }
+        }
+
+        let table = HcTable::builder().trusted_hash().build();
+        let first = table.hashcons(Blob(vec![7; 1024]));
+        let second = table.hashcons(Blob(vec![7; 1024]));
+        let other = table.hashcons(Blob(vec![8; 1024]));
+
+        assert!(std::ptr::eq(first.get(), second.get()));
+        assert!(!std::ptr::eq(first.get(), other.get()));
+        assert_eq!(table.len(), 2);
+    }
+
+    /// Interning with fallible map growth shares handles with `hashcons`, and reserving
+    /// impossible growth fails.
+    #[test]
+    fn test_hashcons_try_reserve() {
+        let table = HcTable::new();
+        let ahc_true = table.hashcons(BoolExpr::Const(true));
+        let ahc_true_v2 = table
+            .hashcons_try_reserve(BoolExpr::Const(true))
+            .expect("Interning an existing value should not allocate");
+        let ahc_not = table
+            .hashcons_try_reserve(BoolExpr::Not(ahc_true.clone()))
+            .expect("Interning a small value should succeed");
+
+        assert!(ahc_true == ahc_true_v2);
+        assert!(ahc_not != ahc_true);
+        assert!(table.try_reserve(100).is_ok());
+        assert!(
+            table.try_reserve(usize::MAX).is_err(),
+            "Reserving more than the address space should fail"
+        );
+        assert_eq!(table.len(), 2);
+    }
+
+    /// Memory usage accounts for live values, dead entries and the table itself.
+    #[test]
+    fn test_memory_usage_estimate() {
+        let table: HcTable<Vec<u64>> = HcTable::new();
+        let empty = table.memory_usage();
+        assert_eq!(empty.live + empty.dead, 0);
+
+        let small = table.hashcons(vec![1]);
+        let large = table.hashcons(vec![0; 1000]);
+        let usage = table.memory_usage();
+        assert!(
+            usage.live >= 1001 * 8,
+            "Heap memory of values should be counted"
+        );
+        assert!(usage.overhead > 0);
+        assert_eq!(usage.total(), usage.live + usage.dead + usage.overhead);
+
+        drop(large);
+        let after_drop = table.memory_usage();
+        #[cfg(feature = "auto-cleanup")]
+        assert_eq!(after_drop.dead, 0);
+        #[cfg(not(feature = "auto-cleanup"))]
+        assert!(after_drop.dead >= 1000 * 8, "Dropped values should be dead");
+        assert!(after_drop.live < usage.live);
+        drop(small);
+    }
+
+    /// Counters distinguish created from reused values and track map growth.
+    #[test]
+    fn test_stats_counters() {
+        let table = HcTable::new();
+        let ahc_data: Vec<_> = (0..1000u32).map(|i| table.hashcons(i % 100)).collect();
+
+        let stats = table.stats();
+        assert_eq!(stats.created, 100);
+        assert_eq!(stats.reused, 900);
+        assert_eq!(stats.lookups(), 1000);
+        assert!((stats.reuse_ratio() - 0.9).abs() < 1e-9);
+        assert!(
+            stats.map_reallocations > 0,
+            "Growing to 100 entries should reallocate the map"
+        );
+        drop(ahc_data);
+    }
+
+    /// Capacity diagnostics report tombstones and shrink after cleanup.
+    #[test]
+    fn test_capacity_diagnostics() {
+        let table = HcTable::new();
+        assert_eq!(table.load_factor(), 0.0);
+
+        let ahc_data: Vec<_> = (0..1000u32).map(|i| table.hashcons(i)).collect();
+        let capacity = table.capacity();
+        assert!(capacity >= 1000);
+        assert!(table.load_factor() > 0.0 && table.load_factor() <= 1.0);
+        assert_eq!(table.dead_count(), 0);
+
+        drop(ahc_data);
+        #[cfg(not(feature = "auto-cleanup"))]
+        {
+            assert_eq!(
+                table.dead_count(),
+                1000,
+                "Dropped values should be tombstones"
+            );
+            table.cleanup();
+        }
+        assert_eq!(table.dead_count(), 0);
+
+        table.shrink_to_fit();
+        assert!(table.capacity() < capacity);
+    }
+
+    /// Values with a constant hash show up as collisions in the diagnostics, and are reported
+    /// once they exceed the threshold.
+    #[test]
+    fn test_hash_diagnostics() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        #[derive(PartialEq, Eq)]
+        struct Constant(u32);
+
+        impl Hash for Constant {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                0u32.hash(state);
+            }
+        }
+
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let sink = reported.clone();
+        let table = HcTable::builder()
+            .collision_warning(4, move |shared| sink.borrow_mut().push(shared))
+            .build();
+        let distinct: Vec<_> = (0..3u32).map(|i| table.hashcons(Constant(i))).collect();
+        let diagnostics = table.hash_diagnostics();
+        assert_eq!(diagnostics.distinct_hashes, 1);
+        assert_eq!(diagnostics.colliding_values, 3);
+        assert_eq!(diagnostics.max_collisions, 3);
+        assert_eq!(diagnostics.occupancy, vec![0, 0, 0, 1]);
+        assert!(reported.borrow().is_empty());
+
+        let colliding: Vec<_> = (3..8u32).map(|i| table.hashcons(Constant(i))).collect();
+        assert_eq!(table.len(), 8);
+        assert_eq!(table.hash_diagnostics().max_collisions, 8);
+        assert_eq!(*reported.borrow(), [5]);
+        drop((distinct, colliding));
+
+        let integers = HcTable::new();
+        let values: Vec<_> = (0..100u32).map(|i| integers.hashcons(i)).collect();
+        let diagnostics = integers.hash_diagnostics();
+        assert_eq!(diagnostics.distinct_hashes, 100);
+        assert_eq!(diagnostics.occupancy[1], 100);
+        assert_eq!(diagnostics.colliding_values, 0);
+        drop(values);
+    }
+
+    /// Tables can be debugged whatever the value type, and list their live values on request.
+    #[test]
+    fn test_table_debug() {
+        #[derive(Hash, PartialEq, Eq)]
+        struct Opaque(u32);
+
+        #[derive(Debug)]
+        struct Context {
+            table: HcTable<Opaque>,
+        }
+
+        let context = Context {
+            table: HcTable::new(),
+        };
+        let opaque = context.table.hashcons(Opaque(1));
+        let summary = format!("{:?}", context);
+        assert!(summary.starts_with("Context { table: HcTable { len: 1, "));
+        drop(opaque);
+
+        let table = HcTable::new();
+        let values: Vec<_> = (0..5u32).map(|i| table.hashcons(i)).collect();
+        assert_eq!(format!("{:?}", table.debug_entries(0)), "[]");
+        assert_eq!(
+            format!("{:?}", table.debug_entries(2)).matches(',').count(),
+            1
+        );
+
+        drop(values);
+        assert_eq!(format!("{:?}", table.debug_entries(5)), "[]");
+    }
+
+    /// Removed entries are counted and the counters display as a one-line summary.
+    #[test]
+    fn test_stats_display() {
+        let table = HcTable::new();
+        let values: Vec<_> = (0..10u32).map(|i| table.hashcons(i)).collect();
+        let repeated = table.hashcons(0);
+        assert_eq!(table.stats().len, 10);
+        assert!(table
+            .stats()
+            .to_string()
+            .starts_with("10 entries, 11 lookups (9.1% reused), 0 removed, "));
+
+        drop((values, repeated));
+        #[cfg(not(feature = "auto-cleanup"))]
+        table.cleanup();
+        let stats = table.stats();
+        assert_eq!(stats.removed, 10);
+        assert_eq!(stats.len, 0);
+        assert!(stats.to_string().starts_with("0 entries, 11 lookups"));
+    }
+
+    /// Prehashed tables use the written fingerprint as the hash and still compare values.
+    #[test]
+    fn test_prehashed() {
+        use std::hash::Hasher;
+
+        let table = HcTable::builder()
+            .hashing(|value: &(u64, String), state: &mut dyn Hasher| state.write_u64(value.0))
+            .prehashed()
+            .build();
+
+        let first = table.hashcons((1, "first".to_string()));
+        let second = table.hashcons((2, "second".to_string()));
+        let clash = table.hashcons((1, "clash".to_string()));
+        assert_eq!(table.hashcons((1, "first".to_string())), first);
+        assert_ne!(first, clash);
+        assert_eq!(table.len(), 3);
+
+        let diagnostics = table.hash_diagnostics();
+        assert_eq!(diagnostics.distinct_hashes, 2);
+        assert_eq!(diagnostics.colliding_values, 2);
+        drop((first, second, clash));
+
+        let trusted = HcTable::builder()
+            .hashing(|value: &(u128, String), state: &mut dyn Hasher| state.write_u128(value.0))
+            .prehashed()
+            .trusted_hash()
+            .build();
+        let original = trusted.hashcons((u128::MAX, "original".to_string()));
+        let merged = trusted.hashcons((u128::MAX, "merged".to_string()));
+        assert_eq!(original, merged);
+        assert_ne!(
+            trusted.hashcons((u64::MAX as u128, "low".to_string())),
+            original
+        );
+    }
+
+    /// Tables built with the same seed lay out their entries identically.
+    #[test]
+    fn test_seeded_tables() {
+        let build = || HcTable::builder().seed(7).trusted_hash().build();
+        let (first, second) = (build(), build());
+        let values: Vec<_> = (0..500u32)
+            .flat_map(|i| [first.hashcons(i), second.hashcons(i)])
+            .collect();
+        assert_eq!(
+            format!("{:?}", first.debug_entries(500)),
+            format!("{:?}", second.debug_entries(500))
+        );
+        assert_eq!(first.hashcons(42), values[84]);
+        assert_eq!(first.len(), 500);
+
+        let other = HcTable::builder().seed(8).build();
+        let others: Vec<_> = (0..500u32).map(|i| other.hashcons(i)).collect();
+        assert_ne!(
+            format!("{:?}", first.debug_entries(500)),
+            format!("{:?}", other.debug_entries(500))
+        );
+        drop(others);
+    }
+
+    /// Copying a value out and re-interning it leaves existing handles unchanged.
+    #[test]
+    fn test_make_owned() {
+        let table = HcTable::new();
+        let original = table.hashcons(String::from("hash"));
+
+        let mut owned = original.make_owned();
+        owned.push_str("_cons");
+        let modified = table.hashcons(owned);
+        assert_eq!(*original, "hash");
+        assert_eq!(*modified, "hash_cons");
+
+        assert_eq!(table.hashcons(original.make_owned()), original);
+        assert_eq!(table.len(), 2);
+    }
+
+    /// Cleanup reports the removed and remaining entries, including released children.
+    #[cfg(not(feature = "auto-cleanup"))]
+    #[test]
+    fn test_cleanup_report() {
+        let table = HcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let and = table.hashcons(BoolExpr::And(t.clone(), f));
+        let kept = table.hashcons(BoolExpr::Not(t));
+        drop(and);
+
+        let report = table.cleanup();
+        assert_eq!(report.removed, 2, "The dropped node and its released child");
+        assert_eq!(report.remaining, 2);
+        assert_eq!(table.cleanup().removed, 0);
+        drop(kept);
+    }
+
+    /// Batched cleanup sweeps a bounded number of entries per call and eventually all of them.
+    #[cfg(not(feature = "auto-cleanup"))]
+    #[test]
+    fn test_cleanup_batch() {
+        let table = HcTable::new();
+        let values: Vec<_> = (0..1000u32).map(|i| table.hashcons(i)).collect();
+        let kept: Vec<_> = values.iter().step_by(2).cloned().collect();
+        drop(values);
+
+        let mut removed = 0;
+        let mut calls = 0;
+        while removed < 500 {
+            let report = table.cleanup_batch(64);
+            assert!(report.removed <= 64);
+            removed += report.removed;
+            calls += 1;
+            assert!(
+                calls <= 100,
+                "Batches should cover the table in a few rounds"
+            );
+        }
+        assert_eq!(removed, 500);
+        assert_eq!(table.len(), 500);
+        assert_eq!(table.cleanup().removed, 0);
+        drop(kept);
+    }
+
+    /// Interning between batches neither skips nor revisits the entries of a round, even when
+    /// it grows the table.
+    #[cfg(not(feature = "auto-cleanup"))]
+    #[test]
+    fn test_cleanup_batch_interleaved() {
+        let table = HcTable::new();
+        let values: Vec<_> = (0..1000u32).map(|i| table.hashcons(i)).collect();
+        let kept: Vec<_> = values.iter().step_by(2).cloned().collect();
+        drop(values);
+
+        let mut added = Vec::new();
+        let mut removed = 0;
+        // One round visits the 1000 hashes stored when it started, 64 at a time
+        for batch in 0..16u32 {
+            removed += table.cleanup_batch(64).removed;
+            added.extend((0..500).map(|i| table.hashcons(1000 + batch * 500 + i)));
+        }
+        assert_eq!(removed, 500);
+        assert_eq!(table.len(), 500 + added.len());
+        assert_eq!(table.cleanup().removed, 0);
+        drop((kept, added));
+    }
+
+    /// Idle cleanup waits until a call sees no interning for the whole window.
+    #[cfg(not(feature = "auto-cleanup"))]
+    #[test]
+    fn test_cleanup_when_idle() {
+        use std::time::Duration;
+
+        let table = HcTable::new();
+        drop(table.hashcons(1u32));
+        assert!(table.cleanup_when_idle(Duration::ZERO).is_none());
+        assert!(table.cleanup_when_idle(Duration::from_secs(3600)).is_none());
+
+        drop(table.hashcons(2u32));
+        assert!(table.cleanup_when_idle(Duration::ZERO).is_none());
+        let report = table.cleanup_when_idle(Duration::ZERO).unwrap();
+        assert_eq!(report.removed, 2);
+        assert!(table.is_empty());
+    }
+
+    /// Deferred reclamation queues dead entries until they are collected.
+    #[cfg(feature = "auto-cleanup")]
+    #[test]
+    fn test_deferred_reclamation() {
+        let table = HcTable::builder().deferred_reclamation().build();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let not = table.hashcons(BoolExpr::Not(t.clone()));
+        drop(t);
+        drop(not);
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            table.dead_count(),
+            1,
+            "The child is kept alive by the retired parent"
+        );
+
+        assert_eq!(
+            table.collect_retired(),
+            2,
+            "The child is released by the parent"
+        );
+        assert!(table.is_empty());
+
+        // A retired entry can be taken over before it is collected.
+        let first = table.hashcons(BoolExpr::Const(false));
+        drop(first);
+        let second = table.hashcons(BoolExpr::Const(false));
+        assert_eq!(table.collect_retired(), 0);
+        assert_eq!(table.len(), 1);
+        drop(second);
+
+        // Full queues are collected by the dropping handle.
+        let bulk = HcTable::builder().deferred_reclamation().build();
+        drop((0..5000u32).map(|i| bulk.hashcons(i)).collect::<Vec<_>>());
+        assert!(bulk.len() < 1024);
+    }
+
+    /// Values are stamped with the generation they were stored in and evicted by generation.
+    #[test]
+    fn test_generations() {
+        let table = HcTable::new();
+        let parsed = table.hashcons(BoolExpr::Const(true));
+        assert_eq!(table.advance_generation(), 1);
+        let lowered = table.hashcons(BoolExpr::Not(parsed.clone()));
+        let checkpoint = table.advance_generation();
+        assert_eq!(table.generation(), 2);
+        let optimized = table.hashcons(BoolExpr::Const(false));
+        drop((parsed, lowered, optimized));
+
+        #[cfg(not(feature = "auto-cleanup"))]
+        {
+            let report = table.cleanup_older_than(checkpoint);
+            assert_eq!(report.removed, 2, "Both earlier phases are evicted");
+            assert_eq!(report.remaining, 1, "The current phase is kept");
+            assert_eq!(table.cleanup_older_than(checkpoint).removed, 0);
+            assert_eq!(table.cleanup().removed, 1);
+        }
+        #[cfg(feature = "auto-cleanup")]
+        let _ = checkpoint;
+        assert!(table.is_empty());
+    }
+
+    /// Idle eviction keeps unused values that were interned recently.
+    #[cfg(all(feature = "access-time", not(feature = "auto-cleanup")))]
+    #[test]
+    fn test_cleanup_idle_for() {
+        use std::time::Duration;
+
+        let table = HcTable::new();
+        drop(table.hashcons(1u32));
+        let kept = table.hashcons(2u32);
+        drop(table.hashcons(3u32));
+        assert_eq!(table.cleanup_idle_for(Duration::from_secs(3600)).removed, 0);
+
+        let report = table.cleanup_idle_for(Duration::ZERO);
+        assert_eq!(report.removed, 2);
+        assert_eq!(report.remaining, 1);
+        drop(kept);
+    }
+
+    /// Weighted eviction removes unused values until the table is within its budget.
+    #[test]
+    fn test_evict_until_weight() {
+        let table = HcTable::builder()
+            .weight(|value: &Vec<u8>| value.len())
+            .build();
+        let kept = table.hashcons(vec![0; 10]);
+        drop(table.hashcons(vec![1; 100]));
+        drop(table.hashcons(vec![2; 40]));
+        table.advance_generation();
+        drop(table.hashcons(vec![3; 30]));
+
+        #[cfg(not(feature = "auto-cleanup"))]
+        {
+            use hash_cons::EvictionOrder;
+
+            assert_eq!(table.total_weight(), 180);
+
+            let report = table.evict_until_weight(80, EvictionOrder::Heaviest);
+            assert_eq!(
+                report.removed, 1,
+                "The heaviest value alone meets the budget"
+            );
+            assert_eq!(table.total_weight(), 80);
+
+            let report = table.evict_until_weight(40, EvictionOrder::Oldest);
+            assert_eq!(
+                report.removed, 1,
+                "The value of the older generation goes first"
+            );
+            assert_eq!(table.total_weight(), 40);
+            assert_eq!(table.hashcons(vec![3; 30]).len(), 30);
+
+            let report = table.evict_until_weight(0, EvictionOrder::Heaviest);
+            assert_eq!(report.removed, 1);
+            assert_eq!(report.remaining, 1, "Values in use are never evicted");
+            assert_eq!(table.total_weight(), 10);
+        }
+        #[cfg(feature = "auto-cleanup")]
+        assert_eq!(table.total_weight(), 10);
+        drop(kept);
+    }
+
+    /// Migrating a table of the other implementation keeps shared subterms shared.
+    #[test]
+    fn test_migration() {
+        use hash_cons::{thread_safe, Migration};
+
+        #[derive(Hash, PartialEq, Eq)]
+        enum Source {
+            Const(bool),
+            And(thread_safe::Hc<Source>, thread_safe::Hc<Source>),
+        }
+
+        fn convert(value: &Source, migration: &mut Migration<Source, BoolExpr>) -> BoolExpr {
+            match value {
+                Source::Const(flag) => BoolExpr::Const(*flag),
+                Source::And(a, b) => {
+                    BoolExpr::And(migration.migrate(a, convert), migration.migrate(b, convert))
+                }
+            }
+        }
+
+        let source = thread_safe::HcTable::new();
+        let leaf = source.hashcons(Source::Const(true));
+        let root = source.hashcons(Source::And(leaf.clone(), leaf.clone()));
+        let unrelated = source.hashcons(Source::Const(false));
+
+        let table = HcTable::new();
+        let mut migration = Migration::new(&table);
+        let migrated_root = migration.migrate(&root, convert);
+        assert_eq!(migration.len(), 2, "The shared leaf is migrated once");
+        assert!(migration.get(&unrelated).is_none());
+        match &*migrated_root {
+            BoolExpr::And(a, b) => assert!(a == b),
+            _ => panic!("The structure is preserved"),
+        }
+
+        assert_eq!(migration.migrate_table(&source, convert), 1);
+        assert!(migration.migrate(&root, convert) == migrated_root);
+        assert_eq!(table.len(), 3);
+    }
+
+    /// Borrowed keys find stored values without constructing them.
+    #[test]
+    fn test_get_or_insert_with() {
+        let table: HcTable<String> = HcTable::new();
+        let first = table.get_or_insert_with("hash", str::to_owned);
+        let second = table.get_or_insert_with("hash", |_| panic!("The value is stored"));
+        assert!(first == second);
+        assert!(table.hashcons("hash".to_string()) == first);
+        assert_eq!(table.stats().created, 1);
+
+        // Custom strategies may treat keys differently, so the value is constructed every time.
+        let folded = HcTable::builder()
+            .normalize(|value: String| value.to_lowercase())
+            .build();
+        let upper = folded.get_or_insert_with("CONS", str::to_owned);
+        assert_eq!(*upper, "cons");
+        assert!(folded.get_or_insert_with("Cons", str::to_owned) == upper);
+    }
+
+    /// The asynchronous variant constructs missing values with a future.
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_get_or_insert_with_async() {
+        use std::future::Future;
+        use std::pin::pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Unpark(std::thread::Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+            let mut future = pin!(future);
+            loop {
+                match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                    Poll::Ready(output) => return output,
+                    Poll::Pending => std::thread::park(),
+                }
+            }
+        }
+
+        let table: HcTable<String> = HcTable::new();
+        let first = block_on(table.get_or_insert_with_async("hash", |key| {
+            let value = key.to_owned();
+            async move { value }
+        }));
+        let second = block_on(
+            table.get_or_insert_with_async("hash", |_| async { panic!("The value is stored") }),
+        );
+        assert!(first == second);
+    }
+
+    /// Failed constructions are reported and leave the table unchanged.
+    #[test]
+    fn test_get_or_try_insert_with() {
+        let table: HcTable<String> = HcTable::new();
+        let parse = |key: &str| -> Result<String, String> {
+            match key.chars().all(char::is_alphabetic) {
+                true => Ok(key.to_owned()),
+                false => Err(format!("invalid identifier {key:?}")),
+            }
+        };
+
+        let error = table.get_or_try_insert_with("1x", parse).err();
+        assert_eq!(error.as_deref(), Some("invalid identifier \"1x\""));
+        assert!(table.is_empty());
+        assert_eq!(table.stats().lookups(), 0);
+
+        let valid = table
+            .get_or_try_insert_with("x", parse)
+            .expect("x is valid");
+        let again = table.get_or_try_insert_with("x", |_| Err("The value is stored".to_string()));
+        assert!(again.ok().as_ref() == Some(&valid));
+        assert_eq!(table.len(), 1);
+    }
+
+    /// Panics of user code during interning leave the table consistent and usable.
+    #[test]
+    fn test_panic_safety() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let table = HcTable::builder()
+            .equivalence(|stored: &u32, value: &u32| {
+                assert_ne!(*value, 13, "Eq panics");
+                stored == value
+            })
+            .hashing(|value: &u32, state: &mut dyn Hasher| {
+                assert_ne!(*value, 7, "Hash panics");
+                state.write_u32(*value)
+            })
+            .normalize(|value: u32| {
+                assert_ne!(value, 99, "Normalize panics");
+                value
+            })
+            .build();
+        let unlucky = table.hashcons(13);
+
+        for value in [13, 7, 99] {
+            let result = catch_unwind(AssertUnwindSafe(|| table.hashcons(value)));
+            assert!(result.is_err());
+            assert_eq!(table.len(), 1, "No entry is added for {value}");
+        }
+        assert_eq!(*unlucky, 13);
+        let value = table.hashcons(1);
+        assert!(table.hashcons(1) == value);
+        assert_eq!(table.len(), 2);
+
+        // A panicking constructor inserts nothing and releases its key.
+        let strings: HcTable<String> = HcTable::new();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            strings.get_or_insert_with("key", |_| panic!("The constructor panics"))
+        }));
+        assert!(result.is_err());
+        assert!(strings.is_empty());
+        assert_eq!(*strings.get_or_insert_with("key", str::to_owned), "key");
+    }
+
+    /// Frozen snapshots look up the values alive when they were taken.
+    #[test]
+    fn test_freeze() {
+        let table = HcTable::builder()
+            .equivalence(|a: &String, b: &String| a.eq_ignore_ascii_case(b))
+            .hashing(|value: &String, state: &mut dyn Hasher| {
+                state.write(value.to_ascii_lowercase().as_bytes())
+            })
+            .build();
+        let kept = table.hashcons("Hash".to_string());
+        drop(table.hashcons("dropped".to_string()));
+        let frozen = table.freeze();
+        let later = table.hashcons("later".to_string());
+
+        assert_eq!(frozen.len(), 1);
+        assert!(frozen.get(&"HASH".to_string()) == Some(&kept));
+        assert!(!frozen.contains(&"dropped".to_string()));
+        assert!(!frozen.contains(&"later".to_string()));
+        assert!(frozen.iter().eq([&kept]));
+
+        // The snapshot keeps its values alive.
+        drop((kept, later));
+        assert_eq!(*frozen.get(&"hash".to_string()).unwrap().get(), "Hash");
+        let shared = frozen.clone();
+        drop(frozen);
+        assert!(!shared.is_empty());
+    }
+
+    /// Exported entries are live, ordered by id, and ids are never reused.
+    #[test]
+    fn test_export_entries() {
+        let table = HcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let not = table.hashcons(BoolExpr::Not(t.clone()));
+        let f_id = table.entry_id(&f).expect("f is stored");
+        drop(f);
+
+        let entries = table.export_entries();
+        assert_eq!(entries.len(), 2, "Dropped values are not exported");
+        assert!(entries[0].1 == t && entries[1].1 == not);
+        assert!(entries[0].0 < entries[1].0);
+        match &*entries[1].1 {
+            BoolExpr::Not(child) => assert_eq!(table.entry_id(child), Some(entries[0].0)),
+            _ => panic!("The second entry is the negation"),
+        }
+
+        let f = table.hashcons(BoolExpr::Const(false));
+        let new_id = table.entry_id(&f).expect("f is stored again");
+        assert!(new_id > entries[1].0 && new_id != f_id);
+        assert_eq!(
+            table.export_entries().last().map(|(id, _)| *id),
+            Some(new_id)
+        );
+        assert_eq!(HcTable::new().entry_id(&f), None);
+    }
+
+    /// Code that only reads handles needs no `Hash + Eq` bounds on the value type.
+    #[test]
+    fn test_handles_without_bounds() {
+        struct Labelled<T> {
+            label: &'static str,
+            node: Hc<T>,
+        }
+
+        fn describe<T: std::fmt::Debug>(labelled: &Labelled<T>) -> String {
+            format!("{}: {:?}", labelled.label, *labelled.node)
+        }
+
+        let table = HcTable::new();
+        let labelled = Labelled {
+            label: "answer",
+            node: table.hashcons(42),
+        };
+        assert_eq!(describe(&labelled), "answer: 42");
+    }
+
+    /// Handles of identity tables compare by address, so equal values of different tables differ.
+    #[allow(clippy::mutable_key_type)] // the table behind a handle does not take part in its hash
+    #[test]
+    fn test_identity_semantics() {
+        use hash_cons::Identity;
+        use std::collections::HashMap;
+
+        let table = HcTable::builder().identity().build();
+        let other = HcTable::builder().identity().build();
+        let t: Hc<BoolExpr, Identity> = table.hashcons(BoolExpr::Const(true));
+        let t_again = table.hashcons(BoolExpr::Const(true));
+        let t_other = other.hashcons(BoolExpr::Const(true));
+        assert!(t == t_again);
+        assert!(t != t_other);
+        assert!(*t == *t_other);
+
+        let mut names = HashMap::new();
+        names.insert(t.clone(), "first");
+        names.insert(t_other, "second");
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[&t_again], "first");
+
+        // Structural handles of different tables stay equal.
+        let (first, second) = (HcTable::new(), HcTable::new());
+        assert!(first.hashcons(BoolExpr::Const(true)) == second.hashcons(BoolExpr::Const(true)));
+    }
+
+    /// Handles of equal values share the address of the value, which `{:p}` prints.
+    #[test]
+    fn test_as_ptr() {
+        let table = HcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        assert_eq!(t.as_ptr(), table.hashcons(BoolExpr::Const(true)).as_ptr());
+        assert_ne!(t.as_ptr(), f.as_ptr());
+        assert!(std::ptr::eq(t.as_ptr(), t.get()));
+        assert_eq!(format!("{t:p}"), format!("{:p}", t.get()));
+        assert!(format!("{f:p}").starts_with("0x"));
+    }
+
+    /// Equal values under different tags are interned separately, and equal tagged values shared.
+    #[test]
+    fn test_hashcons_tagged() {
+        let table = HcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let scoped = HcTable::new();
+        let in_scope = scoped.hashcons_tagged(0u8, t.clone());
+        let in_other_scope = scoped.hashcons_tagged(1u8, t.clone());
+        assert!(in_scope != in_other_scope);
+        assert!(in_scope == scoped.hashcons_tagged(0u8, t.clone()));
+        assert!(**in_scope == t && in_scope.value == in_other_scope.value);
+        assert_eq!((in_scope.tag, in_other_scope.tag), (0, 1));
+        assert_eq!(scoped.len(), 2);
+    }
+
+    /// Values differing only in skipped fields share one entry, which keeps the first fields.
+    #[test]
+    fn test_skipped_fields() {
+        use hash_cons::Skipped;
+
+        #[derive(Debug, Hash, PartialEq, Eq)]
+        struct Spanned {
+            expr: u32,
+            span: Skipped<(usize, usize)>,
+        }
+
+        let table = HcTable::new();
+        let first = table.hashcons(Spanned {
+            expr: 1,
+            span: Skipped((0, 5)),
+        });
+        let second = table.hashcons(Spanned {
+            expr: 1,
+            span: Skipped((10, 15)),
+        });
+        let other = table.hashcons(Spanned {
+            expr: 2,
+            span: Skipped((0, 5)),
+        });
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(*second.span, (0, 5));
+        assert_eq!(table.len(), 2);
+    }
+
+    /// Handles wrapped into a transparent newtype share the entry of the value and dereference
+    /// to the newtype.
+    #[test]
+    fn test_wrap() {
+        use hash_cons::{Transparent, Wrapped};
+
+        #[repr(transparent)]
+        struct Name(String);
+
+        // SAFETY: `Name` is a transparent newtype over any `String`.
+        unsafe impl Transparent<String> for Name {}
+
+        let table: HcTable<String> = HcTable::new();
+        let hc = table.hashcons("alice".to_string());
+        let name = hc.clone().wrap::<Name>();
+        assert_eq!(name.0, "alice");
+        assert!(std::ptr::eq(&name.0, hc.get()));
+        assert_eq!(name, Wrapped::wrap(table.hashcons("alice".to_string())));
+        assert_eq!(table.len(), 1);
+
+        assert_eq!(name.into_inner(), hc);
+    }
+
+    /// Handles report the generation in which their value was first stored.
+    #[test]
+    fn test_birth_generation() {
+        let table = HcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        assert_eq!(table.advance_generation(), 1);
+        let f = table.hashcons(BoolExpr::Const(false));
+        let and = table.hashcons(BoolExpr::And(t.clone(), f.clone()));
+        assert_eq!(table.hashcons(BoolExpr::Const(true)).birth_generation(), 0);
+        assert_eq!((f.birth_generation(), and.birth_generation()), (1, 1));
+
+        // A value stored again after it was dropped is born again.
+        drop(and);
+        table.advance_generation();
+        let and = table.hashcons(BoolExpr::And(t, f));
+        assert_eq!(and.birth_generation(), 2);
+    }
+
+    /// Rolling back removes the unused values stored since the checkpoint, and nothing else,
+    /// whatever the ids of the values.
+    #[cfg(not(feature = "auto-cleanup"))]
+    #[test]
+    fn test_checkpoint_rollback() {
+        let mut next = u64::MAX;
+        let tables: [HcTable<BoolExpr>; 4] = [
+            HcTable::new(),
+            HcTable::builder()
+                .ids(hash_cons::RandomIds::default())
+                .build(),
+            HcTable::builder().ids(hash_cons::ContentIds).build(),
+            HcTable::builder()
+                .ids(move |_hash| {
+                    next -= 1;
+                    next
+                })
+                .build(),
+        ];
+        for table in tables {
+            let t = table.hashcons(BoolExpr::Const(true));
+            let f = table.hashcons(BoolExpr::Const(false));
+            drop(f);
+            let checkpoint = table.checkpoint();
+
+            let not = table.hashcons(BoolExpr::Not(t.clone()));
+            let and = table.hashcons(BoolExpr::And(not.clone(), t.clone()));
+            let kept = table.hashcons(BoolExpr::Or(t.clone(), t.clone()));
+            drop((not, and));
+
+            // `Not` is released by removing `And`, and `False` predates the checkpoint.
+            let report = table.rollback(checkpoint);
+            assert_eq!((report.removed, report.remaining), (2, 3));
+            assert_eq!(table.rollback(checkpoint).removed, 0);
+            assert!(*kept == BoolExpr::Or(t.clone(), t));
+        }
+    }
+
+    /// Namespaces share the storage of their table and list the values interned through them.
+    #[test]
+    fn test_namespaces() {
+        let table = HcTable::new();
+        let parser = table.namespace("parser");
+        let lexer = table.namespace("lexer");
+        let t = parser.hashcons(BoolExpr::Const(true));
+        let not = parser.hashcons(BoolExpr::Not(t.clone()));
+        let shared = lexer.hashcons(BoolExpr::Const(true));
+
+        assert!(shared == t);
+        assert_eq!(table.len(), 2);
+        assert!(parser.handles() == vec![t.clone(), not.clone()]);
+        assert!(lexer.contains(&t) && !lexer.contains(&not));
+        assert!(table.namespace("parser").contains(&not));
+        assert!(!HcTable::new().namespace("parser").contains(&t));
+
+        drop(not);
+        assert_eq!((parser.len(), lexer.len()), (1, 1));
+        assert!(table.namespace("unused").is_empty());
+        assert_eq!(lexer.name(), "lexer");
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// Evicting a namespace keeps the values other namespaces interned.
+    #[test]
+    fn test_namespace_evict() {
+        let table = HcTable::new();
+        let parser = table.namespace("parser");
+        let f = parser.hashcons(BoolExpr::Const(false));
+        let not = parser.hashcons(BoolExpr::Not(f.clone()));
+        let t = parser.hashcons(BoolExpr::Const(true));
+        let shared = table.namespace("lexer").hashcons(BoolExpr::Const(true));
+        drop((f, not, t, shared));
+
+        // `False` is released by removing `Not`, and `True` is interned by the lexer as well.
+        let report = parser.evict();
+        assert_eq!((report.removed, report.remaining), (2, 1));
+        assert_eq!(table.namespace("lexer").evict().removed, 1);
+        assert!(table.is_empty());
+    }
+
+    /// Edges carry attribute bits in the handle, so that complemented values share storage.
+    #[test]
+    fn test_hashcons_edge() {
+        use hash_cons::{Canonical, HcEdge};
+
+        #[derive(Hash, PartialEq, Eq)]
+        enum Gate {
+            Input(u32),
+            Not(HcEdge<Gate>),
+            And(HcEdge<Gate>, HcEdge<Gate>),
+        }
+
+        // Strips negations into the complement bit of the edge.
+        fn canonical(gate: Gate) -> Canonical<Gate, HcEdge<Gate>> {
+            match gate {
+                Gate::Not(edge) => Canonical::Edge(edge.flip(1)),
+                gate => Canonical::Node(gate, 0),
+            }
+        }
+
+        let table = HcTable::new();
+        let x = table.hashcons_edge(Gate::Input(0), canonical);
+        let y = table.hashcons_edge(Gate::Input(1), canonical);
+        let not_x = table.hashcons_edge(Gate::Not(x.clone()), canonical);
+        let and = table.hashcons_edge(Gate::And(not_x.clone(), y.clone()), canonical);
+        let nand = table.hashcons_edge(Gate::Not(and.clone()), canonical);
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            (x.attributes(), not_x.attributes(), nand.attributes()),
+            (0, 1, 1)
+        );
+        assert!(not_x != x && not_x.clone().flip(1) == x);
+        assert!(nand.handle() == and.handle());
+        assert!(matches!(*nand, Gate::And(ref a, _) if *a == not_x));
+        assert!(table.hashcons_edge(Gate::Not(nand), canonical) == and);
+
+        let marked = y.clone().with_attributes(HcEdge::<Gate>::ATTRIBUTE_MASK);
+        assert_eq!(marked.attributes(), 3);
+        assert!(marked.into_handle() == HcEdge::from(y.handle()).into_handle());
+        drop((x, y, not_x, and));
+        assert_eq!(
+            table.len(),
+            if cfg!(feature = "auto-cleanup") { 0 } else { 3 }
+        );
+    }
+
+    /// The memo cache returns results while their operands and the result are alive.
+    #[test]
+    fn test_pair_table() {
+        use hash_cons::HcPairTable;
+
+        let table = HcTable::new();
+        let cache: HcPairTable<BoolExpr> = HcPairTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+
+        let mut computed = 0;
+        let mut and = |a: &Hc<BoolExpr>, b: &Hc<BoolExpr>| {
+            cache.get_or_insert_with(a, b, || {
+                computed += 1;
+                table.hashcons(BoolExpr::And(a.clone(), b.clone()))
+            })
+        };
+        let tf = and(&t, &f);
+        assert!(and(&t, &f) == tf);
+        let ft = and(&f, &t);
+        assert_eq!(computed, 2);
+        assert_eq!(cache.len(), 2);
+
+        // Results of another table are not mistaken for results of this one.
+        let other = HcTable::new();
+        let t2 = other.hashcons(BoolExpr::Const(true));
+        let f2 = other.hashcons(BoolExpr::Const(false));
+        assert!(cache.get(&t2, &f2).is_none());
+
+        drop(ft);
+        assert!(cache.get(&f, &t).is_none());
+        assert_eq!(cache.len(), 1);
+        drop(f);
+        // `False` is kept alive by the cached `And`.
+        assert_eq!(cache.purge(), 0);
+        drop(tf);
+        assert_eq!(cache.purge(), 1);
+        assert!(cache.is_empty());
+    }
+
+    /// The engine caches the results of its operations, also for recursive subproblems.
+    #[test]
+    fn test_cons_engine() {
+        use hash_cons::ConsEngine;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let counted = calls.clone();
+
+        // Pushes a negation through conjunctions, applying itself to the operands.
+        let mut engine: ConsEngine<BoolExpr> = ConsEngine::new();
+        let negate = engine.register(move |engine, expr, _| {
+            counted.set(counted.get() + 1);
+            let op = engine.op(0);
+            match expr.get() {
+                BoolExpr::Const(value) => engine.hashcons(BoolExpr::Const(!value)),
+                BoolExpr::And(a, b) => {
+                    let (a, b) = (engine.apply(op, a, a), engine.apply(op, b, b));
+                    engine.hashcons(BoolExpr::Or(a, b))
+                }
+                _ => engine.hashcons(BoolExpr::Not(expr.clone())),
+            }
+        });
+
+        let t = engine.hashcons(BoolExpr::Const(true));
+        let and = engine.hashcons(BoolExpr::And(t.clone(), t.clone()));
+        let nested = engine.hashcons(BoolExpr::And(and.clone(), t.clone()));
+        let negated = engine.apply(negate, &nested, &nested);
+
+        // `True` is negated once, although it occurs three times.
+        assert_eq!(calls.get(), 3);
+        let f = engine.hashcons(BoolExpr::Const(false));
+        let or = engine.hashcons(BoolExpr::Or(f.clone(), f.clone()));
+        assert!(negated == engine.hashcons(BoolExpr::Or(or.clone(), f.clone())));
+        assert!(engine.apply(negate, &nested, &nested) == negated);
+        assert_eq!(calls.get(), 3);
+        assert_eq!(engine.cached(), 3);
+
+        drop(negated);
+        assert_eq!(engine.purge(), 1);
+        assert_eq!(
+            engine.table().len(),
+            if cfg!(feature = "auto-cleanup") { 5 } else { 6 }
+        );
+    }
+
+    /// Batch lookups find stored values without storing missing ones.
+    #[test]
+    fn test_get_many() {
+        let table: HcTable<String> = HcTable::new();
+        let a = table.hashcons("a".to_string());
+        let b = table.hashcons("b".to_string());
+        drop(table.hashcons("dead".to_string()));
+
+        let values = ["b", "c", "a", "dead"].map(String::from);
+        assert_eq!(
+            table.get_many(&values),
+            [Some(b.clone()), None, Some(a.clone()), None]
+        );
+        assert_eq!(
+            table.get_many_by_key(&["a", "c", "dead"]),
+            [Some(a.clone()), None, None]
+        );
+        assert_eq!(
+            table.len(),
+            if cfg!(feature = "auto-cleanup") { 2 } else { 3 }
+        );
+        assert_eq!(table.stats().reused, 0);
+
+        let folded = HcTable::builder()
+            .equivalence(|x: &String, y: &String| x.eq_ignore_ascii_case(y))
+            .hashing(|value: &String, state: &mut dyn Hasher| {
+                state.write(value.to_ascii_lowercase().as_bytes())
+            })
+            .build();
+        let upper = folded.hashcons("A".to_string());
+        assert_eq!(folded.get_many(&["a".to_string()]), [Some(upper)]);
+        assert_eq!(folded.get_many_by_key(&["A"]), [None]);
+    }
+
+    /// Removing a value hands it back only while the caller holds its only handle.
+    #[test]
+    fn test_remove() {
+        let table: HcTable<String> = HcTable::new();
+        let value = table.hashcons("value".to_string());
+        let shared = value.clone();
+        let kept = table.hashcons("kept".to_string());
+
+        let value = table.remove(value).unwrap_err();
+        drop(shared);
+        let other: HcTable<String> = HcTable::new();
+        let value = other.remove(value).unwrap_err();
+
+        assert_eq!(table.remove(value).unwrap(), "value");
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.stats().removed, 1);
+
+        let again = table.hashcons("value".to_string());
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.remove(again).unwrap(), "value");
+        assert_eq!(*kept, "kept");
+    }
+
+    /// Borrowed values are copied only when missing, owned ones are stored without copying.
+    #[test]
+    fn test_hashcons_cow() {
+        use std::borrow::Cow;
+
+        let table: HcTable<String> = HcTable::new();
+        let token = "token".to_string();
+        let buffer = token.as_ptr();
+        let owned = table.hashcons_cow(Cow::<str>::Owned(token));
+
+        assert_eq!(owned.as_str().as_ptr(), buffer);
+        assert_eq!(table.hashcons_cow(Cow::Borrowed("token")), owned);
+        assert_eq!(
+            table.hashcons_cow(Cow::<str>::Owned("token".to_string())),
+            owned
+        );
+        let other = table.hashcons_cow(Cow::Borrowed("other"));
+        assert_eq!(*other, "other");
+        assert_eq!(table.len(), 2);
+
+        let bytes: HcTable<Vec<u8>> = HcTable::new();
+        let borrowed = bytes.hashcons_cow(Cow::Borrowed(&b"raw"[..]));
+        assert_eq!(
+            bytes.hashcons_cow(Cow::<[u8]>::Owned(b"raw".to_vec())),
+            borrowed
+        );
+    }
+
+    /// Forks share the values of their parent, keep their own additions, and merge them back.
+    #[test]
+    fn test_fork() {
+        let table: HcTable<String> = HcTable::builder()
+            .normalize(|value: String| value.to_lowercase())
+            .build();
+        let shared = table.hashcons("shared".to_string());
+
+        let fork = table.fork();
+        let own = fork.hashcons("Own".to_string());
+        assert!(fork.hashcons("SHARED".to_string()).as_ptr() == shared.as_ptr());
+        assert!(fork.hashcons("own".to_string()).as_ptr() == own.as_ptr());
+        assert_eq!(*own, "own");
+        assert_eq!((table.len(), fork.len()), (1, 1));
+        assert_eq!(fork.additions(), std::slice::from_ref(&own));
+
+        // Values the parent stores later are found, unless the fork stored them first.
+        let later = table.hashcons("later".to_string());
+        assert!(fork.hashcons("later".to_string()).as_ptr() == later.as_ptr());
+        let both = fork.hashcons("both".to_string());
+        let parent_both = table.hashcons("both".to_string());
+        assert!(fork.hashcons("both".to_string()).as_ptr() == both.as_ptr());
+
+        let merged = fork.merge();
+        assert_eq!(merged.len(), 2);
+        assert!(merged[1].as_ptr() == parent_both.as_ptr());
+        assert!(table.hashcons("own".to_string()).as_ptr() == merged[0].as_ptr());
+        assert_eq!(table.len(), 4);
+
+        // Dropping a fork discards its additions.
+        let discarded = table.fork();
+        drop(discarded.hashcons("discarded".to_string()));
+        assert!(discarded.additions().is_empty());
+        drop(discarded);
+        assert_eq!(table.len(), 4);
+    }
+
+    /// Forks draw their ids from the allocator of their parent, so ids stay distinct and keep
+    /// the prefix of the parent.
+    #[test]
+    fn test_fork_ids() {
+        let table: HcTable<u32> = HcTable::builder().id_prefix(1).build();
+        let fork = table.fork();
+        let handles = [
+            table.hashcons(1),
+            fork.hashcons(2),
+            fork.hashcons(3),
+            table.hashcons(4),
+            fork.hashcons(5),
+        ];
+
+        let ids: Vec<_> = handles
+            .iter()
+            .map(|hc| fork.entry_id(hc).unwrap() - (1 << 48))
+            .collect();
+        assert_eq!(ids, [0, 1, 2, 3, 4]);
+        assert_eq!(table.entry_id(&handles[3]), Some((1 << 48) + 3));
+        assert_eq!(table.entry_id(&handles[1]), None);
+    }
+
+    /// Live sets compare the values with handles of two tables, by value.
+    #[test]
+    fn test_live_set_comparison() {
+        let expected: HcTable<String> = HcTable::new();
+        let actual: HcTable<String> = HcTable::new();
+        assert!(expected.same_live_set(&actual));
+
+        let _kept = (
+            expected.hashcons("kept".to_string()),
+            actual.hashcons("kept".to_string()),
+        );
+        let old = expected.hashcons("old".to_string());
+        let new = actual.hashcons("new".to_string());
+        assert!(!expected.same_live_set(&actual));
+        assert!(expected.same_live_set(&expected.clone()));
+
+        let diff = expected.diff_live_set(&actual);
+        assert!(!diff.is_empty());
+        assert!(diff.removed[0].as_ptr() == old.as_ptr());
+        assert!(diff.added[0].as_ptr() == new.as_ptr());
+        assert_eq!((diff.removed.len(), diff.added.len()), (1, 1));
+
+        let _new = expected.hashcons("new".to_string());
+        let _old = actual.hashcons("old".to_string());
+        assert!(expected.same_live_set(&actual));
+        assert!(expected.diff_live_set(&actual).is_empty());
+    }
+
+    /// Differences list the values with handles missing from the other table, in storing order.
+    #[test]
+    fn test_difference() {
+        let run_a: HcTable<u32> = HcTable::new();
+        let run_b: HcTable<u32> = HcTable::new();
+        let _a: Vec<_> = [1, 2, 3].into_iter().map(|n| run_a.hashcons(n)).collect();
+        let _b: Vec<_> = [4, 3, 5, 2]
+            .into_iter()
+            .map(|n| run_b.hashcons(n))
+            .collect();
+        drop(run_b.hashcons(1));
+
+        let values = |hcs: Vec<Hc<u32>>| hcs.iter().map(|hc| **hc).collect::<Vec<_>>();
+        assert_eq!(values(run_a.difference(&run_b).collect()), [1]);
+        assert_eq!(values(run_b.difference(&run_a).collect()), [4, 5]);
+        assert_eq!(
+            values(run_a.symmetric_difference(&run_b).collect()),
+            [1, 4, 5]
+        );
+        assert_eq!(run_a.difference(&run_a).count(), 0);
+        assert_eq!(run_a.symmetric_difference(&run_a.clone()).count(), 0);
+    }
+
+    /// Values whose hash changed after they were interned are found, and optionally checked for a
+    /// batch at a time.
+    #[test]
+    fn test_hash_drift() {
+        use std::cell::{Cell, RefCell};
+        use std::hash::{Hash, Hasher};
+        use std::rc::Rc;
+
+        #[derive(Debug)]
+        struct Version(Cell<u32>);
+
+        impl PartialEq for Version {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.get() == other.0.get()
+            }
+        }
+        impl Eq for Version {}
+        impl Hash for Version {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.get().hash(state)
+            }
+        }
+
+        let table = HcTable::new();
+        let first = table.hashcons(Version(Cell::new(1)));
+        let second = table.hashcons(Version(Cell::new(2)));
+        assert!(table.hash_drift().is_empty());
+
+        first.0.set(3);
+        let drifted = table.hash_drift();
+        assert_eq!(drifted.len(), 1);
+        assert!(drifted[0].as_ptr() == first.as_ptr());
+        let duplicate = table.hashcons(Version(Cell::new(3)));
+        assert!(duplicate.as_ptr() != first.as_ptr());
+        assert!(table.hashcons(Version(Cell::new(2))).as_ptr() == second.as_ptr());
+
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let sink = reported.clone();
+        let checked = HcTable::builder()
+            .hash_drift_check(2, move |value: &Version| {
+                sink.borrow_mut().push(value.0.get())
+            })
+            .build();
+        let value = checked.hashcons(Version(Cell::new(1)));
+        value.0.set(2);
+        let mut kept = vec![checked.hashcons(Version(Cell::new(5)))];
+        assert_eq!(*reported.borrow(), [2]);
+
+        // The next round checks the four values stored when it starts, two per check
+        kept.extend((6..10).map(|i| checked.hashcons(Version(Cell::new(i)))));
+        assert_eq!(*reported.borrow(), [2, 2]);
+        drop((value, kept));
+    }
+
+    /// Evicted values are passed to the observer, values taken back with `remove` are not.
+    #[test]
+    fn test_on_evict() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let evicted = Rc::new(RefCell::new(Vec::new()));
+        let sink = evicted.clone();
+        let table = HcTable::builder()
+            .on_evict(move |value: &String| sink.borrow_mut().push(value.clone()))
+            .build();
+        let kept = table.hashcons("kept".to_string());
+        drop(table.hashcons("dropped".to_string()));
+        #[cfg(not(feature = "auto-cleanup"))]
+        table.cleanup();
+
+        assert_eq!(table.len(), 1);
+        assert_eq!(*evicted.borrow(), ["dropped"]);
+        assert_eq!(table.remove(kept).unwrap(), "kept");
+        assert_eq!(evicted.borrow().len(), 1);
+    }
+
+    /// Values interned while a pin scope lives stay stored without handles until it ends.
+    #[test]
+    fn test_pin_scope() {
+        let table: HcTable<String> = HcTable::new();
+        drop(table.hashcons("before".to_string()));
+
+        let outer = table.pin_scope();
+        drop(table.hashcons("outer".to_string()));
+        let inner = table.pin_scope();
+        drop(table.hashcons("inner".to_string()));
+        drop(table.hashcons("inner".to_string()));
+        assert_eq!((outer.len(), inner.len()), (2, 1));
+
+        drop(outer);
+        #[cfg(not(feature = "auto-cleanup"))]
+        table.cleanup();
+        assert_eq!(table.len(), 1);
+        assert!(!inner.is_empty());
+
+        drop(inner);
+        #[cfg(not(feature = "auto-cleanup"))]
+        table.cleanup();
+        assert!(table.is_empty());
+    }
+
+    /// Named tables report their name in their `Debug` output and counters.
+    #[test]
+    fn test_named_table() {
+        let table = HcTable::builder().name("types").build();
+        let _value = table.hashcons(1);
+
+        assert_eq!(table.name(), Some("types"));
+        assert!(format!("{table:?}").starts_with("HcTable { name: \"types\", len: 1, "));
+        assert_eq!(table.stats().name, Some("types"));
+        assert!(table.stats().to_string().starts_with("types: 1 entries"));
+
+        let unnamed: HcTable<u32> = HcTable::new();
+        assert_eq!(unnamed.name(), None);
+        assert!(unnamed.stats().to_string().starts_with("0 entries"));
+    }
+
+    /// Tables compare and hash by identity, so that they can key a map.
+    #[allow(clippy::mutable_key_type)] // tables hash by identity, not by their contents
+    #[test]
+    fn test_table_identity() {
+        use std::collections::HashMap;
+
+        let (first, second) = (HcTable::new(), HcTable::new());
+        let _one = first.hashcons(1);
+        let _other = second.hashcons(1);
+        assert!(first == first.clone());
+        assert!(first != second);
+
+        let mut names = HashMap::new();
+        names.insert(first.clone(), "first");
+        names.insert(second.clone(), "second");
+        names.insert(first.clone(), "renamed");
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[&first], "renamed");
+    }
+
+    /// The equivalence of values may intern into the table it is called for, which is borrowed
+    /// by a lookup.
+    #[test]
+    fn test_reentrant_equivalence() {
+        use std::hash::{Hash, Hasher};
+
+        /// A value whose comparison of two nonzero instances interns `Probe(0)` into its table.
+        #[derive(Debug)]
+        struct Probe(u32);
+
+        thread_local! {
+            static PROBES: HcTable<Probe> = HcTable::new();
+        }
+
+        impl PartialEq for Probe {
+            fn eq(&self, other: &Self) -> bool {
+                if self.0 != 0 && other.0 != 0 {
+                    PROBES.with(|table| drop(table.hashcons(Probe(0))));
+                }
+                self.0 == other.0
+            }
+        }
+        impl Eq for Probe {}
+        impl Hash for Probe {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                (self.0 % 2).hash(state)
+            }
+        }
+
+        PROBES.with(|table| {
+            let one = table.hashcons(Probe(1));
+            let three = table.hashcons(Probe(3));
+            assert!(table.hashcons(Probe(3)).as_ptr() == three.as_ptr());
+            assert_eq!(table.get_many(&[Probe(1), Probe(5)])[0], Some(one.clone()));
+            let zero = table.hashcons(Probe(0));
+            assert_eq!(table.len(), 3);
+            drop(zero);
+        });
+    }
+
+    #[cfg(not(feature = "auto-cleanup"))]
+    /// The weight function may intern into the table it weighs, since values are weighed while
+    /// the table is not borrowed.
+    #[test]
+    fn test_reentrant_weight() {
+        use hash_cons::EvictionOrder;
+
+        thread_local! {
+            static SIZES: HcTable<u32> = HcTable::builder()
+                .weight(|value: &u32| {
+                    SIZES.with(|table| drop(table.hashcons(0)));
+                    *value as usize
+                })
+                .build();
+        }
+
+        SIZES.with(|table| {
+            let kept = table.hashcons(5);
+            drop(table.hashcons(7));
+            assert_eq!(table.total_weight(), 12);
+
+            let report = table.evict_until_weight(5, EvictionOrder::Heaviest);
+            assert_eq!(report.removed, 1);
+            assert_eq!(table.total_weight(), 5);
+            drop(kept);
+        });
+    }
+
+    /// Iterating takes a snapshot, so the loop may intern into the table it iterates.
+    #[test]
+    fn test_iter_while_interning() {
+        let table = HcTable::new();
+        let kept: Vec<_> = (0..10u32).map(|i| table.hashcons(i)).collect();
+
+        let mut added = Vec::new();
+        let mut visited = Vec::new();
+        for hc in &table {
+            added.push(table.hashcons(*hc + 100));
+            visited.push(*hc);
+        }
+        assert_eq!(visited, (0..10).collect::<Vec<_>>());
+        assert_eq!(table.iter().len(), kept.len() + added.len());
+
+        let iter = table.iter();
+        drop((kept, added));
+        assert_eq!(iter.count(), 20);
+    }
+
+    /// Handles expose the hash their value is stored under, which seeded tables agree on.
+    #[test]
+    fn test_hash_value() {
+        let table = HcTable::builder().seed(7).build();
+        let term = table.hashcons(vec![1, 2, 3]);
+        assert_eq!(
+            table.hashcons(vec![1, 2, 3]).hash_value(),
+            term.hash_value()
+        );
+        assert_eq!(table.entry_id(&term), Some(0));
+
+        let other = HcTable::builder().seed(7).build();
+        assert_eq!(
+            other.hashcons(vec![1, 2, 3]).hash_value(),
+            term.hash_value()
+        );
+        let distinct: std::collections::HashSet<_> = (0..100)
+            .map(|i| table.hashcons(vec![i]).hash_value())
+            .collect();
+        assert_eq!(distinct.len(), 100);
+    }
+
+    /// Tokens resolve to their value through its table until the value is removed.
+    #[test]
+    fn test_token() {
+        let table = HcTable::new();
+        let value = table.hashcons("value".to_string());
+        let token = value.token();
+
+        assert_eq!(table.resolve(token), Some(value.clone()));
+        assert_eq!(table.hashcons("value".to_string()).token(), token);
+        assert_eq!(HcTable::<String>::new().resolve(token), None);
+        drop(value);
+        assert_eq!(table.resolve(token), None);
+        let again = table.hashcons("value".to_string());
+        assert_ne!(again.token(), token);
+    }
+
+    /// Tables with distinct id prefixes never assign the same id, and ids keep increasing.
+    #[test]
+    fn test_id_prefix() {
+        let nodes: Vec<HcTable<u32>> = (1..=3)
+            .map(|node| HcTable::builder().id_prefix(node).build())
+            .collect();
+        let handles: Vec<_> = nodes
+            .iter()
+            .flat_map(|table| [table.hashcons(1), table.hashcons(2)])
+            .collect();
+
+        let mut merged = std::collections::HashMap::new();
+        for table in &nodes {
+            for (id, hc) in table.export_entries() {
+                assert!(merged.insert(id, *hc).is_none());
+            }
+        }
+        assert_eq!(merged.len(), 6);
+        let entries = nodes[2].export_entries();
+        assert_eq!((entries[0].0, entries[1].0), (3 << 48, (3 << 48) + 1));
+        assert_eq!(handles.len(), 6);
+    }
+
+    /// Sequential ids stop at the end of their range instead of handing out ids again.
+    #[test]
+    #[should_panic(expected = "all ids up to 0x11 are used")]
+    fn test_sequential_ids_used_up() {
+        let table = HcTable::builder()
+            .ids(hash_cons::SequentialIds::within(0x10..=0x11))
+            .build();
+        let _handles: Vec<_> = (0..3).map(|n| table.hashcons(n)).collect();
+    }
+
+    /// Tables assign ids through their allocator: content ids are the same when a value is
+    /// interned again, random ids are distinct, and closures can hand out any ids.
+    #[test]
+    fn test_id_allocator() {
+        let table = HcTable::builder().ids(hash_cons::ContentIds).build();
+        let id = table.entry_id(&table.hashcons("a")).unwrap();
+        assert_eq!(table.entry_id(&table.hashcons("a")), Some(id));
+        assert_ne!(table.entry_id(&table.hashcons("b")), Some(id));
+
+        let table = HcTable::builder()
+            .ids(hash_cons::RandomIds::default())
+            .build();
+        let handles: Vec<_> = (0..100).map(|n| table.hashcons(n)).collect();
+        let ids: std::collections::HashSet<_> =
+            handles.iter().filter_map(|hc| table.entry_id(hc)).collect();
+        assert_eq!(ids.len(), 100);
+
+        let mut next = 10;
+        let table = HcTable::builder()
+            .ids(move |_hash| {
+                next += 10;
+                next
+            })
+            .build();
+        let (a, b) = (table.hashcons('a'), table.hashcons('b'));
+        assert_eq!(
+            (table.entry_id(&a), table.entry_id(&b)),
+            (Some(20), Some(30))
+        );
+        let entries = table.export_entries();
+        assert_eq!((entries[0].0, entries[1].0), (20, 30));
+
+        // Values sharing a content id keep their own tokens and their storing order.
+        let table = HcTable::builder()
+            .hashing(|_: &u32, _: &mut dyn std::hash::Hasher| {})
+            .ids(hash_cons::ContentIds)
+            .build();
+        let (one, two) = (table.hashcons(1), table.hashcons(2));
+        assert_eq!(table.entry_id(&one), table.entry_id(&two));
+        assert_eq!(table.resolve(two.token()), Some(two.clone()));
+        let entries = table.export_entries();
+        assert!(*entries[0].1 == 1 && *entries[1].1 == 2);
+        drop(one);
+    }
+
+    /// Values can be probed and interned by a hash computed upstream, and are shared with the
+    /// values interned by `hashcons`.
+    #[test]
+    fn test_prehashed_lookup() {
+        use std::hash::Hasher;
+
+        let table = HcTable::builder()
+            .hashing(|value: &(u64, String), state: &mut dyn Hasher| state.write_u64(value.0))
+            .prehashed()
+            .build();
+        assert!(table.get_prehashed(1, |_| true).is_none());
+
+        let first = table.hashcons_prehashed(1, (1, "first".to_string()));
+        let clash = table.hashcons((1, "clash".to_string()));
+        assert_eq!(table.hashcons((1, "first".to_string())), first);
+        assert_eq!(table.hashcons_prehashed(1, (1, "clash".to_string())), clash);
+        assert_eq!(
+            table.get_prehashed(1, |value| value.1 == "clash"),
+            Some(clash.clone())
+        );
+        assert!(table.get_prehashed(2, |value| value.1 == "first").is_none());
+        assert_eq!(table.len(), 2);
+
+        // Tables that hash their values find them by the hash values of their handles.
+        let table = HcTable::new();
+        let word = table.hashcons("word".to_string());
+        let hash = word.hash_value();
+        assert_eq!(
+            table.get_prehashed(hash, |value| value == "word"),
+            Some(word.clone())
+        );
+        assert_eq!(table.hashcons_prehashed(hash, "word".to_string()), word);
+    }
+
+    /// A value interned from an `Rc` is stored in that allocation, and a value already stored
+    /// is returned instead of the `Rc`.
+    #[test]
+    fn test_hashcons_rc() {
+        use std::rc::Rc;
+
+        let table: HcTable<String> = HcTable::new();
+        let shared = Rc::new("shared".to_string());
+        let hc = table.hashcons_rc(shared.clone());
+        assert!(std::ptr::eq(hc.as_ptr(), Rc::as_ptr(&shared)));
+        assert_eq!(table.hashcons("shared".to_string()), hc);
+
+        let copy = Rc::new("shared".to_string());
+        assert_eq!(table.hashcons_rc(copy.clone()), hc);
+        assert_eq!(Rc::strong_count(&copy), 1);
+        assert_eq!(table.len(), 1);
+
+        // The value cannot be moved out while the caller still shares it.
+        let hc = table.remove(hc).unwrap_err();
+        drop(shared);
+        assert_eq!(table.remove(hc).unwrap(), "shared");
+    }
+
+    /// Trait-object handles share the value and keep the table entry alive.
+    #[test]
+    fn test_into_dyn() {
+        trait Node {
+            fn arity(&self) -> usize;
+        }
+        impl Node for BoolExpr {
+            fn arity(&self) -> usize {
+                match self {
+                    BoolExpr::Const(_) => 0,
+                    BoolExpr::Not(_) => 1,
+                    BoolExpr::And(_, _) | BoolExpr::Or(_, _) => 2,
+                }
+            }
+        }
+        impl Node for u64 {
+            fn arity(&self) -> usize {
+                0
+            }
+        }
+
+        let exprs = HcTable::new();
+        let ints = HcTable::new();
+        let ahc_true = exprs.hashcons(BoolExpr::Const(true));
+        let ahc_not = exprs.hashcons(BoolExpr::Not(ahc_true.clone()));
+
+        let nodes: Vec<HcDyn<dyn Node>> = vec![
+            ahc_not.into_dyn::<dyn Node>(|value| value),
+            ints.hashcons(7u64).into_dyn::<dyn Node>(|value| value),
+            exprs
+                .hashcons(BoolExpr::Not(ahc_true))
+                .into_dyn::<dyn Node>(|value| value),
+        ];
+
+        assert_eq!(nodes[0].arity(), 1);
+        assert_eq!(nodes[1].arity(), 0);
+        assert!(
+            nodes[0] == nodes[2],
+            "Equal values should share the same handle"
+        );
+        assert!(nodes[0] != nodes[1]);
+        assert_eq!(
+            exprs.len(),
+            2,
+            "Trait-object handles should keep values alive"
+        );
+    }
+
+    /// A coercion that returns a fresh allocation is rejected.
+    #[test]
+    #[should_panic(expected = "must return the pointer it was given")]
+    fn test_into_dyn_fresh_allocation() {
+        let table = HcTable::new();
+        let _ = table
+            .hashcons(7u64)
+            .into_dyn::<u64>(|value| std::rc::Rc::new(*value));
+    }
+}
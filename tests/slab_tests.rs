@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod slab_tests {
+    use hash_cons::slab::{SlabHcTable, SlabId};
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum BoolExpr {
+        Const(bool),
+        And(SlabId<BoolExpr>, SlabId<BoolExpr>),
+        Not(SlabId<BoolExpr>),
+    }
+
+    /// Ids are dense, shared by equal values and ordered children first.
+    #[test]
+    fn test_slab_hashconsing() {
+        let mut table = SlabHcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let and = table.hashcons(BoolExpr::And(t, f));
+        let not = table.hashcons(BoolExpr::Not(and));
+
+        assert_eq!(table.hashcons(BoolExpr::Const(true)), t);
+        assert_eq!(table.hashcons(BoolExpr::And(t, f)), and);
+        assert_eq!(
+            [t, f, and, not].map(SlabId::index),
+            [0, 1, 2, 3],
+            "Ids should be handed out in interning order"
+        );
+        assert!(t < not);
+        assert_eq!(table[not], BoolExpr::Not(and));
+        assert_eq!(table.len(), 4);
+    }
+
+    /// Iteration visits the values in slab order.
+    #[test]
+    fn test_slab_iter() {
+        let mut table = SlabHcTable::with_capacity(16);
+        for i in 0..100u32 {
+            table.hashcons(i % 10);
+        }
+
+        let values: Vec<u32> = table.iter().map(|(_, value)| *value).collect();
+        assert_eq!(values, (0..10).collect::<Vec<_>>());
+        assert!(table.iter().all(|(id, value)| table[id] == *value));
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod strong_tests {
+    use hash_cons::strong::{StrongHcTable, StrongId};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[derive(Hash, PartialEq, Eq, Debug)]
+    enum BoolExpr {
+        Const(bool),
+        And(StrongId<BoolExpr>, StrongId<BoolExpr>),
+        Not(StrongId<BoolExpr>),
+    }
+
+    /// Ids are dense, shared by equal values and ordered children first.
+    #[test]
+    fn test_strong_hashconsing() {
+        let table = StrongHcTable::new();
+        let t = table.hashcons(BoolExpr::Const(true));
+        let f = table.hashcons(BoolExpr::Const(false));
+        let and = table.hashcons(BoolExpr::And(t, f));
+        let not = table.hashcons(BoolExpr::Not(and));
+
+        assert_eq!(table.hashcons(BoolExpr::Const(true)), t);
+        assert_eq!(table.lookup(&BoolExpr::And(t, f)), Some(and));
+        assert_eq!(table.lookup(&BoolExpr::Not(t)), None);
+        assert_eq!([t, f, and, not].map(StrongId::index), [0, 1, 2, 3]);
+        assert_eq!(table[not], BoolExpr::Not(and));
+        assert_eq!(table.len(), 4);
+    }
+
+    /// References to values stay valid while more values are interned, from several threads.
+    #[test]
+    fn test_strong_concurrent() {
+        let table = Arc::new(StrongHcTable::new());
+        let first = table.hashcons(0u64);
+        let value = table.get(first);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let table = table.clone();
+                thread::spawn(move || (0..1000u64).map(|i| table.hashcons(i)).collect::<Vec<_>>())
+            })
+            .collect();
+        let ids: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(*value, 0);
+        assert!(ids.iter().all(|run| run == &ids[0]));
+        assert!(ids[0]
+            .iter()
+            .enumerate()
+            .all(|(i, &id)| table[id] == i as u64));
+        assert_eq!(table.len(), 1000);
+    }
+}
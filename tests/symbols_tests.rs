@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod symbols_tests {
+    use hash_cons::symbols::{Folding, SymbolTable};
+    use std::ops::Bound;
+
+    fn names(symbols: &[hash_cons::Hc<String>]) -> Vec<&str> {
+        symbols.iter().map(|symbol| symbol.as_str()).collect()
+    }
+
+    /// Interning returns one handle per spelling, shared with the underlying table.
+    #[test]
+    fn test_symbol_interning() {
+        let symbols = SymbolTable::new();
+        let main = symbols.intern("main");
+
+        assert_eq!(symbols.intern("main"), main);
+        assert_eq!(symbols.get("main"), Some(main.clone()));
+        assert!(symbols.get("other").is_none());
+        assert_eq!(symbols.table().hashcons("main".to_string()), main);
+        assert_eq!((symbols.len(), symbols.table().len()), (1, 1));
+    }
+
+    /// Symbols are listed in order, by prefix or by range.
+    #[test]
+    fn test_symbol_queries() {
+        let symbols = SymbolTable::default();
+        for name in ["foo::bar", "foo", "foo::baz", "fop", "bar", "foo::"] {
+            symbols.intern(name);
+        }
+
+        assert_eq!(
+            names(&symbols.symbols()),
+            ["bar", "foo", "foo::", "foo::bar", "foo::baz", "fop"]
+        );
+        assert_eq!(
+            names(&symbols.symbols_with_prefix("foo::")),
+            ["foo::", "foo::bar", "foo::baz"]
+        );
+        assert!(symbols.symbols_with_prefix("zzz").is_empty());
+        assert_eq!(symbols.symbols_with_prefix("").len(), 6);
+        assert_eq!(
+            names(&symbols.symbols_in("foo".."foo::c")),
+            ["foo", "foo::", "foo::bar", "foo::baz"]
+        );
+        assert_eq!(
+            names(&symbols.symbols_in((Bound::Excluded("foo::baz"), Bound::Unbounded))),
+            ["fop"]
+        );
+        assert_eq!(
+            format!("{symbols:?}"),
+            r#"{"bar", "foo", "foo::", "foo::bar", "foo::baz", "fop"}"#
+        );
+    }
+
+    /// Case-folding tables deduplicate spellings differing in case and keep the first one.
+    #[test]
+    fn test_symbol_case_folding() {
+        let symbols = SymbolTable::with_folding(Folding::AsciiCase);
+        let begin = symbols.intern("Begin");
+        symbols.intern("end");
+
+        assert_eq!(symbols.intern("BEGIN"), begin);
+        assert_eq!(symbols.get("begin"), Some(begin.clone()));
+        assert_eq!(symbols.intern("begin").as_str(), "Begin");
+        assert_eq!((symbols.len(), symbols.table().len()), (2, 2));
+        assert_eq!(names(&symbols.symbols_with_prefix("BE")), ["Begin"]);
+        assert_eq!(names(&symbols.symbols_in("A".."C")), ["Begin"]);
+        assert_eq!(names(&symbols.symbols()), ["Begin", "end"]);
+        assert_eq!(symbols.folding(), Folding::AsciiCase);
+        assert_eq!(Folding::AsciiCase.fold("Straße"), "straße");
+    }
+
+    /// NFC tables deduplicate precomposed and decomposed spellings.
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_symbol_nfc_folding() {
+        let symbols = SymbolTable::with_folding(Folding::Nfc);
+        let cafe = symbols.intern("cafe\u{301}");
+
+        assert_eq!(symbols.intern("caf\u{e9}"), cafe);
+        assert_eq!(symbols.get("caf\u{e9}").unwrap().as_str(), "cafe\u{301}");
+        assert_eq!(symbols.len(), 1);
+        assert!(matches!(
+            Folding::Nfc.fold("caf\u{e9}"),
+            std::borrow::Cow::Borrowed(_)
+        ));
+    }
+}
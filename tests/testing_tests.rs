@@ -0,0 +1,101 @@
+#[cfg(feature = "testing")]
+#[cfg(test)]
+mod testing_tests {
+    use hash_cons::backend::ConsTable;
+    use hash_cons::testing::{DagBuilder, DagStats, Failure, Injected, MockTable, RandomNode};
+    use hash_cons::{HashConsed, Hc, HcTable};
+    use std::collections::HashMap;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    /// Queued failures are injected one per interning, after which interning succeeds with
+    /// sequential ids.
+    #[test]
+    fn test_injected_failures() {
+        let table = MockTable::new();
+        table.fail_next(Failure::Full);
+        table.fail_next(Failure::Poisoned);
+
+        let full = table.try_hashcons("a").unwrap_err();
+        assert_eq!(full.failure(), Failure::Full);
+        assert!(matches!(
+            table.try_hashcons("a"),
+            Err(Injected::Poisoned("a"))
+        ));
+        assert_eq!(full.into_inner(), "a");
+
+        let a = table.try_hashcons("a").unwrap();
+        let b = table.hashcons("b");
+        assert_eq!((table.id(&a), table.id(&b)), (0, 1));
+        assert_eq!(table.hashcons("a"), a);
+        assert_eq!((table.len(), table.calls()), (2, 5));
+
+        table.fail_next(Failure::Poisoned);
+        let panicked = catch_unwind(AssertUnwindSafe(|| ConsTable::hashcons(&table, "c")));
+        assert!(panicked.is_err());
+    }
+
+    /// A table with a capacity finds stored values once full, but stores no others.
+    #[test]
+    fn test_capacity() {
+        let table = MockTable::with_capacity(1);
+        let one = table.hashcons(1);
+
+        assert_eq!(table.try_hashcons(1).unwrap(), one);
+        assert!(matches!(table.try_hashcons(2), Err(Injected::Full(2))));
+        assert_eq!(
+            format!("{table:?}"),
+            "MockTable { len: 1, capacity: Some(1), pending_failures: 0 }"
+        );
+    }
+
+    /// Counts the DAG below `node` independently of the generator, by address of the nodes.
+    fn count(
+        node: &Hc<RandomNode>,
+        seen: &mut HashMap<*const RandomNode, u128>,
+        stats: &mut DagStats,
+    ) -> u128 {
+        if let Some(&tree_size) = seen.get(&node.as_ptr()) {
+            return tree_size;
+        }
+        let children = node.children();
+        stats.nodes += 1;
+        stats.edges += children.len();
+        stats.leaves += usize::from(children.is_empty());
+        let tree_size = 1 + children
+            .iter()
+            .map(|child| count(child, seen, stats))
+            .sum::<u128>();
+        seen.insert(node.as_ptr(), tree_size);
+        tree_size
+    }
+
+    /// The reported statistics match the generated DAG, which is the same for the same seed.
+    #[test]
+    fn test_dag_builder() {
+        let builder = DagBuilder::new().depth(7).arity(3).sharing(0.6).seed(7);
+        let table = HcTable::new();
+        let (root, stats) = builder.build(&table);
+
+        let mut counted = DagStats::default();
+        counted.tree_size = count(&root, &mut HashMap::new(), &mut counted);
+        // Which children were shared is only known to the generator
+        counted.shared_children = stats.shared_children;
+        assert_eq!(counted, stats);
+        assert!(stats.shared_children > 0 && stats.tree_size > stats.nodes as u128);
+        assert_eq!(table.len(), stats.nodes);
+
+        let again = HcTable::new();
+        assert_eq!(builder.build(&again).1, stats);
+        let other = HcTable::new();
+        assert_ne!(builder.clone().seed(8).build(&other).1, stats);
+
+        // Without sharing, a chain has one node per level
+        let chain = HcTable::new();
+        let (_, stats) = DagBuilder::new()
+            .depth(5)
+            .arity(1)
+            .sharing(0.0)
+            .build(&chain);
+        assert_eq!((stats.nodes, stats.edges, stats.tree_size), (6, 5, 6));
+    }
+}
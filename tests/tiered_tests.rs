@@ -0,0 +1,86 @@
+#[cfg(feature = "tiered")]
+#[cfg(test)]
+mod tiered_tests {
+    use hash_cons::tiered::TieredTable;
+    use std::path::PathBuf;
+
+    fn log_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("hash_cons_{}_{}.log", name, std::process::id()))
+    }
+
+    /// Only values without outside handles are spilled, least recently used first, and they come
+    /// back under their ids.
+    #[test]
+    fn test_spill_and_rehydrate() {
+        let path = log_path("spill");
+        let table = TieredTable::open(&path).unwrap();
+        let held = table.hashcons(0u64).unwrap();
+        let ids: Vec<u64> = (1..=4u64)
+            .map(|n| table.id(&table.hashcons(n).unwrap()).unwrap())
+            .collect();
+        // Touching 1 makes 2 the least recently used value.
+        table.hashcons(1).unwrap();
+
+        assert_eq!(table.spill(3).unwrap(), 2);
+        assert_eq!((table.hot_len(), table.cold_len()), (3, 2));
+        assert_eq!(*table.resolve(ids[0]).unwrap().unwrap(), 1);
+
+        // The held value is never spilled, however old.
+        assert_eq!(table.spill(0).unwrap(), 2);
+        assert_eq!((table.hot_len(), table.cold_len()), (1, 4));
+        assert_eq!(table.id(&held), Some(0));
+
+        let two = table.hashcons(2).unwrap();
+        assert_eq!(table.id(&two), Some(ids[1]));
+        assert_eq!(*table.resolve(ids[3]).unwrap().unwrap(), 4);
+        assert_eq!(table.cold_len(), 2);
+        assert!(table.resolve(99).unwrap().is_none());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Values spilled several times are read back from their latest record.
+    #[test]
+    fn test_repeated_spills() {
+        let path = log_path("repeated");
+        let table = TieredTable::open(&path).unwrap();
+        let id = table
+            .id(&table.hashcons(vec!["a".to_string()]).unwrap())
+            .unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(table.spill(0).unwrap(), 1);
+            let value = table.resolve(id).unwrap().unwrap();
+            assert_eq!(*value, ["a"]);
+            drop(value);
+        }
+        assert_eq!(format!("{:?}", table), "TieredTable { hot: 1, cold: 0 }");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Compressed cold values take less memory than their serialized form, and are equal to the
+    /// values interned again after them.
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_cold_tier() {
+        let table = TieredTable::compressed();
+        let rows: Vec<Vec<u32>> = (0..3).map(|row| vec![row; 512]).collect();
+        let ids: Vec<u64> = rows
+            .iter()
+            .map(|row| table.id(&table.hashcons(row.clone()).unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(table.spill(0).unwrap(), 3);
+        let serialized: usize = rows
+            .iter()
+            .map(|row| serde_json::to_vec(row).unwrap().len())
+            .sum();
+        assert!(table.cold_bytes() * 10 < serialized);
+
+        let second = table.hashcons(rows[1].clone()).unwrap();
+        assert_eq!(table.id(&second), Some(ids[1]));
+        assert_eq!(*table.resolve(ids[2]).unwrap().unwrap(), rows[2]);
+        assert_eq!((table.hot_len(), table.cold_len()), (2, 1));
+    }
+}
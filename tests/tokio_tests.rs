@@ -0,0 +1,35 @@
+#[cfg(feature = "tokio")]
+#[cfg(test)]
+mod tokio_tests {
+    use hash_cons::thread_safe::HcTable;
+    use std::time::Duration;
+
+    /// Creates a table whose dead entries stay until they are swept.
+    fn table() -> HcTable<u32> {
+        #[cfg(feature = "auto-cleanup")]
+        return HcTable::builder().deferred_reclamation().build();
+        #[cfg(not(feature = "auto-cleanup"))]
+        return HcTable::new();
+    }
+
+    /// The task sweeps the table periodically and publishes its counters.
+    #[tokio::test]
+    async fn test_spawn_maintenance() {
+        let table = table();
+        let kept = table.hashcons(0);
+        let maintenance = table.spawn_maintenance(Duration::from_millis(5));
+        assert_eq!(maintenance.stats().created, 1);
+
+        drop((1..=10).map(|i| table.hashcons(i)).collect::<Vec<_>>());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let stats = maintenance.stats();
+        assert_eq!((stats.created, stats.removed, stats.len), (11, 10, 1));
+        assert_eq!(table.len(), 1);
+        assert!(!maintenance.is_finished());
+
+        // The task stops once the table and its handles are gone.
+        drop((table, kept));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(maintenance.is_finished());
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(feature = "wal")]
+#[cfg(test)]
+mod wal_tests {
+    use hash_cons::wal::WalTable;
+    use hash_cons::Hc;
+    use serde::{Deserialize, Serialize};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[derive(Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+    enum Term {
+        Var(String),
+        App(
+            #[serde(with = "hash_cons::serde_hc")] Hc<Term>,
+            #[serde(with = "hash_cons::serde_hc")] Hc<Term>,
+        ),
+    }
+
+    fn log_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("hash_cons_wal_{}_{}.log", name, std::process::id()))
+    }
+
+    /// Replaying the log restores the values and the sharing between them.
+    #[test]
+    fn test_replay_restores_sharing() {
+        let path = log_path("sharing");
+        {
+            let table = WalTable::open(&path).unwrap();
+            let x = table.hashcons(Term::Var("x".to_string())).unwrap();
+            let app = table.hashcons(Term::App(x.clone(), x.clone())).unwrap();
+            table.hashcons(Term::App(app, x)).unwrap();
+            table.sync().unwrap();
+            assert_eq!(table.len(), 3);
+        }
+
+        let table = WalTable::<Term>::open(&path).unwrap();
+        assert_eq!((table.len(), table.table().len()), (3, 3));
+        let x = table.hashcons(Term::Var("x".to_string())).unwrap();
+        let app = table.hashcons(Term::App(x.clone(), x.clone())).unwrap();
+        let Term::App(left, right) = app.get() else {
+            panic!("expected an application")
+        };
+        assert!(*left == x && *right == x);
+        assert_eq!(table.len(), 3);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// A record torn by a crash is dropped, and the records appended after it are kept.
+    #[test]
+    fn test_torn_record() {
+        let path = log_path("torn");
+        {
+            let table = WalTable::open(&path).unwrap();
+            table.hashcons(1u32).unwrap();
+            table.hashcons(2u32).unwrap();
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"00000000deadbeef 3").unwrap();
+        drop(file);
+
+        {
+            let table = WalTable::open(&path).unwrap();
+            assert_eq!(table.len(), 2);
+            table.hashcons(4u32).unwrap();
+        }
+        let table = WalTable::<u32>::open(&path).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(format!("{:?}", table), "WalTable { values: 3 }");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}